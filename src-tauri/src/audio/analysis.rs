@@ -0,0 +1,375 @@
+// src/audio/analysis.rs
+
+use biquad::{Biquad, Coefficients, DirectForm2Transposed};
+use rodio::Source;
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use std::f32::consts::PI;
+
+// =================================================================
+// 🔍 母带削波检测：满幅连续样本 + 4x 过采样样间峰值 (True Peak 估算)
+// =================================================================
+const FULL_SCALE: f32 = 0.999;
+const MIN_CLIP_RUN: u32 = 3;
+const OVERSAMPLE: usize = 4;
+const TAPS_PER_PHASE: usize = 8;
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ClipReport {
+    pub clipped_runs: u32,        // 长度 ≥3 的满幅样本段数量
+    pub clipped_samples: u64,
+    pub sample_peak: f32,
+    pub true_peak: f32,           // 4x 过采样估算的样间峰值 (线性)
+    pub true_peak_dbfs: f32,
+    pub severity: f32,            // 0 ~ 100
+}
+
+// 多相插值器：32 阶 Hann 窗 sinc 原型拆成 4 相，每相 8 抽头
+pub struct TruePeakMeter {
+    phases: [[f32; TAPS_PER_PHASE]; OVERSAMPLE],
+    history: Vec<[f32; TAPS_PER_PHASE]>,
+    peak: f32,
+}
+
+impl TruePeakMeter {
+    pub fn new(channels: usize) -> Self {
+        let len = OVERSAMPLE * TAPS_PER_PHASE;
+        let center = (len as f32 - 1.0) / 2.0;
+        let mut phases = [[0.0f32; TAPS_PER_PHASE]; OVERSAMPLE];
+        for n in 0..len {
+            let x = (n as f32 - center) / OVERSAMPLE as f32;
+            let sinc = if x.abs() < 1e-6 { 1.0 } else { (PI * x).sin() / (PI * x) };
+            let window = 0.5 - 0.5 * (2.0 * PI * (n as f32 + 0.5) / len as f32).cos();
+            phases[n % OVERSAMPLE][n / OVERSAMPLE] = sinc * window;
+        }
+        // 每相归一化为单位直流增益，避免插值本身引入电平偏差
+        for phase in phases.iter_mut() {
+            let sum: f32 = phase.iter().sum();
+            if sum.abs() > 1e-6 { for c in phase.iter_mut() { *c /= sum; } }
+        }
+        Self { phases, history: vec![[0.0; TAPS_PER_PHASE]; channels.max(1)], peak: 0.0 }
+    }
+
+    #[inline]
+    pub fn push(&mut self, channel: usize, sample: f32) {
+        let hist = &mut self.history[channel];
+        hist.copy_within(1.., 0);
+        hist[TAPS_PER_PHASE - 1] = sample;
+        for phase in &self.phases {
+            let mut acc = 0.0;
+            for (h, c) in hist.iter().zip(phase.iter().rev()) { acc += h * c; }
+            self.peak = self.peak.max(acc.abs());
+        }
+    }
+
+    pub fn peak(&self) -> f32 { self.peak }
+}
+
+pub struct ClipAnalyzer {
+    channels: usize,
+    channel_idx: usize,
+    run_lengths: Vec<u32>,
+    clipped_runs: u32,
+    clipped_samples: u64,
+    sample_peak: f32,
+    frames: u64,
+    sample_rate: u32,
+    true_peak: TruePeakMeter,
+}
+
+impl ClipAnalyzer {
+    pub fn new(channels: u16, sample_rate: u32) -> Self {
+        let channels = channels.max(1) as usize;
+        Self {
+            channels, channel_idx: 0, run_lengths: vec![0; channels],
+            clipped_runs: 0, clipped_samples: 0, sample_peak: 0.0, frames: 0,
+            sample_rate: sample_rate.max(1), true_peak: TruePeakMeter::new(channels),
+        }
+    }
+
+    #[inline]
+    pub fn push(&mut self, sample: f32) {
+        let ch = self.channel_idx;
+        let abs = sample.abs();
+        self.sample_peak = self.sample_peak.max(abs);
+        if abs >= FULL_SCALE {
+            self.run_lengths[ch] += 1;
+            self.clipped_samples += 1;
+            if self.run_lengths[ch] == MIN_CLIP_RUN { self.clipped_runs += 1; }
+        } else {
+            self.run_lengths[ch] = 0;
+        }
+        self.true_peak.push(ch, sample);
+
+        self.channel_idx += 1;
+        if self.channel_idx == self.channels { self.channel_idx = 0; self.frames += 1; }
+    }
+
+    // 严重度：每分钟削波段数贡献 70 分（50 段/分封顶），样间峰值超出 0dBFS 的部分贡献 30 分（+3dB 封顶）
+    pub fn finish(&self) -> ClipReport {
+        let minutes = (self.frames as f32 / self.sample_rate as f32 / 60.0).max(1.0 / 60.0);
+        let true_peak = self.true_peak.peak().max(self.sample_peak);
+        let true_peak_dbfs = 20.0 * true_peak.max(1e-9).log10();
+        let run_score = (self.clipped_runs as f32 / minutes).min(50.0) / 50.0 * 70.0;
+        let isp_score = true_peak_dbfs.clamp(0.0, 3.0) / 3.0 * 30.0;
+        ClipReport {
+            clipped_runs: self.clipped_runs,
+            clipped_samples: self.clipped_samples,
+            sample_peak: self.sample_peak,
+            true_peak,
+            true_peak_dbfs,
+            severity: run_score + isp_score,
+        }
+    }
+}
+
+// 直通式分析包装：在重采样/限幅之前观测原始解码样本，结束时写回报告槽
+pub struct AnalyzingSource<I: Source<Item = f32>> {
+    input: I,
+    analyzer: ClipAnalyzer,
+    report: Arc<RwLock<Option<ClipReport>>>,
+    done: bool,
+}
+
+impl<I: Source<Item = f32>> AnalyzingSource<I> {
+    pub fn new(input: I, report: Arc<RwLock<Option<ClipReport>>>) -> Self {
+        let analyzer = ClipAnalyzer::new(input.channels(), input.sample_rate());
+        Self { input, analyzer, report, done: false }
+    }
+}
+
+impl<I: Source<Item = f32>> Iterator for AnalyzingSource<I> {
+    type Item = f32;
+    #[inline(always)]
+    fn next(&mut self) -> Option<f32> {
+        match self.input.next() {
+            Some(s) => { self.analyzer.push(s); Some(s) }
+            None => {
+                if !self.done {
+                    self.done = true;
+                    *self.report.write().unwrap_or_else(|e| e.into_inner()) = Some(self.analyzer.finish());
+                }
+                None
+            }
+        }
+    }
+}
+
+impl<I: Source<Item = f32>> Source for AnalyzingSource<I> {
+    fn current_frame_len(&self) -> Option<usize> { self.input.current_frame_len() }
+    fn channels(&self) -> u16 { self.input.channels() }
+    fn sample_rate(&self) -> u32 { self.input.sample_rate() }
+    fn total_duration(&self) -> Option<Duration> { self.input.total_duration() }
+}
+
+// =================================================================
+// 📏 响度测量 (ITU-R BS.1770 / EBU R128 积分响度)
+// =================================================================
+// K 计权 = 高架 +4dB (1681.97Hz) + 高通 (38.13Hz)；400ms 块、75% 重叠，
+// 先按 -70 LUFS 绝对门限、再按低于均值 10LU 的相对门限两级门控。
+const LOUDNESS_BLOCK_HOP_SECS: f64 = 0.1; // 子块 100ms，4 个子块组成一个测量块
+const LOUDNESS_ABSOLUTE_GATE: f64 = -70.0;
+const LOUDNESS_RELATIVE_GATE: f64 = -10.0;
+
+// 返回 [高架, 高通]；系数由 BS.1770 的模拟原型按当前采样率双线性变换得到（同 libebur128），
+// 采样率过低无法构造滤波器时退化为不计权
+pub fn k_weighting(sample_rate: u32) -> [Coefficients<f32>; 2] {
+    const SHELF_HZ: f64 = 1681.974450955533;
+    const SHELF_GAIN_DB: f64 = 3.999843853973347;
+    const SHELF_Q: f64 = 0.7071752369554196;
+    const HIGHPASS_HZ: f64 = 38.13547087602444;
+    const HIGHPASS_Q: f64 = 0.5003270373238773;
+    let fs = sample_rate as f64;
+    if fs <= 2.0 * SHELF_HZ {
+        let unity = Coefficients { a1: 0.0, a2: 0.0, b0: 1.0, b1: 0.0, b2: 0.0 };
+        return [unity, unity];
+    }
+
+    let k = (std::f64::consts::PI * SHELF_HZ / fs).tan();
+    let vh = 10f64.powf(SHELF_GAIN_DB / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / SHELF_Q + k * k;
+    let shelf = Coefficients {
+        b0: ((vh + vb * k / SHELF_Q + k * k) / a0) as f32,
+        b1: (2.0 * (k * k - vh) / a0) as f32,
+        b2: ((vh - vb * k / SHELF_Q + k * k) / a0) as f32,
+        a1: (2.0 * (k * k - 1.0) / a0) as f32,
+        a2: ((1.0 - k / SHELF_Q + k * k) / a0) as f32,
+    };
+
+    let k = (std::f64::consts::PI * HIGHPASS_HZ / fs).tan();
+    let a0 = 1.0 + k / HIGHPASS_Q + k * k;
+    let highpass = Coefficients {
+        b0: 1.0, b1: -2.0, b2: 1.0,
+        a1: (2.0 * (k * k - 1.0) / a0) as f32,
+        a2: ((1.0 - k / HIGHPASS_Q + k * k) / a0) as f32,
+    };
+    [shelf, highpass]
+}
+
+pub struct LoudnessMeter {
+    channels: usize,
+    channel_idx: usize,
+    weights: Vec<f64>,
+    filters: Vec<[DirectForm2Transposed<f32>; 2]>,
+    hop_len: usize,
+    hop_frames: usize,
+    hop_energy: f64,
+    recent_hops: [f64; 4],
+    hops_seen: usize,
+    blocks: Vec<f64>, // 各测量块的加权均方值
+    sample_peak: f32,
+}
+
+impl LoudnessMeter {
+    pub fn new(channels: u16, sample_rate: u32) -> Self {
+        let channels = channels.max(1) as usize;
+        let [shelf, highpass] = k_weighting(sample_rate);
+        // 5.1 及以上：LFE (第 4 声道) 不计入，环绕声道加权 1.41
+        let weights = (0..channels).map(|c| match c {
+            3 if channels >= 6 => 0.0,
+            c if c >= 4 && channels >= 6 => 1.41,
+            _ => 1.0,
+        }).collect();
+        Self {
+            channels, channel_idx: 0, weights,
+            filters: (0..channels).map(|_| [DirectForm2Transposed::<f32>::new(shelf), DirectForm2Transposed::<f32>::new(highpass)]).collect(),
+            hop_len: ((sample_rate.max(1) as f64 * LOUDNESS_BLOCK_HOP_SECS) as usize).max(1),
+            hop_frames: 0, hop_energy: 0.0, recent_hops: [0.0; 4], hops_seen: 0,
+            blocks: Vec::new(), sample_peak: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn push(&mut self, sample: f32) {
+        let ch = self.channel_idx;
+        self.sample_peak = self.sample_peak.max(sample.abs());
+        let [shelf, highpass] = &mut self.filters[ch];
+        let weighted = highpass.run(shelf.run(sample)) as f64;
+        self.hop_energy += self.weights[ch] * weighted * weighted;
+
+        self.channel_idx += 1;
+        if self.channel_idx < self.channels { return; }
+        self.channel_idx = 0;
+        self.hop_frames += 1;
+        if self.hop_frames < self.hop_len { return; }
+
+        self.recent_hops.rotate_left(1);
+        self.recent_hops[3] = self.hop_energy / self.hop_len as f64;
+        self.hop_energy = 0.0;
+        self.hop_frames = 0;
+        self.hops_seen += 1;
+        if self.hops_seen >= 4 { self.blocks.push(self.recent_hops.iter().sum::<f64>() / 4.0); }
+    }
+
+    pub fn sample_peak(&self) -> f32 { self.sample_peak }
+
+    fn block_lufs(mean_square: f64) -> f64 { -0.691 + 10.0 * mean_square.max(1e-12).log10() }
+
+    // 有效内容不足一个测量块或全部低于绝对门限时返回 None
+    pub fn integrated_lufs(&self) -> Option<f64> {
+        let gated_mean = |threshold: f64| -> Option<f64> {
+            let kept: Vec<f64> = self.blocks.iter().copied().filter(|&z| Self::block_lufs(z) > threshold).collect();
+            if kept.is_empty() { None } else { Some(kept.iter().sum::<f64>() / kept.len() as f64) }
+        };
+        let absolute = gated_mean(LOUDNESS_ABSOLUTE_GATE)?;
+        let relative = gated_mean(Self::block_lufs(absolute) + LOUDNESS_RELATIVE_GATE)?;
+        Some(Self::block_lufs(relative))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze(channels: u16, rate: u32, frames: impl Iterator<Item = f32>) -> ClipReport {
+        let mut analyzer = ClipAnalyzer::new(channels, rate);
+        for s in frames.flat_map(|s| std::iter::repeat_n(s, channels as usize)) { analyzer.push(s); }
+        analyzer.finish()
+    }
+
+    #[test]
+    fn quarter_rate_sine_at_45_degrees_reports_3db_intersample_peak() {
+        // fs/4 正弦相位偏 45°：所有采样点都落在 ±A/√2，真实峰值在两点正中，恰好比样本峰值高 3.01dB
+        let report = analyze(1, 48000, (0..4800).map(|n| (PI / 2.0 * (n % 4) as f32 + PI / 4.0).sin() * 2f32.sqrt()));
+        assert!((report.sample_peak - 1.0).abs() < 1e-4);
+        assert!(report.true_peak_dbfs > 2.8 && report.true_peak_dbfs < 3.02, "true peak {} dBFS", report.true_peak_dbfs);
+        assert!(report.severity >= 28.0);
+    }
+
+    #[test]
+    fn low_frequency_sine_has_no_intersample_overs() {
+        let report = analyze(2, 48000, (0..48000).map(|n| (2.0 * PI * 997.0 * n as f32 / 48000.0).sin() * 0.5));
+        assert!((report.true_peak - 0.5).abs() < 1e-3, "true peak {}", report.true_peak);
+        assert_eq!((report.clipped_runs, report.clipped_samples), (0, 0));
+        assert_eq!(report.severity, 0.0);
+    }
+
+    #[test]
+    fn clipped_sine_counts_one_run_per_half_cycle_per_channel() {
+        // 1.5 倍过载的 100Hz 正弦硬削波：1 秒 200 个半周期，每个半周期一段满幅平顶
+        let report = analyze(2, 48000, (0..48000).map(|n| ((2.0 * PI * 100.0 * n as f32 / 48000.0).sin() * 1.5).clamp(-1.0, 1.0)));
+        assert_eq!(report.clipped_runs, 400);
+        assert!(report.severity >= 70.0);
+    }
+
+    #[test]
+    fn short_full_scale_bursts_are_not_runs() {
+        let report = analyze(1, 48000, (0..4800).map(|n| if n % 100 < 2 { 1.0 } else { 0.0 }));
+        assert_eq!(report.clipped_runs, 0);
+        assert_eq!(report.clipped_samples, 96);
+    }
+
+    fn loudness(channels: u16, rate: u32, frames: impl Iterator<Item = f32>) -> Option<f64> {
+        let mut meter = LoudnessMeter::new(channels, rate);
+        for s in frames.flat_map(|s| std::iter::repeat_n(s, channels as usize)) { meter.push(s); }
+        meter.integrated_lufs()
+    }
+
+    fn sine(rate: u32, freq: f32, amplitude: f32) -> impl Fn(usize) -> f32 {
+        move |n| (2.0 * PI * freq * n as f32 / rate as f32).sin() * amplitude
+    }
+
+    #[test]
+    fn full_scale_997hz_sine_hits_the_bs1770_calibration_point() {
+        // 单声道 0dBFS 997Hz 正弦为 -3.01 LUFS，两个声道同信号为 0 LUFS
+        for rate in [44100, 48000, 96000] {
+            let mono = loudness(1, rate, (0..rate as usize * 10).map(sine(rate, 997.0, 1.0))).unwrap();
+            let stereo = loudness(2, rate, (0..rate as usize * 10).map(sine(rate, 997.0, 1.0))).unwrap();
+            assert!((mono + 3.01).abs() < 0.05, "{} Hz: {} LUFS", rate, mono);
+            assert!(stereo.abs() < 0.05, "{} Hz: {} LUFS", rate, stereo);
+        }
+    }
+
+    #[test]
+    fn gating_ignores_silence_and_quiet_passages() {
+        assert_eq!(loudness(2, 48000, std::iter::repeat_n(0.0, 48000 * 5)), None);
+        // -80dBFS 全部低于 -70 LUFS 绝对门限
+        assert_eq!(loudness(1, 48000, (0..48000 * 5).map(sine(48000, 997.0, 1e-4))), None);
+        // 不足一个 400ms 测量块
+        assert_eq!(loudness(1, 48000, (0..48000 * 3 / 10).map(sine(48000, 997.0, 1.0))), None);
+
+        // 5 秒满幅接 20 秒 -40dB：不门控约为 -10 LUFS，相对门限去掉安静段后接近满幅段本身
+        let loud = sine(48000, 997.0, 1.0);
+        let gated = loudness(1, 48000, (0..48000 * 25).map(|n| if n < 48000 * 5 { loud(n) } else { loud(n) * 0.01 })).unwrap();
+        assert!((gated + 3.01).abs() < 0.3, "{} LUFS", gated);
+    }
+
+    #[test]
+    fn lfe_is_left_out_of_5_1_loudness() {
+        let tone = sine(48000, 997.0, 1.0);
+        let only_in = |channel: usize| {
+            let mut meter = LoudnessMeter::new(6, 48000);
+            for n in 0..48000 * 5 {
+                for c in 0..6 { meter.push(if c == channel { tone(n) } else { 0.0 }); }
+            }
+            meter.integrated_lufs()
+        };
+        assert_eq!(only_in(3), None);
+        let front = only_in(0).unwrap();
+        // 环绕声道加权 1.41 ≈ +1.5dB
+        let surround = only_in(4).unwrap();
+        assert!((front + 3.01).abs() < 0.05 && (surround - front - 1.49).abs() < 0.05, "{} / {}", front, surround);
+    }
+}
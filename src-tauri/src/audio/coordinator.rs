@@ -0,0 +1,225 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+// ==========================================
+// 🚦 资源协调器：主曲目 PCM 缓存填充优先，辅助解码/渲染按令牌桶让路
+// ==========================================
+// 主解码线程每产出一个块就向桶里投放令牌；主缓存进度低于阈值时，
+// 辅助任务每个块都要先取到令牌才能继续，取不到就缩小块并让出 CPU。
+pub const MAIN_CHUNK: usize = 4096;
+const AUX_CHUNK_FULL: usize = 4096;
+const AUX_CHUNK_THROTTLED: usize = 1024;
+const PRIORITY_THRESHOLD_PERMILLE: u32 = 600;
+const TOKEN_CAP: u64 = 8;
+const AUX_WAIT_STEP: Duration = Duration::from_millis(2);
+const AUX_MAX_WAIT: Duration = Duration::from_millis(20);
+
+pub struct ResourceCoordinator {
+    main_jobs: AtomicUsize,
+    main_progress_permille: AtomicU32,
+    tokens: AtomicU64,
+    aux_jobs: AtomicUsize,
+    aux_chunks: AtomicU64,
+    aux_throttled_chunks: AtomicU64,
+    aux_wait_us: AtomicU64,
+}
+
+pub static COORDINATOR: ResourceCoordinator = ResourceCoordinator::new();
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CoordinatorStats {
+    pub main_active: bool,
+    pub main_progress: f32,
+    pub tokens: u64,
+    pub aux_jobs: usize,
+    pub aux_chunks: u64,
+    pub aux_throttled_chunks: u64,
+    pub aux_wait_ms: f64,
+}
+
+impl ResourceCoordinator {
+    const fn new() -> Self {
+        Self {
+            main_jobs: AtomicUsize::new(0),
+            main_progress_permille: AtomicU32::new(1000),
+            tokens: AtomicU64::new(TOKEN_CAP),
+            aux_jobs: AtomicUsize::new(0),
+            aux_chunks: AtomicU64::new(0),
+            aux_throttled_chunks: AtomicU64::new(0),
+            aux_wait_us: AtomicU64::new(0),
+        }
+    }
+
+    pub fn begin_main(&'static self, expected_samples: usize) -> MainJob {
+        self.main_jobs.fetch_add(1, Ordering::SeqCst);
+        self.main_progress_permille.store(0, Ordering::Relaxed);
+        MainJob { owner: self, expected_samples: expected_samples.max(1) }
+    }
+
+    pub fn register_aux(&'static self) -> AuxJob {
+        self.aux_jobs.fetch_add(1, Ordering::SeqCst);
+        AuxJob { owner: self }
+    }
+
+    fn main_constrained(&self) -> bool {
+        self.main_jobs.load(Ordering::SeqCst) > 0
+            && self.main_progress_permille.load(Ordering::Relaxed) < PRIORITY_THRESHOLD_PERMILLE
+    }
+
+    fn try_take_token(&self) -> bool {
+        self.tokens.fetch_update(Ordering::AcqRel, Ordering::Acquire, |t| t.checked_sub(1)).is_ok()
+    }
+
+    pub fn stats(&self) -> CoordinatorStats {
+        CoordinatorStats {
+            main_active: self.main_jobs.load(Ordering::SeqCst) > 0,
+            main_progress: self.main_progress_permille.load(Ordering::Relaxed) as f32 / 1000.0,
+            tokens: self.tokens.load(Ordering::Relaxed),
+            aux_jobs: self.aux_jobs.load(Ordering::SeqCst),
+            aux_chunks: self.aux_chunks.load(Ordering::Relaxed),
+            aux_throttled_chunks: self.aux_throttled_chunks.load(Ordering::Relaxed),
+            aux_wait_ms: self.aux_wait_us.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+// 主缓存填充任务：存活期间辅助任务受约束，析构即解除
+pub struct MainJob {
+    owner: &'static ResourceCoordinator,
+    expected_samples: usize,
+}
+
+impl MainJob {
+    pub fn report(&self, produced_samples: usize) {
+        let permille = (produced_samples.saturating_mul(1000) / self.expected_samples).min(1000) as u32;
+        self.owner.main_progress_permille.store(permille, Ordering::Relaxed);
+        let _ = self.owner.tokens.fetch_update(Ordering::AcqRel, Ordering::Acquire, |t| Some((t + 1).min(TOKEN_CAP)));
+    }
+}
+
+impl Drop for MainJob {
+    fn drop(&mut self) {
+        if self.owner.main_jobs.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.owner.main_progress_permille.store(1000, Ordering::Relaxed);
+        }
+    }
+}
+
+// 辅助任务（预载、预览、试音等）：每处理完一个块调用 pace()，返回下一块的大小
+pub struct AuxJob {
+    owner: &'static ResourceCoordinator,
+}
+
+impl AuxJob {
+    pub fn pace(&self) -> usize {
+        self.owner.aux_chunks.fetch_add(1, Ordering::Relaxed);
+        if !self.owner.main_constrained() {
+            thread::yield_now();
+            return AUX_CHUNK_FULL;
+        }
+
+        self.owner.aux_throttled_chunks.fetch_add(1, Ordering::Relaxed);
+        let started = Instant::now();
+        while !self.owner.try_take_token() && self.owner.main_constrained() && started.elapsed() < AUX_MAX_WAIT {
+            thread::sleep(AUX_WAIT_STEP);
+        }
+        self.owner.aux_wait_us.fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+        AUX_CHUNK_THROTTLED
+    }
+}
+
+impl Drop for AuxJob {
+    fn drop(&mut self) { self.owner.aux_jobs.fetch_sub(1, Ordering::SeqCst); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    // 不碰全局 COORDINATOR：每个测试独占一个
+    fn coordinator() -> &'static ResourceCoordinator { Box::leak(Box::new(ResourceCoordinator::new())) }
+
+    // 与样本数成正比的纯计算，代替解码 / 渲染
+    fn work(samples: usize) -> u64 {
+        let mut x = samples as u64 | 1;
+        for _ in 0..samples * 16 { x ^= x << 13; x ^= x >> 7; x ^= x << 17; }
+        std::hint::black_box(x)
+    }
+
+    const MAIN_CHUNKS: usize = 200;
+    // 辅助线程数为核数两倍，不加协调时主解码约慢 3 倍；前 60% 受保护后应明显低于此
+    const MAX_SLOWDOWN: f64 = 2.5;
+
+    fn main_decode(owner: &'static ResourceCoordinator) -> Duration {
+        let started = Instant::now();
+        let job = owner.begin_main(MAIN_CHUNKS * MAIN_CHUNK);
+        for i in 1..=MAIN_CHUNKS {
+            work(MAIN_CHUNK);
+            job.report(i * MAIN_CHUNK);
+        }
+        started.elapsed()
+    }
+
+    #[test]
+    fn aux_is_throttled_only_while_the_main_fill_is_behind() {
+        let owner = coordinator();
+        let aux = owner.register_aux();
+        assert_eq!(aux.pace(), AUX_CHUNK_FULL);
+
+        let main = owner.begin_main(1000);
+        for _ in 0..TOKEN_CAP { assert_eq!(aux.pace(), AUX_CHUNK_THROTTLED); }
+        // 桶已取空：等满上限后照样放行，辅助任务不会被饿死
+        let started = Instant::now();
+        assert_eq!(aux.pace(), AUX_CHUNK_THROTTLED);
+        assert!(started.elapsed() >= AUX_MAX_WAIT);
+        // 主解码每投一块就多一个令牌
+        main.report(100);
+        let started = Instant::now();
+        assert_eq!(aux.pace(), AUX_CHUNK_THROTTLED);
+        assert!(started.elapsed() < AUX_MAX_WAIT);
+        main.report(PRIORITY_THRESHOLD_PERMILLE as usize);
+        assert_eq!(aux.pace(), AUX_CHUNK_FULL);
+
+        drop(main);
+        let stats = owner.stats();
+        assert!(!stats.main_active);
+        assert_eq!(stats.main_progress, 1.0);
+        assert_eq!((stats.aux_jobs, stats.aux_chunks, stats.aux_throttled_chunks), (1, TOKEN_CAP + 4, TOKEN_CAP + 2));
+        assert!(stats.aux_wait_ms >= AUX_MAX_WAIT.as_secs_f64() * 1000.0);
+        drop(aux);
+        assert_eq!(owner.stats().aux_jobs, 0);
+    }
+
+    #[test]
+    fn main_decode_slowdown_under_aux_load_is_bounded() {
+        let owner = coordinator();
+        let alone = (0..3).map(|_| main_decode(owner)).min().unwrap();
+
+        let done = Arc::new(AtomicBool::new(false));
+        let workers = thread::available_parallelism().map_or(2, |n| n.get()) * 2;
+        let handles: Vec<_> = (0..workers).map(|_| {
+            let done = done.clone();
+            thread::spawn(move || {
+                let aux = owner.register_aux();
+                let mut chunk = AUX_CHUNK_FULL;
+                while !done.load(Ordering::Relaxed) {
+                    work(chunk);
+                    chunk = aux.pace();
+                }
+            })
+        }).collect();
+        let contended = main_decode(owner);
+        done.store(true, Ordering::Relaxed);
+        handles.into_iter().for_each(|h| h.join().unwrap());
+
+        let slowdown = contended.as_secs_f64() / alone.as_secs_f64();
+        assert!(slowdown <= MAX_SLOWDOWN, "main decode {alone:?} alone, {contended:?} with {workers} aux jobs ({slowdown:.2}x)");
+        let stats = owner.stats();
+        assert!(stats.aux_throttled_chunks > 0);
+        assert_eq!(stats.aux_jobs, 0);
+    }
+}
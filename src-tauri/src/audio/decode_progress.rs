@@ -0,0 +1,135 @@
+// src/audio/decode_progress.rs
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use crate::modules::api_schema::{events, Event};
+
+// =================================================================
+// ⏳ 解码进度：后台解码线程上报 decode-start / decode-progress / decode-complete
+// =================================================================
+// 与 playback-error 同一套路：引擎构造时拿到发送端，接收端留在音频 actor 统一发给前端。
+// 预载也会上报，前端按 path 对应到当前曲目。
+// Galaxy 的整曲缓存另发 cache-progress / cache-ready，按载入代次标记：只有当前代次的才发出，
+// 预载在被载入接管之前、以及切歌前排队未发的旧代次事件都会被丢弃。
+// 整曲解码完成后按实际样本数算出的时长与载入时的估算不符 (或估算为 0) 时，另发 duration-updated，同样按代次标记。
+const INTERVAL: Duration = Duration::from_millis(500);
+
+static CACHE_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// 每次载入或停止换一个新代次，旧代次的缓存事件随之作废
+pub fn next_generation() -> u64 { CACHE_GENERATION.fetch_add(1, Ordering::AcqRel) + 1 }
+pub fn is_current(generation: u64) -> bool { generation != 0 && generation == CACHE_GENERATION.load(Ordering::Acquire) }
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DecodeProgress {
+    pub path: String,
+    pub engine: &'static str,
+    pub percent: Option<f32>, // 拿不到时长估算时为 None
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CacheProgress {
+    pub generation: u64,
+    pub path: String,
+    pub percent: f32,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DurationUpdate {
+    pub generation: u64,
+    pub path: String,
+    pub duration: f64,
+}
+
+// 带着各自要发出的事件，actor 按变体发出
+#[derive(Clone, Debug)]
+pub enum ProgressEvent {
+    Decode(Event<DecodeProgress>, DecodeProgress),
+    Cache(Event<CacheProgress>, CacheProgress),
+    Duration(DurationUpdate),
+}
+
+impl ProgressEvent {
+    // 音频 actor 发出前再核对一次代次，切歌前已排队的旧事件在这里丢弃
+    pub fn is_stale(&self) -> bool {
+        match self {
+            Self::Decode(..) => false,
+            Self::Cache(_, cache) => !is_current(cache.generation),
+            Self::Duration(update) => !is_current(update.generation),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ProgressReporter {
+    tx: Sender<ProgressEvent>,
+    engine: &'static str,
+}
+
+impl ProgressReporter {
+    pub fn new(tx: Sender<ProgressEvent>, engine: &'static str) -> Self { Self { tx, engine } }
+
+    // expected 为预计的样本总数，0 表示未知
+    pub fn start(&self, path: &str, expected: usize) -> DecodeTracker {
+        let tracker = DecodeTracker { reporter: self.clone(), path: path.to_string(), expected, last: Instant::now(), cache: None };
+        tracker.send(events::DECODE_START, Some(0.0));
+        tracker
+    }
+
+    // 缓存在载入接管之前就已填满 (预载命中) 时由引擎补发
+    pub fn cache_ready(&self, generation: u64, path: &str) {
+        if !is_current(generation) { return; }
+        let _ = self.tx.send(ProgressEvent::Cache(events::CACHE_READY, CacheProgress { generation, path: path.to_string(), percent: 100.0 }));
+    }
+}
+
+pub struct DecodeTracker {
+    reporter: ProgressReporter,
+    path: String,
+    expected: usize,
+    last: Instant,
+    cache: Option<Arc<AtomicU64>>, // 所服务的载入代次，0 表示尚未被载入 (预载)
+}
+
+impl DecodeTracker {
+    // 同时上报整曲缓存的填充进度 (Galaxy)
+    pub fn with_cache(mut self, generation: Arc<AtomicU64>) -> Self {
+        self.cache = Some(generation);
+        self
+    }
+
+    fn send(&self, event: Event<DecodeProgress>, percent: Option<f32>) {
+        let progress = DecodeProgress { path: self.path.clone(), engine: self.reporter.engine, percent };
+        let _ = self.reporter.tx.send(ProgressEvent::Decode(event, progress));
+    }
+
+    fn send_cache(&self, event: Event<CacheProgress>, percent: f32) {
+        let Some(generation) = self.cache.as_ref().map(|g| g.load(Ordering::Acquire)) else { return };
+        if !is_current(generation) { return; }
+        let _ = self.reporter.tx.send(ProgressEvent::Cache(event, CacheProgress { generation, path: self.path.clone(), percent }));
+    }
+
+    // 按已解码样本数节流上报；估算偏短时封顶 99%，满格留给 complete
+    pub fn advance(&mut self, decoded: usize) {
+        if self.last.elapsed() < INTERVAL { return; }
+        self.last = Instant::now();
+        let percent = (self.expected > 0).then(|| (decoded as f32 / self.expected as f32 * 100.0).min(99.0));
+        self.send(events::DECODE_PROGRESS, percent);
+        if let Some(percent) = percent { self.send_cache(events::CACHE_PROGRESS, percent); }
+    }
+
+    // 解码完成后按实际样本数得出的精确时长；预载尚未被接管时不发，由载入时按缓存长度重算
+    pub fn duration_measured(&self, duration: f64) {
+        let Some(generation) = self.cache.as_ref().map(|g| g.load(Ordering::Acquire)) else { return };
+        if !is_current(generation) { return; }
+        let _ = self.reporter.tx.send(ProgressEvent::Duration(DurationUpdate { generation, path: self.path.clone(), duration }));
+    }
+
+    pub fn complete(self) {
+        self.send(events::DECODE_COMPLETE, Some(100.0));
+        self.send_cache(events::CACHE_READY, 100.0);
+    }
+}
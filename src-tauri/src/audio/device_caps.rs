@@ -0,0 +1,193 @@
+// src/audio/device_caps.rs
+
+use rodio::cpal::traits::DeviceTrait;
+use rodio::cpal::Device;
+use serde::Serialize;
+use std::collections::HashMap;
+use super::devices;
+
+// =================================================================
+// 🔌 声卡能力缓存：每个设备每次会话只探测一次，设备列表变动时整体失效
+// =================================================================
+// 部分 USB 声卡枚举 supported_output_configs 需要 200–800ms，
+// 声道钳制、测试音、流配置等调用方一律从这里取结果。
+
+const PROBE_RATES: &[u32] = &[44100, 48000, 88200, 96000, 176400, 192000, 352800, 384000];
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DeviceCapabilities {
+    pub device: String,
+    pub max_channels: u16,
+    pub sample_rates: Vec<u32>,
+    pub sample_formats: Vec<String>,
+    pub default_channels: u16,
+    pub default_sample_rate: u32,
+    pub default_sample_format: String,
+    pub probed_at: i64,
+    pub stale: bool,
+}
+
+// 探测后端抽象：生产环境走 cpal，便于在无硬件环境下替换
+pub trait CapabilityProbe {
+    fn probe(&self, device: &str) -> Result<DeviceCapabilities, String>;
+}
+
+pub struct CpalProbe;
+
+impl CapabilityProbe for CpalProbe {
+    fn probe(&self, device_id: &str) -> Result<DeviceCapabilities, String> {
+        let device = devices::find(&rodio::cpal::default_host(), device_id)?
+            .ok_or_else(|| "DEVICE_NOT_FOUND".to_string())?;
+        describe(device_id, &device)
+    }
+}
+
+pub fn describe(device_id: &str, device: &Device) -> Result<DeviceCapabilities, String> {
+    let default = device.default_output_config().map_err(|e| e.to_string())?;
+    let mut max_channels = default.channels();
+    let mut rates: Vec<u32> = vec![default.sample_rate().0];
+    let mut formats: Vec<String> = vec![format!("{:?}", default.sample_format())];
+
+    if let Ok(configs) = device.supported_output_configs() {
+        for range in configs {
+            max_channels = max_channels.max(range.channels());
+            let fmt = format!("{:?}", range.sample_format());
+            if !formats.contains(&fmt) { formats.push(fmt); }
+            for &rate in PROBE_RATES {
+                if (range.min_sample_rate().0..=range.max_sample_rate().0).contains(&rate) && !rates.contains(&rate) {
+                    rates.push(rate);
+                }
+            }
+        }
+    }
+    rates.sort_unstable();
+
+    Ok(DeviceCapabilities {
+        device: device_id.to_string(),
+        max_channels,
+        sample_rates: rates,
+        sample_formats: formats,
+        default_channels: default.channels(),
+        default_sample_rate: default.sample_rate().0,
+        default_sample_format: format!("{:?}", default.sample_format()),
+        probed_at: chrono::Local::now().timestamp_millis(),
+        stale: false,
+    })
+}
+
+#[derive(Default)]
+pub struct CapabilityCache {
+    entries: HashMap<String, DeviceCapabilities>,
+    known_devices: Vec<String>,
+}
+
+impl CapabilityCache {
+    // 命中且未失效直接返回；失效条目在下次取用时重新探测
+    pub fn get(&mut self, probe: &dyn CapabilityProbe, device: &str) -> Result<DeviceCapabilities, String> {
+        if let Some(caps) = self.entries.get(device).filter(|c| !c.stale) { return Ok(caps.clone()); }
+        self.refresh(probe, device)
+    }
+
+    pub fn refresh(&mut self, probe: &dyn CapabilityProbe, device: &str) -> Result<DeviceCapabilities, String> {
+        let caps = probe.probe(device)?;
+        self.entries.insert(device.to_string(), caps.clone());
+        Ok(caps)
+    }
+
+    // 设备列表与上次不同则把全部条目标记为过期，并移除已拔出的设备；返回是否发生变动
+    pub fn sync_device_list(&mut self, mut devices: Vec<String>) -> bool {
+        devices.sort();
+        if devices == self.known_devices { return false; }
+        self.entries.retain(|name, _| devices.contains(name));
+        self.entries.values_mut().for_each(|c| c.stale = true);
+        self.known_devices = devices;
+        true
+    }
+
+    pub fn report(&self) -> Vec<DeviceCapabilities> {
+        let mut entries: Vec<DeviceCapabilities> = self.entries.values().cloned().collect();
+        entries.sort_by(|a, b| a.device.cmp(&b.device));
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    // 按设备计数的替身后端：拔出的设备探测失败
+    #[derive(Default)]
+    struct CountingProbe {
+        present: RefCell<Vec<String>>,
+        probes: RefCell<HashMap<String, usize>>,
+    }
+
+    impl CountingProbe {
+        fn with(devices: &[&str]) -> Self {
+            Self { present: RefCell::new(devices.iter().map(|d| d.to_string()).collect()), ..Default::default() }
+        }
+        fn count(&self, device: &str) -> usize { self.probes.borrow().get(device).copied().unwrap_or(0) }
+        fn device_names(&self) -> Vec<String> { self.present.borrow().clone() }
+    }
+
+    impl CapabilityProbe for CountingProbe {
+        fn probe(&self, device: &str) -> Result<DeviceCapabilities, String> {
+            *self.probes.borrow_mut().entry(device.to_string()).or_default() += 1;
+            if !self.present.borrow().iter().any(|d| d == device) { return Err("DEVICE_NOT_FOUND".to_string()); }
+            Ok(DeviceCapabilities {
+                device: device.to_string(), max_channels: 8, sample_rates: vec![44100, 48000], sample_formats: vec!["F32".to_string()],
+                default_channels: 2, default_sample_rate: 48000, default_sample_format: "F32".to_string(),
+                probed_at: self.count(device) as i64, stale: false,
+            })
+        }
+    }
+
+    #[test]
+    fn get_probes_each_device_once_and_refresh_always_probes() {
+        let probe = CountingProbe::with(&["USB DAC", "Speakers"]);
+        let mut cache = CapabilityCache::default();
+        for _ in 0..3 {
+            assert_eq!(cache.get(&probe, "USB DAC").unwrap().max_channels, 8);
+            cache.get(&probe, "Speakers").unwrap();
+        }
+        assert_eq!((probe.count("USB DAC"), probe.count("Speakers")), (1, 1));
+
+        assert_eq!(cache.refresh(&probe, "USB DAC").unwrap().probed_at, 2);
+        cache.refresh(&probe, "USB DAC").unwrap();
+        assert_eq!(probe.count("USB DAC"), 3);
+        // 刷新后的结果同样进缓存
+        assert_eq!(cache.get(&probe, "USB DAC").unwrap().probed_at, 3);
+        assert_eq!(probe.count("USB DAC"), 3);
+
+        // 探测失败不留条目，下次仍会重试
+        assert_eq!(cache.get(&probe, "HDMI").unwrap_err(), "DEVICE_NOT_FOUND");
+        assert!(cache.get(&probe, "HDMI").is_err());
+        assert_eq!(probe.count("HDMI"), 2);
+    }
+
+    #[test]
+    fn device_list_changes_mark_entries_stale_and_drop_unplugged() {
+        let probe = CountingProbe::with(&["USB DAC", "Speakers"]);
+        let mut cache = CapabilityCache::default();
+        assert!(cache.sync_device_list(probe.device_names()));
+        // 同一组设备换个顺序不算变动
+        assert!(!cache.sync_device_list(vec!["Speakers".to_string(), "USB DAC".to_string()]));
+        cache.get(&probe, "USB DAC").unwrap();
+        cache.get(&probe, "Speakers").unwrap();
+
+        // 拔出 USB DAC、接入耳机
+        *probe.present.borrow_mut() = vec!["Speakers".to_string(), "Headphones".to_string()];
+        assert!(cache.sync_device_list(probe.device_names()));
+        let report = cache.report();
+        assert_eq!(report.iter().map(|c| (c.device.as_str(), c.stale)).collect::<Vec<_>>(), [("Speakers", true)]);
+
+        // 过期条目在下次取用时重新探测，报告随之恢复为新鲜
+        cache.get(&probe, "Speakers").unwrap();
+        assert_eq!(probe.count("Speakers"), 2);
+        cache.get(&probe, "Headphones").unwrap();
+        let report = cache.report();
+        assert_eq!(report.iter().map(|c| (c.device.as_str(), c.stale)).collect::<Vec<_>>(), [("Headphones", false), ("Speakers", false)]);
+        assert_eq!(probe.count("USB DAC"), 1);
+    }
+}
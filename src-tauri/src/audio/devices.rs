@@ -0,0 +1,75 @@
+// src/audio/devices.rs
+
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::cpal::{Device, Host};
+use serde::Serialize;
+use std::collections::HashMap;
+use super::device_caps;
+
+// =================================================================
+// 🎛️ 输出设备枚举：为每个设备分配 id，并附带声道数与采样率等详细信息
+// =================================================================
+// cpal 不提供持久的设备标识，id 由名字派生：首个同名设备的 id 就是名字本身 (与旧版按名字选择兼容)，
+// 其后的同名设备依枚举顺序加 "#2"、"#3" 后缀；读不出名字的设备记作 "#序号"。
+// 设备选择、巡检与能力缓存统一以 id 为键。
+
+#[derive(Serialize, Clone, Debug)]
+pub struct OutputDeviceInfo {
+    pub id: String,
+    pub name: Option<String>, // 读不出名字时为 None
+    pub is_default: bool,
+    pub is_current: bool,
+    pub max_channels: Option<u16>,
+    pub default_sample_rate: Option<u32>,
+    pub supported_sample_rates: Vec<u32>,
+    pub error: Option<String>, // 查询失败的原因；设备照常列出，只缺相应字段
+}
+
+// 按枚举顺序返回 (id, 名字, 设备)
+pub fn enumerate(host: &Host) -> Result<Vec<(String, Option<String>, Device)>, String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let devices = host.output_devices().map_err(|e| e.to_string())?;
+    Ok(devices.enumerate().map(|(index, device)| {
+        let name = device.name().ok();
+        let id = match &name {
+            Some(name) => {
+                let count = seen.entry(name.clone()).or_insert(0);
+                *count += 1;
+                if *count == 1 { name.clone() } else { format!("{}#{}", name, count) }
+            }
+            None => format!("#{}", index),
+        };
+        (id, name, device)
+    }).collect())
+}
+
+pub fn ids(host: &Host) -> Option<Vec<String>> {
+    enumerate(host).ok().map(|devices| devices.into_iter().map(|(id, _, _)| id).collect())
+}
+
+pub fn find(host: &Host, id: &str) -> Result<Option<Device>, String> {
+    Ok(enumerate(host)?.into_iter().find(|(device_id, _, _)| device_id == id).map(|(_, _, device)| device))
+}
+
+// 默认设备只能按名字判断，同名时记在第一个上；声道数与采样率由调用方按 id 提供 (走能力缓存，不在这里逐个探测)
+pub fn list<C>(host: &Host, current: &str, mut capabilities: C) -> Result<Vec<OutputDeviceInfo>, String>
+where C: FnMut(&str) -> Result<device_caps::DeviceCapabilities, String> {
+    let default_name = host.default_output_device().and_then(|d| d.name().ok());
+    Ok(enumerate(host)?.into_iter().map(|(id, name, _)| {
+        let is_default = default_name.is_some() && name == default_name && name.as_deref() == Some(id.as_str());
+        let mut info = OutputDeviceInfo {
+            is_current: id == current || (current == "Default" && is_default),
+            id, name, is_default,
+            max_channels: None, default_sample_rate: None, supported_sample_rates: Vec::new(), error: None,
+        };
+        match capabilities(&info.id) {
+            Ok(caps) => {
+                info.max_channels = Some(caps.max_channels);
+                info.default_sample_rate = Some(caps.default_sample_rate);
+                info.supported_sample_rates = caps.sample_rates;
+            }
+            Err(e) => info.error = Some(e),
+        }
+        info
+    }).collect())
+}
@@ -0,0 +1,1261 @@
+// src/audio/dsp.rs
+
+use biquad::{Biquad, Coefficients, DirectForm2Transposed, ToHertz, Type};
+use rodio::Source;
+use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use super::analysis;
+use super::dsp_registry::{hash_params, DspStage, StageMeter};
+
+// =================================================================
+// 🎛️ 可复用 DSP 级：两个引擎都在各自音源链中插入同一套 Source 包装器
+// =================================================================
+// 参数放在共享设置里并带版本号，音频线程每块比较一次版本，变化时才重建系数，
+// 因此调参即时生效且无需重启 Sink；seek 会重建整条链，滤波器状态随之清零。
+
+pub const PARAM_CHECK_STRIDE: usize = 512;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EqBandType { Peak, LowShelf, HighShelf, LowPass, HighPass }
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct EqBand {
+    #[serde(rename = "type")]
+    pub kind: EqBandType,
+    pub freq: f32,
+    pub q: f32,
+    #[serde(default)]
+    pub gain_db: f32,
+}
+
+pub const MAX_EQ_BANDS: usize = 32;
+
+pub fn validate_bands(bands: &[EqBand], sample_rate: u32) -> Result<(), String> {
+    if bands.len() > MAX_EQ_BANDS { return Err(format!("EQ_TOO_MANY_BANDS: at most {} bands are supported", MAX_EQ_BANDS)); }
+    let nyquist = sample_rate as f32 / 2.0;
+    for (i, band) in bands.iter().enumerate() {
+        if !band.freq.is_finite() || band.freq <= 0.0 { return Err(format!("EQ_INVALID_FREQ: band {} has frequency {}", i, band.freq)); }
+        if band.freq >= nyquist {
+            return Err(format!("EQ_ABOVE_NYQUIST: band {} at {}Hz exceeds Nyquist ({}Hz at {}Hz sample rate)", i, band.freq, nyquist, sample_rate));
+        }
+        if !band.q.is_finite() || band.q <= 0.0 { return Err(format!("EQ_INVALID_Q: band {} has Q {}", i, band.q)); }
+        if !band.gain_db.is_finite() || band.gain_db.abs() > 24.0 { return Err(format!("EQ_INVALID_GAIN: band {} gain must be within ±24dB", i)); }
+    }
+    Ok(())
+}
+
+fn band_coefficients(band: &EqBand, sample_rate: u32) -> Option<Coefficients<f32>> {
+    let kind = match band.kind {
+        EqBandType::Peak => Type::PeakingEQ(band.gain_db),
+        EqBandType::LowShelf => Type::LowShelf(band.gain_db),
+        EqBandType::HighShelf => Type::HighShelf(band.gain_db),
+        EqBandType::LowPass => Type::LowPass,
+        EqBandType::HighPass => Type::HighPass,
+    };
+    Coefficients::<f32>::from_params(kind, (sample_rate as f32).hz(), band.freq.hz(), band.q).ok()
+}
+
+// 带版本号的共享参数槽
+pub struct Shared<T: Clone> {
+    value: RwLock<T>,
+    version: AtomicU64,
+}
+
+impl<T: Clone> Shared<T> {
+    pub fn new(value: T) -> Self { Self { value: RwLock::new(value), version: AtomicU64::new(0) } }
+    pub fn set(&self, value: T) {
+        *self.value.write().unwrap_or_else(|e| e.into_inner()) = value;
+        self.version.fetch_add(1, Ordering::Release);
+    }
+    pub fn get(&self) -> T { self.value.read().unwrap_or_else(|e| e.into_inner()).clone() }
+    pub fn version(&self) -> u64 { self.version.load(Ordering::Acquire) }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct BassBoost {
+    pub gain_db: f32,
+    pub freq: f32,
+}
+
+impl Default for BassBoost {
+    fn default() -> Self { Self { gain_db: 0.0, freq: 80.0 } }
+}
+
+pub const MAX_STEREO_WIDTH: f32 = 2.0;
+
+pub const PREAMP_MAX_DB: f32 = 20.0;
+
+// 音量滑块 → 增益：对数曲线把 0–1 映射到 -60dB…0dB，0 仍是完全静音
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeCurve { #[default] Linear, Logarithmic }
+
+pub const VOLUME_CURVE_RANGE_DB: f32 = 60.0;
+
+impl VolumeCurve {
+    pub fn parse(curve: &str) -> Result<Self, String> {
+        match curve {
+            "linear" => Ok(Self::Linear),
+            "logarithmic" => Ok(Self::Logarithmic),
+            _ => Err(format!("UNKNOWN_VOLUME_CURVE: {}", curve)),
+        }
+    }
+
+    pub fn gain(self, slider: f32) -> f32 {
+        match self {
+            Self::Linear => slider,
+            Self::Logarithmic if slider <= 0.0 => 0.0,
+            Self::Logarithmic => 10f32.powf((slider - 1.0) * VOLUME_CURVE_RANGE_DB / 20.0),
+        }
+    }
+
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("volume_curve.json")).ok()
+            .and_then(|json| serde_json::from_str::<Self>(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(dir.join("volume_curve.json"), json).map_err(|e| e.to_string())
+    }
+}
+
+// 声像平衡：-1.0 全左，1.0 全右；只衰减对侧，居中时两侧都是单位增益
+pub fn balance_gains(balance: f32) -> (f32, f32) { ((1.0 - balance).min(1.0), (1.0 + balance).min(1.0)) }
+
+pub fn validate_preamp(gain_db: f32) -> Result<(), String> {
+    if !gain_db.is_finite() || !(-PREAMP_MAX_DB..=PREAMP_MAX_DB).contains(&gain_db) {
+        return Err(format!("PREAMP_INVALID_GAIN: gain must be within ±{}dB", PREAMP_MAX_DB));
+    }
+    Ok(())
+}
+
+// 低音管理分频点：LFE 声道低通，12dB/oct 为单级 Butterworth，24dB/oct 为两级级联
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LfeCrossover {
+    pub freq: f32,
+    pub slope_db: u8,
+}
+
+impl Default for LfeCrossover {
+    fn default() -> Self { Self { freq: 120.0, slope_db: 24 } }
+}
+
+impl LfeCrossover {
+    pub fn stages(&self) -> usize { if self.slope_db >= 24 { 2 } else { 1 } }
+
+    pub fn coefficients(&self, sample_rate: u32) -> Option<Coefficients<f32>> {
+        Coefficients::<f32>::from_params(Type::LowPass, (sample_rate as f32).hz(), self.freq.hz(), std::f32::consts::FRAC_1_SQRT_2).ok()
+    }
+}
+
+pub fn validate_lfe_crossover(crossover: &LfeCrossover, sample_rate: u32) -> Result<(), String> {
+    if crossover.slope_db != 12 && crossover.slope_db != 24 { return Err("LFE_INVALID_SLOPE: slope must be 12 or 24 dB/oct".to_string()); }
+    if !crossover.freq.is_finite() || !(40.0..=300.0).contains(&crossover.freq) || crossover.freq >= sample_rate as f32 / 2.0 {
+        return Err(format!("LFE_INVALID_FREQ: {}Hz is outside 40–300Hz", crossover.freq));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Crossfeed {
+    pub enabled: bool,
+    pub level: u8, // 1–3，越大越强
+}
+
+impl Default for Crossfeed {
+    fn default() -> Self { Self { enabled: false, level: 2 } }
+}
+
+// bs2b 三档 (截止频率 Hz, 串音衰减 dB)，衰减越小串音越多
+const CROSSFEED_LEVELS: [(f32, f32); 3] = [(650.0, 9.5), (700.0, 6.0), (700.0, 4.5)];
+const CROSSFEED_DELAY_SECS: f32 = 0.0003;
+
+pub fn validate_crossfeed(crossfeed: &Crossfeed) -> Result<(), String> {
+    if !(1..=CROSSFEED_LEVELS.len() as u8).contains(&crossfeed.level) {
+        return Err(format!("CROSSFEED_INVALID_LEVEL: level must be 1–{}", CROSSFEED_LEVELS.len()));
+    }
+    Ok(())
+}
+
+// 上混矩阵参数：各电平是乘在内置系数上的倍率，1.0 即原始配比
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct UpmixParams {
+    pub rear_delay_ms: f32,
+    pub rear_level: f32,
+    pub center_level: f32,
+    pub lfe_level: f32,
+}
+
+impl Default for UpmixParams {
+    fn default() -> Self { Self { rear_delay_ms: 20.0, rear_level: 1.0, center_level: 1.0, lfe_level: 1.0 } }
+}
+
+pub const UPMIX_MAX_DELAY_MS: f32 = 50.0;
+pub const UPMIX_MAX_LEVEL: f32 = 2.0;
+
+impl UpmixParams {
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("upmix_params.json")).ok()
+            .and_then(|json| serde_json::from_str::<Self>(&json).ok())
+            .filter(|p| validate_upmix(p).is_ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(dir.join("upmix_params.json"), json).map_err(|e| e.to_string())
+    }
+}
+
+// 环绕声道排布：map[角色] = 7.1 输出中的位置，角色按 L R C LFE Ls Rs Lb Rb 排列；
+// 5.1 输出取前六个角色映射值的相对次序，一张表同时描述两种声道数
+pub const CHANNEL_ROLES: [&str; 8] = ["L", "R", "C", "LFE", "Ls", "Rs", "Lb", "Rb"];
+pub const LFE_ROLE: usize = 3;
+const FILM_MAP: [u8; 8] = [0, 1, 4, 5, 2, 3, 6, 7]; // L R Ls Rs C LFE
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutKind { Smpte, Film, Custom }
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ChannelLayout {
+    pub kind: LayoutKind,
+    pub map: [u8; 8],
+}
+
+impl Default for ChannelLayout {
+    fn default() -> Self { Self { kind: LayoutKind::Smpte, map: [0, 1, 2, 3, 4, 5, 6, 7] } }
+}
+
+impl ChannelLayout {
+    // custom 接受 6 项 (仅 5.1，后两路保持原位) 或 8 项映射
+    pub fn parse(kind: &str, map: Option<Vec<u8>>) -> Result<Self, String> {
+        match kind {
+            "smpte" => Ok(Self::default()),
+            "film" => Ok(Self { kind: LayoutKind::Film, map: FILM_MAP }),
+            "custom" => {
+                let mut map = map.ok_or_else(|| "LAYOUT_MAP_REQUIRED".to_string())?;
+                if map.len() == 6 { map.extend([6, 7]); }
+                let map: [u8; 8] = map.try_into().map_err(|_| "LAYOUT_INVALID_MAP: expected 6 or 8 entries".to_string())?;
+                let layout = Self { kind: LayoutKind::Custom, map };
+                layout.validate()?;
+                Ok(layout)
+            }
+            _ => Err(format!("UNKNOWN_LAYOUT: {}", kind)),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut seen = [false; 8];
+        for &slot in &self.map {
+            if slot >= 8 || std::mem::replace(&mut seen[slot as usize], true) {
+                return Err(format!("LAYOUT_INVALID_MAP: {:?} is not a permutation of 0–7", self.map));
+            }
+        }
+        Ok(())
+    }
+
+    // 各角色在 channels 声道输出中的位置
+    pub fn slots(&self, channels: usize) -> [usize; 8] {
+        let channels = channels.min(8);
+        let mut slots = [0; 8];
+        for (role, slot) in slots.iter_mut().enumerate().take(channels) {
+            *slot = (0..channels).filter(|&other| self.map[other] < self.map[role]).count();
+        }
+        slots
+    }
+
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("channel_layout.json")).ok()
+            .and_then(|json| serde_json::from_str::<Self>(&json).ok())
+            .filter(|l| l.validate().is_ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(dir.join("channel_layout.json"), json).map_err(|e| e.to_string())
+    }
+}
+
+pub fn validate_upmix(params: &UpmixParams) -> Result<(), String> {
+    if !params.rear_delay_ms.is_finite() || !(0.0..=UPMIX_MAX_DELAY_MS).contains(&params.rear_delay_ms) {
+        return Err(format!("UPMIX_INVALID_DELAY: rear delay must be within 0–{}ms", UPMIX_MAX_DELAY_MS));
+    }
+    for (name, level) in [("rear_level", params.rear_level), ("center_level", params.center_level), ("lfe_level", params.lfe_level)] {
+        if !level.is_finite() || !(0.0..=UPMIX_MAX_LEVEL).contains(&level) {
+            return Err(format!("UPMIX_INVALID_LEVEL: {} must be within 0–{}", name, UPMIX_MAX_LEVEL));
+        }
+    }
+    Ok(())
+}
+
+// 输出抖动：量化到设备位深前叠加 TPDF 噪声，把截断失真变成平坦的底噪
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Dither {
+    pub enabled: bool,
+    pub bits: u8,
+}
+
+impl Default for Dither {
+    fn default() -> Self { Self { enabled: false, bits: 16 } }
+}
+
+impl Dither {
+    pub fn lsb(&self) -> f32 { 1.0 / (1u32 << (self.bits.clamp(2, 31) - 1)) as f32 }
+}
+
+pub fn validate_dither(dither: &Dither) -> Result<(), String> {
+    if !(8..=24).contains(&dither.bits) { return Err("DITHER_INVALID_BITS: bits must be within 8–24".to_string()); }
+    Ok(())
+}
+
+// 播放 / 暂停 (含停止) / seek / 载入时的淡变时长，由 UpmixSource 的状态增益执行；0 表示直接切换
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct FadeConfig {
+    pub play_ms: u32,
+    pub pause_ms: u32,
+    pub seek_ms: u32,
+    pub load_ms: u32,
+}
+
+pub const DEFAULT_FADE_MS: u32 = 30;
+pub const FADE_MAX_MS: u32 = 5000;
+
+impl Default for FadeConfig {
+    fn default() -> Self { Self { play_ms: DEFAULT_FADE_MS, pause_ms: DEFAULT_FADE_MS, seek_ms: DEFAULT_FADE_MS, load_ms: DEFAULT_FADE_MS } }
+}
+
+impl FadeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if [self.play_ms, self.pause_ms, self.seek_ms, self.load_ms].iter().any(|&ms| ms > FADE_MAX_MS) {
+            return Err(format!("FADE_INVALID_DURATION: fades must be within 0–{}ms", FADE_MAX_MS));
+        }
+        Ok(())
+    }
+
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("fade_config.json")).ok()
+            .and_then(|json| serde_json::from_str::<Self>(&json).ok())
+            .filter(|f| f.validate().is_ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(dir.join("fade_config.json"), json).map_err(|e| e.to_string())
+    }
+}
+
+// 每帧状态增益的步长；0ms 时一帧到位
+pub fn fade_step(sample_rate: u32, fade_ms: u32) -> f32 {
+    if fade_ms == 0 { 1.0 } else { 1000.0 / (sample_rate.max(1) as f32 * fade_ms as f32) }
+}
+
+// 实时响度拉平：与 ReplayGain 标签无关，按瞬时响度缓慢把电平推向目标
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct AutoLevel {
+    pub enabled: bool,
+    pub target_lufs: f32,
+}
+
+impl Default for AutoLevel {
+    fn default() -> Self { Self { enabled: false, target_lufs: AUTO_LEVEL_DEFAULT_TARGET } }
+}
+
+pub const AUTO_LEVEL_DEFAULT_TARGET: f32 = -18.0;
+pub const AUTO_LEVEL_TARGET_RANGE: (f32, f32) = (-36.0, -6.0);
+
+pub fn validate_auto_level(auto_level: &AutoLevel) -> Result<(), String> {
+    let (lo, hi) = AUTO_LEVEL_TARGET_RANGE;
+    if !auto_level.target_lufs.is_finite() || !(lo..=hi).contains(&auto_level.target_lufs) {
+        return Err(format!("AUTO_LEVEL_INVALID_TARGET: target must be within {}–{} LUFS", lo, hi));
+    }
+    Ok(())
+}
+
+// 测量窗口与当前修正量放在引擎共享设置里：seek 重建链路时接着用，载入新曲目时清零
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AutoLevelState {
+    epoch: u64,
+    gain_db: f32,
+    hops: [f64; 4],
+    hops_seen: usize,
+}
+
+// 两个独立均匀分布相减得到 ±1 LSB 的三角分布
+pub struct TpdfDither { state: u64 }
+
+impl TpdfDither {
+    pub fn new(seed: u64) -> Self { Self { state: seed | 1 } }
+
+    #[inline(always)]
+    fn next_unit(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    #[inline(always)]
+    pub fn noise(&mut self, lsb: f32) -> f32 { (self.next_unit() - self.next_unit()) * lsb }
+}
+
+// 引擎持有的全部 DSP 参数；逐样本读取的标量用原子量，免去读锁
+pub struct DspSettings {
+    pub eq: Shared<Vec<EqBand>>,
+    pub bass: Shared<BassBoost>,
+    pub stereo_width: AtomicU32, // f32 位模式，1.0 为原样
+    pub preamp_db: AtomicU32, // f32 位模式，0dB 为直通
+    pub replaygain_db: AtomicU32, // f32 位模式，随曲目载入更新
+    pub balance: AtomicU32, // f32 位模式，0.0 居中
+    pub crossfeed: Shared<Crossfeed>,
+    pub lfe_crossover: Shared<LfeCrossover>,
+    pub upmix: Shared<UpmixParams>,
+    pub limiter_enabled: AtomicBool,
+    pub limiter_reduction: AtomicU32, // f32 位模式，最近一次上报的增益衰减 dB
+    pub dither: Shared<Dither>,
+    pub downmix_lfe: AtomicBool, // 多声道折叠为立体声时是否并入 LFE
+    pub channel_layout: Shared<ChannelLayout>,
+    pub auto_level: Shared<AutoLevel>,
+    pub auto_level_state: Mutex<AutoLevelState>,
+    pub fade_ms: AtomicU32, // 当前这次转场的淡变时长，引擎在切换播放状态前写入
+}
+
+impl DspSettings {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self { eq: Shared::new(Vec::new()), bass: Shared::new(BassBoost::default()), stereo_width: AtomicU32::new(1f32.to_bits()), preamp_db: AtomicU32::new(0f32.to_bits()), replaygain_db: AtomicU32::new(0f32.to_bits()), balance: AtomicU32::new(0f32.to_bits()), crossfeed: Shared::new(Crossfeed::default()), lfe_crossover: Shared::new(LfeCrossover::default()), upmix: Shared::new(UpmixParams::default()), limiter_enabled: AtomicBool::new(true), limiter_reduction: AtomicU32::new(0f32.to_bits()), dither: Shared::new(Dither::default()), downmix_lfe: AtomicBool::new(false), channel_layout: Shared::new(ChannelLayout::default()), auto_level: Shared::new(AutoLevel::default()), auto_level_state: Mutex::new(AutoLevelState::default()), fade_ms: AtomicU32::new(DEFAULT_FADE_MS) })
+    }
+
+    pub fn stereo_width(&self) -> f32 { f32::from_bits(self.stereo_width.load(Ordering::Relaxed)) }
+    pub fn limiter_reduction_db(&self) -> f32 { f32::from_bits(self.limiter_reduction.load(Ordering::Relaxed)) }
+    pub fn preamp_db(&self) -> f32 { f32::from_bits(self.preamp_db.load(Ordering::Relaxed)) }
+    pub fn balance(&self) -> f32 { f32::from_bits(self.balance.load(Ordering::Relaxed)) }
+    pub fn replaygain_db(&self) -> f32 { f32::from_bits(self.replaygain_db.load(Ordering::Relaxed)) }
+
+    // 前级总增益 (线性)：各增益源按 dB 相加即线性相乘
+    pub fn pre_gain(&self) -> f32 { 10f32.powf((self.preamp_db() + self.replaygain_db()) / 20.0) }
+
+    // 新曲目不沿用上一首的测量；仍在运行的旧链路看到 epoch 变化后不再回写
+    pub fn reset_auto_level(&self) {
+        let mut state = self.auto_level_state.lock().unwrap_or_else(|e| e.into_inner());
+        *state = AutoLevelState { epoch: state.epoch + 1, ..Default::default() };
+    }
+
+    // 响度拉平 / 前级 / 均衡 / 低音 / 串音 / 声场 / 平衡全部处于旁路状态
+    pub fn is_transparent(&self) -> bool {
+        !self.auto_level.get().enabled && self.pre_gain() == 1.0 && self.balance() == 0.0 && self.eq.get().is_empty() && self.bass.get().gain_db == 0.0 && !self.crossfeed.get().enabled && self.stereo_width() == 1.0
+    }
+}
+
+pub fn validate_bass(bass: &BassBoost, sample_rate: u32) -> Result<(), String> {
+    if !bass.gain_db.is_finite() || !(0.0..=BASS_MAX_GAIN_DB).contains(&bass.gain_db) {
+        return Err(format!("BASS_INVALID_GAIN: gain must be within 0–{}dB", BASS_MAX_GAIN_DB));
+    }
+    if !bass.freq.is_finite() || bass.freq <= 0.0 || bass.freq >= sample_rate as f32 / 2.0 {
+        return Err(format!("BASS_INVALID_FREQ: {}Hz is outside (0, Nyquist) at {}Hz sample rate", bass.freq, sample_rate));
+    }
+    Ok(())
+}
+
+// 引擎共用的参数化级联，新级按信号顺序追加在这里，两个引擎的链自动保持一致；
+// target_channels 为 UpmixSource 的声道配置码，仅纯立体声输出才启用耳机类处理
+pub type DspChain<I> = CrossfeedSource<BassBoostSource<ParametricEqSource<PreampSource<AutoLevelSource<I>>>>>;
+
+pub fn chain<I: Source<Item = f32>>(input: I, settings: Arc<DspSettings>, target_channels: u16) -> (DspChain<I>, Vec<Arc<DspStage>>) {
+    let leveled = AutoLevelSource::new(input, settings.clone());
+    let auto_level_stage = leveled.stage();
+    let amplified = PreampSource::new(leveled, settings.clone());
+    let preamp_stage = amplified.stage();
+    let equalized = ParametricEqSource::new(amplified, settings.clone());
+    let eq_stage = equalized.stage();
+    let boosted = BassBoostSource::new(equalized, settings.clone());
+    let bass_stage = boosted.stage();
+    let crossfed = CrossfeedSource::new(boosted, settings, target_channels == 2);
+    let crossfeed_stage = crossfed.stage();
+    (crossfed, vec![auto_level_stage, preamp_stage, eq_stage, bass_stage, crossfeed_stage])
+}
+
+// =================================================================
+// 前视限幅器：所有输出声道联动，峰值到达前就开始压低增益
+// =================================================================
+// 延迟线长度即前视时间；窗口内峰值用单调队列维护，每帧摊还 O(1)。
+// 关闭时仍走同一条延迟线，只把增益换成静态软拐点，开关瞬间不会跳变延迟。
+const LIMITER_CEILING: f32 = 0.98;
+const LIMITER_LOOKAHEAD_SECS: f32 = 0.003;
+const LIMITER_RELEASE_SECS: f32 = 0.08;
+
+pub struct LookaheadLimiter {
+    channels: usize,
+    lookahead: usize,
+    delay: Vec<f32>, // [frame][channel] 环形缓冲
+    pos: usize,
+    frame_no: u64,
+    peaks: VecDeque<(u64, f32)>,
+    gain: f32,
+    attack: f32,
+    release: f32,
+}
+
+impl LookaheadLimiter {
+    pub fn new(channels: usize, sample_rate: u32) -> Self {
+        let lookahead = ((sample_rate as f32 * LIMITER_LOOKAHEAD_SECS) as usize).max(1);
+        Self {
+            channels: channels.max(1), lookahead,
+            delay: vec![0.0; lookahead * channels.max(1)], pos: 0, frame_no: 0,
+            peaks: VecDeque::with_capacity(lookahead + 1), gain: 1.0,
+            // 前视窗口内走完约 5 个时间常数，峰值出延迟线时增益已基本到位
+            attack: (-5.0 / lookahead as f32).exp(),
+            release: (-1.0 / (sample_rate.max(1) as f32 * LIMITER_RELEASE_SECS)).exp(),
+        }
+    }
+
+    pub fn frame_no(&self) -> u64 { self.frame_no }
+
+    pub fn reduction_db(&self) -> f32 { -20.0 * self.gain.max(1e-6).log10() }
+
+    // 原地处理一帧：写入当前帧，取出前视时间之前的那一帧
+    pub fn process(&mut self, frame: &mut [f32], enabled: bool) {
+        let peak = frame.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        while self.peaks.back().is_some_and(|&(_, p)| p <= peak) { self.peaks.pop_back(); }
+        self.peaks.push_back((self.frame_no, peak));
+        while self.peaks.front().is_some_and(|&(i, _)| i + (self.lookahead as u64) < self.frame_no) { self.peaks.pop_front(); }
+
+        let window_peak = self.peaks.front().map(|&(_, p)| p).unwrap_or(0.0);
+        let target = if enabled && window_peak > LIMITER_CEILING { LIMITER_CEILING / window_peak } else { 1.0 };
+        let coef = if target < self.gain { self.attack } else { self.release };
+        self.gain = target + (self.gain - target) * coef;
+
+        let base = self.pos * self.channels;
+        for (c, sample) in frame.iter_mut().take(self.channels).enumerate() {
+            let delayed = std::mem::replace(&mut self.delay[base + c], *sample);
+            // 指数逼近的残差极小，末端钳位只兜底，正常情况下不会触发
+            *sample = if enabled { (delayed * self.gain).clamp(-1.0, 1.0) } else { static_knee(delayed) };
+        }
+        self.pos = (self.pos + 1) % self.lookahead;
+        self.frame_no += 1;
+    }
+}
+
+// 无前视的静态软拐点，限幅器关闭时的最后一道保护
+#[inline(always)]
+pub fn static_knee(val: f32) -> f32 {
+    let abs_val = val.abs();
+    if abs_val <= 0.98 { return val; }
+    let diff = abs_val - 0.98;
+    val.signum() * (0.98 + diff / (1.0 + diff * 8.0))
+}
+
+// 超过 ±1 的部分做平滑压缩，持续低频不会硬削成刺耳失真
+#[inline(always)]
+pub fn soft_clip(x: f32) -> f32 {
+    const KNEE: f32 = 0.9;
+    let a = x.abs();
+    if a <= KNEE { return x; }
+    let over = a - KNEE;
+    x.signum() * (KNEE + (1.0 - KNEE) * (over / (1.0 - KNEE)).tanh())
+}
+
+// =================================================================
+// 参数均衡：任意数量的 biquad 级联（每声道独立状态）
+// =================================================================
+pub struct ParametricEqSource<I: Source<Item = f32>> {
+    input: I,
+    channels: usize,
+    sample_rate: u32,
+    settings: Arc<DspSettings>,
+    seen_version: u64,
+    filters: Vec<Vec<DirectForm2Transposed<f32>>>, // [band][channel]
+    channel_idx: usize,
+    check_tick: usize,
+    meter: StageMeter,
+    busy: Duration,
+}
+
+impl<I: Source<Item = f32>> ParametricEqSource<I> {
+    pub fn new(input: I, settings: Arc<DspSettings>) -> Self {
+        let channels = input.channels().max(1) as usize;
+        let sample_rate = input.sample_rate();
+        let mut source = Self {
+            input, channels, sample_rate, settings,
+            seen_version: u64::MAX, filters: Vec::new(), channel_idx: 0, check_tick: 0,
+            meter: StageMeter::new("parametric_eq", false, 0),
+            busy: Duration::ZERO,
+        };
+        source.refresh();
+        source
+    }
+
+    pub fn stage(&self) -> Arc<DspStage> { self.meter.stage() }
+
+    fn refresh(&mut self) {
+        let version = self.settings.eq.version();
+        if version == self.seen_version { return; }
+        self.seen_version = version;
+        let bands = self.settings.eq.get();
+
+        // 已有滤波器只换系数、保留状态，避免调参瞬间的爆音
+        let coeffs: Vec<Coefficients<f32>> = bands.iter().filter_map(|b| band_coefficients(b, self.sample_rate)).collect();
+        self.filters.truncate(coeffs.len());
+        for (i, c) in coeffs.iter().enumerate() {
+            match self.filters.get_mut(i) {
+                Some(per_channel) => per_channel.iter_mut().for_each(|f| f.update_coefficients(*c)),
+                None => self.filters.push(vec![DirectForm2Transposed::<f32>::new(*c); self.channels]),
+            }
+        }
+        let hash = hash_params(bands.iter().map(|b| (b.kind, b.freq.to_bits(), b.q.to_bits(), b.gain_db.to_bits())).collect::<Vec<_>>());
+        self.meter.set_params(!self.filters.is_empty(), hash);
+    }
+}
+
+impl<I: Source<Item = f32>> Iterator for ParametricEqSource<I> {
+    type Item = f32;
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        if self.channel_idx == 0 {
+            self.check_tick += 1;
+            if self.check_tick >= PARAM_CHECK_STRIDE {
+                self.check_tick = 0;
+                self.refresh();
+                self.meter.record(std::mem::take(&mut self.busy));
+            }
+        }
+        let sample = self.input.next()?;
+        let ch = self.channel_idx;
+        self.channel_idx = (self.channel_idx + 1) % self.channels;
+        if self.filters.is_empty() { return Some(sample); }
+
+        let started = if self.check_tick == 0 && ch == 0 { Some(Instant::now()) } else { None };
+        let out = self.filters.iter_mut().fold(sample, |acc, band| band[ch].run(acc));
+        if let Some(t) = started { self.busy += t.elapsed() * (PARAM_CHECK_STRIDE * self.channels) as u32; }
+        Some(out)
+    }
+}
+
+impl<I: Source<Item = f32>> Source for ParametricEqSource<I> {
+    fn current_frame_len(&self) -> Option<usize> { self.input.current_frame_len() }
+    fn channels(&self) -> u16 { self.input.channels() }
+    fn sample_rate(&self) -> u32 { self.input.sample_rate() }
+    fn total_duration(&self) -> Option<Duration> { self.input.total_duration() }
+}
+
+// =================================================================
+// 实时响度拉平：K 计权瞬时响度 (400ms 窗口，100ms 步进) 驱动慢速增益修正
+// =================================================================
+// 测量取自解码后的原始信号，不受本级与后级增益影响，不存在反馈回路；
+// 压低快、抬升慢，都以数百毫秒的时间常数逼近，避免把音乐本身的动态抽平。
+// 低于门限的段落（静音、淡出）保持当前增益，峰值由最终限幅器兜底。
+const AUTO_LEVEL_HOP_SECS: f64 = 0.1;
+const AUTO_LEVEL_GATE_LUFS: f64 = -50.0;
+const AUTO_LEVEL_MAX_BOOST_DB: f32 = 12.0;
+const AUTO_LEVEL_MAX_CUT_DB: f32 = 18.0;
+const AUTO_LEVEL_ATTACK_SECS: f32 = 0.3; // 压低
+const AUTO_LEVEL_RELEASE_SECS: f32 = 0.8; // 抬升
+
+pub struct AutoLevelSource<I: Source<Item = f32>> {
+    input: I,
+    channels: usize,
+    settings: Arc<DspSettings>,
+    params: AutoLevel,
+    params_version: u64,
+    filters: Vec<[DirectForm2Transposed<f32>; 2]>,
+    hop_len: usize,
+    hop_frames: usize,
+    hop_energy: f64,
+    state: AutoLevelState,
+    target_db: f32,
+    gain: f32, // 线性，随 state.gain_db 更新
+    attack: f32,
+    release: f32,
+    channel_idx: usize,
+    meter: StageMeter,
+}
+
+impl<I: Source<Item = f32>> AutoLevelSource<I> {
+    pub fn new(input: I, settings: Arc<DspSettings>) -> Self {
+        let channels = input.channels().max(1) as usize;
+        let sample_rate = input.sample_rate().max(1);
+        let [shelf, highpass] = analysis::k_weighting(sample_rate);
+        let state = *settings.auto_level_state.lock().unwrap_or_else(|e| e.into_inner());
+        let coeff = |secs: f32| 1.0 - (-1.0 / (secs * sample_rate as f32)).exp();
+        let mut source = Self {
+            input, channels,
+            params: settings.auto_level.get(), params_version: settings.auto_level.version(), settings,
+            filters: (0..channels).map(|_| [DirectForm2Transposed::<f32>::new(shelf), DirectForm2Transposed::<f32>::new(highpass)]).collect(),
+            hop_len: ((sample_rate as f64 * AUTO_LEVEL_HOP_SECS) as usize).max(1), hop_frames: 0, hop_energy: 0.0,
+            state, target_db: state.gain_db, gain: 10f32.powf(state.gain_db / 20.0),
+            attack: coeff(AUTO_LEVEL_ATTACK_SECS), release: coeff(AUTO_LEVEL_RELEASE_SECS),
+            channel_idx: 0,
+            meter: StageMeter::new("auto_level", false, 0),
+        };
+        source.refresh();
+        source
+    }
+
+    pub fn stage(&self) -> Arc<DspStage> { self.meter.stage() }
+
+    fn refresh(&mut self) {
+        self.params = self.settings.auto_level.get();
+        self.meter.set_params(self.params.enabled, hash_params(self.params.target_lufs.to_bits()));
+        // 关闭后目标回到 0dB，同样平滑退出
+        if !self.params.enabled { self.target_db = 0.0; }
+    }
+
+    // 一个 100ms 子块结束：更新瞬时响度与目标增益，并把状态交回共享设置
+    fn finish_hop(&mut self) {
+        let s = &mut self.state;
+        s.hops.rotate_left(1);
+        s.hops[3] = self.hop_energy / self.hop_len as f64;
+        s.hops_seen += 1;
+        self.hop_energy = 0.0;
+        self.hop_frames = 0;
+        if self.params.enabled && s.hops_seen >= 4 {
+            let momentary = -0.691 + 10.0 * (s.hops.iter().sum::<f64>() / 4.0).max(1e-12).log10();
+            if momentary > AUTO_LEVEL_GATE_LUFS {
+                self.target_db = ((self.params.target_lufs as f64 - momentary) as f32).clamp(-AUTO_LEVEL_MAX_CUT_DB, AUTO_LEVEL_MAX_BOOST_DB);
+            }
+        }
+        // 音频线程不等锁：拿不到就留到下一个子块
+        if let Ok(mut shared) = self.settings.auto_level_state.try_lock() {
+            if shared.epoch == self.state.epoch { *shared = self.state; }
+        }
+    }
+
+    #[inline(always)]
+    fn step_gain(&mut self) {
+        let diff = self.target_db - self.state.gain_db;
+        if diff == 0.0 { return; }
+        let coeff = if diff < 0.0 { self.attack } else { self.release };
+        self.state.gain_db = if diff.abs() < 1e-3 { self.target_db } else { self.state.gain_db + diff * coeff };
+        self.gain = 10f32.powf(self.state.gain_db / 20.0);
+    }
+}
+
+impl<I: Source<Item = f32>> Iterator for AutoLevelSource<I> {
+    type Item = f32;
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        let ch = self.channel_idx;
+        if self.params.enabled {
+            let [shelf, highpass] = &mut self.filters[ch];
+            let weighted = highpass.run(shelf.run(sample)) as f64;
+            self.hop_energy += weighted * weighted;
+        }
+        self.channel_idx += 1;
+        if self.channel_idx == self.channels {
+            self.channel_idx = 0;
+            self.hop_frames += 1;
+            if self.hop_frames >= self.hop_len {
+                let version = self.settings.auto_level.version();
+                if version != self.params_version { self.params_version = version; self.refresh(); }
+                self.finish_hop();
+            }
+            self.step_gain();
+        }
+        Some(if self.gain == 1.0 { sample } else { sample * self.gain })
+    }
+}
+
+impl<I: Source<Item = f32>> Source for AutoLevelSource<I> {
+    fn current_frame_len(&self) -> Option<usize> { self.input.current_frame_len() }
+    fn channels(&self) -> u16 { self.input.channels() }
+    fn sample_rate(&self) -> u32 { self.input.sample_rate() }
+    fn total_duration(&self) -> Option<Duration> { self.input.total_duration() }
+}
+
+// =================================================================
+// 前级增益：EQ 之前的纯乘法级，与用户音量 (Sink::set_volume) 相互独立
+// =================================================================
+// 给后级的 EQ 提升与限幅器留出余量；增益变化按帧平滑逼近，避免拉动时出现拉链噪声。
+const PREAMP_SMOOTHING: f32 = 0.999;
+
+pub struct PreampSource<I: Source<Item = f32>> {
+    input: I,
+    channels: usize,
+    settings: Arc<DspSettings>,
+    current: f32,
+    target: f32,
+    channel_idx: usize,
+    check_tick: usize,
+    meter: StageMeter,
+}
+
+impl<I: Source<Item = f32>> PreampSource<I> {
+    pub fn new(input: I, settings: Arc<DspSettings>) -> Self {
+        let channels = input.channels().max(1) as usize;
+        let gain = settings.pre_gain();
+        let mut source = Self {
+            input, channels, settings, current: gain, target: gain, channel_idx: 0, check_tick: 0,
+            meter: StageMeter::new("preamp", false, 0),
+        };
+        source.refresh();
+        source
+    }
+
+    pub fn stage(&self) -> Arc<DspStage> { self.meter.stage() }
+
+    fn refresh(&mut self) {
+        self.target = self.settings.pre_gain();
+        self.meter.set_params(self.target != 1.0, hash_params(self.target.to_bits()));
+    }
+}
+
+impl<I: Source<Item = f32>> Iterator for PreampSource<I> {
+    type Item = f32;
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        if self.channel_idx == 0 {
+            self.check_tick += 1;
+            if self.check_tick >= PARAM_CHECK_STRIDE { self.check_tick = 0; self.refresh(); }
+            if self.current != self.target {
+                self.current = self.target + (self.current - self.target) * PREAMP_SMOOTHING;
+                if (self.current - self.target).abs() < 1e-5 { self.current = self.target; }
+            }
+        }
+        let sample = self.input.next()?;
+        self.channel_idx = (self.channel_idx + 1) % self.channels;
+        Some(if self.current == 1.0 { sample } else { sample * self.current })
+    }
+}
+
+impl<I: Source<Item = f32>> Source for PreampSource<I> {
+    fn current_frame_len(&self) -> Option<usize> { self.input.current_frame_len() }
+    fn channels(&self) -> u16 { self.input.channels() }
+    fn sample_rate(&self) -> u32 { self.input.sample_rate() }
+    fn total_duration(&self) -> Option<Duration> { self.input.total_duration() }
+}
+
+// =================================================================
+// 单声道复制为双声道：后级 DSP 与空间矩阵从此只需面对立体声帧
+// =================================================================
+pub struct MonoToStereoSource<I: Source<Item = f32>> {
+    input: I,
+    mono: bool,
+    pending: Option<f32>,
+    meter: StageMeter,
+}
+
+impl<I: Source<Item = f32>> MonoToStereoSource<I> {
+    pub fn new(input: I) -> Self {
+        let mono = input.channels() == 1;
+        Self { input, mono, pending: None, meter: StageMeter::new("mono_to_stereo", mono, 0) }
+    }
+
+    pub fn stage(&self) -> Arc<DspStage> { self.meter.stage() }
+}
+
+impl<I: Source<Item = f32>> Iterator for MonoToStereoSource<I> {
+    type Item = f32;
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        if !self.mono { return self.input.next(); }
+        if let Some(sample) = self.pending.take() { return Some(sample); }
+        let sample = self.input.next()?;
+        self.pending = Some(sample);
+        Some(sample)
+    }
+}
+
+impl<I: Source<Item = f32>> Source for MonoToStereoSource<I> {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { if self.mono { 2 } else { self.input.channels() } }
+    fn sample_rate(&self) -> u32 { self.input.sample_rate() }
+    fn total_duration(&self) -> Option<Duration> { self.input.total_duration() }
+}
+
+// =================================================================
+// 多声道折叠为立体声 (ITU-R BS.775)：中置与环绕 -3dB 并入左右，LFE 可选
+// =================================================================
+// 后级的 DSP 与空间矩阵只处理立体声帧，因此多于两声道的源一律先在这里折叠；
+// 两声道及以下直通。系数按单侧总和归一，所有声道同时满幅也不会越界。
+pub const DOWNMIX_LFE_GAIN: f32 = std::f32::consts::FRAC_1_SQRT_2;
+const MINUS_3DB: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[derive(Clone, Copy)]
+enum Speaker { Left, Right, Center, Lfe, SurroundLeft, SurroundRight, BackCenter }
+
+// 解码器按 WAVEFORMATEXTENSIBLE 顺序交付声道；7.1 为 后环绕 + 侧环绕，两组同样折入
+fn speaker_layout(channels: u16) -> &'static [Speaker] {
+    use Speaker::*;
+    match channels {
+        3 => &[Left, Right, Center],
+        4 => &[Left, Right, SurroundLeft, SurroundRight],
+        5 => &[Left, Right, Center, SurroundLeft, SurroundRight],
+        6 => &[Left, Right, Center, Lfe, SurroundLeft, SurroundRight],
+        7 => &[Left, Right, Center, Lfe, BackCenter, SurroundLeft, SurroundRight],
+        _ => &[Left, Right, Center, Lfe, SurroundLeft, SurroundRight, SurroundLeft, SurroundRight],
+    }
+}
+
+// 每个输入声道对 (L, R) 的系数；超出已知布局的声道丢弃
+fn downmix_matrix(channels: u16, include_lfe: bool) -> Vec<(f32, f32)> {
+    let layout = speaker_layout(channels);
+    let mut matrix: Vec<(f32, f32)> = (0..channels as usize).map(|i| match layout.get(i) {
+        Some(Speaker::Left) => (1.0, 0.0),
+        Some(Speaker::Right) => (0.0, 1.0),
+        Some(Speaker::Center) => (MINUS_3DB, MINUS_3DB),
+        Some(Speaker::Lfe) if include_lfe => (DOWNMIX_LFE_GAIN, DOWNMIX_LFE_GAIN),
+        Some(Speaker::SurroundLeft) => (MINUS_3DB, 0.0),
+        Some(Speaker::SurroundRight) => (0.0, MINUS_3DB),
+        Some(Speaker::BackCenter) => (0.5, 0.5), // 先 -3dB 分到两侧环绕，再随环绕 -3dB 并入
+        _ => (0.0, 0.0),
+    }).collect();
+    let norm = matrix.iter().map(|c| c.0).sum::<f32>().max(1.0);
+    for c in &mut matrix { *c = (c.0 / norm, c.1 / norm); }
+    matrix
+}
+
+pub struct DownmixSource<I: Source<Item = f32>> {
+    input: I,
+    matrix: Vec<(f32, f32)>, // 为空表示直通
+    matrix_lfe: Vec<(f32, f32)>,
+    settings: Arc<DspSettings>,
+    pending_right: Option<f32>,
+    meter: StageMeter,
+}
+
+impl<I: Source<Item = f32>> DownmixSource<I> {
+    pub fn new(input: I, settings: Arc<DspSettings>) -> Self {
+        let channels = input.channels();
+        let folding = channels > 2;
+        let (matrix, matrix_lfe) = if folding { (downmix_matrix(channels, false), downmix_matrix(channels, true)) } else { (Vec::new(), Vec::new()) };
+        Self { input, matrix, matrix_lfe, settings, pending_right: None, meter: StageMeter::new("downmix", folding, hash_params(channels)) }
+    }
+
+    pub fn stage(&self) -> Arc<DspStage> { self.meter.stage() }
+
+    fn fold_frame(&mut self) -> Option<(f32, f32)> {
+        let matrix = if self.settings.downmix_lfe.load(Ordering::Relaxed) { &self.matrix_lfe } else { &self.matrix };
+        let first = self.input.next()?;
+        let (mut l, mut r) = (first * matrix[0].0, first * matrix[0].1);
+        // 末尾残缺帧按静音补齐
+        for &(cl, cr) in &matrix[1..] {
+            let sample = self.input.next().unwrap_or(0.0);
+            l += sample * cl;
+            r += sample * cr;
+        }
+        Some((l, r))
+    }
+}
+
+impl<I: Source<Item = f32>> Iterator for DownmixSource<I> {
+    type Item = f32;
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        if self.matrix.is_empty() { return self.input.next(); }
+        if let Some(r) = self.pending_right.take() { return Some(r); }
+        let (l, r) = self.fold_frame()?;
+        self.pending_right = Some(r);
+        Some(l)
+    }
+}
+
+impl<I: Source<Item = f32>> Source for DownmixSource<I> {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { if self.matrix.is_empty() { self.input.channels() } else { 2 } }
+    fn sample_rate(&self) -> u32 { self.input.sample_rate() }
+    fn total_duration(&self) -> Option<Duration> { self.input.total_duration() }
+}
+
+// =================================================================
+// 低音增强：单个低架滤波；增益为 0 时完全直通，位流不变
+// =================================================================
+pub const BASS_LIMIT_THRESHOLD_DB: f32 = 6.0;
+pub const BASS_MAX_GAIN_DB: f32 = 15.0;
+
+pub struct BassBoostSource<I: Source<Item = f32>> {
+    input: I,
+    channels: usize,
+    sample_rate: u32,
+    settings: Arc<DspSettings>,
+    seen_version: u64,
+    filters: Option<Vec<DirectForm2Transposed<f32>>>,
+    limit: bool,
+    channel_idx: usize,
+    check_tick: usize,
+    meter: StageMeter,
+}
+
+impl<I: Source<Item = f32>> BassBoostSource<I> {
+    pub fn new(input: I, settings: Arc<DspSettings>) -> Self {
+        let channels = input.channels().max(1) as usize;
+        let sample_rate = input.sample_rate();
+        let mut source = Self {
+            input, channels, sample_rate, settings,
+            seen_version: u64::MAX, filters: None, limit: false, channel_idx: 0, check_tick: 0,
+            meter: StageMeter::new("bass_boost", false, 0),
+        };
+        source.refresh();
+        source
+    }
+
+    pub fn stage(&self) -> Arc<DspStage> { self.meter.stage() }
+
+    fn refresh(&mut self) {
+        let version = self.settings.bass.version();
+        if version == self.seen_version { return; }
+        self.seen_version = version;
+        let bass = self.settings.bass.get();
+
+        let coeffs = if bass.gain_db == 0.0 { None } else {
+            Coefficients::<f32>::from_params(Type::LowShelf(bass.gain_db), (self.sample_rate as f32).hz(), bass.freq.hz(), 0.707).ok()
+        };
+        self.filters = match (coeffs, self.filters.take()) {
+            (Some(c), Some(mut existing)) => { existing.iter_mut().for_each(|f| f.update_coefficients(c)); Some(existing) }
+            (Some(c), None) => Some(vec![DirectForm2Transposed::<f32>::new(c); self.channels]),
+            (None, _) => None,
+        };
+        self.limit = bass.gain_db > BASS_LIMIT_THRESHOLD_DB;
+        self.meter.set_params(self.filters.is_some(), hash_params((bass.gain_db.to_bits(), bass.freq.to_bits())));
+    }
+}
+
+impl<I: Source<Item = f32>> Iterator for BassBoostSource<I> {
+    type Item = f32;
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        if self.channel_idx == 0 {
+            self.check_tick += 1;
+            if self.check_tick >= PARAM_CHECK_STRIDE { self.check_tick = 0; self.refresh(); }
+        }
+        let sample = self.input.next()?;
+        let ch = self.channel_idx;
+        self.channel_idx = (self.channel_idx + 1) % self.channels;
+        let Some(filters) = self.filters.as_mut() else { return Some(sample); };
+        let out = filters[ch].run(sample);
+        Some(if self.limit { soft_clip(out) } else { out })
+    }
+}
+
+impl<I: Source<Item = f32>> Source for BassBoostSource<I> {
+    fn current_frame_len(&self) -> Option<usize> { self.input.current_frame_len() }
+    fn channels(&self) -> u16 { self.input.channels() }
+    fn sample_rate(&self) -> u32 { self.input.sample_rate() }
+    fn total_duration(&self) -> Option<Duration> { self.input.total_duration() }
+}
+
+// =================================================================
+// 耳机串音 (bs2b 风格)：对侧声道低通、衰减、微延迟后混入本侧
+// =================================================================
+// 仅在立体声输入 + 立体声输出时生效，环绕模式下整级直通。
+struct CrossfeedState {
+    lowpass: [DirectForm2Transposed<f32>; 2],
+    delay: [Vec<f32>; 2],
+    delay_pos: usize,
+    cross_gain: f32,
+    norm: f32,
+}
+
+pub struct CrossfeedSource<I: Source<Item = f32>> {
+    input: I,
+    sample_rate: u32,
+    applicable: bool,
+    settings: Arc<DspSettings>,
+    seen_version: u64,
+    state: Option<CrossfeedState>,
+    pending_right: Option<f32>,
+    check_tick: usize,
+    meter: StageMeter,
+}
+
+impl<I: Source<Item = f32>> CrossfeedSource<I> {
+    pub fn new(input: I, settings: Arc<DspSettings>, stereo_output: bool) -> Self {
+        let applicable = stereo_output && input.channels() == 2;
+        let sample_rate = input.sample_rate();
+        let mut source = Self {
+            input, sample_rate, applicable, settings,
+            seen_version: u64::MAX, state: None, pending_right: None, check_tick: 0,
+            meter: StageMeter::new("crossfeed", false, 0),
+        };
+        source.refresh();
+        source
+    }
+
+    pub fn stage(&self) -> Arc<DspStage> { self.meter.stage() }
+
+    fn refresh(&mut self) {
+        let version = self.settings.crossfeed.version();
+        if version == self.seen_version { return; }
+        self.seen_version = version;
+        let crossfeed = self.settings.crossfeed.get();
+        let level = (crossfeed.level.max(1) as usize).min(CROSSFEED_LEVELS.len()) - 1;
+        let (cutoff, atten_db) = CROSSFEED_LEVELS[level];
+
+        self.state = if crossfeed.enabled && self.applicable {
+            Coefficients::<f32>::from_params(Type::LowPass, (self.sample_rate as f32).hz(), cutoff.hz(), 0.5).ok().map(|c| {
+                let cross_gain = 10f32.powf(-atten_db / 20.0);
+                let delay_len = ((self.sample_rate as f32 * CROSSFEED_DELAY_SECS) as usize).max(1);
+                // 档位切换时沿用已有延迟线内容，仅首次开启时从静音起步
+                let (delay, delay_pos) = match self.state.take() {
+                    Some(old) if old.delay[0].len() == delay_len => (old.delay, old.delay_pos),
+                    _ => ([vec![0.0; delay_len], vec![0.0; delay_len]], 0),
+                };
+                CrossfeedState { lowpass: [DirectForm2Transposed::<f32>::new(c), DirectForm2Transposed::<f32>::new(c)], delay, delay_pos, cross_gain, norm: 1.0 / (1.0 + cross_gain) }
+            })
+        } else { None };
+        self.meter.set_params(self.state.is_some(), hash_params((crossfeed.enabled, crossfeed.level, self.applicable)));
+    }
+}
+
+impl CrossfeedState {
+    #[inline(always)]
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        let feed_l = self.delay[0][self.delay_pos];
+        let feed_r = self.delay[1][self.delay_pos];
+        self.delay[0][self.delay_pos] = self.lowpass[0].run(l);
+        self.delay[1][self.delay_pos] = self.lowpass[1].run(r);
+        self.delay_pos = (self.delay_pos + 1) % self.delay[0].len();
+        ((l + feed_r * self.cross_gain) * self.norm, (r + feed_l * self.cross_gain) * self.norm)
+    }
+}
+
+impl<I: Source<Item = f32>> Iterator for CrossfeedSource<I> {
+    type Item = f32;
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        if let Some(r) = self.pending_right.take() { return Some(r); }
+        if !self.applicable { return self.input.next(); }
+
+        self.check_tick += 1;
+        if self.check_tick >= PARAM_CHECK_STRIDE { self.check_tick = 0; self.refresh(); }
+
+        let l = self.input.next()?;
+        let Some(state) = self.state.as_mut() else { self.pending_right = self.input.next(); return Some(l); };
+        let r = self.input.next().unwrap_or(l);
+        let (out_l, out_r) = state.process(l, r);
+        self.pending_right = Some(out_r);
+        Some(out_l)
+    }
+}
+
+impl<I: Source<Item = f32>> Source for CrossfeedSource<I> {
+    fn current_frame_len(&self) -> Option<usize> { self.input.current_frame_len() }
+    fn channels(&self) -> u16 { self.input.channels() }
+    fn sample_rate(&self) -> u32 { self.input.sample_rate() }
+    fn total_duration(&self) -> Option<Duration> { self.input.total_duration() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn sine(freq: f32, amplitude: f32, rate: u32, frames: usize) -> impl Iterator<Item = f32> {
+        (0..frames).map(move |n| (2.0 * PI * freq * n as f32 / rate as f32).sin() * amplitude)
+    }
+
+    // 立体声同相送入限幅器，返回左声道输出
+    fn limit(limiter: &mut LookaheadLimiter, input: impl Iterator<Item = f32>, enabled: bool) -> Vec<f32> {
+        input.map(|s| { let mut frame = [s, s]; limiter.process(&mut frame, enabled); frame[0] }).collect()
+    }
+
+    #[test]
+    fn limiter_holds_3db_overdrive_below_full_scale() {
+        let overdrive = 10f32.powf(3.0 / 20.0);
+        for freq in [60.0, 997.0, 9600.0] {
+            let mut limiter = LookaheadLimiter::new(2, 48000);
+            let silence = std::iter::repeat_n(0.0, 1000);
+            let out = limit(&mut limiter, silence.chain(sine(freq, overdrive, 48000, 48000)), true);
+            let peak = out.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+            // 前视增益在峰值出延迟线前就位：不靠末端钳位也不超过 1
+            assert!(peak <= LIMITER_CEILING + 0.005, "{freq}Hz peaked at {peak}");
+            assert!(limiter.reduction_db() > 2.5);
+        }
+    }
+
+    #[test]
+    fn limiter_holds_isolated_transient_and_releases() {
+        let mut limiter = LookaheadLimiter::new(2, 48000);
+        let burst = std::iter::repeat_n(0.0, 1000).chain(std::iter::repeat_n(1.4, 10));
+        let out = limit(&mut limiter, burst.chain(std::iter::repeat_n(0.0, 1000)), true);
+        assert!(out.iter().all(|s| s.abs() <= LIMITER_CEILING + 0.005));
+        // 释放之后安静段原样通过
+        let tail = limit(&mut limiter, std::iter::repeat_n(0.0, 48000).chain(sine(997.0, 0.5, 48000, 4800)), true);
+        let peak = tail.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!((peak - 0.5).abs() < 0.005, "quiet material was still attenuated: {peak}");
+    }
+
+    #[test]
+    fn limiter_delays_by_lookahead_and_knees_when_disabled() {
+        let mut limiter = LookaheadLimiter::new(2, 48000);
+        let out = limit(&mut limiter, std::iter::once(0.5).chain(std::iter::repeat_n(0.0, 200)), false);
+        assert_eq!(out.iter().position(|&s| s != 0.0), Some(144));
+        let out = limit(&mut LookaheadLimiter::new(2, 48000), sine(997.0, 0.9, 48000, 4800), false);
+        assert!(out.iter().all(|s| s.abs() <= 0.9));
+        assert!(static_knee(1.41) < 1.1 && static_knee(1.41) > LIMITER_CEILING);
+    }
+
+    // 16-bit 量化：TPDF 抖动 (方差 LSB²/6) 加舍入误差 (LSB²/12)，总噪声 RMS 为 LSB/2，
+    // 相对满幅正弦 (RMS 1/√2) 约 -93.3dB
+    #[test]
+    fn dither_16bit_noise_floor_is_about_minus_93db() {
+        let dither = Dither { enabled: true, bits: 16 };
+        let lsb = dither.lsb();
+        assert_eq!(lsb, 1.0 / 32768.0);
+        let mut rng = TpdfDither::new(0x5eed);
+        let (mut energy, mut sum, mut peak) = (0.0f64, 0.0f64, 0.0f32);
+        let input: Vec<f32> = sine(997.0, 0.001, 48000, 480_000).collect();
+        for &x in &input {
+            let noise = rng.noise(lsb);
+            peak = peak.max(noise.abs());
+            let quantized = ((x + noise) / lsb).round() * lsb;
+            let error = (quantized - x) as f64;
+            energy += error * error;
+            sum += error;
+        }
+        let rms = (energy / input.len() as f64).sqrt();
+        let db = 20.0 * (rms / std::f64::consts::FRAC_1_SQRT_2).log10();
+        assert!((db + 93.3).abs() < 0.5, "noise floor {db:.2} dB");
+        assert!(peak <= lsb, "TPDF noise must stay within ±1 LSB");
+        assert!((sum / input.len() as f64).abs() < lsb as f64 * 0.01);
+    }
+
+    #[test]
+    fn dither_lsb_tracks_bit_depth() {
+        assert_eq!(Dither { enabled: true, bits: 24 }.lsb(), 1.0 / 8388608.0);
+        assert!(validate_dither(&Dither { enabled: true, bits: 7 }).is_err());
+        assert!(validate_dither(&Dither { enabled: true, bits: 24 }).is_ok());
+    }
+
+    fn boosted(bass: BassBoost, input: Vec<f32>) -> Vec<f32> {
+        let settings = DspSettings::new();
+        settings.bass.set(bass);
+        BassBoostSource::new(rodio::buffer::SamplesBuffer::new(1, 48000, input), settings).collect()
+    }
+
+    fn peak_after(samples: &[f32], skip: usize) -> f32 { samples[skip..].iter().fold(0.0f32, |m, s| m.max(s.abs())) }
+
+    #[test]
+    fn bass_boost_above_6db_keeps_full_scale_60hz_in_range() {
+        for gain_db in [6.5, 12.0, BASS_MAX_GAIN_DB] {
+            let out = boosted(BassBoost { gain_db, freq: 80.0 }, sine(60.0, 1.0, 48000, 48000).collect());
+            assert!(out.iter().all(|s| (-1.0..=1.0).contains(s)), "+{gain_db}dB escaped [-1, 1]");
+            assert!(peak_after(&out, 4800) > 0.9, "+{gain_db}dB should saturate, not collapse");
+        }
+    }
+
+    #[test]
+    fn bass_boost_shapes_only_the_low_end() {
+        // +6dB 不触发软削波：20Hz 接近满增益，5kHz 不受影响
+        let bass = BassBoost { gain_db: 6.0, freq: 80.0 };
+        let low = peak_after(&boosted(bass, sine(20.0, 0.1, 48000, 96000).collect()), 48000);
+        assert!((20.0 * (low / 0.1).log10() - 6.0).abs() < 0.5, "20Hz gain {low}");
+        let high = peak_after(&boosted(bass, sine(5000.0, 0.1, 48000, 9600).collect()), 4800);
+        assert!((high - 0.1).abs() < 0.002, "5kHz gain {high}");
+        let input: Vec<f32> = sine(60.0, 1.0, 48000, 4800).collect();
+        assert_eq!(boosted(BassBoost { gain_db: 0.0, freq: 80.0 }, input.clone()), input);
+    }
+}
@@ -0,0 +1,97 @@
+// src/audio/dsp_registry.rs
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+// =================================================================
+// 📊 DSP 链路登记表：各级处理器的启用状态与实时 CPU 开销
+// =================================================================
+static ACTIVE_CHAIN: RwLock<Vec<Arc<DspStage>>> = RwLock::new(Vec::new());
+
+pub struct DspStage {
+    pub name: &'static str,
+    enabled: AtomicBool,
+    params_hash: AtomicU64,
+    cpu_us_per_sec: AtomicU64, // f64 bits
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DspStageInfo {
+    pub name: &'static str,
+    pub bypassed: bool,
+    pub params_hash: u64,
+    pub cpu_us_per_sec: f64,
+}
+
+impl DspStage {
+    pub fn info(&self) -> DspStageInfo {
+        DspStageInfo {
+            name: self.name,
+            bypassed: !self.enabled.load(Ordering::Relaxed),
+            params_hash: self.params_hash.load(Ordering::Relaxed),
+            cpu_us_per_sec: f64::from_bits(self.cpu_us_per_sec.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+pub fn hash_params<T: Hash>(params: T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    params.hash(&mut hasher);
+    hasher.finish()
+}
+
+// 每级处理器私有的计量器：仅在块处理边界打点，按秒滚动发布到共享 Stage
+pub struct StageMeter {
+    stage: Arc<DspStage>,
+    busy: Duration,
+    window_start: Instant,
+}
+
+impl StageMeter {
+    pub fn new(name: &'static str, enabled: bool, params_hash: u64) -> Self {
+        let stage = Arc::new(DspStage {
+            name,
+            enabled: AtomicBool::new(enabled),
+            params_hash: AtomicU64::new(params_hash),
+            cpu_us_per_sec: AtomicU64::new(0f64.to_bits()),
+        });
+        Self { stage, busy: Duration::ZERO, window_start: Instant::now() }
+    }
+
+    pub fn stage(&self) -> Arc<DspStage> { self.stage.clone() }
+
+    // 参数可在线调整的级在重建系数后同步登记表
+    pub fn set_params(&self, enabled: bool, params_hash: u64) {
+        self.stage.enabled.store(enabled, Ordering::Relaxed);
+        self.stage.params_hash.store(params_hash, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn record(&mut self, elapsed: Duration) {
+        self.busy += elapsed;
+        let window = self.window_start.elapsed();
+        if window >= Duration::from_secs(1) {
+            let rate = self.busy.as_secs_f64() * 1_000_000.0 / window.as_secs_f64();
+            self.stage.cpu_us_per_sec.store(rate.to_bits(), Ordering::Relaxed);
+            self.busy = Duration::ZERO;
+            self.window_start = Instant::now();
+        }
+    }
+}
+
+// 引擎每次重建音源链后调用，按信号流顺序整体替换
+pub fn publish_chain(stages: Vec<Arc<DspStage>>) {
+    *ACTIVE_CHAIN.write().unwrap_or_else(|e| e.into_inner()) = stages;
+}
+
+pub fn clear_chain() {
+    ACTIVE_CHAIN.write().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+pub fn snapshot() -> Vec<DspStageInfo> {
+    ACTIVE_CHAIN.read().unwrap_or_else(|e| e.into_inner()).iter().map(|s| s.info()).collect()
+}
@@ -0,0 +1,88 @@
+// src/audio/endpoint.rs
+
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+use super::output_mode::OutputRate;
+
+// =================================================================
+// 🔌 输出端点状态：功放关机 / HDMI 拔出时暂停播放，端点恢复后按设置续播
+// =================================================================
+// WASAPI 能报告端点的 插孔 / 连接器 状态，拔出后端点仍在但不再是 ACTIVE；
+// 其余平台拿不到这一信息，返回 None，由调用方退回到 “设备是否仍在列表中” 的判断。
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct OutputPrefs {
+    #[serde(default)]
+    pub auto_resume: bool, // 端点恢复后自动续播；否则保持暂停等待用户
+    #[serde(default)]
+    pub prefer_device: bool, // 选中的设备被拔出而回退到默认设备后，它重新出现时切回去
+    #[serde(default = "follow_default_on")]
+    pub follow_default: bool, // "Default" 模式下跟随系统默认设备的变更；关闭则钉在当初打开的设备上
+    #[serde(default, alias = "exclusive")]
+    pub match_source_format: bool, // 按曲目的采样率与位深打开输出设备 (仍是共享模式)，见 output_mode
+    #[serde(default)]
+    pub sample_rate: OutputRate,
+    #[serde(default)]
+    pub max_output_rate: Option<u32>, // 跟随曲目采样率时的上限
+}
+
+fn follow_default_on() -> bool { true }
+
+impl Default for OutputPrefs {
+    fn default() -> Self { Self { auto_resume: false, prefer_device: false, follow_default: follow_default_on(), match_source_format: false, sample_rate: OutputRate::Device, max_output_rate: None } }
+}
+
+impl OutputPrefs {
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("output_prefs.json")).ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(dir.join("output_prefs.json"), json).map_err(|e| e.to_string())
+    }
+}
+
+// 因端点失活而暂停时记下的书签
+#[derive(Serialize, Clone, Debug)]
+pub struct InactivePause {
+    pub device: String,
+    pub path: Option<String>,
+    pub position: f64,
+}
+
+// 失活的端点恢复在线；resumed 表示是否按自动续播设置继续了播放
+#[derive(Serialize, Clone, Debug)]
+pub struct OutputReactivated {
+    pub device: String,
+    pub position: f64,
+    pub resumed: bool,
+}
+
+#[cfg(target_os = "windows")]
+pub fn endpoint_active(device_name: &str) -> Option<bool> {
+    use windows::Win32::Devices::FunctionDiscovery::PKEY_Device_FriendlyName;
+    use windows::Win32::Media::Audio::{eRender, IMMDeviceEnumerator, MMDeviceEnumerator, DEVICE_STATEMASK_ALL, DEVICE_STATE_ACTIVE};
+    use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_MULTITHREADED, STGM_READ};
+
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL).ok()?;
+        let endpoints = enumerator.EnumAudioEndpoints(eRender, DEVICE_STATEMASK_ALL).ok()?;
+        for i in 0..endpoints.GetCount().ok()? {
+            let Ok(device) = endpoints.Item(i) else { continue };
+            let Ok(store) = device.OpenPropertyStore(STGM_READ) else { continue };
+            let Ok(name) = store.GetValue(&PKEY_Device_FriendlyName) else { continue };
+            if name.to_string() != device_name { continue; }
+            return device.GetState().ok().map(|state| state == DEVICE_STATE_ACTIVE);
+        }
+        None
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn endpoint_active(_device_name: &str) -> Option<bool> { None }
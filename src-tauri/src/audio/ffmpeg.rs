@@ -1,25 +1,42 @@
 // src/audio/ffmpeg.rs
 
-use super::AudioEngine;
+use super::{begin_fade, lock_sink, pause_after_fade, AudioEngine, EngineCapabilities};
 use std::process::{Command, Stdio};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::ffi::OsString;
 use std::fs;
 use tokio::time::timeout;
 use std::env;
-use std::io::{Cursor, Read, BufReader, BufRead}; 
+use std::io::{BufReader, BufRead, Write}; 
 use std::sync::{Arc, Mutex, RwLock, OnceLock};
 use std::sync::atomic::{AtomicUsize, AtomicBool, AtomicU32, AtomicU64, Ordering}; 
 use std::thread;
 use std::time::{Duration, Instant};
-use tauri::{Window, Emitter, Manager}; 
+use tauri::{Window, Manager};
+use crate::modules::api_schema::events;
 use zip::ZipArchive;
-use rodio::{OutputStreamHandle, Sink, Source, buffer::SamplesBuffer};
+use lofty::{read_from_path, AudioFile};
+use sha2::{Digest, Sha256};
+use serde::{Serialize, Deserialize};
+use rodio::{OutputStreamHandle, Sink, Source};
 use rodio::cpal::traits::{HostTrait, DeviceTrait};
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
-use super::galaxy::{UpmixSource, ChannelConfig};
+use super::galaxy::{UpmixSource, ChannelConfig, AbLoopSource, LoopRegion};
+use super::memory_source::ArcSliceSource;
+use super::pcm_spill::CacheStrategy;
+use super::silence::{SilenceCache, SilenceCursor, SkipLog, SkipSilence};
+use super::stretch::{self, TimeStretchSource};
+use super::dsp_registry;
+use super::pcm_stream::{self, PcmStream, StreamSource};
+use super::threads;
+use super::playback_error::{ErrorReporter, PlaybackErrorCode};
+use super::decode_progress::ProgressReporter;
+use super::ffprobe;
+use super::dsp::{self, BassBoost, Crossfeed, Dither, DspSettings, EqBand, LfeCrossover, UpmixParams};
+use crate::util::jobs::JobHandle;
 
 // =================================================================
 // ⏱️ 全局高精度原子时钟基准 (Lock-Free Epoch)
@@ -35,6 +52,7 @@ fn f64_to_bits(f: f64) -> u64 { f.to_bits() }
 fn f64_from_bits(b: u64) -> f64 { f64::from_bits(b) }
 
 fn get_dynamic_target_sr() -> u32 {
+    if let Some(sr) = super::stream_sample_rate() { return sr; }
     if let Some(device) = rodio::cpal::default_host().default_output_device() {
         if let Ok(config) = device.default_output_config() {
             return config.sample_rate().0;
@@ -43,47 +61,286 @@ fn get_dynamic_target_sr() -> u32 {
     48000
 }
 
+// 依次尝试的下载源：ghproxy 加速的 BtbN 发布页，其次直连 GitHub
+const MIRRORS: &[&str] = &[
+    "https://ghproxy.net/https://github.com/BtbN/FFmpeg-Builds/releases/download/latest",
+    "https://github.com/BtbN/FFmpeg-Builds/releases/download/latest",
+];
+const ARCHIVE_NAME: &str = "ffmpeg-master-latest-win64-gpl.zip";
+const DOWNLOAD_ATTEMPTS: u32 = 3;
+const DOWNLOAD_FAILED: &str = "DOWNLOAD_FAILED";
+pub const FFMPEG_BUSY: &str = "FFMPEG_BUSY";
+const FFMPEG_EXE: &str = if cfg!(windows) { "ffmpeg.exe" } else { "ffmpeg" };
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FFmpegSource { System, Local }
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FFmpegInfo {
+    pub installed: bool, // 找到且能跑出 -version
+    pub path: Option<String>,
+    pub version: Option<String>,
+    pub source: Option<FFmpegSource>,
+}
+
+// 下载代理 (http:// https:// socks5://)，存于配置目录 download_prefs.json
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct DownloadPrefs {
+    pub proxy: Option<String>,
+}
+
+impl DownloadPrefs {
+    pub fn new(proxy: Option<String>) -> Result<Self, String> {
+        let proxy = proxy.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+        if let Some(p) = &proxy { reqwest::Proxy::all(p).map_err(|_| "INVALID_PROXY".to_string())?; }
+        Ok(Self { proxy })
+    }
+
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("download_prefs.json")).ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(dir.join("download_prefs.json"), json).map_err(|e| e.to_string())
+    }
+}
+
+fn download_client(proxy: Option<&str>) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder().connect_timeout(Duration::from_secs(10));
+    if let Some(proxy) = proxy { builder = builder.proxy(reqwest::Proxy::all(proxy).map_err(|_| "INVALID_PROXY".to_string())?); }
+    builder.build().map_err(|e| e.to_string())
+}
+
+const FIRST_CHUNK_WAIT: Duration = Duration::from_secs(10);
+const SEEK_WAIT: Duration = Duration::from_secs(20);
+const SEEK_LEAD_SECS: f64 = 10.0; // 目标在解码进度前方这么近时顺读过去，比重起进程快
+const MAX_RESIDENT: usize = 3;    // 同时保留的解码流 (时间段) 数，最久未用的先关掉
+// 可直接载入的扩展名；Galaxy 解不了的格式也由 AudioManager 转交到这里
+pub const FORMATS: &[&str] = &["mp3", "flac", "wav", "ogg", "opus", "m4a", "aac", "alac", "wma", "ape", "wv", "tta", "aiff", "aif", "dsf", "dff", "mka", "webm"];
+
+// Windows 的 MAX_PATH 按 UTF-16 单元计，CJK 路径的 UTF-8 字节数远大于它
+const MAX_PATH: usize = 260;
+
+fn too_long(path: &str) -> bool { path.encode_utf16().count() >= MAX_PATH }
+
+// 超过 MAX_PATH 的绝对路径改写为 \\?\ 形式，UNC 路径对应 \\?\UNC\；已带前缀、设备路径与相对路径不改写
+fn extended_length_path(path: &str) -> Option<String> {
+    if !too_long(path) || path.starts_with(r"\\?\") || path.starts_with(r"\\.\") { return None; }
+    let path = path.replace('/', "\\");
+    if let Some(unc) = path.strip_prefix(r"\\") { return Some(format!(r"\\?\UNC\{}", unc)); }
+    let b = path.as_bytes();
+    let drive_absolute = b.len() >= 3 && b[0].is_ascii_alphabetic() && b[1] == b':' && b[2] == b'\\';
+    drive_absolute.then(|| format!(r"\\?\{}", path))
+}
+
+// 路径以 OsStr 原样交给 ffmpeg (Windows 上经 CreateProcessW 以 UTF-16 传递，不走代码页)
+fn input_path(path: &str) -> OsString {
+    match extended_length_path(path).filter(|_| cfg!(windows)) {
+        Some(extended) => OsString::from(extended),
+        None => OsString::from(path),
+    }
+}
+
+// 按路径仍打不开时，非 ASCII 或超长路径改由本进程读文件经 stdin 喂给 ffmpeg
+fn needs_pipe_fallback(path: &str) -> bool { !path.is_ascii() || too_long(path) }
+
+// 解码器拿不到精确样本数时，时长先按容器头估算；lofty 读不出时问 ffprobe
+pub fn probe_duration(path: &str) -> f64 {
+    read_from_path(path).map(|f| f.properties().duration().as_secs_f64()).ok()
+        .filter(|d| *d > 0.0)
+        .or_else(|| ffprobe::probe(Path::new(path)).map(|p| p.duration))
+        .unwrap_or(0.0)
+}
+
 pub struct FFmpegEngine {
     sink: Arc<Mutex<Sink>>,
     stream_handle: OutputStreamHandle,
-    current_samples: Option<Arc<Vec<f32>>>, 
+    current_samples: Option<Arc<Vec<f32>>>, // 整曲读完并缓存后才有
+    streams: Vec<Arc<PcmStream>>, // 各自覆盖一段已解码的时间范围，最近使用的在最后
     sample_rate: u32,
+    duration: f64, // 载入时的时长 (估算或精确)，供进度百分比换算
+    pipe_input: bool, // 当前曲目经 stdin 喂给 ffmpeg，-ss 重起进程时沿用
     current_volume: Arc<AtomicU32>, 
     playback_pos: Arc<AtomicU64>,
     last_play_us: Arc<AtomicU64>,
     is_playing: Arc<AtomicBool>,
     channel_mode: Arc<RwLock<ChannelConfig>>,
     fade_token: Arc<AtomicUsize>,
+    loaded_path: Option<String>,
+    loop_region: Arc<LoopRegion>,
+    stretch_rate: f32,
+    dsp: Arc<DspSettings>,
+    fades: dsp::FadeConfig,
+    skip_silence: SkipSilence,
+    silence_cache: SilenceCache,
+    skips: Arc<SkipLog>,
+    errors: ErrorReporter,
+    progress: ProgressReporter,
 }
 
 impl FFmpegEngine {
-    pub fn new(stream_handle: OutputStreamHandle) -> Self { 
-        let sink = Sink::try_new(&stream_handle).expect("Failed to create FFmpeg Sink");
+    pub fn new(stream_handle: OutputStreamHandle, errors: ErrorReporter, progress: ProgressReporter) -> Self { 
+        let sink = errors.new_sink(&stream_handle, None);
         Self { 
             sink: Arc::new(Mutex::new(sink)),
             stream_handle,
             current_samples: None,
+            streams: Vec::new(),
             sample_rate: 48000, 
+            duration: 0.0,
+            pipe_input: false,
             current_volume: Arc::new(AtomicU32::new(1f32.to_bits())), 
             playback_pos: Arc::new(AtomicU64::new(f64_to_bits(0.0))),
             last_play_us: Arc::new(AtomicU64::new(u64::MAX)),
             is_playing: Arc::new(AtomicBool::new(false)),
             channel_mode: Arc::new(RwLock::new(ChannelConfig::Stereo)),
             fade_token: Arc::new(AtomicUsize::new(0)),
+            loaded_path: None,
+            loop_region: Arc::new(LoopRegion::new()),
+            stretch_rate: 1.0,
+            dsp: DspSettings::new(),
+            fades: dsp::FadeConfig::default(),
+            skip_silence: SkipSilence::default(),
+            silence_cache: SilenceCache::default(),
+            skips: Arc::new(SkipLog::default()),
+            errors,
+            progress,
         } 
     }
 
+    // 输出固定为立体声，静音表按 2 声道扫描
+    fn silence_cursor(&mut self, samples: &Arc<Vec<f32>>) -> Option<SilenceCursor> {
+        let map = self.silence_cache.get(samples, 2, self.sample_rate, self.skip_silence)?;
+        Some(SilenceCursor::new(map, self.skips.clone(), self.loop_region.clone(), 2, self.sample_rate))
+    }
+
+    // 起一个 ffmpeg 进程，从第 start 个样本处开始解码，stdout 交给后台读线程边读边播
+    fn spawn_decoder(&self, path: &str, target_sr: u32, start: usize, duration: f64, pipe_input: bool) -> Result<Arc<PcmStream>, String> {
+        // 声道折叠固定在这一级 aresample 内完成，LFE 混入与 Galaxy 引擎保持一致
+        let lfe_mix = if self.dsp.downmix_lfe.load(Ordering::Relaxed) { dsp::DOWNMIX_LFE_GAIN } else { 0.0 };
+        let filters = format!("aresample=resampler=soxr:precision=28:cheby=1:dither_method=triangular:out_chlayout=stereo:lfe_mix_level={},alimiter=limit=0.99:attack=1:release=20:asc=0", lfe_mix);
+        let exe = Self::get_ffmpeg_exe();
+        let mut cmd = Command::new(&exe);
+        // -ss 放在 -i 之前走输入端定位，转码时会解码并丢弃到精确位置，不必从头解
+        if start > 0 { cmd.args(["-ss", &format!("{:.6}", start as f64 / 2.0 / target_sr as f64)]); }
+        cmd.arg("-i").arg(if pipe_input { OsString::from("pipe:0") } else { input_path(path) });
+        cmd.args([
+            "-f", "f32le", "-ac", "2", "-ar", &target_sr.to_string(), 
+            "-af", &filters,
+            "-vn", "-sn", "-map_metadata", "-1", "-v", "error", "pipe:1"
+        ])
+        .stdin(if pipe_input { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+        #[cfg(target_os = "windows")]
+        { cmd.creation_flags(0x08000000); }
+
+        let mut child = cmd.spawn().map_err(|e| {
+            let message = format!("{} ({})", e, exe.display());
+            self.errors.report(PlaybackErrorCode::FfmpegSpawnFailed, message.clone(), Some(path));
+            format!("Spawn failed: {}", message)
+        })?;
+        let stderr = child.stderr.take();
+        // 管道输入不可回退定位，-ss 只能顺读丢弃到目标处；进程被杀后写入失败，线程随之结束
+        if let Some(mut stdin) = child.stdin.take() {
+            let file = fs::File::open(path);
+            threads::spawn("ffmpeg-stdin", move || {
+                if let Ok(mut file) = file { let _ = std::io::copy(&mut file, &mut stdin); }
+            });
+        }
+
+        let progress = self.progress.start(path, (duration * target_sr as f64) as usize * 2);
+        let stream = PcmStream::spawn(child, start, 2, target_sr, self.errors.clone(), progress, path.to_string())?;
+
+        // stderr 照旧打到控制台，末尾几行另记在流上，失败时随错误上报
+        if let Some(stderr) = stderr {
+            let log = stream.clone();
+            threads::spawn("ffmpeg-stderr", move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    eprintln!("\x1b[33m[FFMPEG LOG] {}\x1b[0m", line);
+                    log.log_line(line);
+                }
+            });
+        }
+        Ok(stream)
+    }
+
+    // 首块到达即起播；进程退出仍无输出说明解码失败，特殊路径再经 stdin 重试一次
+    fn start_decoder(&self, path: &str, target_sr: u32, duration: f64) -> Result<(Arc<PcmStream>, bool), String> {
+        let mut pipe_input = false;
+        loop {
+            let stream = self.spawn_decoder(path, target_sr, 0, duration, pipe_input)?;
+            stream.wait_for(0, FIRST_CHUNK_WAIT);
+            if stream.produced() > 0 { return Ok((stream, pipe_input)); }
+            stream.cancel();
+            if !pipe_input && needs_pipe_fallback(path) {
+                println!("[FFMPEG] Path input failed, retrying via stdin: {}", path);
+                pipe_input = true;
+                continue;
+            }
+            let log = stream.log_tail();
+            let message = if log.is_empty() { "ffmpeg produced no audio".to_string() } else { format!("ffmpeg produced no audio: {}", log) };
+            self.errors.report(PlaybackErrorCode::FfmpegFailed, message.clone(), Some(path));
+            return Err(message);
+        }
+    }
+
+    fn append_chain<S>(&self, sink: &Sink, source: S)
+    where S: Source<Item = f32> + Send + 'static {
+        let target_channels = *self.channel_mode.read().unwrap_or_else(|e| e.into_inner()) as u16;
+        let stretched = TimeStretchSource::new(source, self.stretch_rate);
+        let stretch_stage = stretched.stage();
+        let (processed, dsp_stages) = dsp::chain(stretched, self.dsp.clone(), target_channels);
+        let mixed_source = UpmixSource::new(processed, target_channels, self.is_playing.clone(), self.current_volume.clone(), self.dsp.clone());
+        dsp_registry::publish_chain([vec![stretch_stage], dsp_stages, vec![mixed_source.stage()]].concat());
+        sink.append(mixed_source);
+    }
+
+    // 找出已覆盖该时间点的解码流；都不覆盖时用 -ss 从该处新起一条，不必先解出前面的部分
+    fn stream_source(&mut self, time: f64) -> Option<StreamSource> {
+        let offset = (time * self.sample_rate as f64) as usize * 2;
+        let lead = (SEEK_LEAD_SECS * self.sample_rate as f64) as usize * 2;
+        let stream = match self.streams.iter().position(|s| s.covers(offset, lead)) {
+            Some(index) => self.streams.remove(index),
+            None => {
+                let path = self.loaded_path.clone()?;
+                let fresh = self.spawn_decoder(&path, self.sample_rate, offset, self.duration, self.pipe_input).ok()?;
+                if self.streams.len() >= MAX_RESIDENT { self.streams.remove(0).cancel(); }
+                fresh
+            }
+        };
+        self.streams.push(stream.clone());
+        // 先接管限速游标，读线程才会全速读到目标位置
+        let source = stream.source_at(offset);
+        if !stream.wait_for(offset, SEEK_WAIT) {
+            eprintln!("[FFMPEG] Decoder has not reached {:.1}s yet, output stays silent until it does", time);
+        }
+        Some(source)
+    }
+
     fn get_ffmpeg_dir() -> PathBuf {
         let mut p = env::current_exe().unwrap_or_else(|_| PathBuf::from("."));
         p.pop(); 
         p.join("engine").join("ffmpeg")
     }
 
-    fn get_ffmpeg_exe() -> PathBuf {
-        let exe_name = if cfg!(windows) { "ffmpeg.exe" } else { "ffmpeg" };
+    fn get_ffmpeg_exe() -> PathBuf { Self::get_ffmpeg_dir().join(FFMPEG_EXE) }
+
+    fn get_ffprobe_exe() -> PathBuf {
+        let exe_name = if cfg!(windows) { "ffprobe.exe" } else { "ffprobe" };
         Self::get_ffmpeg_dir().join(exe_name)
     }
 
+    // 章节重封装等工具性调用只要求可执行文件存在
+    pub fn installed_exe() -> Option<PathBuf> { Some(Self::get_ffmpeg_exe()).filter(|p| p.exists()) }
+    pub fn installed_probe() -> Option<PathBuf> { Some(Self::get_ffprobe_exe()).filter(|p| p.exists()) }
+
     pub fn check_availability(_app_handle: &tauri::AppHandle) -> bool {
         let exe_path = Self::get_ffmpeg_exe();
         if exe_path.exists() {
@@ -99,57 +356,356 @@ impl FFmpegEngine {
         false
     }
 
-    pub async fn download_and_install(window: Window) -> Result<(), String> {
+    // 引擎只用 engine/ffmpeg 下的本地副本；本地没有时报告 PATH 上的系统 ffmpeg，仅供前端提示
+    pub fn info() -> FFmpegInfo {
+        let system = || env::var_os("PATH").and_then(|paths| env::split_paths(&paths).map(|dir| dir.join(FFMPEG_EXE)).find(|p| p.is_file()));
+        let found = Self::installed_exe().map(|p| (p, FFmpegSource::Local))
+            .or_else(|| system().map(|p| (p, FFmpegSource::System)));
+        let Some((path, source)) = found else { return FFmpegInfo { installed: false, path: None, version: None, source: None } };
+        // 首行形如 "ffmpeg version N-117000-g1234abcd-20240901 Copyright ..."
+        let version = Self::run_version(&path)
+            .and_then(|line| line.strip_prefix("ffmpeg version ").and_then(|rest| rest.split_whitespace().next()).map(str::to_string));
+        FFmpegInfo { installed: version.is_some(), path: Some(path.to_string_lossy().to_string()), version, source: Some(source) }
+    }
+
+    // 失败原因对应的 ffmpeg-status
+    pub fn install_failure_status(error: &str) -> &'static str {
+        match error {
+            "CANCELLED" => "cancelled",
+            "CHECKSUM_FAILED" => "checksum-failed",
+            FFMPEG_BUSY => "busy",
+            _ => "error",
+        }
+    }
+
+    // 下载包写入 bin 目录下的 .part 文件，边写边算 SHA-256，与发布页的校验和比对通过才解压；
+    // 解压出的 ffmpeg 与 ffprobe 先各跑一遍 -version 确认能执行，再替换正式文件。
+    // 网络中断或取消时保留 .part 供下次续传，其余结果 (成功或校验失败) 都清掉半成品。
+    pub async fn download_and_install(window: Window, job: JobHandle) -> Result<(), String> {
         let bin_dir = Self::get_ffmpeg_dir();
         if !bin_dir.exists() { fs::create_dir_all(&bin_dir).map_err(|e| e.to_string())?; }
-        #[cfg(windows)]
-        let url = "https://ghproxy.net/https://github.com/BtbN/FFmpeg-Builds/releases/download/latest/ffmpeg-master-latest-win64-gpl.zip";
-        let client = reqwest::Client::builder().connect_timeout(Duration::from_secs(10)).build().map_err(|e| e.to_string())?;
-        window.emit("ffmpeg-status", "downloading").unwrap();
-        let mut response = client.get(url).send().await.map_err(|e| e.to_string())?;
-        let total_size = response.content_length().unwrap_or(0);
-        let mut downloaded: u64 = 0;
-        let mut chunks = Vec::new();
+        let part_path = bin_dir.join("ffmpeg-download.zip.part");
+        let prefs = window.app_handle().path().app_config_dir().map(|dir| DownloadPrefs::load(&dir)).unwrap_or_default();
+        let client = download_client(prefs.proxy.as_deref())?;
+        let result = Self::install_into(&window, &job, &client, &part_path).await;
+        if !matches!(result.as_ref().map_err(String::as_str), Err("CANCELLED") | Err(DOWNLOAD_FAILED)) { let _ = fs::remove_file(&part_path); }
+        for exe in [Self::get_ffmpeg_exe(), Self::get_ffprobe_exe()] { let _ = fs::remove_file(exe.with_extension("new")); }
+        result
+    }
+
+    async fn install_into(window: &Window, job: &JobHandle, client: &reqwest::Client, part_path: &Path) -> Result<(), String> {
+        events::FFMPEG_STATUS.emit(window, "downloading").unwrap();
+        let mut verified = None;
+        for mirror in MIRRORS {
+            let expected = match Self::fetch_checksum(client, &format!("{}/checksums.sha256", mirror)).await {
+                Ok(hash) => hash,
+                Err(e) => { eprintln!("[FFMPEG] Mirror {} unavailable: {}", mirror, e); continue; }
+            };
+            match Self::download_with_retry(window, job, client, &format!("{}/{}", mirror, ARCHIVE_NAME), part_path).await {
+                Ok(actual) => { verified = Some((expected, actual)); break; }
+                Err(e) if e == "CANCELLED" => return Err(e),
+                Err(e) => eprintln!("[FFMPEG] Mirror {} failed: {}", mirror, e),
+            }
+        }
+        let (expected, actual) = verified.ok_or(DOWNLOAD_FAILED)?;
+
+        let _ = events::FFMPEG_STATUS.emit(window, "verifying");
+        if actual != expected {
+            eprintln!("[FFMPEG] Checksum mismatch: expected {}, got {}", expected, actual);
+            return Err("CHECKSUM_FAILED".into());
+        }
+
+        let _ = events::FFMPEG_STATUS.emit(window, "extracting");
+        let file = fs::File::open(part_path).map_err(|e| e.to_string())?;
+        let mut archive = ZipArchive::new(file).map_err(|e| format!("Corrupt archive: {}", e))?;
+        let targets = [Self::get_ffmpeg_exe(), Self::get_ffprobe_exe()];
+        for target in &targets {
+            let staged = target.with_extension("new");
+            Self::extract_entry(&mut archive, target, &staged)?;
+            let version = Self::run_version(&staged).ok_or_else(|| format!("Extracted {:?} does not run", target.file_name().unwrap_or_default()))?;
+            println!("[FFMPEG] Staged: {}", version);
+        }
+        // 两个都验过再替换，不留一新一旧；下载期间开始了播放则放弃替换，旧版本原样保留
+        if pcm_stream::live_decoders() > 0 { return Err(FFMPEG_BUSY.into()); }
+        for target in &targets {
+            fs::rename(target.with_extension("new"), target).map_err(|e| e.to_string())?;
+        }
+        let _ = events::FFMPEG_STATUS.emit(window, "ready");
+        Ok(())
+    }
+
+    // 连接类错误按指数退避重试；每次都从 .part 已有的长度续传
+    async fn download_with_retry(window: &Window, job: &JobHandle, client: &reqwest::Client, url: &str, part_path: &Path) -> Result<String, String> {
+        let mut last_error = String::new();
+        for attempt in 0..DOWNLOAD_ATTEMPTS {
+            if attempt > 0 { tokio::time::sleep(Duration::from_secs(1 << attempt)).await; }
+            match Self::download_once(window, job, client, url, part_path).await {
+                Ok(hash) => return Ok(hash),
+                Err(e) if e == "CANCELLED" => return Err(e),
+                Err(e) => {
+                    eprintln!("[FFMPEG] Download attempt {} failed: {}", attempt + 1, e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    // 返回整个文件的 SHA-256；续传时先把已下载的部分补进摘要
+    async fn download_once(window: &Window, job: &JobHandle, client: &reqwest::Client, url: &str, part_path: &Path) -> Result<String, String> {
+        let offset = fs::metadata(part_path).map(|m| m.len()).unwrap_or(0);
+        let mut request = client.get(url);
+        if offset > 0 { request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset)); }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            // 发布页的文件已更新或 .part 已损坏，丢掉重下
+            let _ = fs::remove_file(part_path);
+            return Err("Partial download no longer matches, restarting".into());
+        }
+        let mut response = response.error_for_status().map_err(|e| e.to_string())?;
+
+        let mut hasher = Sha256::new();
+        let resumed = offset > 0 && response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+        let mut out = if resumed {
+            let mut existing = fs::File::open(part_path).map_err(|e| e.to_string())?;
+            std::io::copy(&mut existing, &mut hasher).map_err(|e| e.to_string())?;
+            fs::OpenOptions::new().append(true).open(part_path).map_err(|e| e.to_string())?
+        } else {
+            fs::File::create(part_path).map_err(|e| e.to_string())?
+        };
+        let mut downloaded = if resumed { offset } else { 0 };
+        let total_size = response.content_length().map(|len| len + downloaded).unwrap_or(0);
+        if resumed { println!("[FFMPEG] Resuming download at {} bytes", offset); }
         loop {
             match timeout(Duration::from_secs(15), response.chunk()).await {
                 Ok(Ok(Some(chunk))) => {
-                    downloaded += chunk.len() as u64; chunks.extend_from_slice(&chunk);
-                    if total_size > 0 { let _ = window.emit("ffmpeg-progress", (downloaded as f64 / total_size as f64) * 90.0); }
+                    if job.is_cancelled() { return Err("CANCELLED".into()); }
+                    downloaded += chunk.len() as u64;
+                    hasher.update(&chunk);
+                    out.write_all(&chunk).map_err(|e| e.to_string())?;
+                    if total_size > 0 {
+                        job.set_progress(downloaded as f32 / total_size as f32);
+                        let _ = events::FFMPEG_PROGRESS.emit(window, &((downloaded as f64 / total_size as f64) * 90.0));
+                    }
                 },
                 Ok(Ok(None)) => break,
-                _ => return Err("Download Failed".into()),
-            }
-        }
-        window.emit("ffmpeg-status", "extracting");
-        let mut archive = ZipArchive::new(Cursor::new(chunks)).map_err(|e| e.to_string())?;
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i).unwrap();
-            if file.name().ends_with("ffmpeg.exe") {
-                let target_path = Self::get_ffmpeg_exe();
-                if let Some(p) = target_path.parent() { fs::create_dir_all(p).ok(); }
-                let mut out = fs::File::create(&target_path).map_err(|e| e.to_string())?;
-                std::io::copy(&mut file, &mut out).ok();
-                break;
+                Ok(Err(e)) => return Err(e.to_string()),
+                Err(_) => return Err("Download stalled".into()),
             }
         }
-        window.emit("ffmpeg-status", "ready");
+        if total_size > 0 && downloaded != total_size { return Err(format!("Download truncated: {} of {} bytes", downloaded, total_size)); }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    // checksums.sha256 每行 "<hex>  <文件名>"，取下载包那一行
+    async fn fetch_checksum(client: &reqwest::Client, checksum_url: &str) -> Result<String, String> {
+        let body = client.get(checksum_url).send().await.and_then(|r| r.error_for_status()).map_err(|e| e.to_string())?
+            .text().await.map_err(|e| e.to_string())?;
+        body.lines()
+            .filter_map(|line| line.split_once(char::is_whitespace))
+            .find(|(_, file)| file.trim().trim_start_matches('*') == ARCHIVE_NAME)
+            .map(|(hash, _)| hash.to_ascii_lowercase())
+            .ok_or_else(|| format!("No checksum published for {}", ARCHIVE_NAME))
+    }
+
+    // 按文件名在压缩包里找到对应条目，解到 dest
+    fn extract_entry(archive: &mut ZipArchive<fs::File>, target: &Path, dest: &Path) -> Result<(), String> {
+        let name = target.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let index = (0..archive.len())
+            .find(|&i| archive.by_index(i).map(|f| f.name().ends_with(&name)).unwrap_or(false))
+            .ok_or_else(|| format!("Archive does not contain {}", name))?;
+        let mut entry = archive.by_index(index).map_err(|e| format!("Corrupt archive: {}", e))?;
+        let mut staged = fs::File::create(dest).map_err(|e| e.to_string())?;
+        std::io::copy(&mut entry, &mut staged).map_err(|e| format!("Corrupt archive: {}", e))?;
         Ok(())
     }
+
+    // 跑一遍 -version，能执行就返回首行版本信息
+    fn run_version(exe: &Path) -> Option<String> {
+        let mut cmd = Command::new(exe);
+        cmd.arg("-version").stdin(Stdio::null());
+        #[cfg(target_os = "windows")]
+        { cmd.creation_flags(0x08000000); }
+        let output = cmd.output().ok().filter(|o| o.status.success())?;
+        String::from_utf8_lossy(&output.stdout).lines().next().map(str::to_string)
+    }
+}
+
+// 引擎被换下或随 actor 退出时杀掉仍在运行的解码进程
+impl Drop for FFmpegEngine {
+    fn drop(&mut self) {
+        for stream in self.streams.drain(..) { stream.cancel(); }
+    }
 }
 
 impl AudioEngine for FFmpegEngine {
     fn name(&self) -> &str { "FFmpeg soxr-VHQ (Mastering Grade)" }
+    fn id(&self) -> &'static str { "ffmpeg" }
+
+    // 边解码边播放，但不预读下一首，曲间无法无缝衔接
+    fn capabilities(&self) -> EngineCapabilities {
+        EngineCapabilities {
+            gapless: false, fades: true, upmix: true, accurate_seek: true, bitperfect: false, time_stretch: true, generator: false,
+            formats: FORMATS.to_vec(),
+        }
+    }
 
     fn get_current_time(&self) -> f64 {
-        let pos = f64_from_bits(self.playback_pos.load(Ordering::Relaxed));
+        // 挂钟只记播放了多久，被跳过的静音另行补上
+        let pos = f64_from_bits(self.playback_pos.load(Ordering::Relaxed)) + self.skips.since_anchor();
         let start_us = self.last_play_us.load(Ordering::Relaxed);
         if start_us != u64::MAX {
             let epoch = get_time_epoch();
             let now_us = Instant::now().duration_since(epoch).as_micros() as u64;
             let elapsed = now_us.saturating_sub(start_us) as f64 / 1_000_000.0;
-            pos + elapsed
+            self.loop_region.fold(pos + elapsed * self.stretch_rate as f64)
         } else {
-            pos
+            self.loop_region.fold(pos)
+        }
+    }
+
+    fn is_playing(&self) -> bool { self.is_playing.load(Ordering::SeqCst) }
+
+    fn current_path(&self) -> Option<String> { self.loaded_path.clone() }
+
+    fn cache_strategy(&self) -> CacheStrategy {
+        match (&self.loaded_path, &self.current_samples) {
+            (None, _) => CacheStrategy::None,
+            (Some(_), Some(_)) => CacheStrategy::Memory,
+            (Some(_), None) => CacheStrategy::Stream,
+        }
+    }
+
+    fn set_loop(&mut self, start: f64, end: f64) -> Result<(), String> {
+        if !start.is_finite() || !end.is_finite() || start < 0.0 || start >= end { return Err("INVALID_LOOP_RANGE".to_string()); }
+        if self.loaded_path.is_none() { return Err("NO_TRACK_LOADED".to_string()); }
+        let position = self.get_current_time();
+        self.loop_region.set(start, end);
+        if position >= end {
+            self.seek(start);
+        } else if self.skips.since_anchor() > 0.0 {
+            // 跳过静音后循环源的样本计数已落后，重挂链路对齐
+            self.seek(position);
+        }
+        Ok(())
+    }
+
+    fn set_rate_preserve_pitch(&mut self, rate: f32) -> Result<(), String> {
+        if !rate.is_finite() || !(stretch::MIN_RATE..=stretch::MAX_RATE).contains(&rate) { return Err("INVALID_RATE".to_string()); }
+        // 以旧倍速折算出当前位置后重建音源链，时钟从该位置按新倍速推进
+        let position = self.get_current_time();
+        self.stretch_rate = rate;
+        if self.loaded_path.is_some() { self.seek(position); }
+        Ok(())
+    }
+
+    fn set_parametric_eq(&mut self, bands: Vec<EqBand>) -> Result<(), String> {
+        dsp::validate_bands(&bands, self.sample_rate)?;
+        self.dsp.eq.set(bands);
+        Ok(())
+    }
+
+    fn set_bass_boost(&mut self, bass: BassBoost) -> Result<(), String> {
+        dsp::validate_bass(&bass, self.sample_rate)?;
+        self.dsp.bass.set(bass);
+        Ok(())
+    }
+
+    fn set_lfe_crossover(&mut self, crossover: LfeCrossover) -> Result<(), String> {
+        dsp::validate_lfe_crossover(&crossover, self.sample_rate)?;
+        self.dsp.lfe_crossover.set(crossover);
+        Ok(())
+    }
+
+    fn set_upmix_params(&mut self, params: UpmixParams) -> Result<(), String> {
+        dsp::validate_upmix(&params)?;
+        self.dsp.upmix.set(params);
+        Ok(())
+    }
+
+    fn set_limiter_enabled(&mut self, enabled: bool) {
+        self.dsp.limiter_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    fn limiter_reduction_db(&self) -> f32 { self.dsp.limiter_reduction_db() }
+
+    fn set_dither(&mut self, dither: Dither) -> Result<(), String> {
+        dsp::validate_dither(&dither)?;
+        self.dsp.dither.set(dither);
+        Ok(())
+    }
+
+    fn set_crossfeed(&mut self, crossfeed: Crossfeed) -> Result<(), String> {
+        dsp::validate_crossfeed(&crossfeed)?;
+        self.dsp.crossfeed.set(crossfeed);
+        Ok(())
+    }
+
+    fn set_stereo_width(&mut self, width: f32) -> Result<(), String> {
+        if !width.is_finite() || !(0.0..=dsp::MAX_STEREO_WIDTH).contains(&width) { return Err("INVALID_WIDTH".to_string()); }
+        self.dsp.stereo_width.store(width.to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_balance(&mut self, balance: f32) -> Result<(), String> {
+        if !balance.is_finite() || !(-1.0..=1.0).contains(&balance) { return Err("INVALID_BALANCE".to_string()); }
+        self.dsp.balance.store(balance.to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_preamp(&mut self, gain_db: f32) -> Result<(), String> {
+        dsp::validate_preamp(gain_db)?;
+        self.dsp.preamp_db.store(gain_db.to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    // 折叠由 FFmpeg 的 -ac 2 完成，LFE 混入系数随下一次 load 生效
+    fn set_downmix_lfe(&mut self, enabled: bool) -> Result<(), String> {
+        self.dsp.downmix_lfe.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_auto_level(&mut self, auto_level: dsp::AutoLevel) -> Result<(), String> {
+        dsp::validate_auto_level(&auto_level)?;
+        self.dsp.auto_level.set(auto_level);
+        Ok(())
+    }
+
+    fn set_replaygain_db(&mut self, gain_db: f32) -> Result<(), String> {
+        self.dsp.replaygain_db.store(gain_db.to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_channel_layout(&mut self, layout: dsp::ChannelLayout) -> Result<(), String> {
+        layout.validate()?;
+        self.dsp.channel_layout.set(layout);
+        Ok(())
+    }
+
+    fn set_fade_config(&mut self, fades: dsp::FadeConfig) -> Result<(), String> {
+        fades.validate()?;
+        self.fades = fades;
+        Ok(())
+    }
+
+    fn set_skip_silence(&mut self, prefs: SkipSilence) -> Result<(), String> {
+        prefs.validate()?;
+        if prefs == self.skip_silence { return Ok(()); }
+        self.skip_silence = prefs;
+        if self.loaded_path.is_some() { self.seek(self.get_current_time()); }
+        Ok(())
+    }
+
+    fn take_skipped_silence(&self) -> f64 { self.skips.take_pending() }
+
+    fn clear_loop(&mut self) {
+        let position = self.get_current_time();
+        self.loop_region.clear();
+        self.playback_pos.store(f64_to_bits(position), Ordering::SeqCst);
+        self.skips.reset_anchor();
+        if self.last_play_us.load(Ordering::SeqCst) != u64::MAX {
+            let now_us = Instant::now().duration_since(get_time_epoch()).as_micros() as u64;
+            self.last_play_us.store(now_us, Ordering::SeqCst);
         }
     }
 
@@ -159,7 +715,7 @@ impl AudioEngine for FFmpegEngine {
 
         if was_playing { 
             self.is_playing.store(false, Ordering::SeqCst); 
-            if let Ok(s) = self.sink.lock() { s.pause(); }
+            lock_sink(&self.sink).pause();
             thread::sleep(Duration::from_millis(50)); 
         }
         
@@ -172,52 +728,28 @@ impl AudioEngine for FFmpegEngine {
     }
 
     fn load(&mut self, path: &str) -> Result<f64, String> {
-        if self.is_playing.load(Ordering::SeqCst) { self.is_playing.store(false, Ordering::SeqCst); thread::sleep(Duration::from_millis(40)); }
+        if self.is_playing.load(Ordering::SeqCst) {
+            let fade = begin_fade(&self.dsp, self.fades.load_ms);
+            self.is_playing.store(false, Ordering::SeqCst);
+            thread::sleep(fade);
+        }
+        self.dsp.reset_auto_level();
 
-        let ffmpeg_exe = Self::get_ffmpeg_exe();
         let target_sr = get_dynamic_target_sr();
         
         println!("\x1b[36m[FFMPEG] Audio Engine Decoder Initialized: Target SR = {}Hz, Channels = 2\x1b[0m", target_sr);
         
-        let mut cmd = Command::new(&ffmpeg_exe);
-        cmd.args(&[
-            "-i", path, "-f", "f32le", "-ac", "2", "-ar", &target_sr.to_string(), 
-            "-af", "aresample=resampler=soxr:precision=28:cheby=1:dither_method=triangular,alimiter=limit=0.99:attack=1:release=20:asc=0",
-            "-vn", "-sn", "-map_metadata", "-1", "-v", "error", "pipe:1"
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-
-        #[cfg(target_os = "windows")]
-        { cmd.creation_flags(0x08000000); }
-
-        let mut child = cmd.spawn().map_err(|e| format!("Spawn failed: {}", e))?;
-        let mut stdout = child.stdout.take().ok_or("Stdout failed")?;
-        let stderr = child.stderr.take().ok_or("Stderr failed")?;
+        let estimate = probe_duration(path);
+        let (stream, pipe_input) = self.start_decoder(path, target_sr, estimate)?;
+        self.pipe_input = pipe_input;
 
-        thread::spawn(move || {
-            let reader = BufReader::new(stderr);
-            for line in reader.lines() {
-                if let Ok(l) = line { eprintln!("\x1b[33m[FFMPEG LOG] {}\x1b[0m", l); }
-            }
-        });
-
-        let mut raw_bytes = Vec::new();
-        stdout.read_to_end(&mut raw_bytes).map_err(|e| e.to_string())?;
-
-        if raw_bytes.is_empty() { return Err("FFmpeg output is empty. Check logs.".into()); }
-
-        let sample_count = raw_bytes.len() / 4;
-        let mut samples = Vec::with_capacity(sample_count);
-        for chunk in raw_bytes.chunks_exact(4) {
-            samples.push(f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
-        }
-
-        let samples_arc = Arc::new(samples);
-        self.current_samples = Some(samples_arc.clone());
+        for previous in std::mem::replace(&mut self.streams, vec![stream.clone()]) { previous.cancel(); }
+        self.current_samples = None;
+        self.loaded_path = Some(path.to_string());
         self.sample_rate = target_sr;
         
         self.playback_pos.store(f64_to_bits(0.0), Ordering::SeqCst);
+        self.skips.reset_anchor();
         let epoch = get_time_epoch();
         if self.is_playing.load(Ordering::SeqCst) {
             let now_us = Instant::now().duration_since(epoch).as_micros() as u64;
@@ -228,30 +760,37 @@ impl AudioEngine for FFmpegEngine {
         
         self.fade_token.fetch_add(1, Ordering::SeqCst);
 
-        let target_channels = *self.channel_mode.read().unwrap() as u16;
-        let buffer = SamplesBuffer::new(2, target_sr, samples_arc.to_vec());
-        let duration = buffer.total_duration().unwrap_or(Duration::from_secs(0)).as_secs_f64();
+        self.loop_region.clear();
+        // 短曲在等首块期间可能已整曲读完，此时样本数就是精确时长
+        let duration = if stream.is_done() { stream.produced() as f64 / 2.0 / target_sr as f64 } else { estimate };
+        self.duration = duration;
 
-        let mut sink_guard = self.sink.lock().unwrap();
-        *sink_guard = Sink::try_new(&self.stream_handle).unwrap();
+        let mut sink_guard = lock_sink(&self.sink);
+        *sink_guard = self.errors.new_sink(&self.stream_handle, Some(path));
         sink_guard.set_volume(1.0);
-        sink_guard.append(UpmixSource::new(buffer, target_channels, self.is_playing.clone(), self.current_volume.clone()));
+        // 静音跳过要扫描整曲，流式播放阶段不生效，整曲读完后的下一次 seek 起生效
+        let source = AbLoopSource::new(stream.source_at(0), self.loop_region.clone(), 0.0);
+        self.append_chain(&sink_guard, source);
         sink_guard.play();
 
         Ok(duration)
     }
 
     fn play(&mut self) {
+        if self.is_playing.load(Ordering::SeqCst) { return; }
+        begin_fade(&self.dsp, self.fades.play_ms);
         if self.is_playing.swap(true, Ordering::SeqCst) { return; }
         
         let epoch = get_time_epoch();
         let now_us = Instant::now().duration_since(epoch).as_micros() as u64;
         self.last_play_us.store(now_us, Ordering::SeqCst);
         
-        if let Ok(s) = self.sink.lock() { s.play(); } 
+        lock_sink(&self.sink).play(); 
     }
 
     fn pause(&mut self) {
+        if !self.is_playing.load(Ordering::SeqCst) { return; }
+        let fade = begin_fade(&self.dsp, self.fades.pause_ms);
         if !self.is_playing.swap(false, Ordering::SeqCst) { return; }
         
         let start_us = self.last_play_us.swap(u64::MAX, Ordering::SeqCst);
@@ -262,7 +801,7 @@ impl AudioEngine for FFmpegEngine {
             
             let mut current = self.playback_pos.load(Ordering::Relaxed);
             loop {
-                let new_val = f64_from_bits(current) + elapsed;
+                let new_val = f64_from_bits(current) + elapsed * self.stretch_rate as f64;
                 match self.playback_pos.compare_exchange_weak(current, f64_to_bits(new_val), Ordering::SeqCst, Ordering::Relaxed) {
                     Ok(_) => break,
                     Err(x) => current = x,
@@ -270,23 +809,44 @@ impl AudioEngine for FFmpegEngine {
             }
         }
         
-        let my_token = self.fade_token.fetch_add(1, Ordering::SeqCst) + 1;
-        let token_ref = self.fade_token.clone();
-        let sink_clone = self.sink.clone();
-        let is_playing_flag = self.is_playing.clone();
-        thread::spawn(move || {
-            thread::sleep(Duration::from_millis(1000)); 
-            if token_ref.load(Ordering::SeqCst) == my_token && !is_playing_flag.load(Ordering::SeqCst) {
-                if let Ok(s) = sink_clone.lock() { s.pause(); } 
-            }
-        });
+        pause_after_fade(&self.sink, &self.fade_token, &self.is_playing, fade);
+    }
+
+    fn stop(&mut self) {
+        if self.is_playing.load(Ordering::SeqCst) {
+            let fade = begin_fade(&self.dsp, self.fades.pause_ms);
+            self.is_playing.store(false, Ordering::SeqCst);
+            thread::sleep(fade);
+        }
+        self.is_playing.store(false, Ordering::SeqCst);
+        self.fade_token.fetch_add(1, Ordering::SeqCst);
+
+        {
+            let mut sink_guard = lock_sink(&self.sink);
+            sink_guard.stop();
+            *sink_guard = self.errors.new_sink(&self.stream_handle, None);
+        }
+
+        for stream in self.streams.drain(..) { stream.cancel(); }
+        self.current_samples = None;
+        self.loaded_path = None;
+        self.loop_region.clear();
+        dsp_registry::clear_chain();
+        self.playback_pos.store(f64_to_bits(0.0), Ordering::SeqCst);
+        self.skips.reset_anchor();
+        self.last_play_us.store(u64::MAX, Ordering::SeqCst);
     }
 
     fn seek(&mut self, time: f64) {
         let is_playing_now = self.is_playing.load(Ordering::SeqCst);
-        if is_playing_now { self.is_playing.store(false, Ordering::SeqCst); thread::sleep(Duration::from_millis(40)); }
+        if is_playing_now {
+            let fade = begin_fade(&self.dsp, self.fades.seek_ms);
+            self.is_playing.store(false, Ordering::SeqCst);
+            thread::sleep(fade);
+        }
         
         self.playback_pos.store(f64_to_bits(time), Ordering::SeqCst);
+        self.skips.reset_anchor();
         let epoch = get_time_epoch();
         if is_playing_now {
             let now_us = Instant::now().duration_since(epoch).as_micros() as u64;
@@ -296,23 +856,102 @@ impl AudioEngine for FFmpegEngine {
         }
         
         {
-            let mut sink_guard = self.sink.lock().unwrap();
-            *sink_guard = Sink::try_new(&self.stream_handle).unwrap();
+            let mut sink_guard = lock_sink(&self.sink);
+            *sink_guard = self.errors.new_sink(&self.stream_handle, self.loaded_path.as_deref());
         }
-        let target_channels = *self.channel_mode.read().unwrap() as u16;
-        if let Some(samples_arc) = &self.current_samples {
-             let source = SamplesBuffer::new(2, self.sample_rate, samples_arc.to_vec()).skip_duration(Duration::from_secs_f64(time));
-             let sink_guard = self.sink.lock().unwrap();
-             sink_guard.set_volume(1.0);
-             sink_guard.append(UpmixSource::new(source, target_channels, self.is_playing.clone(), self.current_volume.clone()));
+        // 从曲首起的流已整曲读完且缓存完整，转为整曲内存源
+        if self.current_samples.is_none() {
+            if let Some(samples) = self.streams.iter().find_map(|s| s.take_samples()) {
+                self.current_samples = Some(samples);
+                for stream in self.streams.drain(..) { stream.cancel(); }
+            }
+        }
+        if let Some(samples_arc) = self.current_samples.clone() {
+            let silence = self.silence_cursor(&samples_arc);
+            let source = ArcSliceSource::new(samples_arc, 2, self.sample_rate).with_silence(silence).starting_at(Duration::from_secs_f64(time));
+            let source = AbLoopSource::new(source, self.loop_region.clone(), time);
+            let sink_guard = lock_sink(&self.sink);
+            sink_guard.set_volume(1.0);
+            self.append_chain(&sink_guard, source);
+        } else if let Some(source) = self.stream_source(time) {
+            let source = AbLoopSource::new(source, self.loop_region.clone(), time);
+            let sink_guard = lock_sink(&self.sink);
+            sink_guard.set_volume(1.0);
+            self.append_chain(&sink_guard, source);
         }
-        if is_playing_now { self.is_playing.store(true, Ordering::SeqCst); self.sink.lock().unwrap().play(); }
+        if is_playing_now { self.is_playing.store(true, Ordering::SeqCst); lock_sink(&self.sink).play(); }
     }
 
-    fn set_volume(&mut self, vol: f32) { self.current_volume.store(vol.to_bits(), Ordering::SeqCst); }
+    fn set_volume(&mut self, vol: f32) -> Result<(), String> { self.current_volume.store(vol.to_bits(), Ordering::SeqCst); Ok(()) }
 
-    fn set_channel_mode(&mut self, _mode: u16) {
-        let config = match _mode { 6 => ChannelConfig::Surround51, 8 => ChannelConfig::Surround71, 106 => ChannelConfig::True51, 108 => ChannelConfig::True71, _ => ChannelConfig::Stereo };
-        *self.channel_mode.write().unwrap() = config;
+    fn set_channel_mode(&mut self, mode: u16) -> Result<(), String> {
+        let config = match mode { 6 => ChannelConfig::Surround51, 8 => ChannelConfig::Surround71, 106 => ChannelConfig::True51, 108 => ChannelConfig::True71, _ => ChannelConfig::Stereo };
+        let previous = std::mem::replace(&mut *self.channel_mode.write().unwrap_or_else(|e| e.into_inner()), config);
+        // 输出声道数在 UpmixSource 建立时就已固定，已载入曲目时原位重挂链路立即生效
+        if previous != config && self.loaded_path.is_some() { self.seek(self.get_current_time()); }
+        Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 拼出恰好 len 个 UTF-16 单元的路径
+    fn padded(prefix: &str, len: usize) -> String {
+        let used = prefix.encode_utf16().count() + ".flac".len();
+        format!("{}{}.flac", prefix, "a".repeat(len - used))
+    }
+
+    #[test]
+    fn cjk_and_emoji_paths_stay_verbatim_but_fall_back_to_stdin() {
+        for path in [r"C:\音乐\周杰伦\七里香.flac", r"D:\Music\🎧 Mix\夜曲 🌙.m4a", "/home/用户/音乐/晴天.mp3"] {
+            assert_eq!(extended_length_path(path), None);
+            assert_eq!(input_path(path), OsString::from(path));
+            assert!(needs_pipe_fallback(path));
+        }
+        assert!(!needs_pipe_fallback(r"C:\Music\track.flac"));
+    }
+
+    #[test]
+    fn max_path_is_measured_in_utf16_units() {
+        // 100 个汉字的 UTF-8 超过 260 字节，但 UTF-16 只占约 110 个单元
+        let cjk = format!(r"C:\{}.flac", "歌".repeat(100));
+        assert!(cjk.len() > MAX_PATH);
+        assert_eq!(extended_length_path(&cjk), None);
+        // 代理对按两个单元计
+        let emoji = format!(r"C:\{}.flac", "🎵".repeat(126));
+        assert_eq!(emoji.encode_utf16().count(), 260);
+        assert!(extended_length_path(&emoji).is_some());
+
+        assert_eq!(extended_length_path(&padded(r"C:\Music\", 259)), None);
+        assert!(extended_length_path(&padded(r"C:\Music\", 260)).is_some());
+    }
+
+    #[test]
+    fn long_drive_paths_get_the_extended_prefix() {
+        let path = padded("C:/Music/Long Album/", 300);
+        let extended = extended_length_path(&path).unwrap();
+        assert_eq!(extended, format!(r"\\?\{}", path.replace('/', "\\")));
+        assert!(needs_pipe_fallback(&path));
+        // 只有 Windows 上才真的改写
+        let expected = if cfg!(windows) { OsString::from(&extended) } else { OsString::from(&path) };
+        assert_eq!(input_path(&path), expected);
+    }
+
+    #[test]
+    fn long_unc_paths_get_the_unc_prefix() {
+        let path = padded(r"\\nas\share\音乐\", 300);
+        let extended = extended_length_path(&path).unwrap();
+        assert_eq!(extended, format!(r"\\?\UNC\{}", &path[2..]));
+        assert!(!extended.contains(r"\\nas"));
+    }
+
+    #[test]
+    fn prefixed_device_and_relative_paths_are_left_alone() {
+        for prefix in [r"\\?\C:\Music\", r"\\?\UNC\nas\share\", r"\\.\pipe\", r"Music\Relative\", "/home/user/music/"] {
+            let path = padded(prefix, 300);
+            assert_eq!(extended_length_path(&path), None, "{}", prefix);
+            assert_eq!(input_path(&path), OsString::from(&path));
+        }
+    }
+}
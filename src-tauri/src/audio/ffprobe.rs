@@ -0,0 +1,85 @@
+// src/audio/ffprobe.rs
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use serde_json::Value;
+use super::ffmpeg::FFmpegEngine;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+// =================================================================
+// 🔍 ffprobe 兜底：lofty 读不了的容器 (老 WMA、APE、不规范的 MP4 等) 或读不出时长时，借已安装的 ffprobe 取标签与时长
+// =================================================================
+// 封面按 attached_pic 流定位，再由 ffmpeg 原样拷出图片字节。
+// 结果按 路径 + 修改时间 缓存 (失败同样缓存)，导入扫描与解码估时不会对同一文件重复起进程。
+#[derive(Default, Debug)]
+pub struct Probe {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: f64,
+    pub cover: Option<Vec<u8>>,
+}
+
+type Cache = Mutex<HashMap<PathBuf, (Option<SystemTime>, Option<Arc<Probe>>)>>;
+static CACHE: OnceLock<Cache> = OnceLock::new();
+
+fn command(exe: &Path) -> Command {
+    let mut cmd = Command::new(exe);
+    cmd.stdin(Stdio::null()).stderr(Stdio::null());
+    #[cfg(target_os = "windows")]
+    { cmd.creation_flags(0x08000000); }
+    cmd
+}
+
+// 未安装 ffprobe 时直接返回 None，不记缓存，装好之后即可生效
+pub fn probe(path: &Path) -> Option<Arc<Probe>> {
+    let exe = FFmpegEngine::installed_probe()?;
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    let cache = CACHE.get_or_init(Default::default);
+    if let Some((stamp, probe)) = cache.lock().unwrap_or_else(|e| e.into_inner()).get(path) {
+        if *stamp == modified { return probe.clone(); }
+    }
+    let probe = run(&exe, path).map(Arc::new);
+    cache.lock().unwrap_or_else(|e| e.into_inner()).insert(path.to_path_buf(), (modified, probe.clone()));
+    probe
+}
+
+fn run(exe: &Path, path: &Path) -> Option<Probe> {
+    let output = command(exe).args(["-v", "quiet", "-print_format", "json", "-show_format", "-show_streams"]).arg(path).output().ok()?;
+    if !output.status.success() { return None; }
+    let json: Value = serde_json::from_slice(&output.stdout).ok()?;
+    let format = &json["format"];
+    let streams = json["streams"].as_array().map(Vec::as_slice).unwrap_or_default();
+    // 标签大多挂在 format 上，Ogg 系容器挂在音频流上
+    let tag = |key: &str| {
+        std::iter::once(format).chain(streams.iter()).find_map(|node| {
+            node["tags"].as_object()?.iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .and_then(|(_, v)| v.as_str())
+                .map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+        })
+    };
+    let duration = format["duration"].as_str().and_then(|d| d.parse::<f64>().ok())
+        .or_else(|| streams.iter().find_map(|s| s["duration"].as_str()?.parse().ok()))
+        .filter(|d| d.is_finite() && *d > 0.0)
+        .unwrap_or(0.0);
+    let cover = streams.iter()
+        .find(|s| s["disposition"]["attached_pic"].as_i64() == Some(1))
+        .and_then(|s| s["index"].as_u64())
+        .and_then(|index| extract_picture(path, index));
+    Some(Probe { title: tag("title"), artist: tag("artist"), album: tag("album"), duration, cover })
+}
+
+fn extract_picture(path: &Path, index: u64) -> Option<Vec<u8>> {
+    let ffmpeg = FFmpegEngine::installed_exe()?;
+    let output = command(&ffmpeg).args(["-v", "quiet", "-i"]).arg(path)
+        .args(["-map", &format!("0:{}", index), "-c", "copy", "-frames:v", "1", "-f", "image2pipe", "-"])
+        .output().ok()?;
+    (output.status.success() && !output.stdout.is_empty()).then_some(output.stdout)
+}
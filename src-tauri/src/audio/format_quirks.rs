@@ -0,0 +1,446 @@
+// src/audio/format_quirks.rs
+
+use rodio::Source;
+use serde::Serialize;
+use std::time::Duration;
+
+// =================================================================
+// 🧩 格式怪癖登记表：编码器延迟 / 尾部填充 / 时长校正 / seek 精度
+// =================================================================
+// 每种格式一个小探针，load 时对 raw_bytes 运行一次；无缝裁剪、时长权威与
+// seek 逻辑统一读取这里的结果，不再各自散落 if 判断。
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerFormat { Mp3, Mp4, Ogg, Flac, Wav, Unknown }
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DurationCorrection {
+    None,                     // 信任解码器给出的时长
+    ExactSampleCount,         // 容器内记录了精确有效样本数（Xing 帧数 / iTunSMPB / elst）
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SeekAccuracy { Exact, FrameGranular, Approximate }
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct FormatQuirks {
+    pub format: ContainerFormat,
+    pub encoder_delay_samples: u32,
+    pub padding_samples: u32,
+    pub valid_samples: Option<u64>,   // 单声道帧计
+    pub sample_rate: Option<u32>,
+    pub duration_correction: DurationCorrection,
+    pub seek_accuracy: SeekAccuracy,
+    pub decoder_trims: bool,          // 解码器（symphonia gapless）已自行裁剪，不可重复裁
+}
+
+impl FormatQuirks {
+    fn plain(format: ContainerFormat, seek_accuracy: SeekAccuracy) -> Self {
+        Self {
+            format, encoder_delay_samples: 0, padding_samples: 0, valid_samples: None, sample_rate: None,
+            duration_correction: DurationCorrection::None, seek_accuracy, decoder_trims: false,
+        }
+    }
+
+    // 时长权威：容器给出精确样本数时以其为准，否则沿用解码器值
+    pub fn corrected_duration(&self, decoder_duration: f64) -> f64 {
+        match (self.duration_correction, self.valid_samples, self.sample_rate) {
+            (DurationCorrection::ExactSampleCount, Some(n), Some(sr)) if sr > 0 => n as f64 / sr as f64,
+            _ => decoder_duration,
+        }
+    }
+
+    // 需要由我们自己完成的裁剪：(起始跳过帧数, 有效帧数上限)
+    pub fn trim_frames(&self) -> (u64, Option<u64>) {
+        if self.decoder_trims { return (0, None); }
+        (self.encoder_delay_samples as u64, self.valid_samples)
+    }
+}
+
+type Detect = fn(&[u8]) -> bool;
+type Probe = fn(&[u8]) -> FormatQuirks;
+
+// 登记表：按顺序匹配，Ogg/Opus 的 pre-skip 探针后续只需在此追加一行
+const REGISTRY: &[(ContainerFormat, Detect, Probe)] = &[
+    (ContainerFormat::Mp4, |b| b.get(4..8) == Some(&b"ftyp"[..]), probe_mp4),
+    (ContainerFormat::Ogg, |b| b.starts_with(b"OggS"), |_| FormatQuirks::plain(ContainerFormat::Ogg, SeekAccuracy::Approximate)),
+    (ContainerFormat::Flac, |b| b.starts_with(b"fLaC"), |_| FormatQuirks::plain(ContainerFormat::Flac, SeekAccuracy::Exact)),
+    (ContainerFormat::Wav, |b| b.starts_with(b"RIFF"), |_| FormatQuirks::plain(ContainerFormat::Wav, SeekAccuracy::Exact)),
+    (ContainerFormat::Mp3, detect_mp3, probe_mp3),
+];
+
+pub fn probe(bytes: &[u8]) -> FormatQuirks {
+    REGISTRY.iter()
+        .find(|(_, detect, _)| detect(bytes))
+        .map(|(_, _, run)| run(bytes))
+        .unwrap_or_else(|| FormatQuirks::plain(ContainerFormat::Unknown, SeekAccuracy::Approximate))
+}
+
+// ---------------- MP3：Xing/Info + LAME 标签 ----------------
+
+// LAME 解码端固有延迟（528 + 1），与标签中的编码器延迟叠加
+const MP3_DECODER_DELAY: u32 = 529;
+
+fn id3v2_len(b: &[u8]) -> usize {
+    if b.len() < 10 || &b[0..3] != b"ID3" { return 0; }
+    let size = b[6..10].iter().fold(0usize, |acc, &x| (acc << 7) | (x & 0x7F) as usize);
+    let footer = if b[5] & 0x10 != 0 { 10 } else { 0 };
+    10 + size + footer
+}
+
+fn detect_mp3(b: &[u8]) -> bool {
+    let start = id3v2_len(b);
+    b.len() > start + 1 && b[start] == 0xFF && b[start + 1] & 0xE0 == 0xE0
+}
+
+fn be_u32(b: &[u8], at: usize) -> Option<u32> {
+    b.get(at..at + 4).map(|s| u32::from_be_bytes([s[0], s[1], s[2], s[3]]))
+}
+
+fn probe_mp3(b: &[u8]) -> FormatQuirks {
+    let mut quirks = FormatQuirks::plain(ContainerFormat::Mp3, SeekAccuracy::Approximate);
+    // symphonia 开启 gapless 时会自行应用 LAME 延迟/填充
+    quirks.decoder_trims = true;
+
+    let start = id3v2_len(b);
+    let Some(header) = be_u32(b, start) else { return quirks; };
+    let version_bits = (header >> 19) & 0x3;   // 3 = MPEG1, 2 = MPEG2, 0 = MPEG2.5
+    let sr_index = ((header >> 10) & 0x3) as usize;
+    let mono = (header >> 6) & 0x3 == 3;
+    let base_sr = [44100u32, 48000, 32000].get(sr_index).copied();
+    let sample_rate = base_sr.map(|sr| match version_bits { 3 => sr, 2 => sr / 2, _ => sr / 4 });
+    let samples_per_frame: u64 = if version_bits == 3 { 1152 } else { 576 };
+    let side_info = match (version_bits == 3, mono) { (true, true) => 17, (true, false) => 32, (false, true) => 9, (false, false) => 17 };
+
+    let xing = start + 4 + side_info;
+    let tag = b.get(xing..xing + 4);
+    if tag != Some(&b"Xing"[..]) && tag != Some(&b"Info"[..]) { return quirks; }
+    quirks.sample_rate = sample_rate;
+    quirks.seek_accuracy = SeekAccuracy::FrameGranular;
+
+    let flags = be_u32(b, xing + 4).unwrap_or(0);
+    let mut cursor = xing + 8;
+    let frames = if flags & 0x1 != 0 { let f = be_u32(b, cursor); cursor += 4; f } else { None };
+    if flags & 0x2 != 0 { cursor += 4; }
+    if flags & 0x4 != 0 { cursor += 100; }
+    if flags & 0x8 != 0 { cursor += 4; }
+
+    // LAME 扩展标签：偏移 21 处 24 bit 存放 12 bit 延迟 + 12 bit 填充
+    if let Some(d) = b.get(cursor + 21..cursor + 24) {
+        if matches!(b.get(cursor..cursor + 4), Some(b"LAME") | Some(b"Lavf") | Some(b"Lavc")) {
+            quirks.encoder_delay_samples = ((d[0] as u32) << 4 | (d[1] as u32) >> 4) + MP3_DECODER_DELAY;
+            quirks.padding_samples = ((d[1] as u32 & 0x0F) << 8 | d[2] as u32).saturating_sub(MP3_DECODER_DELAY);
+        }
+    }
+
+    if let Some(frames) = frames {
+        let total = frames as u64 * samples_per_frame;
+        quirks.valid_samples = Some(total.saturating_sub(quirks.encoder_delay_samples as u64 + quirks.padding_samples as u64));
+        quirks.duration_correction = DurationCorrection::ExactSampleCount;
+    }
+    quirks
+}
+
+// ---------------- AAC/M4A：iTunSMPB 与 edts/elst ----------------
+
+// 遍历一层 ISO-BMFF box，返回 (类型, 负载)
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < 8 { return None; }
+        let size32 = be_u32(data, 0)? as usize;
+        let kind = &data[4..8];
+        let (header, size) = match size32 {
+            1 => (16, u64::from_be_bytes(data.get(8..16)?.try_into().ok()?) as usize),
+            0 => (8, data.len()),
+            n => (8, n),
+        };
+        if size < header || size > data.len() { return None; }
+        let payload = &data[header..size];
+        data = &data[size..];
+        Some((kind, payload))
+    })
+}
+
+fn child<'a>(data: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    boxes(data).find(|(k, _)| *k == kind).map(|(_, p)| p)
+}
+
+struct TrackTiming { media_timescale: u32, edit_media_time: Option<i64>, edit_duration: Option<u64> }
+
+fn mp4_audio_timing(moov: &[u8]) -> Option<TrackTiming> {
+    let movie_timescale = child(moov, b"mvhd").and_then(|m| match m.first()? {
+        1 => be_u32(m, 20),
+        _ => be_u32(m, 12),
+    })?;
+
+    boxes(moov).filter(|(k, _)| *k == b"trak").find_map(|(_, trak)| {
+        let mdia = child(trak, b"mdia")?;
+        let handler = child(mdia, b"hdlr")?;
+        if handler.get(8..12) != Some(&b"soun"[..]) { return None; }
+        let mdhd = child(mdia, b"mdhd")?;
+        let media_timescale = match mdhd.first()? { 1 => be_u32(mdhd, 20)?, _ => be_u32(mdhd, 12)? };
+
+        let mut timing = TrackTiming { media_timescale, edit_media_time: None, edit_duration: None };
+        if let Some(elst) = child(trak, b"edts").and_then(|edts| child(edts, b"elst")) {
+            let v1 = elst.first() == Some(&1);
+            let entry = 8;
+            let (seg, media) = if v1 {
+                (elst.get(entry..entry + 8).map(|s| u64::from_be_bytes(s.try_into().unwrap())),
+                 elst.get(entry + 8..entry + 16).map(|s| i64::from_be_bytes(s.try_into().unwrap())))
+            } else {
+                (be_u32(elst, entry).map(|v| v as u64), be_u32(elst, entry + 4).map(|v| v as i32 as i64))
+            };
+            timing.edit_media_time = media.filter(|m| *m >= 0);
+            timing.edit_duration = seg.map(|s| s * media_timescale as u64 / movie_timescale.max(1) as u64);
+        }
+        Some(timing)
+    })
+}
+
+// iTunSMPB: " 00000000 00000840 000001CA 00000000003F31F6 ..."（延迟 / 填充 / 原始样本数，十六进制）
+fn itunsmpb(b: &[u8]) -> Option<(u32, u32, u64)> {
+    let at = b.windows(8).position(|w| w == b"iTunSMPB")?;
+    let rest = &b[at + 8..];
+    let data_at = rest.windows(4).position(|w| w == b"data")?;
+    let text: String = rest.get(data_at + 12..)?.iter().take(128)
+        .take_while(|c| c.is_ascii_hexdigit() || **c == b' ')
+        .map(|&c| c as char).collect();
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    let delay = u32::from_str_radix(fields.get(1)?, 16).ok()?;
+    let padding = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+    let samples = u64::from_str_radix(fields.get(3)?, 16).ok()?;
+    Some((delay, padding, samples))
+}
+
+fn probe_mp4(b: &[u8]) -> FormatQuirks {
+    let mut quirks = FormatQuirks::plain(ContainerFormat::Mp4, SeekAccuracy::Exact);
+    let timing = child(b, b"moov").and_then(mp4_audio_timing);
+    quirks.sample_rate = timing.as_ref().map(|t| t.media_timescale);
+
+    if let Some((delay, padding, samples)) = itunsmpb(b) {
+        quirks.encoder_delay_samples = delay;
+        quirks.padding_samples = padding;
+        quirks.valid_samples = Some(samples);
+        quirks.duration_correction = DurationCorrection::ExactSampleCount;
+    } else if let Some(t) = timing {
+        if let Some(media_time) = t.edit_media_time {
+            quirks.encoder_delay_samples = media_time as u32;
+            quirks.valid_samples = t.edit_duration;
+            quirks.duration_correction = if t.edit_duration.is_some() { DurationCorrection::ExactSampleCount } else { DurationCorrection::None };
+        }
+    }
+    quirks
+}
+
+// =================================================================
+// ✂️ 无缝裁剪：跳过编码器延迟，截断尾部填充（解码器未代劳时）
+// =================================================================
+pub struct GaplessTrim<I: Source<Item = f32>> {
+    input: I,
+    skip_remaining: u64,
+    remaining: Option<u64>,
+}
+
+impl<I: Source<Item = f32>> GaplessTrim<I> {
+    pub fn new(input: I, quirks: &FormatQuirks) -> Self {
+        let channels = input.channels().max(1) as u64;
+        let (skip, valid) = quirks.trim_frames();
+        Self { skip_remaining: skip * channels, remaining: valid.map(|v| v * channels), input }
+    }
+}
+
+impl<I: Source<Item = f32>> Iterator for GaplessTrim<I> {
+    type Item = f32;
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        while self.skip_remaining > 0 {
+            self.input.next()?;
+            self.skip_remaining -= 1;
+        }
+        match &mut self.remaining {
+            Some(0) => None,
+            Some(n) => { *n -= 1; self.input.next() }
+            None => self.input.next(),
+        }
+    }
+}
+
+impl<I: Source<Item = f32>> Source for GaplessTrim<I> {
+    fn current_frame_len(&self) -> Option<usize> { self.input.current_frame_len() }
+    fn channels(&self) -> u16 { self.input.channels() }
+    fn sample_rate(&self) -> u32 { self.input.sample_rate() }
+    fn total_duration(&self) -> Option<Duration> { self.input.total_duration() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn bx(kind: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut out = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    // version/flags 之后的第 3 个 32 bit 字段即时间刻度 (v0 的 mvhd / mdhd 相同布局)
+    fn timescale_box(kind: &[u8], timescale: u32) -> Vec<u8> {
+        let mut p = vec![0u8; 12];
+        p.extend_from_slice(&timescale.to_be_bytes());
+        p.extend_from_slice(&0u32.to_be_bytes());
+        bx(kind, &p)
+    }
+
+    fn trak(handler: &[u8], media_timescale: u32, elst: Option<Vec<u8>>) -> Vec<u8> {
+        let mut hdlr = vec![0u8; 8];
+        hdlr.extend_from_slice(handler);
+        hdlr.extend_from_slice(&[0u8; 12]);
+        let mdia = bx(b"mdia", &[bx(b"hdlr", &hdlr), timescale_box(b"mdhd", media_timescale)].concat());
+        let edts = elst.map(|e| bx(b"edts", &bx(b"elst", &e))).unwrap_or_default();
+        bx(b"trak", &[edts, mdia].concat())
+    }
+
+    fn elst_v0(segment: u32, media_time: i32) -> Vec<u8> {
+        [&[0u8, 0, 0, 0][..], &1u32.to_be_bytes(), &segment.to_be_bytes(), &media_time.to_be_bytes(), &[0, 1, 0, 0]].concat()
+    }
+
+    fn elst_v1(segment: u64, media_time: i64) -> Vec<u8> {
+        [&[1u8, 0, 0, 0][..], &1u32.to_be_bytes(), &segment.to_be_bytes(), &media_time.to_be_bytes(), &[0, 1, 0, 0]].concat()
+    }
+
+    // 视频轨排在前面，确认取的是音频轨的 elst
+    fn m4a(media_timescale: u32, elst: Option<Vec<u8>>, extra: &[u8]) -> Vec<u8> {
+        let video = trak(b"vide", 90000, Some(elst_v0(10_000, 3000)));
+        let moov = bx(b"moov", &[timescale_box(b"mvhd", 1000), video, trak(b"soun", media_timescale, elst), extra.to_vec()].concat());
+        [bx(b"ftyp", b"M4A \0\0\0\0isomiso2"), moov].concat()
+    }
+
+    fn itunes_smpb(text: &str) -> Vec<u8> {
+        let name = bx(b"name", &[&[0u8; 4][..], b"iTunSMPB"].concat());
+        let data = bx(b"data", &[&[0u8, 0, 0, 1][..], &[0; 4], text.as_bytes()].concat());
+        let freeform = bx(b"----", &[bx(b"mean", b"\0\0\0\0com.apple.iTunes"), name, data].concat());
+        bx(b"udta", &bx(b"meta", &[&[0u8; 4][..], &bx(b"ilst", &freeform)[..]].concat()))
+    }
+
+    #[test]
+    fn mp4_edit_list_golden() {
+        let q = probe(&m4a(44100, Some(elst_v0(10_000, 2112)), &[]));
+        assert_eq!(q.format, ContainerFormat::Mp4);
+        assert_eq!((q.encoder_delay_samples, q.padding_samples, q.valid_samples, q.sample_rate), (2112, 0, Some(441_000), Some(44100)));
+        assert_eq!((q.duration_correction, q.seek_accuracy, q.decoder_trims), (DurationCorrection::ExactSampleCount, SeekAccuracy::Exact, false));
+        assert_eq!(q.trim_frames(), (2112, Some(441_000)));
+        assert_eq!(q.corrected_duration(10.07), 10.0);
+
+        // 64 bit 版本的 elst，媒体刻度与影片刻度不同
+        let q = probe(&m4a(48000, Some(elst_v1(20_000, 1024)), &[]));
+        assert_eq!((q.encoder_delay_samples, q.valid_samples, q.sample_rate), (1024, Some(960_000), Some(48000)));
+        assert_eq!(q.corrected_duration(0.0), 20.0);
+    }
+
+    #[test]
+    fn mp4_empty_edit_or_no_edit_list_trusts_the_decoder() {
+        for elst in [Some(elst_v0(10_000, -1)), None] {
+            let q = probe(&m4a(44100, elst, &[]));
+            assert_eq!((q.encoder_delay_samples, q.valid_samples), (0, None));
+            assert_eq!(q.duration_correction, DurationCorrection::None);
+            assert_eq!(q.corrected_duration(12.5), 12.5);
+        }
+    }
+
+    #[test]
+    fn mp4_itunsmpb_golden_overrides_the_edit_list() {
+        let smpb = itunes_smpb(" 00000000 00000840 000001CA 00000000003F31F6 00000000 00000000 00000000 00000000");
+        let q = probe(&m4a(44100, Some(elst_v0(10_000, 1024)), &smpb));
+        assert_eq!((q.encoder_delay_samples, q.padding_samples, q.valid_samples), (2112, 458, Some(4_141_558)));
+        assert_eq!(q.duration_correction, DurationCorrection::ExactSampleCount);
+        assert_eq!(q.trim_frames(), (2112, Some(4_141_558)));
+        assert!((q.corrected_duration(0.0) - 4_141_558.0 / 44100.0).abs() < 1e-9);
+
+        // 字段不全时不采信，退回 elst
+        let q = probe(&m4a(44100, Some(elst_v0(10_000, 1024)), &itunes_smpb(" 00000000 00000840")));
+        assert_eq!((q.encoder_delay_samples, q.padding_samples, q.valid_samples), (1024, 0, Some(441_000)));
+    }
+
+    // MPEG1 Layer III 44.1 kHz 联合立体声，Info 标签带全部四个可选字段与 LAME 扩展
+    fn lame_mp3(id3: &[u8], delay: u32, padding: u32, frames: u32) -> Vec<u8> {
+        let mut b = id3.to_vec();
+        b.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        b.extend_from_slice(&[0u8; 32]);
+        b.extend_from_slice(b"Info");
+        b.extend_from_slice(&0x0Fu32.to_be_bytes());
+        b.extend_from_slice(&frames.to_be_bytes());
+        b.extend_from_slice(&[0u8; 4 + 100 + 4]);
+        let mut lame = b"LAME3.100".to_vec();
+        lame.resize(21, 0);
+        lame.extend_from_slice(&[(delay >> 4) as u8, ((delay & 0xF) << 4 | padding >> 8) as u8, (padding & 0xFF) as u8]);
+        b.extend_from_slice(&lame);
+        b.resize(b.len() + 64, 0);
+        b
+    }
+
+    #[test]
+    fn mp3_lame_tag_golden() {
+        let q = probe(&lame_mp3(&[], 576, 1728, 1000));
+        assert_eq!(q.format, ContainerFormat::Mp3);
+        // 标签里的延迟/填充按 LAME 解码端固有的 529 样本校正
+        assert_eq!((q.encoder_delay_samples, q.padding_samples), (1105, 1199));
+        assert_eq!((q.valid_samples, q.sample_rate), (Some(1_152_000 - 1105 - 1199), Some(44100)));
+        assert_eq!((q.duration_correction, q.seek_accuracy), (DurationCorrection::ExactSampleCount, SeekAccuracy::FrameGranular));
+        // symphonia 已自行裁剪，我们不再重复
+        assert!(q.decoder_trims);
+        assert_eq!(q.trim_frames(), (0, None));
+        assert!((q.corrected_duration(0.0) - 1_149_696.0 / 44100.0).abs() < 1e-9);
+
+        // ID3v2 (syncsafe 长度 + footer) 之后同样能找到帧头
+        let id3 = [&b"ID3\x04\x00\x10\x00\x00\x01\x00"[..], &[0u8; 128 + 10]].concat();
+        let q = probe(&lame_mp3(&id3, 576, 1728, 1000));
+        assert_eq!((q.encoder_delay_samples, q.valid_samples), (1105, Some(1_149_696)));
+    }
+
+    #[test]
+    fn mp3_mpeg2_mono_xing_without_lame() {
+        let mut b = vec![0xFF, 0xF3, 0x80, 0xC4];
+        b.extend_from_slice(&[0u8; 9]);
+        b.extend_from_slice(b"Xing");
+        b.extend_from_slice(&1u32.to_be_bytes());
+        b.extend_from_slice(&100u32.to_be_bytes());
+        b.resize(b.len() + 64, 0);
+        let q = probe(&b);
+        assert_eq!((q.encoder_delay_samples, q.padding_samples, q.valid_samples, q.sample_rate), (0, 0, Some(57_600), Some(22050)));
+        assert_eq!(q.corrected_duration(0.0), 57_600.0 / 22050.0);
+    }
+
+    #[test]
+    fn mp3_without_xing_and_other_containers_stay_plain() {
+        let mut cbr = vec![0xFF, 0xFB, 0x90, 0x64];
+        cbr.resize(512, 0);
+        let q = probe(&cbr);
+        assert_eq!((q.format, q.seek_accuracy, q.valid_samples, q.duration_correction), (ContainerFormat::Mp3, SeekAccuracy::Approximate, None, DurationCorrection::None));
+        for (bytes, format, accuracy) in [
+            (&b"fLaC\0\0\0\x22"[..], ContainerFormat::Flac, SeekAccuracy::Exact),
+            (&b"OggS\0\x02"[..], ContainerFormat::Ogg, SeekAccuracy::Approximate),
+            (&b"RIFF\x24\0\0\0WAVE"[..], ContainerFormat::Wav, SeekAccuracy::Exact),
+            (&b"not audio"[..], ContainerFormat::Unknown, SeekAccuracy::Approximate),
+        ] {
+            let q = probe(bytes);
+            assert_eq!((q.format, q.seek_accuracy, q.trim_frames()), (format, accuracy, (0, None)));
+        }
+    }
+
+    #[test]
+    fn gapless_trim_skips_delay_and_cuts_padding_per_frame() {
+        let samples: Vec<f32> = (0..40).map(|i| i as f32).collect();
+        let mut quirks = FormatQuirks::plain(ContainerFormat::Mp4, SeekAccuracy::Exact);
+        (quirks.encoder_delay_samples, quirks.valid_samples) = (3, Some(5));
+        let out: Vec<f32> = GaplessTrim::new(SamplesBuffer::new(2, 44100, samples.clone()), &quirks).collect();
+        assert_eq!(out, samples[6..16]);
+
+        // 解码器已裁剪时原样透传
+        quirks.decoder_trims = true;
+        assert_eq!(GaplessTrim::new(SamplesBuffer::new(2, 44100, samples.clone()), &quirks).count(), 40);
+    }
+}
@@ -1,7 +1,27 @@
-use super::AudioEngine;
+use super::{begin_fade, lock_sink, pause_after_fade, AudioEngine, EngineCapabilities};
+use super::dsp_registry::{self, hash_params, DspStage, StageMeter};
+use super::analysis::{AnalyzingSource, ClipReport};
+use super::coordinator::{self, COORDINATOR};
+use super::stretch::{self, TimeStretchSource};
+use super::format_quirks::{self, FormatQuirks, GaplessTrim};
+use super::threads;
+use super::spectrum;
+use super::level_meter;
+use super::playback_error::{ErrorReporter, PlaybackErrorCode};
+use super::decode_progress::{self, DecodeTracker, ProgressReporter};
+use super::silence::{SilenceCache, SilenceCursor, SkipLog, SkipSilence};
+use super::memory_source::{ArcSliceSource, PartialPcm};
+use super::pcm_spill::{self, CacheStrategy, SpillFile};
+use super::ffmpeg;
+use super::dsp::{self, BassBoost, Crossfeed, Dither, DownmixSource, DspSettings, EqBand, LfeCrossover, MonoToStereoSource, UpmixParams};
+use biquad::{Biquad, DirectForm2Transposed};
+use crate::util::cancel::CancellationToken;
+use crate::util::jobs::{JobHandle, JobKind, JobRegistry};
 use rodio::{Decoder, OutputStreamHandle, Sink, Source};
+use rodio::source::SamplesConverter;
 use std::fs::File;
 use std::io::{Cursor, Read};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{Arc, RwLock, Mutex, OnceLock};
 use std::sync::atomic::{AtomicUsize, AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::time::{Duration, Instant};
@@ -39,10 +59,26 @@ pub enum ChannelConfig {
     True71 = 108,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResamplerQuality {
+    Fast,        // 交由 rodio 内置线性转换
+    HighQuality, // Rubato Sinc 分块重采样
+}
+
+impl ResamplerQuality {
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id { "fast" => Some(Self::Fast), "high_quality" => Some(Self::HighQuality), _ => None }
+    }
+    pub fn id(&self) -> &'static str {
+        match self { Self::Fast => "fast", Self::HighQuality => "high_quality" }
+    }
+}
+
 // =================================================================
 // 🧠 动态硬件采样率嗅探器
 // =================================================================
 fn get_dynamic_target_sr() -> u32 {
+    if let Some(sr) = super::stream_sample_rate() { return sr; }
     if let Some(device) = rodio::cpal::default_host().default_output_device() {
         if let Ok(config) = device.default_output_config() {
             let sr = config.sample_rate().0;
@@ -94,6 +130,8 @@ pub mod mmcss {
 // =================================================================
 // 🚀 发烧级 Rubato Sinc 重采样器
 // =================================================================
+type SeekedDecoder = RubatoSource<SamplesConverter<Decoder<Cursor<Vec<u8>>>, f32>>;
+
 pub struct RubatoSource<I: Source<Item = f32>> {
     input: I,
     resampler: Option<SincFixedIn<f32>>,
@@ -105,6 +143,9 @@ pub struct RubatoSource<I: Source<Item = f32>> {
     target_sr: u32,
     source_sr: u32,
     exhausted: bool,
+    frames_in: usize,  // 累计读入 / 交出的帧数，收尾时按总量对齐到 输入 × 比率
+    frames_out: usize,
+    meter: StageMeter,
 }
 
 impl<I: Source<Item = f32>> RubatoSource<I> {
@@ -116,7 +157,8 @@ impl<I: Source<Item = f32>> RubatoSource<I> {
             debug_log!("Source SR ({}) matches Target SR. Bypassing Resampler.", source_sr);
             return Self {
                 input, resampler: None, input_buffers: vec![], output_buffer: vec![],
-                output_pos: 0, channels, chunk_size: 0, target_sr, source_sr, exhausted: false,
+                output_pos: 0, channels, chunk_size: 0, target_sr, source_sr, exhausted: false, frames_in: 0, frames_out: 0,
+                meter: StageMeter::new("resampler", false, hash_params((source_sr, target_sr))),
             };
         }
 
@@ -144,12 +186,21 @@ impl<I: Source<Item = f32>> RubatoSource<I> {
             resampler: Some(resampler),
             input_buffers: vec![Vec::with_capacity(chunk_size); channels],
             output_buffer: Vec::with_capacity(chunk_size * channels * 3), 
-            output_pos: 0, channels, chunk_size, target_sr, source_sr, exhausted: false,
+            output_pos: 0, channels, chunk_size, target_sr, source_sr, exhausted: false, frames_in: 0, frames_out: 0,
+            meter: StageMeter::new("resampler", true, hash_params((source_sr, target_sr, chunk_size))),
         }
     }
 
+    pub fn stage(&self) -> Arc<DspStage> { self.meter.stage() }
+
     fn process_next_chunk(&mut self) {
         if self.resampler.is_none() || self.exhausted { return; }
+        let started = Instant::now();
+        self.process_chunk_inner();
+        self.meter.record(started.elapsed());
+    }
+
+    fn process_chunk_inner(&mut self) {
         for ch in 0..self.channels { self.input_buffers[ch].clear(); }
         let mut frames_read = 0;
         for _ in 0..self.chunk_size {
@@ -170,13 +221,15 @@ impl<I: Source<Item = f32>> RubatoSource<I> {
             frames_read += 1;
         }
 
-        if frames_read == 0 && self.exhausted { return; }
+        self.frames_in += frames_read;
+        // 按累计总量而不是最后半块计算收尾帧数，逐块取整的误差不会累积；输入恰好整块结束时再用一块静音冲出尾巴
+        let expected_total = (self.frames_in as f64 * (self.target_sr as f64 / self.source_sr as f64)).round() as usize;
+        if self.exhausted && expected_total <= self.frames_out { return; }
         let out_buffers = self.resampler.as_mut().unwrap().process(&self.input_buffers, None).unwrap();
         self.output_buffer.clear();
         let out_frames = out_buffers[0].len();
-        let valid_out_frames = if self.exhausted {
-            (frames_read as f64 * (self.target_sr as f64 / self.source_sr as f64)).round() as usize
-        } else { out_frames };
+        let valid_out_frames = if self.exhausted { expected_total - self.frames_out } else { out_frames };
+        self.frames_out += valid_out_frames.min(out_frames);
 
         for i in 0..valid_out_frames.min(out_frames) {
             for ch in 0..self.channels {
@@ -219,24 +272,36 @@ impl<I: Source<Item = f32>> Source for RubatoSource<I> {
 // 空间混音与软拐点压限器
 // =================================================================
 pub struct SpatialProcessor {
-    lfe_state: f32, delay_buffer: Vec<(f32, f32)>, delay_pos: usize, alpha: f32,
+    lfe_filters: Vec<DirectForm2Transposed<f32>>, delay_buffer: Vec<(f32, f32)>, delay_pos: usize, sample_rate: u32,
 }
 
 impl SpatialProcessor {
-    pub fn new(sample_rate: u32) -> Self {
-        let delay_samples = (sample_rate as f32 * 0.020) as usize;
-        let dt = 1.0 / sample_rate as f32;
-        let rc = 1.0 / (2.0 * std::f32::consts::PI * 120.0);
-        let alpha = dt / (rc + dt);
-        Self { lfe_state: 0.0, delay_buffer: vec![(0.0, 0.0); delay_samples.max(1)], delay_pos: 0, alpha }
+    pub fn new(sample_rate: u32, crossover: LfeCrossover, rear_delay_ms: f32) -> Self {
+        let mut processor = Self { lfe_filters: Vec::new(), delay_buffer: Vec::new(), delay_pos: 0, sample_rate };
+        processor.set_crossover(crossover);
+        processor.set_rear_delay(rear_delay_ms);
+        processor
+    }
+    // 长度变化时整块换成静音缓冲，读写指针归零，不会越界也不会读到旧长度的残留
+    pub fn set_rear_delay(&mut self, rear_delay_ms: f32) {
+        let delay_samples = ((self.sample_rate as f32 * rear_delay_ms / 1000.0) as usize).max(1);
+        if delay_samples == self.delay_buffer.len() { return; }
+        self.delay_buffer = vec![(0.0, 0.0); delay_samples];
+        self.delay_pos = 0;
+    }
+    // 换系数时连同滤波器状态一起重建，旧状态配新系数会在 LFE 上产生一下低频冲击
+    pub fn set_crossover(&mut self, crossover: LfeCrossover) {
+        if let Some(c) = crossover.coefficients(self.sample_rate) {
+            self.lfe_filters = vec![DirectForm2Transposed::<f32>::new(c); crossover.stages()];
+        }
     }
     pub fn process(&mut self, l: f32, r: f32) -> (f32, f32, f32) {
         let mono = (l + r) * 0.5;
-        self.lfe_state += self.alpha * (mono - self.lfe_state);
+        let lfe = self.lfe_filters.iter_mut().fold(mono, |acc, f| f.run(acc));
         let (delayed_l, delayed_r) = self.delay_buffer[self.delay_pos];
         self.delay_buffer[self.delay_pos] = (l, r);
         self.delay_pos = (self.delay_pos + 1) % self.delay_buffer.len();
-        (self.lfe_state, delayed_l, delayed_r)
+        (lfe, delayed_l, delayed_r)
     }
 }
 
@@ -252,47 +317,150 @@ pub struct UpmixSource<I: Source<Item = f32>> {
     
     is_playing_flag: Arc<AtomicBool>,
     state_vol: f32,
+    fade_ms: u32,
     fade_step: f32,
 
     master_vol_target: Arc<AtomicU32>,
     master_vol_current: f32,
     master_vol_alpha: f32,
+
+    settings: Arc<DspSettings>,
+    width_current: f32,
+    balance_current: f32,
+    limiter: dsp::LookaheadLimiter,
+    dither: Dither,
+    dither_version: u64,
+    dither_active: bool,
+    auto_level_version: u64,
+    auto_level_on: bool, // 响度拉平开启时限幅器强制工作，作为硬上限
+    dither_rng: dsp::TpdfDither,
+    crossover_version: u64,
+    upmix: UpmixParams,
+    upmix_version: u64,
+    layout_slots: [usize; 8],
+    layout_identity: bool,
+    layout_version: u64,
+    frame_tick: usize,
     
     is_first_run: bool, 
+    meter: StageMeter,
+    meter_tick: u32,
+    levels: level_meter::LevelTap,
 }
 
 impl<I: Source<Item = f32>> UpmixSource<I> {
-    pub fn new(input: I, config_code: u16, is_playing_flag: Arc<AtomicBool>, master_vol_target: Arc<AtomicU32>) -> Self {
+    pub fn new(input: I, config_code: u16, is_playing_flag: Arc<AtomicBool>, master_vol_target: Arc<AtomicU32>, settings: Arc<DspSettings>) -> Self {
         let sample_rate = input.sample_rate();
+        let fade_ms = settings.fade_ms.load(Ordering::Relaxed);
+        spectrum::set_sample_rate(sample_rate);
         let (target_channels, virtualize) = match config_code {
             6 => (6, true), 8 => (8, true), 106 => (6, false), 108 => (8, false), _ => (2, false),
         };
-        Self { 
+        let mut source = Self { 
             input, target_channels, virtualize, current_frame: Vec::with_capacity(8), 
-            dsp: SpatialProcessor::new(sample_rate),
+            dsp: SpatialProcessor::new(sample_rate, settings.lfe_crossover.get(), settings.upmix.get().rear_delay_ms),
+            crossover_version: settings.lfe_crossover.version(), frame_tick: 0,
+            upmix: settings.upmix.get(), upmix_version: settings.upmix.version(),
+            layout_slots: [0; 8], layout_identity: true, layout_version: settings.channel_layout.version(),
             dc_l: 0.0, dc_r: 0.0, prev_l: 0.0, prev_r: 0.0,
-            is_playing_flag, state_vol: 0.0, fade_step: 1.0 / (sample_rate.max(1) as f32 * 0.03), 
+            is_playing_flag, state_vol: 0.0, fade_ms, fade_step: dsp::fade_step(sample_rate, fade_ms), 
             master_vol_current: f32::from_bits(master_vol_target.load(Ordering::Relaxed)),
             master_vol_target, master_vol_alpha: 1.0 / (sample_rate.max(1) as f32 * 0.02), 
+            width_current: settings.stereo_width(),
+            balance_current: settings.balance(),
+            limiter: dsp::LookaheadLimiter::new(if virtualize { 2 } else { target_channels as usize }, sample_rate),
+            dither: settings.dither.get(), dither_version: settings.dither.version(), dither_active: false,
+            auto_level_version: settings.auto_level.version(), auto_level_on: settings.auto_level.get().enabled,
+            dither_rng: dsp::TpdfDither::new(get_time_epoch().elapsed().as_nanos() as u64), settings,
             is_first_run: true,
-        }
+            meter: StageMeter::new("spatial_upmix", target_channels != 2 || virtualize, hash_params((target_channels, virtualize))),
+            meter_tick: 0,
+            levels: level_meter::LevelTap::new(sample_rate),
+        };
+        source.refresh_layout();
+        source
     }
 
+    pub fn stage(&self) -> Arc<DspStage> { self.meter.stage() }
+
+    fn refresh_layout(&mut self) {
+        let n = self.target_channels as usize;
+        self.layout_slots = self.settings.channel_layout.get().slots(n);
+        self.layout_identity = self.layout_slots.iter().take(n).enumerate().all(|(role, &slot)| role == slot);
+    }
+
+    // 帧按角色顺序 (L R C LFE Ls Rs Lb Rb) 组装，这里重排到设备期望的声道位置
     #[inline(always)]
-    fn audiophile_limiter(mut val: f32) -> f32 {
-        let abs_val = val.abs();
-        if abs_val <= 0.98 {
-            val 
-        } else {
-            let diff = abs_val - 0.98;
-            val.signum() * (0.98 + diff / (1.0 + diff * 8.0)) 
+    fn apply_layout(&mut self) {
+        if self.layout_identity { return; }
+        let n = self.current_frame.len();
+        let mut roles = [0f32; 8];
+        roles[..n].copy_from_slice(&self.current_frame);
+        for (role, &sample) in roles[..n].iter().enumerate() { self.current_frame[self.layout_slots[role]] = sample; }
+    }
+
+    // 加宽时侧声道放大会让样本越界，超过 1.0 的宽度统一过软削波
+    #[inline(always)]
+    fn apply_width(&self, l: f32, r: f32) -> (f32, f32) {
+        let width = self.width_current;
+        if width == 1.0 { return (l, r); }
+        let mid = (l + r) * 0.5;
+        let side = (l - r) * 0.5 * width;
+        if width > 1.0 { (dsp::soft_clip(mid + side), dsp::soft_clip(mid - side)) } else { (mid + side, mid - side) }
+    }
+
+    // 整帧送入前视限幅器，再叠加抖动后倒序，供 next() 逐个 pop；按块上报增益衰减并刷新抖动参数
+    #[inline(always)]
+    fn finish_frame(&mut self) {
+        self.limiter.process(&mut self.current_frame, self.auto_level_on || self.settings.limiter_enabled.load(Ordering::Relaxed));
+        if self.limiter.frame_no().is_multiple_of(dsp::PARAM_CHECK_STRIDE as u64) {
+            let reduction = self.limiter.reduction_db();
+            self.settings.limiter_reduction.store(reduction.to_bits(), Ordering::Relaxed);
+            let version = self.settings.dither.version();
+            if version != self.dither_version {
+                self.dither_version = version;
+                self.dither = self.settings.dither.get();
+            }
+            let version = self.settings.auto_level.version();
+            if version != self.auto_level_version {
+                self.auto_level_version = version;
+                self.auto_level_on = self.settings.auto_level.get().enabled;
+            }
+            // 满音量直通且没有任何处理时不加噪声，保持比特透明
+            let transparent = self.master_vol_current == 1.0 && self.state_vol == 1.0 && self.target_channels == 2 && !self.virtualize
+                && reduction == 0.0 && self.settings.is_transparent();
+            self.dither_active = self.dither.enabled && self.state_vol > 0.0 && !transparent;
         }
+        if self.dither_active {
+            let lsb = self.dither.lsb();
+            for sample in self.current_frame.iter_mut() { *sample = (*sample + self.dither_rng.noise(lsb)).clamp(-1.0, 1.0); }
+        }
+        self.levels.push(&self.current_frame);
+        // 多声道帧已按布局重排 (Film / Custom 下 1 号位可能是中置)，频谱与示波器按布局取回 L、R
+        let (left, right) = if self.virtualize || self.target_channels == 2 { (0, 1) } else { (self.layout_slots[0], self.layout_slots[1]) };
+        if let (Some(&l), Some(&r)) = (self.current_frame.get(left), self.current_frame.get(right)) { spectrum::push(l, r); }
+        self.current_frame.reverse();
     }
 }
 
+// 逐样本链路按 1/1024 抽样计时并等比放大，避免每个样本都打时间戳
+const UPMIX_METER_STRIDE: u32 = 1024;
+
 impl<I: Source<Item = f32>> Iterator for UpmixSource<I> {
     type Item = f32;
     fn next(&mut self) -> Option<f32> {
+        self.meter_tick = self.meter_tick.wrapping_add(1);
+        if !self.meter_tick.is_multiple_of(UPMIX_METER_STRIDE) { return self.next_sample(); }
+        let started = Instant::now();
+        let out = self.next_sample();
+        self.meter.record(started.elapsed() * UPMIX_METER_STRIDE);
+        out
+    }
+}
+
+impl<I: Source<Item = f32>> UpmixSource<I> {
+    #[inline(always)]
+    fn next_sample(&mut self) -> Option<f32> {
         if self.is_first_run {
             self.is_first_run = false;
             mmcss::elevate_thread();
@@ -300,6 +468,11 @@ impl<I: Source<Item = f32>> Iterator for UpmixSource<I> {
         }
 
         if self.current_frame.is_empty() {
+            let fade_ms = self.settings.fade_ms.load(Ordering::Relaxed);
+            if fade_ms != self.fade_ms {
+                self.fade_ms = fade_ms;
+                self.fade_step = dsp::fade_step(self.input.sample_rate(), fade_ms);
+            }
             let target_state = if self.is_playing_flag.load(Ordering::Relaxed) { 1.0 } else { 0.0 };
             if self.state_vol != target_state {
                 if self.state_vol < target_state { self.state_vol = (self.state_vol + self.fade_step).min(target_state); } 
@@ -309,7 +482,8 @@ impl<I: Source<Item = f32>> Iterator for UpmixSource<I> {
 
             if m == 0.0 && target_state == 0.0 {
                 let out_channels = if self.virtualize { 2 } else { self.target_channels };
-                for _ in 0..out_channels { self.current_frame.push(0.0); }
+                self.current_frame.resize(out_channels as usize, 0.0);
+                self.finish_frame();
                 return self.current_frame.pop();
             }
 
@@ -330,42 +504,81 @@ impl<I: Source<Item = f32>> Iterator for UpmixSource<I> {
             self.dc_l = l; self.dc_r = r;
             self.prev_l = raw_l; self.prev_r = raw_r;
 
+            // M/S 声场宽度：与音量同样做平滑过渡，实时调节不产生拉链噪声
+            let target_width = self.settings.stereo_width();
+            let width_diff = target_width - self.width_current;
+            if width_diff.abs() > 0.0001 { self.width_current += width_diff * self.master_vol_alpha; }
+            else { self.width_current = target_width; }
+            let (l, r) = self.apply_width(l, r);
+
+            let target_balance = self.settings.balance();
+            let balance_diff = target_balance - self.balance_current;
+            if balance_diff.abs() > 0.0001 { self.balance_current += balance_diff * self.master_vol_alpha; }
+            else { self.balance_current = target_balance; }
+            // 平衡只作用于左右成对的声道，中置与 LFE 不动
+            let (bal_l, bal_r) = dsp::balance_gains(self.balance_current);
+
             if self.target_channels == 2 && !self.virtualize {
-                self.current_frame.push(Self::audiophile_limiter(r * final_gain));
-                self.current_frame.push(Self::audiophile_limiter(l * final_gain));
+                self.current_frame.push(l * bal_l * final_gain);
+                self.current_frame.push(r * bal_r * final_gain);
+                self.finish_frame();
                 return self.current_frame.pop();
             }
             
+            // 分频与上混参数按块检查一次版本，变化时才读锁重建
+            self.frame_tick += 1;
+            if self.frame_tick >= dsp::PARAM_CHECK_STRIDE {
+                self.frame_tick = 0;
+                let version = self.settings.lfe_crossover.version();
+                if version != self.crossover_version {
+                    self.crossover_version = version;
+                    self.dsp.set_crossover(self.settings.lfe_crossover.get());
+                }
+                let version = self.settings.upmix.version();
+                if version != self.upmix_version {
+                    self.upmix_version = version;
+                    self.upmix = self.settings.upmix.get();
+                    self.dsp.set_rear_delay(self.upmix.rear_delay_ms);
+                }
+                let version = self.settings.channel_layout.version();
+                if version != self.layout_version {
+                    self.layout_version = version;
+                    self.refresh_layout();
+                }
+            }
             let (lfe_raw, rear_l_raw, rear_r_raw) = self.dsp.process(l, r);
-            let center = (l + r) * 0.5;
+            let UpmixParams { rear_level, center_level, lfe_level, .. } = self.upmix;
+            let (lfe_raw, rear_l_raw, rear_r_raw) = (lfe_raw * lfe_level, rear_l_raw * rear_level, rear_r_raw * rear_level);
+            let center = (l + r) * 0.5 * center_level;
             
             if self.virtualize {
                 if self.target_channels == 6 {
                     let mix_l = l * 0.75 + center * 0.3 + lfe_raw * 0.6 - rear_r_raw * 0.45;
                     let mix_r = r * 0.75 + center * 0.3 + lfe_raw * 0.6 - rear_l_raw * 0.45;
-                    self.current_frame.push(Self::audiophile_limiter(mix_l * final_gain)); 
-                    self.current_frame.push(Self::audiophile_limiter(mix_r * final_gain)); 
+                    self.current_frame.push(mix_l * bal_l * final_gain); 
+                    self.current_frame.push(mix_r * bal_r * final_gain); 
                 } else {
                     let mix_l = l * 0.65 + center * 0.3 + lfe_raw * 0.7 - rear_r_raw * 0.55 + rear_l_raw * 0.2;
                     let mix_r = r * 0.65 + center * 0.3 + lfe_raw * 0.7 - rear_l_raw * 0.55 + rear_r_raw * 0.2;
-                    self.current_frame.push(Self::audiophile_limiter(mix_l * final_gain)); 
-                    self.current_frame.push(Self::audiophile_limiter(mix_r * final_gain)); 
+                    self.current_frame.push(mix_l * bal_l * final_gain); 
+                    self.current_frame.push(mix_r * bal_r * final_gain); 
                 }
             } else {
                 let lfe = lfe_raw * 1.2;
-                self.current_frame.push(Self::audiophile_limiter(l * final_gain));          
-                self.current_frame.push(Self::audiophile_limiter(r * final_gain));          
-                self.current_frame.push(Self::audiophile_limiter(center * final_gain));     
-                self.current_frame.push(Self::audiophile_limiter(lfe * final_gain));        
-                self.current_frame.push(Self::audiophile_limiter(rear_l_raw * final_gain)); 
-                self.current_frame.push(Self::audiophile_limiter(rear_r_raw * final_gain)); 
+                self.current_frame.push(l * bal_l * final_gain);          
+                self.current_frame.push(r * bal_r * final_gain);          
+                self.current_frame.push(center * final_gain);     
+                self.current_frame.push(lfe * final_gain);        
+                self.current_frame.push(rear_l_raw * bal_l * final_gain); 
+                self.current_frame.push(rear_r_raw * bal_r * final_gain); 
                 
                 if self.target_channels == 8 {
-                    self.current_frame.push(Self::audiophile_limiter(rear_l_raw * 0.8 * final_gain)); 
-                    self.current_frame.push(Self::audiophile_limiter(rear_r_raw * 0.8 * final_gain)); 
+                    self.current_frame.push(rear_l_raw * 0.8 * bal_l * final_gain); 
+                    self.current_frame.push(rear_r_raw * 0.8 * bal_r * final_gain); 
                 }
+                self.apply_layout();
             }
-            self.current_frame.reverse(); 
+            self.finish_frame();
         }
         self.current_frame.pop()
     }
@@ -375,57 +588,337 @@ impl<I: Source<Item = f32>> Source for UpmixSource<I> {
     fn current_frame_len(&self) -> Option<usize> { None }
     fn channels(&self) -> u16 { if self.virtualize { 2 } else { self.target_channels } } 
     fn sample_rate(&self) -> u32 { self.input.sample_rate() }
+    // 升混只增加每帧的声道数，帧数不变，时长沿用输入
     fn total_duration(&self) -> Option<Duration> { self.input.total_duration() }
 }
 
 // =================================================================
-// 后台零拷贝内存源引擎 (最核心的 O(1) 瞬切基石)
+// 🔁 A-B 循环：包裹可 seek 的内存源，越过 B 点时样本级回跳 A 点，无需重建 Sink
 // =================================================================
-#[derive(Clone)]
-pub struct ArcSliceSource {
-    data: Arc<Vec<f32>>, pos: usize, channels: u16, sample_rate: u32,
+pub struct LoopRegion {
+    active: AtomicBool,
+    start: AtomicU64, // f64 bits (秒)
+    end: AtomicU64,
 }
 
-impl ArcSliceSource {
-    pub fn new(data: Arc<Vec<f32>>, channels: u16, sample_rate: u32) -> Self {
-        Self { data, pos: 0, channels, sample_rate }
+impl LoopRegion {
+    pub fn new() -> Self {
+        Self { active: AtomicBool::new(false), start: AtomicU64::new(f64_to_bits(0.0)), end: AtomicU64::new(f64_to_bits(0.0)) }
     }
-    pub fn skip_duration(mut self, duration: Duration) -> Self {
-        let offset = (duration.as_secs_f64() * self.sample_rate as f64 * self.channels as f64) as usize;
-        self.pos = offset.min(self.data.len());
-        self.pos -= self.pos % self.channels as usize;
-        self
+    pub fn set(&self, start: f64, end: f64) {
+        self.start.store(f64_to_bits(start), Ordering::SeqCst);
+        self.end.store(f64_to_bits(end), Ordering::SeqCst);
+        self.active.store(true, Ordering::SeqCst);
     }
+    pub fn clear(&self) { self.active.store(false, Ordering::SeqCst); }
+    pub fn bounds(&self) -> Option<(f64, f64)> {
+        if !self.active.load(Ordering::Relaxed) { return None; }
+        Some((f64_from_bits(self.start.load(Ordering::Relaxed)), f64_from_bits(self.end.load(Ordering::Relaxed))))
+    }
+    // 把挂钟推算出的线性时间折叠回循环区间内
+    pub fn fold(&self, t: f64) -> f64 {
+        match self.bounds() {
+            Some((start, end)) if t >= end && end > start => start + (t - start) % (end - start),
+            _ => t,
+        }
+    }
+}
+
+pub struct AbLoopSource<I: Source<Item = f32>> {
+    input: I,
+    region: Arc<LoopRegion>,
+    sample_idx: u64,
+    channels: u64,
+    sample_rate: f64,
 }
 
-impl Iterator for ArcSliceSource {
+impl<I: Source<Item = f32>> AbLoopSource<I> {
+    pub fn new(input: I, region: Arc<LoopRegion>, start_time: f64) -> Self {
+        let channels = input.channels().max(1) as u64;
+        let sample_rate = input.sample_rate() as f64;
+        let sample_idx = (start_time.max(0.0) * sample_rate) as u64 * channels;
+        Self { input, region, sample_idx, channels, sample_rate }
+    }
+}
+
+impl<I: Source<Item = f32>> Iterator for AbLoopSource<I> {
     type Item = f32;
     #[inline(always)]
     fn next(&mut self) -> Option<f32> {
-        if self.pos < self.data.len() { let val = self.data[self.pos]; self.pos += 1; Some(val) } else { None }
+        if self.sample_idx.is_multiple_of(self.channels) {
+            if let Some((start, end)) = self.region.bounds() {
+                let end_idx = (end * self.sample_rate) as u64 * self.channels;
+                if self.sample_idx >= end_idx && self.input.try_seek(Duration::from_secs_f64(start)).is_ok() {
+                    self.sample_idx = (start * self.sample_rate) as u64 * self.channels;
+                }
+            }
+        }
+        let val = self.input.next()?;
+        self.sample_idx += 1;
+        Some(val)
+    }
+}
+
+impl<I: Source<Item = f32>> Source for AbLoopSource<I> {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { self.input.channels() }
+    fn sample_rate(&self) -> u32 { self.input.sample_rate() }
+    fn total_duration(&self) -> Option<Duration> { self.input.total_duration() }
+}
+
+// =================================================================
+// 🔊 信号发生器：正弦 / 扫频 / 粉噪 / 白噪，经完整 DSP 链输出用于房间调校
+// =================================================================
+pub const GENERATOR_DEFAULT_DB: f32 = -20.0;
+pub const GENERATOR_MAX_DB: f32 = -6.0; // 硬上限，保护耳朵与高音单元
+const GENERATOR_DEFAULT_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GeneratorSignal {
+    Sine { freq: f32 },
+    Sweep { start: f32, end: f32, seconds: f32, log: bool },
+    PinkNoise,
+    WhiteNoise,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+pub struct GeneratorParams {
+    pub freq: Option<f32>,
+    pub start: Option<f32>,
+    pub end: Option<f32>,
+    pub seconds: Option<f32>,
+    #[serde(default)]
+    pub log: bool,
+    pub level_db: Option<f32>,
+    pub seed: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GeneratorRequest {
+    pub signal: GeneratorSignal,
+    pub level_db: f32,
+    pub seed: u64,
+}
+
+impl GeneratorRequest {
+    pub fn parse(kind: &str, params: &GeneratorParams) -> Result<Self, String> {
+        let signal = match kind {
+            "sine" => GeneratorSignal::Sine { freq: params.freq.unwrap_or(1000.0) },
+            "sweep" => GeneratorSignal::Sweep {
+                start: params.start.unwrap_or(20.0), end: params.end.unwrap_or(20000.0),
+                seconds: params.seconds.unwrap_or(10.0), log: params.log,
+            },
+            "pink_noise" => GeneratorSignal::PinkNoise,
+            "white_noise" => GeneratorSignal::WhiteNoise,
+            _ => return Err("UNKNOWN_GENERATOR".to_string()),
+        };
+        let level_db = params.level_db.unwrap_or(GENERATOR_DEFAULT_DB);
+        if !level_db.is_finite() { return Err("GENERATOR_INVALID_LEVEL".to_string()); }
+        Ok(Self { signal, level_db: level_db.min(GENERATOR_MAX_DB), seed: params.seed.unwrap_or(GENERATOR_DEFAULT_SEED) })
+    }
+
+    pub fn validate(&self, sample_rate: u32) -> Result<(), String> {
+        let nyquist = sample_rate as f32 / 2.0;
+        let in_band = |f: f32| f.is_finite() && f > 0.0 && f < nyquist;
+        match self.signal {
+            GeneratorSignal::Sine { freq } if !in_band(freq) => Err(format!("GENERATOR_INVALID_FREQ: {}Hz", freq)),
+            GeneratorSignal::Sweep { start, end, seconds, .. } if !in_band(start) || !in_band(end) || !seconds.is_finite() || seconds <= 0.0 => {
+                Err(format!("GENERATOR_INVALID_SWEEP: {}Hz → {}Hz over {}s", start, end, seconds))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// 立体声同相输出；扫频以相位累加实现，频率变化与循环回起点时波形都连续
+pub struct SignalGenerator {
+    request: GeneratorRequest,
+    sample_rate: u32,
+    amplitude: f32,
+    phase: f64,
+    elapsed: u64,
+    rng: u64,
+    pink: [f32; 7],
+    pink_peak: f32, // 粉噪滤波输出出现过的最大幅值，不低于 1
+    pending_right: Option<f32>,
+}
+
+impl SignalGenerator {
+    pub fn new(request: GeneratorRequest, sample_rate: u32) -> Self {
+        Self {
+            request, sample_rate,
+            amplitude: 10f32.powf(request.level_db.min(GENERATOR_MAX_DB) / 20.0),
+            phase: 0.0, elapsed: 0, rng: request.seed.max(1), pink: [0.0; 7], pink_peak: 1.0, pending_right: None,
+        }
+    }
+
+    // xorshift64*：固定种子下输出可复现
+    #[inline(always)]
+    fn white(&mut self) -> f32 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40;
+        bits as f32 / (1u64 << 23) as f32 - 1.0
+    }
+
+    // Paul Kellett 精化版粉噪滤波；输出偶尔越过 ±1，按出现过的峰值整体缩小而不削波
+    #[inline(always)]
+    fn pink(&mut self) -> f32 {
+        let w = self.white();
+        let b = &mut self.pink;
+        b[0] = 0.99886 * b[0] + w * 0.0555179;
+        b[1] = 0.99332 * b[1] + w * 0.0750759;
+        b[2] = 0.96900 * b[2] + w * 0.153852;
+        b[3] = 0.86650 * b[3] + w * 0.3104856;
+        b[4] = 0.55000 * b[4] + w * 0.5329522;
+        b[5] = -0.7616 * b[5] - w * 0.0168980;
+        let out = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + w * 0.5362;
+        b[6] = w * 0.115926;
+        let out = out * 0.11;
+        self.pink_peak = self.pink_peak.max(out.abs());
+        out / self.pink_peak
+    }
+
+    #[inline(always)]
+    fn tone(&mut self, freq: f64) -> f32 {
+        let out = (self.phase * std::f64::consts::TAU).sin() as f32;
+        self.phase = (self.phase + freq / self.sample_rate as f64).fract();
+        out
+    }
+
+    fn next_frame(&mut self) -> f32 {
+        let raw = match self.request.signal {
+            GeneratorSignal::Sine { freq } => self.tone(freq as f64),
+            GeneratorSignal::Sweep { start, end, seconds, log } => {
+                let period = (seconds as f64 * self.sample_rate as f64).max(1.0) as u64;
+                let t = (self.elapsed % period) as f64 / period as f64;
+                let freq = if log { start as f64 * (end as f64 / start as f64).powf(t) } else { start as f64 + (end - start) as f64 * t };
+                self.tone(freq)
+            }
+            GeneratorSignal::PinkNoise => self.pink(),
+            GeneratorSignal::WhiteNoise => self.white(),
+        };
+        self.elapsed += 1;
+        raw * self.amplitude
+    }
+}
+
+impl Iterator for SignalGenerator {
+    type Item = f32;
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        if let Some(r) = self.pending_right.take() { return Some(r); }
+        let sample = self.next_frame();
+        self.pending_right = Some(sample);
+        Some(sample)
     }
 }
 
-impl Source for ArcSliceSource {
+impl Source for SignalGenerator {
     fn current_frame_len(&self) -> Option<usize> { None }
-    fn channels(&self) -> u16 { self.channels }
+    fn channels(&self) -> u16 { 2 }
+    fn sample_rate(&self) -> u32 { self.sample_rate }
+    fn total_duration(&self) -> Option<Duration> { None }
+}
+
+// =================================================================
+// 🔈 声道识别：按角色顺序逐个声道播放提示音，核对声道排布是否与设备一致
+// =================================================================
+const IDENT_TONE_SECS: f64 = 1.0;
+const IDENT_GAP_SECS: f64 = 0.3;
+const IDENT_LEVEL: f32 = 0.1;
+const IDENT_FADE_SECS: f64 = 0.01;
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ChannelIdent {
+    pub role: &'static str,
+    pub output: usize, // 实际发声的输出声道序号
+    pub start: f64,    // 相对开始播放的秒数
+}
+
+// 绕过 DSP 直接输出离散声道；LFE 用 60Hz，其余 1kHz
+pub struct ChannelIdentSource {
+    slots: [usize; 8],
+    channels: usize,
+    sample_rate: u32,
+    frame: u64,
+    channel: usize,
+    current: (usize, f32),
+    phase: f64,
+}
+
+impl ChannelIdentSource {
+    pub fn new(channel_code: u16, layout: dsp::ChannelLayout, sample_rate: u32) -> Self {
+        let channels = match channel_code { 106 => 6, 108 => 8, _ => 2 };
+        Self { slots: layout.slots(channels), channels, sample_rate, frame: 0, channel: 0, current: (0, 0.0), phase: 0.0 }
+    }
+
+    fn step_frames(&self) -> u64 { ((IDENT_TONE_SECS + IDENT_GAP_SECS) * self.sample_rate as f64) as u64 }
+
+    pub fn plan(&self) -> Vec<ChannelIdent> {
+        (0..self.channels).map(|role| ChannelIdent {
+            role: dsp::CHANNEL_ROLES[role], output: self.slots[role], start: role as f64 * (IDENT_TONE_SECS + IDENT_GAP_SECS),
+        }).collect()
+    }
+
+    // 当前帧的 (发声声道, 样本)；提示音首尾各做短淡入淡出避免爆音
+    fn frame_sample(&mut self) -> (usize, f32) {
+        let step = self.step_frames().max(1);
+        let role = (self.frame / step) as usize;
+        let t = (self.frame % step) as f64 / self.sample_rate as f64;
+        if t >= IDENT_TONE_SECS { return (self.slots[role], 0.0); }
+        let freq = if role == dsp::LFE_ROLE { 60.0 } else { 1000.0 };
+        let envelope = (t / IDENT_FADE_SECS).min((IDENT_TONE_SECS - t) / IDENT_FADE_SECS).min(1.0) as f32;
+        let sample = (self.phase * std::f64::consts::TAU).sin() as f32 * IDENT_LEVEL * envelope;
+        self.phase = (self.phase + freq / self.sample_rate as f64).fract();
+        (self.slots[role], sample)
+    }
+}
+
+impl Iterator for ChannelIdentSource {
+    type Item = f32;
+    fn next(&mut self) -> Option<f32> {
+        if self.channel == 0 {
+            if self.frame >= self.step_frames() * self.channels as u64 { return None; }
+            self.current = self.frame_sample();
+            self.frame += 1;
+        }
+        let out = if self.channel == self.current.0 { self.current.1 } else { 0.0 };
+        self.channel = (self.channel + 1) % self.channels;
+        Some(out)
+    }
+}
+
+impl Source for ChannelIdentSource {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { self.channels as u16 }
     fn sample_rate(&self) -> u32 { self.sample_rate }
     fn total_duration(&self) -> Option<Duration> {
-        let frames_left = (self.data.len() - self.pos) / self.channels as usize;
-        Some(Duration::from_secs_f64(frames_left as f64 / self.sample_rate as f64))
+        Some(Duration::from_secs_f64((IDENT_TONE_SECS + IDENT_GAP_SECS) * self.channels as f64))
     }
 }
 
 // =================================================================
 // GalaxyEngine 主控 (Adaptive Sync Core)
 // =================================================================
+// symphonia 实际编入的容器 (rodio symphonia-all + aiff)；Ogg / MKA 没开对应 demuxer，与 APE / WavPack / Opus / DSD 一样由 AudioManager 转交 FFmpeg
+pub const NATIVE_FORMATS: &[&str] = &["mp3", "flac", "wav", "m4a", "aac", "aiff", "aif"];
+// seek 目标略超出已解码范围时最多等这么久，仍未解到就改走 IO 定位
+const SEEK_GRACE: Duration = Duration::from_millis(300);
+// 估算时长与实际解码结果相差超过此值 (秒) 才发 duration-updated
+const DURATION_TOLERANCE: f64 = 0.05;
+// 同一时刻只跑一条主解码：快速切歌时被叫停的旧线程在下一个检查点退出，新线程等它让出槽位，不与之争抢 CPU
+static MAIN_DECODE_SLOT: Mutex<()> = Mutex::new(());
+
 pub struct GalaxyEngine {
     sink: Arc<Mutex<Sink>>,
     stream_handle: OutputStreamHandle,
     raw_bytes: Option<Arc<Vec<u8>>>,
     decoded_samples: Arc<RwLock<Option<Arc<Vec<f32>>>>>, 
     is_decoded: Arc<AtomicBool>, 
-    decode_session: Arc<AtomicUsize>, 
+    partial: Arc<PartialPcm>, // 整曲解码完成前已解出的部分
+    spill: Option<Arc<SpillFile>>, // 超出内存预算时整曲 PCM 改写在这里，不进内存缓存
     is_playing: Arc<AtomicBool>, 
     sample_rate: u32,
     channels: u16,
@@ -434,18 +927,37 @@ pub struct GalaxyEngine {
     playback_pos: Arc<AtomicU64>,
     last_play_us: Arc<AtomicU64>, 
     fade_token: Arc<AtomicUsize>, 
+    resampler_quality: ResamplerQuality,
+    loaded_path: Option<String>,
+    preloaded: Option<Preloaded>,
+    decode_token: Option<CancellationToken>, // 当前缓存槽对应的后台解码任务
+    jobs: Arc<JobRegistry>,
+    clip_report: Arc<RwLock<Option<ClipReport>>>,
+    loop_region: Arc<LoopRegion>,
+    chain_loopable: bool, // 当前链路是否挂在可回跳的内存源上（流式解码阶段不可）
+    stretch_rate: f32, // 变速不变调倍率，1.0 时该级直通
+    quirks: Option<FormatQuirks>,
+    dsp: Arc<DspSettings>,
+    fades: dsp::FadeConfig,
+    skip_silence: SkipSilence,
+    silence_cache: SilenceCache,
+    skips: Arc<SkipLog>,
+    bitperfect: bool, // 比特完美直通：不重采样、不变速、不经 DSP 与 UpmixSource
+    errors: ErrorReporter,
+    progress: ProgressReporter,
 }
 
 impl GalaxyEngine {
-    pub fn new(stream_handle: OutputStreamHandle) -> Self {
-        let sink = Sink::try_new(&stream_handle).unwrap();
+    pub fn new(stream_handle: OutputStreamHandle, jobs: Arc<JobRegistry>, errors: ErrorReporter, progress: ProgressReporter) -> Self {
+        let sink = errors.new_sink(&stream_handle, None);
         Self {
             sink: Arc::new(Mutex::new(sink)),
             stream_handle,
             raw_bytes: None,
             decoded_samples: Arc::new(RwLock::new(None)),
             is_decoded: Arc::new(AtomicBool::new(false)),
-            decode_session: Arc::new(AtomicUsize::new(0)),
+            partial: PartialPcm::new(0),
+            spill: None,
             is_playing: Arc::new(AtomicBool::new(false)), 
             sample_rate: 44100, 
             channels: 2,
@@ -454,38 +966,480 @@ impl GalaxyEngine {
             playback_pos: Arc::new(AtomicU64::new(f64_to_bits(0.0))),
             last_play_us: Arc::new(AtomicU64::new(u64::MAX)),
             fade_token: Arc::new(AtomicUsize::new(0)),
+            resampler_quality: ResamplerQuality::HighQuality,
+            loaded_path: None,
+            preloaded: None,
+            decode_token: None,
+            jobs,
+            clip_report: Arc::new(RwLock::new(None)),
+            loop_region: Arc::new(LoopRegion::new()),
+            chain_loopable: false,
+            stretch_rate: 1.0,
+            quirks: None,
+            dsp: DspSettings::new(),
+            fades: dsp::FadeConfig::default(),
+            skip_silence: SkipSilence::default(),
+            silence_cache: SilenceCache::default(),
+            skips: Arc::new(SkipLog::default()),
+            bitperfect: false,
+            errors,
+            progress,
         }
     }
 
+    // 只有整曲 PCM 缓存才能预先标出静音段，流式解码阶段不跳
+    fn silence_cursor(&mut self, samples: &Arc<Vec<f32>>) -> Option<SilenceCursor> {
+        let map = self.silence_cache.get(samples, self.channels, self.sample_rate, self.skip_silence)?;
+        Some(SilenceCursor::new(map, self.skips.clone(), self.loop_region.clone(), self.channels, self.sample_rate))
+    }
+
+    // 比特完美链路没有 UpmixSource，转场一律直接切换
+    fn transition_fade(&self, fade_ms: u32) -> Duration {
+        begin_fade(&self.dsp, if self.bitperfect { 0 } else { fade_ms })
+    }
+
+    // Fast 模式下目标采样率取源采样率，RubatoSource 自动旁路，由 Sink 完成线性转换；比特完美模式同理
+    fn resolve_target_sr(&self, source_sr: u32) -> u32 {
+        match self.resampler_quality {
+            _ if self.bitperfect => source_sr,
+            ResamplerQuality::HighQuality => get_dynamic_target_sr(),
+            ResamplerQuality::Fast => source_sr,
+        }
+    }
+
+    // 组装声道归一 (单声道复制 / 多声道折叠) → 变速 → DSP → UpmixSource 链路并挂上 Sink；比特完美模式下解码输出直接入 Sink
+    fn append_chain<S>(&self, sink: &Sink, source: S, stages: Vec<Arc<DspStage>>)
+    where S: Source<Item = f32> + Send + 'static {
+        if self.bitperfect {
+            dsp_registry::publish_chain(stages);
+            sink.append(source);
+            return;
+        }
+        let target_channels = *self.channel_mode.read().unwrap_or_else(|e| e.into_inner()) as u16;
+        let (mixed_source, stages) = Self::processing_chain(source, stages, &self.dsp, self.stretch_rate, target_channels, self.is_playing.clone(), self.current_volume.clone());
+        dsp_registry::publish_chain(stages);
+        sink.append(mixed_source);
+    }
+
+    // 单声道展宽 → 多声道折叠 → 变速 → DSP → 升混；返回末级与整条链的登记 (不依赖输出设备)
+    fn processing_chain<S>(source: S, mut stages: Vec<Arc<DspStage>>, settings: &Arc<DspSettings>, stretch_rate: f32, target_channels: u16, is_playing: Arc<AtomicBool>, volume: Arc<AtomicU32>) -> (impl Source<Item = f32> + Send + 'static, Vec<Arc<DspStage>>)
+    where S: Source<Item = f32> + Send + 'static {
+        let widened = MonoToStereoSource::new(source);
+        stages.push(widened.stage());
+        let folded = DownmixSource::new(widened, settings.clone());
+        stages.push(folded.stage());
+        let stretched = TimeStretchSource::new(folded, stretch_rate);
+        stages.push(stretched.stage());
+        let (processed, dsp_stages) = dsp::chain(stretched, settings.clone(), target_channels);
+        let mixed_source = UpmixSource::new(processed, target_channels, is_playing, volume, settings.clone());
+        let stages = [stages, dsp_stages, vec![mixed_source.stage()]].concat();
+        (mixed_source, stages)
+    }
+
     fn create_decoder(data: &Arc<Vec<u8>>) -> Result<Decoder<Cursor<Vec<u8>>>, String> {
         let cursor = Cursor::new(data.to_vec()); 
         Decoder::new(cursor).map_err(|e| e.to_string())
     }
+
+    fn read_file(path: &str) -> Result<Arc<Vec<u8>>, String> {
+        let mut file = File::open(path).map_err(|e| e.to_string())?;
+        let len = file.metadata().map_err(|e| e.to_string())?.len();
+        let mut buffer = Vec::with_capacity(len as usize);
+        file.read_to_end(&mut buffer).map_err(|e| e.to_string())?;
+        Ok(Arc::new(buffer))
+    }
+
+    // 叫停当前缓存槽的后台解码（含接管来的预载线程），并换上全新的缓存槽
+    fn cancel_active_decode(&mut self) {
+        if let Some(token) = self.decode_token.take() {
+            token.cancel();
+            self.decoded_samples = Arc::new(RwLock::new(None));
+            self.is_decoded = Arc::new(AtomicBool::new(false));
+            self.partial = PartialPcm::new(0);
+            self.spill = None;
+        }
+    }
+
+    // 另起解码器直接按时间定位；定位后的位置与 GaplessTrim 的首尾帧数对不上，这条路径不做修剪
+    fn seek_decoder(&self, raw_bytes: &Arc<Vec<u8>>, time: f64) -> Result<SeekedDecoder, String> {
+        let mut decoder = Self::create_decoder(raw_bytes)?;
+        decoder.try_seek(Duration::from_secs_f64(time)).map_err(|e| e.to_string())?;
+        Ok(RubatoSource::new(decoder.convert_samples::<f32>(), self.sample_rate))
+    }
+
+    // 预计整曲 PCM 超出内存预算时改写临时文件；建不了文件就仍走内存
+    fn spill_for(expected: usize) -> Option<Arc<SpillFile>> {
+        if !pcm_spill::exceeds_budget(expected) { return None; }
+        debug_log!("Estimated PCM ({} MB) exceeds the memory budget, caching to a temp file.", expected * 4 / 1024 / 1024);
+        SpillFile::create().map_err(|e| eprintln!("[GALAXY] Cannot create PCM spill file, keeping it in memory: {}", e)).ok()
+    }
+
+    fn register_decode(&self, path: &str) -> JobHandle {
+        let name = std::path::Path::new(path).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        self.jobs.register(format!("decode_{}", path), JobKind::Decode, name)
+    }
+
+    // 测试用：不经输出设备，按 load 的流程在后台整曲解码内存中的音频；返回解码完成标志
+    #[cfg(test)]
+    pub(crate) fn decode_detached(raw_bytes: Vec<u8>, target_sr: u32, jobs: &Arc<JobRegistry>) -> Arc<AtomicBool> {
+        let raw_bytes = Arc::new(raw_bytes);
+        let quirks = format_quirks::probe(&raw_bytes);
+        let source = Self::create_decoder(&raw_bytes).expect("fixture should decode natively");
+        let expected = source.total_duration().map_or(0, |d| (d.as_secs_f64() * target_sr as f64) as usize * source.channels() as usize);
+        let is_decoded = Arc::new(AtomicBool::new(false));
+        let (errors_tx, _) = std::sync::mpsc::channel();
+        let (progress_tx, _) = std::sync::mpsc::channel();
+        let job = jobs.register("decode_detached", JobKind::Decode, "detached");
+        Self::spawn_full_decode(raw_bytes, quirks, target_sr, expected, Arc::new(RwLock::new(None)), is_decoded.clone(), PartialPcm::new(expected), None,
+            Arc::new(RwLock::new(None)), job, ErrorReporter::new(errors_tx, "galaxy"), ProgressReporter::new(progress_tx, "galaxy").start("detached", expected), "detached".to_string(), || true);
+        is_decoded
+    }
+
+    // is_main 为假时作为辅助任务运行（如预载），在主缓存填充未过阈值前主动让路；被接管后随时晋升为主任务
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_full_decode<G>(raw_bytes: Arc<Vec<u8>>, quirks: FormatQuirks, target_sr: u32, expected_samples: usize, samples_ref: Arc<RwLock<Option<Arc<Vec<f32>>>>>, is_decoded_ref: Arc<AtomicBool>, partial: Arc<PartialPcm>, spill: Option<Arc<SpillFile>>, clip_report_ref: Arc<RwLock<Option<ClipReport>>>, job: JobHandle, errors: ErrorReporter, mut progress: DecodeTracker, path: String, is_main: G)
+    where G: Fn() -> bool + Send + 'static {
+        threads::spawn("galaxy-decode", move || {
+            debug_log!("Background full-decode thread started (Normal Priority to protect real-time stream!).");
+            // 先等上一条主解码退出再开始；排队期间已被叫停 (又切了歌) 就不再复制字节、建解码器
+            // 渐进缓存 / 临时文件停止增长，挂在上面的源播到已解码处结束
+            let release = || {
+                partial.abandon();
+                if let Some(file) = &spill { file.finish(); }
+            };
+            let _slot = is_main().then(|| MAIN_DECODE_SLOT.lock().unwrap_or_else(|e| e.into_inner()));
+            if job.is_cancelled() || threads::shutting_down() { return release(); }
+
+            // 解码失败时叫停本任务的令牌
+            let abandon = |message: String| {
+                job.token().cancel();
+                release();
+                errors.report(PlaybackErrorCode::DecodeFailed, message, Some(&path));
+            };
+            let decoder = match Decoder::new(Cursor::new(raw_bytes.to_vec())) {
+                Ok(decoder) => decoder,
+                Err(e) => return abandon(e.to_string()),
+            };
+            // 解码器遇到损坏的数据可能 panic，截住后按解码失败上报
+            let finished = panic::catch_unwind(AssertUnwindSafe(|| {
+                // 削波分析挂在重采样之前，观测未经软拐点处理的原始样本
+                let analyzed = AnalyzingSource::new(GaplessTrim::new(decoder.convert_samples::<f32>(), &quirks), clip_report_ref);
+                let hq_source = RubatoSource::new(analyzed, target_sr);
+                let channels = hq_source.channels() as usize;
+                // 每到检查点把这一段并入渐进缓存 (超出内存预算时写进临时文件)，seek 随即可用
+                let store = |chunk: &[f32]| match &spill {
+                    Some(file) => file.append(chunk).map_err(|e| format!("PCM spill write failed: {}", e)),
+                    None => { partial.extend(chunk); Ok(()) }
+                };
+                let mut chunk = Vec::with_capacity(coordinator::MAIN_CHUNK);
+                let mut decoded = 0;
+                let mut main_job = None;
+                let mut aux_job = None;
+                let mut next_check = coordinator::MAIN_CHUNK;
+                
+                for sample in hq_source {
+                    chunk.push(sample);
+                    if decoded + chunk.len() < next_check { continue; }
+                    decoded += chunk.len();
+                    store(&chunk)?;
+                    chunk.clear();
+                    if job.is_cancelled() || threads::shutting_down() { return Ok(None); }
+                    if expected_samples > 0 { job.set_progress(decoded as f32 / expected_samples as f32); }
+                    progress.advance(decoded);
+
+                    if is_main() {
+                        aux_job = None;
+                        main_job.get_or_insert_with(|| COORDINATOR.begin_main(expected_samples)).report(decoded);
+                        thread::sleep(Duration::from_millis(1));
+                        next_check += coordinator::MAIN_CHUNK;
+                    } else {
+                        next_check += aux_job.get_or_insert_with(|| COORDINATOR.register_aux()).pace();
+                    }
+                }
+                store(&chunk)?;
+                Ok(Some((decoded + chunk.len(), channels)))
+            }));
+
+            match finished {
+                Ok(Ok(Some((total, channels)))) if !job.is_cancelled() => {
+                    match &spill {
+                        Some(file) => {
+                            file.finish();
+                            debug_log!("Background full-decode complete. PCM kept in temp file, seeks read from disk.");
+                        }
+                        None => {
+                            *samples_ref.write().unwrap_or_else(|e| e.into_inner()) = Some(partial.finish());
+                            is_decoded_ref.store(true, Ordering::Release);
+                            debug_log!("Background full-decode complete. Ready for True O(1) instant seek.");
+                        }
+                    }
+                    // 按实际样本数得出的时长与载入时的估算不符 (容器没给时长时估算为 0) 则以此为准
+                    let per_second = (channels * target_sr as usize) as f64;
+                    let duration = total as f64 / per_second;
+                    if (duration - expected_samples as f64 / per_second).abs() > DURATION_TOLERANCE { progress.duration_measured(duration); }
+                    progress.complete();
+                }
+                Ok(Ok(_)) => release(),
+                Ok(Err(message)) => abandon(message),
+                Err(_) => abandon("decoder panicked".to_string()),
+            }
+        });
+    }
+}
+
+// 预载槽：下一首的原始字节与独立的 PCM 解码缓存
+struct Preloaded {
+    path: String,
+    raw_bytes: Arc<Vec<u8>>,
+    samples: Arc<RwLock<Option<Arc<Vec<f32>>>>>,
+    is_decoded: Arc<AtomicBool>,
+    partial: Arc<PartialPcm>,
+    spill: Option<Arc<SpillFile>>,
+    cache_generation: Arc<AtomicU64>, // 被载入接管时写入该次载入的代次，之后才上报缓存进度
+    cancel: CancellationToken,
+    promoted: Arc<AtomicBool>, // 被 load 接管后转为主解码任务
+    clip_report: Arc<RwLock<Option<ClipReport>>>,
+    sample_rate: u32,
+    channels: u16,
+    duration: f64,
+    quirks: FormatQuirks,
 }
 
 impl AudioEngine for GalaxyEngine {
     fn name(&self) -> &str { "Galaxy DSP (Adaptive Sync Core)" }
+    fn id(&self) -> &'static str { "galaxy" }
+
+    fn capabilities(&self) -> EngineCapabilities {
+        EngineCapabilities {
+            gapless: true, fades: true, upmix: true, accurate_seek: true, bitperfect: true, time_stretch: true, generator: true,
+            formats: NATIVE_FORMATS.to_vec(),
+        }
+    }
 
     fn get_current_time(&self) -> f64 {
-        let pos = f64_from_bits(self.playback_pos.load(Ordering::Relaxed));
+        // 挂钟只记播放了多久，被跳过的静音另行补上
+        let pos = f64_from_bits(self.playback_pos.load(Ordering::Relaxed)) + self.skips.since_anchor();
         let start_us = self.last_play_us.load(Ordering::Relaxed);
         if start_us != u64::MAX {
             let epoch = get_time_epoch();
             let now_us = Instant::now().duration_since(epoch).as_micros() as u64;
             let elapsed = now_us.saturating_sub(start_us) as f64 / 1_000_000.0;
-            pos + elapsed
+            self.loop_region.fold(pos + elapsed * self.stretch_rate as f64)
         } else {
-            pos
+            self.loop_region.fold(pos)
+        }
+    }
+
+    fn is_playing(&self) -> bool { self.is_playing.load(Ordering::SeqCst) }
+
+    fn current_path(&self) -> Option<String> { self.loaded_path.clone() }
+
+    fn cache_strategy(&self) -> CacheStrategy {
+        match (&self.loaded_path, &self.spill) {
+            (None, _) => CacheStrategy::None,
+            (Some(_), Some(_)) => CacheStrategy::TempFile,
+            (Some(_), None) => CacheStrategy::Memory,
+        }
+    }
+
+    fn set_loop(&mut self, start: f64, end: f64) -> Result<(), String> {
+        if !start.is_finite() || !end.is_finite() || start < 0.0 || start >= end { return Err("INVALID_LOOP_RANGE".to_string()); }
+        if self.raw_bytes.is_none() { return Err("NO_TRACK_LOADED".to_string()); }
+        let position = self.get_current_time();
+        self.loop_region.set(start, end);
+        if position >= end {
+            self.seek(start);
+        } else if !self.chain_loopable || self.skips.since_anchor() > 0.0 {
+            // 跳过静音后循环源的样本计数已落后，重挂链路对齐
+            self.seek(position);
         }
+        Ok(())
+    }
+
+    fn set_rate_preserve_pitch(&mut self, rate: f32) -> Result<(), String> {
+        if !rate.is_finite() || !(stretch::MIN_RATE..=stretch::MAX_RATE).contains(&rate) { return Err("INVALID_RATE".to_string()); }
+        if self.bitperfect && rate != 1.0 { return Err("UNAVAILABLE_IN_BITPERFECT".to_string()); }
+        // 以旧倍速折算出当前位置后重建音源链，时钟从该位置按新倍速推进
+        let position = self.get_current_time();
+        self.stretch_rate = rate;
+        if self.raw_bytes.is_some() { self.seek(position); }
+        Ok(())
+    }
+
+    fn set_parametric_eq(&mut self, bands: Vec<EqBand>) -> Result<(), String> {
+        dsp::validate_bands(&bands, self.sample_rate)?;
+        self.dsp.eq.set(bands);
+        Ok(())
+    }
+
+    fn set_bass_boost(&mut self, bass: BassBoost) -> Result<(), String> {
+        dsp::validate_bass(&bass, self.sample_rate)?;
+        self.dsp.bass.set(bass);
+        Ok(())
+    }
+
+    fn start_generator(&mut self, request: GeneratorRequest) -> Result<(), String> {
+        let sample_rate = get_dynamic_target_sr();
+        request.validate(sample_rate)?;
+        self.stop();
+        self.sample_rate = sample_rate;
+        self.channels = 2;
+
+        let target_channels = *self.channel_mode.read().unwrap_or_else(|e| e.into_inner()) as u16;
+        let (processed, dsp_stages) = dsp::chain(SignalGenerator::new(request, sample_rate), self.dsp.clone(), target_channels);
+        let mixed_source = UpmixSource::new(processed, target_channels, self.is_playing.clone(), self.current_volume.clone(), self.dsp.clone());
+        dsp_registry::publish_chain([dsp_stages, vec![mixed_source.stage()]].concat());
+        let sink_guard = lock_sink(&self.sink);
+        sink_guard.set_volume(1.0);
+        sink_guard.append(mixed_source);
+        self.is_playing.store(true, Ordering::SeqCst);
+        sink_guard.play();
+        Ok(())
+    }
+
+    fn set_lfe_crossover(&mut self, crossover: LfeCrossover) -> Result<(), String> {
+        dsp::validate_lfe_crossover(&crossover, self.sample_rate)?;
+        self.dsp.lfe_crossover.set(crossover);
+        Ok(())
+    }
+
+    fn set_upmix_params(&mut self, params: UpmixParams) -> Result<(), String> {
+        dsp::validate_upmix(&params)?;
+        self.dsp.upmix.set(params);
+        Ok(())
+    }
+
+    fn set_limiter_enabled(&mut self, enabled: bool) {
+        self.dsp.limiter_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    fn limiter_reduction_db(&self) -> f32 { self.dsp.limiter_reduction_db() }
+
+    fn set_dither(&mut self, dither: Dither) -> Result<(), String> {
+        dsp::validate_dither(&dither)?;
+        self.dsp.dither.set(dither);
+        Ok(())
+    }
+
+    fn set_crossfeed(&mut self, crossfeed: Crossfeed) -> Result<(), String> {
+        dsp::validate_crossfeed(&crossfeed)?;
+        self.dsp.crossfeed.set(crossfeed);
+        Ok(())
+    }
+
+    fn set_stereo_width(&mut self, width: f32) -> Result<(), String> {
+        if !width.is_finite() || !(0.0..=dsp::MAX_STEREO_WIDTH).contains(&width) { return Err("INVALID_WIDTH".to_string()); }
+        self.dsp.stereo_width.store(width.to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_balance(&mut self, balance: f32) -> Result<(), String> {
+        if !balance.is_finite() || !(-1.0..=1.0).contains(&balance) { return Err("INVALID_BALANCE".to_string()); }
+        self.dsp.balance.store(balance.to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_preamp(&mut self, gain_db: f32) -> Result<(), String> {
+        dsp::validate_preamp(gain_db)?;
+        self.dsp.preamp_db.store(gain_db.to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_downmix_lfe(&mut self, enabled: bool) -> Result<(), String> {
+        self.dsp.downmix_lfe.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_auto_level(&mut self, auto_level: dsp::AutoLevel) -> Result<(), String> {
+        dsp::validate_auto_level(&auto_level)?;
+        self.dsp.auto_level.set(auto_level);
+        Ok(())
+    }
+
+    fn set_replaygain_db(&mut self, gain_db: f32) -> Result<(), String> {
+        self.dsp.replaygain_db.store(gain_db.to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_channel_layout(&mut self, layout: dsp::ChannelLayout) -> Result<(), String> {
+        layout.validate()?;
+        self.dsp.channel_layout.set(layout);
+        Ok(())
     }
 
+    fn set_fade_config(&mut self, fades: dsp::FadeConfig) -> Result<(), String> {
+        fades.validate()?;
+        self.fades = fades;
+        Ok(())
+    }
+
+    // 正在从 PCM 缓存播放时原地重挂链路，新设置立即生效
+    fn set_skip_silence(&mut self, prefs: SkipSilence) -> Result<(), String> {
+        prefs.validate()?;
+        if prefs == self.skip_silence { return Ok(()); }
+        self.skip_silence = prefs;
+        if self.chain_loopable { self.seek(self.get_current_time()); }
+        Ok(())
+    }
+
+    fn take_skipped_silence(&self) -> f64 { self.skips.take_pending() }
+
+    // 与信号发生器相同，占用主 Sink；停止后由管理器恢复原曲目
+    fn identify_channels(&mut self) -> Result<Vec<ChannelIdent>, String> {
+        let sample_rate = get_dynamic_target_sr();
+        self.stop();
+        self.sample_rate = sample_rate;
+        let channel_code = *self.channel_mode.read().unwrap_or_else(|e| e.into_inner()) as u16;
+        let source = ChannelIdentSource::new(channel_code, self.dsp.channel_layout.get(), sample_rate);
+        let plan = source.plan();
+        dsp_registry::publish_chain(Vec::new());
+        let sink_guard = lock_sink(&self.sink);
+        sink_guard.set_volume(1.0);
+        sink_guard.append(source);
+        self.is_playing.store(true, Ordering::SeqCst);
+        sink_guard.play();
+        Ok(plan)
+    }
+
+    // 切换后按新模式重新载入当前曲目：PCM 缓存的采样率随模式改变，仅重挂链路不够
+    fn set_bitperfect(&mut self, enabled: bool) -> Result<(), String> {
+        if enabled && self.stretch_rate != 1.0 { return Err("BITPERFECT_RATE_ACTIVE".to_string()); }
+        if self.bitperfect == enabled { return Ok(()); }
+        self.bitperfect = enabled;
+        if let Some(stale) = self.preloaded.take() { stale.cancel.cancel(); }
+        if let Some(path) = self.loaded_path.clone() {
+            let (position, was_playing) = (self.get_current_time(), self.is_playing());
+            if let Err(e) = self.load(&path) {
+                self.bitperfect = !enabled;
+                return Err(e);
+            }
+            self.seek(position);
+            if was_playing { self.play(); }
+        }
+        Ok(())
+    }
+
+    fn clear_loop(&mut self) {
+        // 以折叠后的时间重新定基，解除循环后自然越过 B 点继续播放
+        let position = self.get_current_time();
+        self.loop_region.clear();
+        self.playback_pos.store(f64_to_bits(position), Ordering::SeqCst);
+        self.skips.reset_anchor();
+        if self.last_play_us.load(Ordering::SeqCst) != u64::MAX {
+            let now_us = Instant::now().duration_since(get_time_epoch()).as_micros() as u64;
+            self.last_play_us.store(now_us, Ordering::SeqCst);
+        }
+    }
+
+    fn clip_report(&self) -> Option<ClipReport> { self.clip_report.read().unwrap_or_else(|e| e.into_inner()).clone() }
+
     fn update_output_stream(&mut self, handle: OutputStreamHandle) {
         let was_playing = self.is_playing.load(Ordering::SeqCst);
         let current_time = (self.get_current_time() - 0.4).max(0.0);
 
         if was_playing {
             self.is_playing.store(false, Ordering::SeqCst);
-            if let Ok(s) = self.sink.lock() { s.pause(); }
+            lock_sink(&self.sink).pause();
             thread::sleep(Duration::from_millis(50)); 
         }
         
@@ -499,32 +1453,92 @@ impl AudioEngine for GalaxyEngine {
 
     fn load(&mut self, path: &str) -> Result<f64, String> {
         if self.is_playing.load(Ordering::SeqCst) {
+            let fade = self.transition_fade(self.fades.load_ms);
             self.is_playing.store(false, Ordering::SeqCst);
-            thread::sleep(Duration::from_millis(40)); 
+            thread::sleep(fade);
         }
 
-        let mut file = File::open(path).map_err(|e| e.to_string())?;
-        let len = file.metadata().map_err(|e| e.to_string())?.len();
-        let mut buffer = Vec::with_capacity(len as usize);
-        file.read_to_end(&mut buffer).map_err(|e| e.to_string())?;
-        let raw_bytes = Arc::new(buffer);
+        self.loop_region.clear();
+        self.dsp.reset_auto_level();
 
-        let source = Self::create_decoder(&raw_bytes)?;
-        
-        debug_log!("Audio Engine Decoder Initialized: Source SR = {}Hz, Channels = {}", source.sample_rate(), source.channels());
-        
-        let target_sr = get_dynamic_target_sr();
-        let hq_source = RubatoSource::new(source.convert_samples::<f32>(), target_sr);
-        
-        self.sample_rate = hq_source.sample_rate(); 
-        self.channels = hq_source.channels();
-        let total_duration = hq_source.total_duration().map(|d| d.as_secs_f64()).unwrap_or(0.0);
+        // 预载命中：直接接管已读入的字节与在途/已完成的 PCM 解码；未命中则作废预载
+        let preloaded = match self.preloaded.take() {
+            Some(pre) if pre.path == path => Some(pre),
+            Some(stale) => { stale.cancel.cancel(); None }
+            None => None,
+        };
+        self.cancel_active_decode();
+
+        let generation = decode_progress::next_generation();
+        let (raw_bytes, total_duration, live_source) = match preloaded {
+            Some(pre) => {
+                debug_log!("Preload hit. Swapping in cached track without re-reading: {}", path);
+                self.sample_rate = pre.sample_rate;
+                self.channels = pre.channels;
+                self.decoded_samples = pre.samples;
+                self.is_decoded = pre.is_decoded;
+                self.partial = pre.partial;
+                self.spill = pre.spill;
+                pre.cache_generation.store(generation, Ordering::Release);
+                self.decode_token = Some(pre.cancel);
+                pre.promoted.store(true, Ordering::SeqCst);
+                self.clip_report = pre.clip_report;
+                self.quirks = Some(pre.quirks);
+                (pre.raw_bytes, pre.duration, None)
+            }
+            None => {
+                let raw_bytes = self.errors.check(PlaybackErrorCode::LoadFailed, path, Self::read_file(path))?;
+                let quirks = format_quirks::probe(&raw_bytes);
+                let source = self.errors.check(PlaybackErrorCode::DecodeFailed, path, Self::create_decoder(&raw_bytes))?;
+                debug_log!("Audio Engine Decoder Initialized: Source SR = {}Hz, Channels = {}", source.sample_rate(), source.channels());
+                let target_sr = self.resolve_target_sr(source.sample_rate());
+                let hq_source = RubatoSource::new(GaplessTrim::new(source.convert_samples::<f32>(), &quirks), target_sr);
+                self.sample_rate = hq_source.sample_rate(); 
+                self.channels = hq_source.channels();
+                // 部分 OGG / 串接文件解码器给不出时长，先按容器头估算，整曲解完后再以实际样本数校正
+                let total_duration = quirks.corrected_duration(hq_source.total_duration().map(|d| d.as_secs_f64()).unwrap_or_else(|| ffmpeg::probe_duration(path)));
+                self.quirks = Some(quirks);
+
+                *self.decoded_samples.write().unwrap_or_else(|e| e.into_inner()) = None;
+                self.is_decoded.store(false, Ordering::Release);
+                self.clip_report = Arc::new(RwLock::new(None));
+
+                let job = self.register_decode(path);
+                self.decode_token = Some(job.token().clone());
+                let expected = (total_duration * target_sr as f64) as usize * self.channels as usize;
+                self.spill = Self::spill_for(expected);
+                self.partial = PartialPcm::new(if self.spill.is_some() { 0 } else { expected });
+                Self::spawn_full_decode(raw_bytes.clone(), quirks, target_sr, expected, self.decoded_samples.clone(), self.is_decoded.clone(), self.partial.clone(), self.spill.clone(), self.clip_report.clone(), job, self.errors.clone(), self.progress.start(path, expected).with_cache(Arc::new(AtomicU64::new(generation))), path.to_string(), || true);
+                (raw_bytes, total_duration, Some(hq_source))
+            }
+        };
 
-        let my_session = self.decode_session.fetch_add(1, Ordering::SeqCst) + 1;
-        *self.decoded_samples.write().unwrap() = None;
-        self.is_decoded.store(false, Ordering::Release);
+        // 预载已解码完成则直接从内存起播；否则（含预载仍在解码）走流式解码
+        let cached = if self.is_decoded.load(Ordering::Acquire) { self.decoded_samples.read().unwrap_or_else(|e| e.into_inner()).clone() } else { None };
+        let spilled = self.spill.as_ref().is_some_and(|spill| spill.is_done()) && !self.decode_token.as_ref().is_some_and(|t| t.is_cancelled());
+        if cached.is_some() || spilled { self.progress.cache_ready(generation, path); }
+        // 预载在接管前已解完时 duration-updated 没有发出，直接按缓存长度给出精确时长
+        let per_second = self.channels as f64 * self.sample_rate as f64;
+        let total_duration = match (&cached, &self.spill) {
+            (Some(samples), _) => samples.len() as f64 / per_second,
+            (None, Some(spill)) if spilled => spill.written() as f64 / per_second,
+            _ => total_duration,
+        };
+        let live_source = match live_source {
+            None if cached.is_none() => {
+                let source = self.errors.check(PlaybackErrorCode::DecodeFailed, path, Self::create_decoder(&raw_bytes))?;
+                let quirks = self.quirks.unwrap_or_else(|| format_quirks::probe(&raw_bytes));
+                Some(RubatoSource::new(GaplessTrim::new(source.convert_samples::<f32>(), &quirks), self.sample_rate))
+            }
+            other => other,
+        };
+        let silence = match (&live_source, &cached) {
+            (None, Some(samples)) => self.silence_cursor(samples),
+            _ => None,
+        };
         
         self.playback_pos.store(f64_to_bits(0.0), Ordering::SeqCst);
+        self.skips.reset_anchor();
         let epoch = get_time_epoch();
         if self.is_playing.load(Ordering::SeqCst) {
             let now_us = Instant::now().duration_since(epoch).as_micros() as u64;
@@ -536,62 +1550,82 @@ impl AudioEngine for GalaxyEngine {
         self.fade_token.fetch_add(1, Ordering::SeqCst); 
 
         {
-            let mut sink_guard = self.sink.lock().unwrap();
-            *sink_guard = Sink::try_new(&self.stream_handle).unwrap();
+            let mut sink_guard = lock_sink(&self.sink);
+            *sink_guard = self.errors.new_sink(&self.stream_handle, Some(path));
             sink_guard.set_volume(1.0);
-            let mixed_source = UpmixSource::new(hq_source, *self.channel_mode.read().unwrap() as u16, self.is_playing.clone(), self.current_volume.clone());
-            sink_guard.append(mixed_source);
+            self.chain_loopable = live_source.is_none();
+            if let Some(hq_source) = live_source {
+                let resampler_stage = hq_source.stage();
+                self.append_chain(&sink_guard, hq_source, vec![resampler_stage]);
+            } else if let Some(samples_arc) = cached {
+                let source = ArcSliceSource::new(samples_arc, self.channels, self.sample_rate).with_silence(silence);
+                let source = AbLoopSource::new(source, self.loop_region.clone(), 0.0);
+                self.append_chain(&sink_guard, source, Vec::new());
+            }
             sink_guard.play(); 
         }
 
-        self.raw_bytes = Some(raw_bytes.clone());
+        self.raw_bytes = Some(raw_bytes);
+        self.loaded_path = Some(path.to_string());
 
-        let session_ref = self.decode_session.clone();
-        let samples_ref = self.decoded_samples.clone();
-        let is_decoded_ref = self.is_decoded.clone();
-        let raw_bytes_clone = raw_bytes.clone();
-        let bg_target_sr = target_sr; 
+        Ok(total_duration)
+    }
 
-        thread::spawn(move || {
-            debug_log!("Background full-decode thread started (Normal Priority to protect real-time stream!).");
-            
-            if let Ok(decoder) = Decoder::new(Cursor::new(raw_bytes_clone.to_vec())) {
-                let hq_source = RubatoSource::new(decoder.convert_samples::<f32>(), bg_target_sr);
-                let mut pcm_buffer = Vec::with_capacity(bg_target_sr as usize * 2 * 180); 
-                let mut count = 0;
-                
-                for sample in hq_source {
-                    pcm_buffer.push(sample);
-                    count += 1;
-                    
-                    if count % 4096 == 0 {
-                        if session_ref.load(Ordering::SeqCst) != my_session { return; }
-                        thread::sleep(Duration::from_millis(1));
-                    }
-                }
-                
-                if session_ref.load(Ordering::SeqCst) == my_session {
-                    *samples_ref.write().unwrap() = Some(Arc::new(pcm_buffer));
-                    is_decoded_ref.store(true, Ordering::Release);
-                    debug_log!("Background full-decode complete. Ready for True O(1) instant seek.");
-                }
-            }
-        });
+    fn preload(&mut self, path: &str) -> Result<(), String> {
+        if self.preloaded.as_ref().map(|p| p.path == path).unwrap_or(false) { return Ok(()); }
+        if let Some(stale) = self.preloaded.take() { stale.cancel.cancel(); }
 
-        Ok(total_duration)
+        let raw_bytes = Self::read_file(path)?;
+        let source = Self::create_decoder(&raw_bytes)?;
+        let target_sr = self.resolve_target_sr(source.sample_rate());
+        let quirks = format_quirks::probe(&raw_bytes);
+        let probe = RubatoSource::new(source.convert_samples::<f32>(), target_sr);
+
+        // 预载作废与登记表里的取消共用同一个令牌
+        let job = self.register_decode(path);
+        let duration = quirks.corrected_duration(probe.total_duration().map(|d| d.as_secs_f64()).unwrap_or_else(|| ffmpeg::probe_duration(path)));
+        let expected = (duration * target_sr as f64) as usize * probe.channels() as usize;
+        let spill = Self::spill_for(expected);
+        let pre = Preloaded {
+            path: path.to_string(),
+            raw_bytes: raw_bytes.clone(),
+            samples: Arc::new(RwLock::new(None)),
+            is_decoded: Arc::new(AtomicBool::new(false)),
+            partial: PartialPcm::new(if spill.is_some() { 0 } else { expected }),
+            spill,
+            cache_generation: Arc::new(AtomicU64::new(0)),
+            cancel: job.token().clone(),
+            promoted: Arc::new(AtomicBool::new(false)),
+            clip_report: Arc::new(RwLock::new(None)),
+            sample_rate: probe.sample_rate(),
+            channels: probe.channels(),
+            duration,
+            quirks,
+        };
+
+        let promoted_ref = pre.promoted.clone();
+        Self::spawn_full_decode(raw_bytes, quirks, target_sr, expected, pre.samples.clone(), pre.is_decoded.clone(), pre.partial.clone(), pre.spill.clone(), pre.clip_report.clone(),
+            job, self.errors.clone(), self.progress.start(path, expected).with_cache(pre.cache_generation.clone()), path.to_string(), move || promoted_ref.load(Ordering::SeqCst));
+        debug_log!("Preload started for next track: {}", path);
+        self.preloaded = Some(pre);
+        Ok(())
     }
 
     fn play(&mut self) { 
+        if self.is_playing.load(Ordering::SeqCst) { return; }
+        self.transition_fade(self.fades.play_ms);
         if self.is_playing.swap(true, Ordering::SeqCst) { return; }
         let epoch = get_time_epoch();
         let now_us = Instant::now().duration_since(epoch).as_micros() as u64;
         self.last_play_us.store(now_us, Ordering::SeqCst);
         
         self.fade_token.fetch_add(1, Ordering::SeqCst); 
-        if let Ok(s) = self.sink.lock() { s.play(); } 
+        lock_sink(&self.sink).play(); 
     }
     
     fn pause(&mut self) { 
+        if !self.is_playing.load(Ordering::SeqCst) { return; }
+        let fade = self.transition_fade(self.fades.pause_ms);
         if !self.is_playing.swap(false, Ordering::SeqCst) { return; }
         
         let start_us = self.last_play_us.swap(u64::MAX, Ordering::SeqCst);
@@ -602,7 +1636,7 @@ impl AudioEngine for GalaxyEngine {
             
             let mut current = self.playback_pos.load(Ordering::Relaxed);
             loop {
-                let new_val = f64_from_bits(current) + elapsed;
+                let new_val = f64_from_bits(current) + elapsed * self.stretch_rate as f64;
                 match self.playback_pos.compare_exchange_weak(current, f64_to_bits(new_val), Ordering::SeqCst, Ordering::Relaxed) {
                     Ok(_) => break,
                     Err(x) => current = x,
@@ -610,27 +1644,57 @@ impl AudioEngine for GalaxyEngine {
             }
         }
 
-        let my_token = self.fade_token.fetch_add(1, Ordering::SeqCst) + 1;
-        let token_ref = self.fade_token.clone();
-        let sink_clone = self.sink.clone();
-        let is_playing_flag = self.is_playing.clone();
-        
-        thread::spawn(move || {
-            thread::sleep(Duration::from_millis(1000)); 
-            if token_ref.load(Ordering::SeqCst) == my_token && !is_playing_flag.load(Ordering::SeqCst) {
-                if let Ok(s) = sink_clone.lock() { s.pause(); } 
-            }
-        });
+        pause_after_fade(&self.sink, &self.fade_token, &self.is_playing, fade);
+    }
+
+    fn stop(&mut self) {
+        if self.is_playing.load(Ordering::SeqCst) {
+            let fade = self.transition_fade(self.fades.pause_ms);
+            self.is_playing.store(false, Ordering::SeqCst);
+            thread::sleep(fade);
+        }
+        self.is_playing.store(false, Ordering::SeqCst);
+        // 后台解码线程由令牌叫停并丢弃结果，换代次作废其尚未发出的缓存事件
+        self.cancel_active_decode();
+        decode_progress::next_generation();
+        if let Some(stale) = self.preloaded.take() { stale.cancel.cancel(); }
+        self.fade_token.fetch_add(1, Ordering::SeqCst);
+
+        {
+            let mut sink_guard = lock_sink(&self.sink);
+            sink_guard.stop();
+            *sink_guard = self.errors.new_sink(&self.stream_handle, None);
+        }
+
+        *self.decoded_samples.write().unwrap_or_else(|e| e.into_inner()) = None;
+        self.is_decoded.store(false, Ordering::Release);
+        self.partial = PartialPcm::new(0);
+        self.spill = None;
+        self.raw_bytes = None;
+        self.loaded_path = None;
+        self.clip_report = Arc::new(RwLock::new(None));
+        self.quirks = None;
+        self.loop_region.clear();
+        self.chain_loopable = false;
+        dsp_registry::clear_chain();
+
+        self.playback_pos.store(f64_to_bits(0.0), Ordering::SeqCst);
+        self.skips.reset_anchor();
+        self.last_play_us.store(u64::MAX, Ordering::SeqCst);
+        debug_log!("Engine stopped. PCM cache released.");
     }
 
     fn seek(&mut self, time: f64) {
         let is_playing_now = self.is_playing.load(Ordering::SeqCst);
         if is_playing_now {
+            let fade = self.transition_fade(self.fades.seek_ms);
             self.is_playing.store(false, Ordering::SeqCst);
-            if let Ok(s) = self.sink.lock() { s.pause(); }
+            thread::sleep(fade);
+            lock_sink(&self.sink).pause();
         }
 
         self.playback_pos.store(f64_to_bits(time), Ordering::SeqCst);
+        self.skips.reset_anchor();
         let epoch = get_time_epoch();
         if is_playing_now {
             let now_us = Instant::now().duration_since(epoch).as_micros() as u64;
@@ -639,40 +1703,353 @@ impl AudioEngine for GalaxyEngine {
             self.last_play_us.store(u64::MAX, Ordering::SeqCst);
         }
 
-        if !self.is_decoded.load(Ordering::Acquire) {
-            debug_log!("Seek triggered before full-decode complete. Synchronously waiting for background process...");
-            while !self.is_decoded.load(Ordering::Acquire) {
-                thread::sleep(Duration::from_millis(50));
-            }
-            debug_log!("Background process finished! Executing zero-copy instant seek.");
-        }
+        let Some(raw_bytes) = self.raw_bytes.clone() else { return };
 
-        let target_channels = *self.channel_mode.read().unwrap() as u16;
-        let mut sink_guard = self.sink.lock().unwrap();
-        *sink_guard = Sink::try_new(&self.stream_handle).unwrap();
+        // 整曲缓存就绪走零拷贝内存源；目标已解码 (至多稍等 SEEK_GRACE) 走渐进源或临时文件；都不满足时另起解码器按 IO 定位，不等后台解码
+        let cached = if self.is_decoded.load(Ordering::Acquire) { self.decoded_samples.read().unwrap_or_else(|e| e.into_inner()).clone() } else { None };
+        let offset = (time * self.sample_rate as f64) as usize * self.channels as usize;
+        let progressive = cached.is_none() && match &self.spill {
+            Some(spill) => spill.wait_for(offset, SEEK_GRACE),
+            None => self.partial.wait_for(offset, SEEK_GRACE),
+        };
+        let spill_source = match (&self.spill, progressive) {
+            (Some(spill), true) => match spill.source_at(offset, self.channels, self.sample_rate) {
+                Ok(source) => Some(source),
+                Err(e) => {
+                    self.errors.report(PlaybackErrorCode::SeekFailed, format!("cannot read PCM spill file: {}", e), self.loaded_path.as_deref());
+                    return;
+                }
+            },
+            _ => None,
+        };
+        let io_source = match (&cached, progressive) {
+            (None, false) => match self.seek_decoder(&raw_bytes, time) {
+                Ok(source) => Some(source),
+                Err(e) => {
+                    self.errors.report(PlaybackErrorCode::SeekFailed, format!("cannot seek to {:.2}s before decode completes: {}", time, e), self.loaded_path.as_deref());
+                    return;
+                }
+            },
+            _ => None,
+        };
+        let silence = cached.as_ref().and_then(|samples| self.silence_cursor(samples));
+        let mut sink_guard = lock_sink(&self.sink);
+        *sink_guard = self.errors.new_sink(&self.stream_handle, self.loaded_path.as_deref());
         
-        if let Some(samples_arc) = self.decoded_samples.read().unwrap().clone() {
+        if let Some(samples_arc) = cached {
             let source = ArcSliceSource::new(samples_arc, self.channels, self.sample_rate)
-                .skip_duration(Duration::from_secs_f64(time));
-            sink_guard.append(UpmixSource::new(source, target_channels, self.is_playing.clone(), self.current_volume.clone()));
+                .with_silence(silence)
+                .starting_at(Duration::from_secs_f64(time));
+            let source = AbLoopSource::new(source, self.loop_region.clone(), time);
+            self.chain_loopable = true;
+            self.append_chain(&sink_guard, source, Vec::new());
+        } else if let Some(hq_source) = io_source {
+            self.chain_loopable = false;
+            let resampler_stage = hq_source.stage();
+            self.append_chain(&sink_guard, hq_source, vec![resampler_stage]);
+        } else if let Some(source) = spill_source {
+            self.chain_loopable = false;
+            self.append_chain(&sink_guard, source, Vec::new());
+        } else {
+            self.chain_loopable = false;
+            self.append_chain(&sink_guard, self.partial.source_at(offset, self.channels, self.sample_rate), Vec::new());
         }
         
         sink_guard.set_volume(1.0); 
         if is_playing_now {
             self.is_playing.store(true, Ordering::SeqCst);
             sink_guard.play(); 
+        } else {
+            sink_guard.pause(); // 暂停态下 seek 保持暂停，不自动起播
         }
     }
 
-    fn set_volume(&mut self, vol: f32) {
+    fn set_volume(&mut self, vol: f32) -> Result<(), String> {
+        if self.bitperfect { return Err("UNAVAILABLE_IN_BITPERFECT".to_string()); }
         self.current_volume.store(vol.to_bits(), Ordering::SeqCst);
+        Ok(())
     }
 
-    fn set_channel_mode(&mut self, _mode: u16) {
-        let config = match _mode {
+    fn set_channel_mode(&mut self, mode: u16) -> Result<(), String> {
+        if self.bitperfect { return Err("UNAVAILABLE_IN_BITPERFECT".to_string()); }
+        let config = match mode {
             6 => ChannelConfig::Surround51, 8 => ChannelConfig::Surround71, 
             106 => ChannelConfig::True51, 108 => ChannelConfig::True71, _ => ChannelConfig::Stereo,
         };
-        *self.channel_mode.write().unwrap() = config;
+        let previous = std::mem::replace(&mut *self.channel_mode.write().unwrap_or_else(|e| e.into_inner()), config);
+        // 同 FFmpeg 引擎：声道数随 UpmixSource 固定，已载入曲目时原位重挂链路
+        if previous != config && self.loaded_path.is_some() { self.seek(self.get_current_time()); }
+        Ok(())
+    }
+
+    fn set_resampler_quality(&mut self, quality: ResamplerQuality) {
+        if self.resampler_quality != quality {
+            debug_log!("Resampler quality set to {} (applies from next load).", quality.id());
+        }
+        self.resampler_quality = quality;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // 440Hz 正弦，16-bit 交错 PCM
+    pub(crate) fn pcm16(rate: u32, channels: u16, frames: usize) -> Vec<i16> {
+        (0..frames).flat_map(|i| {
+            let v = ((i as f32 * 440.0 * std::f32::consts::TAU / rate as f32).sin() * 16000.0) as i16;
+            std::iter::repeat_n(v, channels as usize)
+        }).collect()
+    }
+
+    pub(crate) fn wav_bytes(rate: u32, channels: u16, frames: usize) -> Vec<u8> {
+        let data: Vec<u8> = pcm16(rate, channels, frames).iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut out = Vec::with_capacity(44 + data.len());
+        out.extend_from_slice(b"RIFF");
+        out.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        out.extend_from_slice(b"WAVEfmt ");
+        out.extend_from_slice(&16u32.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&channels.to_le_bytes());
+        out.extend_from_slice(&rate.to_le_bytes());
+        out.extend_from_slice(&(rate * channels as u32 * 2).to_le_bytes());
+        out.extend_from_slice(&(channels * 2).to_le_bytes());
+        out.extend_from_slice(&16u16.to_le_bytes());
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&data);
+        out
+    }
+
+    // AIFF 的采样率是 80-bit IEEE 扩展精度；整数采样率可直接拼出指数与尾数
+    fn extended(rate: u32) -> [u8; 10] {
+        let shift = rate.leading_zeros();
+        let exponent = 16383 + 31 - shift as u16;
+        let mantissa = (rate as u64) << (32 + shift);
+        let mut out = [0u8; 10];
+        out[..2].copy_from_slice(&exponent.to_be_bytes());
+        out[2..].copy_from_slice(&mantissa.to_be_bytes());
+        out
+    }
+
+    fn aiff_bytes(rate: u32, channels: u16, frames: usize) -> Vec<u8> {
+        let data: Vec<u8> = pcm16(rate, channels, frames).iter().flat_map(|s| s.to_be_bytes()).collect();
+        let mut out = Vec::with_capacity(54 + data.len());
+        out.extend_from_slice(b"FORM");
+        out.extend_from_slice(&(46 + data.len() as u32).to_be_bytes());
+        out.extend_from_slice(b"AIFFCOMM");
+        out.extend_from_slice(&18u32.to_be_bytes());
+        out.extend_from_slice(&channels.to_be_bytes());
+        out.extend_from_slice(&(frames as u32).to_be_bytes());
+        out.extend_from_slice(&16u16.to_be_bytes());
+        out.extend_from_slice(&extended(rate));
+        out.extend_from_slice(b"SSND");
+        out.extend_from_slice(&(8 + data.len() as u32).to_be_bytes());
+        out.extend_from_slice(&[0u8; 8]);
+        out.extend_from_slice(&data);
+        out
     }
-}
\ No newline at end of file
+
+    fn decode(bytes: Vec<u8>) -> (u16, u32, usize) {
+        let decoder = GalaxyEngine::create_decoder(&Arc::new(bytes)).expect("fixture should decode natively");
+        let (channels, rate) = (decoder.channels(), decoder.sample_rate());
+        (channels, rate, decoder.count())
+    }
+
+    #[test]
+    fn extended_rate_encoding() {
+        assert_eq!(extended(44100), [0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(extended(48000), [0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn wav_fixture_decodes() {
+        assert_eq!(decode(wav_bytes(44100, 2, 4410)), (2, 44100, 4410 * 2));
+        assert_eq!(decode(wav_bytes(48000, 1, 4800)), (1, 48000, 4800));
+    }
+
+    #[test]
+    fn aiff_fixture_decodes() {
+        assert_eq!(decode(aiff_bytes(44100, 2, 4410)), (2, 44100, 4410 * 2));
+        assert_eq!(decode(aiff_bytes(96000, 1, 9600)), (1, 96000, 9600));
+    }
+
+    #[test]
+    fn resampler_output_length_tracks_ratio() {
+        // 非整块、恰好整块、降采样三种收尾方式，总帧数都应在 输入 × 比率 的 1 帧之内
+        for (source_sr, target_sr, channels, frames) in [(44100, 48000, 1, 44100 + 123), (48000, 44100, 2, 2048 * 10), (96000, 44100, 2, 96000 / 3 + 7)] {
+            let input: Vec<f32> = pcm16(source_sr, channels, frames).iter().map(|&s| s as f32 / 32768.0).collect();
+            let out = RubatoSource::new(rodio::buffer::SamplesBuffer::new(channels, source_sr, input), target_sr).count();
+            assert_eq!(out % channels as usize, 0);
+            let expected = frames as f64 * target_sr as f64 / source_sr as f64;
+            let got = out / channels as usize;
+            assert!((got as f64 - expected).abs() <= 1.0, "{source_sr}->{target_sr}: {got} frames, expected {expected:.1}");
+        }
+        let same = RubatoSource::new(rodio::buffer::SamplesBuffer::new(2, 48000, vec![0.1f32; 960]), 48000);
+        assert_eq!(same.count(), 960);
+    }
+
+    #[test]
+    fn upmix_keeps_duration_of_a_two_second_buffer() {
+        // 立体声升混到 5.1 / 7.1 (以及虚拟化回 2 声道)：每帧声道数变了，帧数与时长不变
+        let data: Arc<Vec<f32>> = Arc::new(pcm16(48000, 2, 96000).iter().map(|&s| s as f32 / 32768.0).collect());
+        for (code, out_channels) in [(2, 2), (106, 6), (108, 8), (6, 2)] {
+            let source = ArcSliceSource::new(data.clone(), 2, 48000);
+            let upmix = UpmixSource::new(source, code, Arc::new(AtomicBool::new(true)), Arc::new(AtomicU32::new(1f32.to_bits())), dsp::DspSettings::new());
+            assert_eq!(upmix.total_duration(), Some(Duration::from_secs(2)));
+            assert_eq!(upmix.channels(), out_channels);
+            assert_eq!(upmix.count(), 96000 * out_channels as usize);
+        }
+    }
+
+    #[test]
+    fn short_mono_wav_loads_and_seeks_in_5_1() {
+        // 与 load 后的内存缓存同一条路：解码成 f32 整曲，再按 seek 的方式从缓存起播并走完整条处理链
+        let decoder = GalaxyEngine::create_decoder(&Arc::new(wav_bytes(48000, 1, 24000))).expect("mono fixture should decode");
+        assert_eq!(decoder.channels(), 1);
+        let samples: Arc<Vec<f32>> = Arc::new(decoder.convert_samples::<f32>().collect());
+        assert_eq!(samples.len(), 24000);
+
+        // 0.1234567s 落在 5925.92 帧：单声道下取整到 5925，不能因声道数为 1 而错位
+        for (time, frames_left) in [(0.0, 24000), (0.25, 12000), (0.1234567, 24000 - 5925), (0.5, 0), (2.0, 0)] {
+            let source = ArcSliceSource::new(samples.clone(), 1, 48000).starting_at(Duration::from_secs_f64(time));
+            let (chain, stages) = GalaxyEngine::processing_chain(source, Vec::new(), &dsp::DspSettings::new(), 1.0, 106, Arc::new(AtomicBool::new(true)), Arc::new(AtomicU32::new(1f32.to_bits())));
+            assert_eq!(stages.first().map(|s| s.name), Some("mono_to_stereo"));
+            assert_eq!(chain.channels(), 6);
+            assert_eq!(chain.sample_rate(), 48000);
+            assert_eq!(chain.count(), frames_left * 6, "seek to {time}s");
+        }
+    }
+
+    #[test]
+    fn mono_widening_duplicates_and_stereo_passes_through() {
+        let mono = Arc::new(vec![0.1f32, -0.2, 0.3]);
+        let widened: Vec<f32> = MonoToStereoSource::new(ArcSliceSource::new(mono, 1, 48000)).collect();
+        assert_eq!(widened, vec![0.1, 0.1, -0.2, -0.2, 0.3, 0.3]);
+        let stereo = Arc::new(vec![0.1f32, -0.2, 0.3, -0.4]);
+        let source = MonoToStereoSource::new(ArcSliceSource::new(stereo.clone(), 2, 48000));
+        assert_eq!(source.channels(), 2);
+        assert_eq!(source.collect::<Vec<_>>(), *stereo);
+    }
+
+    fn generator(kind: &str, params: GeneratorParams) -> SignalGenerator {
+        SignalGenerator::new(GeneratorRequest::parse(kind, &params).unwrap(), 48000)
+    }
+
+    // 左声道 (左右同相，隔一个取一个)
+    fn left(source: SignalGenerator, frames: usize) -> Vec<f32> { source.step_by(2).take(frames).collect() }
+
+    fn peak(samples: &[f32]) -> f32 { samples.iter().fold(0.0, |m, s| m.max(s.abs())) }
+
+    #[test]
+    fn fixed_seed_reproduces_noise() {
+        for kind in ["white_noise", "pink_noise"] {
+            let seeded = |seed| left(generator(kind, GeneratorParams { seed: Some(seed), ..Default::default() }), 4800);
+            assert_eq!(seeded(7), seeded(7), "{kind}");
+            assert_ne!(seeded(7), seeded(8), "{kind}");
+        }
+        let stereo: Vec<f32> = generator("pink_noise", GeneratorParams::default()).take(960).collect();
+        assert!(stereo.chunks(2).all(|f| f[0] == f[1]));
+    }
+
+    #[test]
+    fn sweep_stays_phase_continuous_across_the_wrap() {
+        // 0.01s 一个周期，采 10 个周期；相邻样本之差受最高频率的相位步长约束，回到起点时也一样
+        for log in [false, true] {
+            let source = generator("sweep", GeneratorParams { start: Some(100.0), end: Some(1000.0), seconds: Some(0.01), log, level_db: Some(0.0), ..Default::default() });
+            let amplitude = 10f32.powf(GENERATOR_MAX_DB / 20.0);
+            let max_step = amplitude * std::f32::consts::TAU * 1000.0 / 48000.0 * 1.01;
+            let samples = left(source, 4800);
+            for (i, pair) in samples.windows(2).enumerate() {
+                assert!((pair[1] - pair[0]).abs() <= max_step, "log={log}: jump of {} at frame {}", pair[1] - pair[0], i + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn sine_peaks_at_the_requested_frequency() {
+        const N: usize = 8192;
+        let fft = realfft::RealFftPlanner::<f32>::new().plan_fft_forward(N);
+        for freq in [440.0, 1000.0, 1234.5, 15000.0] {
+            let mut input = left(generator("sine", GeneratorParams { freq: Some(freq), ..Default::default() }), N);
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+            let bin = spectrum.iter().enumerate().max_by(|a, b| a.1.norm().total_cmp(&b.1.norm())).unwrap().0;
+            let found = bin as f32 * 48000.0 / N as f32;
+            assert!((found - freq).abs() <= 48000.0 / N as f32, "{freq}Hz peaked at {found}Hz");
+        }
+    }
+
+    #[test]
+    fn default_level_and_cap() {
+        let request = GeneratorRequest::parse("sine", &GeneratorParams::default()).unwrap();
+        assert_eq!(request.level_db, GENERATOR_DEFAULT_DB);
+        let level = peak(&left(SignalGenerator::new(request, 48000), 48000));
+        assert!((20.0 * level.log10() - GENERATOR_DEFAULT_DB).abs() < 0.01, "{level}");
+
+        let loud = GeneratorParams { level_db: Some(0.0), ..Default::default() };
+        assert_eq!(GeneratorRequest::parse("sine", &loud).unwrap().level_db, GENERATOR_MAX_DB);
+        let cap = 10f32.powf(GENERATOR_MAX_DB / 20.0);
+        assert!((peak(&left(generator("sine", loud), 48000)) - cap).abs() < 1e-3);
+        assert!(GeneratorRequest::parse("sine", &GeneratorParams { level_db: Some(f32::NAN), ..Default::default() }).is_err());
+
+        // 噪声同样不越过上限；粉噪靠缩放而非削波，贴顶的只有刷新峰值的那几个样本
+        assert!(peak(&left(generator("white_noise", loud), 48000 * 10)) <= cap);
+        let pink = left(generator("pink_noise", loud), 48000 * 10);
+        assert!(peak(&pink) <= cap);
+        assert!(pink.iter().filter(|s| s.abs() >= cap * 0.999).count() < 10);
+    }
+
+    // (输入声道, 声道配置码, 变速倍率, 设置, 应处于启用状态的级)
+    type ChainCase = (u16, u16, f32, fn(&DspSettings), &'static [&'static str]);
+
+    #[test]
+    fn published_chain_matches_the_constructed_stages() {
+        const ORDER: [&str; 9] = ["mono_to_stereo", "downmix", "time_stretch", "auto_level", "preamp", "parametric_eq", "bass_boost", "crossfeed", "spatial_upmix"];
+        let cases: [ChainCase; 5] = [
+            (2, 2, 1.0, |_| {}, &[]),
+            (1, 106, 1.0, |_| {}, &["mono_to_stereo", "spatial_upmix"]),
+            (6, 2, 1.25, |s| {
+                s.eq.set(vec![EqBand { kind: dsp::EqBandType::Peak, freq: 1000.0, q: 1.0, gain_db: 3.0 }]);
+                s.preamp_db.store(3f32.to_bits(), Ordering::Relaxed);
+            }, &["downmix", "time_stretch", "preamp", "parametric_eq"]),
+            (2, 2, 1.0, |s| {
+                s.auto_level.set(dsp::AutoLevel { enabled: true, ..Default::default() });
+                s.bass.set(BassBoost { gain_db: 6.0, freq: 80.0 });
+                s.crossfeed.set(Crossfeed { enabled: true, level: 2 });
+            }, &["auto_level", "bass_boost", "crossfeed"]),
+            // 虚拟化输出不是纯立体声：串音已打开也只能登记为旁路
+            (2, 6, 1.0, |s| s.crossfeed.set(Crossfeed { enabled: true, level: 2 }), &["spatial_upmix"]),
+        ];
+        for (channels, code, rate, configure, active) in cases {
+            let settings = DspSettings::new();
+            configure(&settings);
+            let source = rodio::buffer::SamplesBuffer::new(channels, 48000, vec![0.0f32; channels as usize * 4800]);
+            let (mut chain, stages) = GalaxyEngine::processing_chain(source, Vec::new(), &settings, rate, code, Arc::new(AtomicBool::new(true)), Arc::new(AtomicU32::new(1f32.to_bits())));
+            dsp_registry::publish_chain(stages);
+            let published = dsp_registry::snapshot();
+            assert_eq!(published.iter().map(|s| s.name).collect::<Vec<_>>(), ORDER);
+            for stage in &published {
+                assert_eq!(stage.bypassed, !active.contains(&stage.name), "{} with {channels}ch -> {code}", stage.name);
+            }
+
+            // 登记表挂的就是链上的级：播放中改参数，过了检查步长后快照随之更新
+            settings.eq.set(Vec::new());
+            settings.bass.set(BassBoost { gain_db: 4.0, freq: 100.0 });
+            chain.by_ref().take(dsp::PARAM_CHECK_STRIDE * 8 * 2).for_each(drop);
+            let live = dsp_registry::snapshot();
+            let bypassed = |name: &str| live.iter().find(|s| s.name == name).map(|s| s.bypassed);
+            assert_eq!(bypassed("parametric_eq"), Some(true));
+            assert_eq!(bypassed("bass_boost"), Some(false));
+        }
+        dsp_registry::clear_chain();
+    }
+
+    #[test]
+    fn unverified_containers_are_not_native() {
+        // 没开 ogg / mkv demuxer：这些扩展名必须走 FFmpeg，不能被当成原生格式
+        for ext in ["ogg", "mka", "opus", "webm"] {
+            assert!(!NATIVE_FORMATS.contains(&ext), "{ext} has no compiled-in demuxer");
+        }
+    }
+}
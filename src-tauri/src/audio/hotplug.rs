@@ -0,0 +1,67 @@
+// src/audio/hotplug.rs
+
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+// =================================================================
+// 🔌 设备热插拔：跟随设备巡检比对输出设备列表，变动稳定后发 audio-devices-changed
+// =================================================================
+// 蓝牙耳机配对期间设备会反复出现又消失，新列表要连续保持 SETTLE 才算数，一次配对只通知一次。
+// 巡检由 actor 执行，重建输出流不会打断它，也无需重启。
+const SETTLE: Duration = Duration::from_secs(3);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DevicesChanged {
+    pub devices: Vec<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+// 所用设备消失、已回退到系统默认设备
+#[derive(Serialize, Clone, Debug)]
+pub struct DeviceLost {
+    pub device: String,
+    pub fallback: String,
+    pub position: f64,
+    pub resumed: bool,
+}
+
+// 跟随系统默认时默认设备变了，输出流已迁移过去
+#[derive(Serialize, Clone, Debug)]
+pub struct DeviceSwitched {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Default)]
+pub struct DeviceListWatch {
+    announced: Option<Vec<String>>, // 最近一次通知过的列表；首轮巡检只记录
+    pending: Option<(Vec<String>, Instant)>, // 与之不同、正在等待稳定的新列表
+}
+
+impl DeviceListWatch {
+    pub fn observe(&mut self, mut devices: Vec<String>) -> Option<DevicesChanged> {
+        devices.sort();
+        let Some(announced) = &self.announced else {
+            self.announced = Some(devices);
+            return None;
+        };
+        if *announced == devices {
+            self.pending = None;
+            return None;
+        }
+        match &self.pending {
+            Some((pending, since)) if *pending == devices && since.elapsed() >= SETTLE => {}
+            Some((pending, _)) if *pending == devices => return None,
+            _ => {
+                self.pending = Some((devices, Instant::now()));
+                return None;
+            }
+        }
+        self.pending = None;
+        let added = devices.iter().filter(|d| !announced.contains(d)).cloned().collect();
+        let removed = announced.iter().filter(|d| !devices.contains(d)).cloned().collect();
+        self.announced = Some(devices.clone());
+        Some(DevicesChanged { devices, added, removed })
+    }
+}
@@ -0,0 +1,135 @@
+// src/audio/level_meter.rs
+
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use serde::Serialize;
+use tauri::AppHandle;
+use crate::modules::api_schema::events;
+use super::threads;
+
+// =================================================================
+// 📶 电平表：限幅之后逐声道统计峰值与 RMS，约 50ms 一个窗口
+// =================================================================
+// 音频线程在本地累加，窗口结束时才写一次原子量；未开启时每帧只多一次原子读。
+// 削波标记是粘滞的，直到前端确认才清除。多声道输出按实际声道数 (6/8) 逐个上报。
+const WINDOW_MS: u32 = 50;
+const POLL_INTERVAL: Duration = Duration::from_millis(WINDOW_MS as u64);
+const MAX_CHANNELS: usize = 8;
+const CLIP_LEVEL: f32 = 1.0;
+const FLOOR_DB: f32 = -100.0;
+
+struct Levels {
+    enabled: AtomicBool,
+    seq: AtomicU64, // 每发布一个窗口加一
+    channels: AtomicUsize,
+    peak: [AtomicU32; MAX_CHANNELS], // f32 bits，线性
+    rms: [AtomicU32; MAX_CHANNELS],
+    clipped: AtomicU32, // 按声道的位掩码
+}
+
+static LEVELS: OnceLock<Levels> = OnceLock::new();
+static WORKER: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+
+fn levels() -> &'static Levels {
+    LEVELS.get_or_init(|| Levels {
+        enabled: AtomicBool::new(false),
+        seq: AtomicU64::new(0),
+        channels: AtomicUsize::new(0),
+        peak: std::array::from_fn(|_| AtomicU32::new(0)),
+        rms: std::array::from_fn(|_| AtomicU32::new(0)),
+        clipped: AtomicU32::new(0),
+    })
+}
+
+// 挂在 UpmixSource 上的本地累加器
+pub struct LevelTap {
+    peak: [f32; MAX_CHANNELS],
+    sum_sq: [f64; MAX_CHANNELS],
+    frames: u32,
+    window_frames: u32,
+}
+
+impl LevelTap {
+    pub fn new(sample_rate: u32) -> Self {
+        Self { peak: [0.0; MAX_CHANNELS], sum_sq: [0.0; MAX_CHANNELS], frames: 0, window_frames: (sample_rate * WINDOW_MS / 1000).max(1) }
+    }
+
+    #[inline(always)]
+    pub fn push(&mut self, frame: &[f32]) {
+        let Some(levels) = LEVELS.get() else { return };
+        if !levels.enabled.load(Ordering::Relaxed) {
+            self.frames = 0;
+            return;
+        }
+        if self.frames == 0 {
+            self.peak = [0.0; MAX_CHANNELS];
+            self.sum_sq = [0.0; MAX_CHANNELS];
+        }
+        for (ch, &sample) in frame.iter().take(MAX_CHANNELS).enumerate() {
+            self.peak[ch] = self.peak[ch].max(sample.abs());
+            self.sum_sq[ch] += (sample * sample) as f64;
+        }
+        self.frames += 1;
+        if self.frames >= self.window_frames { self.publish(levels, frame.len().min(MAX_CHANNELS)); }
+    }
+
+    fn publish(&mut self, levels: &Levels, channels: usize) {
+        let mut clipped = 0u32;
+        for ch in 0..channels {
+            levels.peak[ch].store(self.peak[ch].to_bits(), Ordering::Relaxed);
+            levels.rms[ch].store(((self.sum_sq[ch] / self.frames as f64).sqrt() as f32).to_bits(), Ordering::Relaxed);
+            if self.peak[ch] >= CLIP_LEVEL { clipped |= 1 << ch; }
+        }
+        if clipped != 0 { levels.clipped.fetch_or(clipped, Ordering::Relaxed); }
+        levels.channels.store(channels, Ordering::Relaxed);
+        levels.seq.fetch_add(1, Ordering::Release);
+        self.frames = 0;
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LevelFrame {
+    pub peak_db: Vec<f32>, // dBFS，按输出声道顺序
+    pub rms_db: Vec<f32>,
+    pub clipped: Vec<bool>, // 粘滞，metering_ack_clip 后清除
+}
+
+fn to_db(linear: f32) -> f32 { (20.0 * linear.max(1e-9).log10()).max(FLOOR_DB) }
+
+pub fn start(app: AppHandle) {
+    let mut worker = WORKER.lock().unwrap_or_else(|e| e.into_inner());
+    if worker.is_some() { return; }
+    let stop = Arc::new(AtomicBool::new(false));
+    *worker = Some(stop.clone());
+    levels().enabled.store(true, Ordering::Relaxed);
+    threads::spawn("level-meter", move || run(app, stop));
+}
+
+pub fn stop() {
+    if let Some(stop) = WORKER.lock().unwrap_or_else(|e| e.into_inner()).take() { stop.store(true, Ordering::Relaxed); }
+    if let Some(levels) = LEVELS.get() { levels.enabled.store(false, Ordering::Relaxed); }
+}
+
+pub fn ack_clip() {
+    if let Some(levels) = LEVELS.get() { levels.clipped.store(0, Ordering::Relaxed); }
+}
+
+fn run(app: AppHandle, stop: Arc<AtomicBool>) {
+    let levels = levels();
+    let mut last_seq = levels.seq.load(Ordering::Acquire);
+    while threads::sleep(POLL_INTERVAL) && !stop.load(Ordering::Relaxed) {
+        // 暂停时没有新窗口，不重复推送
+        let seq = levels.seq.load(Ordering::Acquire);
+        if seq == last_seq { continue; }
+        last_seq = seq;
+        let channels = levels.channels.load(Ordering::Relaxed);
+        let clipped = levels.clipped.load(Ordering::Relaxed);
+        let read = |slots: &[AtomicU32; MAX_CHANNELS]| slots[..channels].iter().map(|s| to_db(f32::from_bits(s.load(Ordering::Relaxed)))).collect();
+        let _ = events::LEVEL_METER.emit(&app, &LevelFrame {
+            peak_db: read(&levels.peak),
+            rms_db: read(&levels.rms),
+            clipped: (0..channels).map(|ch| clipped & (1 << ch) != 0).collect(),
+        });
+    }
+}
@@ -0,0 +1,250 @@
+// src/audio/loudness.rs
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use lofty::{read_from_path, AudioFile};
+use rodio::{Decoder, OutputStreamHandle, Sink, Source};
+use serde::{Serialize, Deserialize};
+use tauri::{AppHandle, Manager};
+use crate::modules::api_schema::events;
+use super::analysis::{LoudnessMeter, TruePeakMeter};
+use super::coordinator::{self, COORDINATOR};
+use super::threads;
+use crate::util::jobs::JobHandle;
+
+// =================================================================
+// 🔊 响度缓存与归一化试听
+// =================================================================
+// 整曲响度按 (大小, mtime) 缓存落盘，只需解码一次。
+// 试听在独立的预览 Sink 上进行，不触碰引擎与主 Sink，主播放状态原样保留。
+pub const REFERENCE_LUFS: f64 = -18.0;
+const NORMALIZE_GAIN_RANGE_DB: (f64, f64) = (-20.0, 12.0);
+pub const PREVIEW_JOB_ID: &str = "loudness_preview";
+const PREVIEW_EXCERPT: Duration = Duration::from_secs(5);
+const PREVIEW_FADE: Duration = Duration::from_millis(30);
+const PREVIEW_EXCERPT_AT: f64 = 0.3; // 片段取自曲目 30% 处，避开前奏
+const PREVIEW_SEGMENT_GRACE: Duration = Duration::from_secs(2); // 输出设备中途消失时不至于无限等待
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Loudness {
+    pub integrated_lufs: f64,
+    pub sample_peak: f32,
+    #[serde(default)]
+    pub true_peak: Option<f32>, // 早期缓存条目没有
+}
+
+impl Loudness {
+    pub fn normalize_gain_db(&self) -> f64 {
+        (REFERENCE_LUFS - self.integrated_lufs).clamp(NORMALIZE_GAIN_RANGE_DB.0, NORMALIZE_GAIN_RANGE_DB.1)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CachedLoudness {
+    size: u64,
+    mtime: i64,
+    loudness: Loudness,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct LoudnessCache {
+    entries: HashMap<String, CachedLoudness>,
+}
+
+pub(crate) fn file_stamp(path: &str) -> Option<(u64, i64)> {
+    let meta = fs::metadata(path).ok()?;
+    let mtime = meta.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_millis() as i64).unwrap_or(0);
+    Some((meta.len(), mtime))
+}
+
+impl LoudnessCache {
+    fn path(app: &AppHandle) -> PathBuf {
+        app.path().app_local_data_dir().unwrap_or_else(|_| PathBuf::from("./data")).join("loudness_cache.json")
+    }
+    pub fn load(app: &AppHandle) -> Self {
+        fs::read_to_string(Self::path(app)).ok().and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default()
+    }
+    pub fn save(&self, app: &AppHandle) {
+        let path = Self::path(app);
+        if let Some(dir) = path.parent() { let _ = fs::create_dir_all(dir); }
+        if let Ok(json) = serde_json::to_string(self) { let _ = fs::write(path, json); }
+    }
+    pub fn lookup(&self, path: &str) -> Option<Loudness> {
+        let (size, mtime) = file_stamp(path)?;
+        self.entries.get(path).filter(|e| e.size == size && e.mtime == mtime).map(|e| e.loudness)
+    }
+    pub fn insert(&mut self, path: &str, loudness: Loudness) {
+        if let Some((size, mtime)) = file_stamp(path) { self.entries.insert(path.to_string(), CachedLoudness { size, mtime, loudness }); }
+    }
+}
+
+// 作为辅助任务整曲解码测量，主曲目缓存填充期间自动让路；取消时返回 Ok(None)
+pub fn measure(path: &str, job: &JobHandle) -> Result<Option<Loudness>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let source = Decoder::new(BufReader::new(file)).map_err(|e| e.to_string())?.convert_samples::<f32>();
+    let channels = source.channels().max(1) as usize;
+    let mut meter = LoudnessMeter::new(source.channels(), source.sample_rate());
+    let mut true_peak = TruePeakMeter::new(channels);
+    let aux = COORDINATOR.register_aux();
+    let (mut pushed, mut next_check) = (0usize, coordinator::MAIN_CHUNK);
+    for sample in source {
+        meter.push(sample);
+        true_peak.push(pushed % channels, sample);
+        pushed += 1;
+        if pushed < next_check { continue; }
+        if job.is_cancelled() || threads::shutting_down() { return Ok(None); }
+        next_check += aux.pace();
+    }
+    let integrated_lufs = meter.integrated_lufs().ok_or_else(|| "LOUDNESS_SILENT".to_string())?;
+    Ok(Some(Loudness { integrated_lufs, sample_peak: meter.sample_peak(), true_peak: Some(true_peak.peak().max(meter.sample_peak())) }))
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PreviewSegment {
+    pub which: &'static str, // a_raw / b_raw / a_normalized / b_normalized
+    pub index: usize,
+    pub path: String,
+    pub gain_db: f64,
+    pub loudness_lufs: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PreviewFinish {
+    pub cancelled: bool,
+    pub error: Option<String>,
+}
+
+fn excerpt(path: &str, gain_db: f64) -> Result<impl Source<Item = f32> + Send, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let decoder = Decoder::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    let total = decoder.total_duration()
+        .or_else(|| read_from_path(path).ok().map(|f| f.properties().duration()))
+        .unwrap_or_default();
+    let start = total.mul_f64(PREVIEW_EXCERPT_AT).min(total.saturating_sub(PREVIEW_EXCERPT));
+    let mut clip = decoder.convert_samples::<f32>().skip_duration(start).take_duration(PREVIEW_EXCERPT);
+    clip.set_filter_fadeout();
+    Ok(clip.fade_in(PREVIEW_FADE).amplify(10f32.powf(gain_db as f32 / 20.0)))
+}
+
+// 两首先取（或补测）响度，再依次播放 A 原始 → B 原始 → A 归一化 → B 归一化
+fn preview_sequence(app: &AppHandle, stream: &OutputStreamHandle, path_a: &str, path_b: &str, job: &JobHandle) -> Result<bool, String> {
+    let mut cache = LoudnessCache::load(app);
+    let cached = (cache.lookup(path_a), cache.lookup(path_b));
+    let measure_missing = |hit: Option<Loudness>, path: &str| match hit {
+        Some(loudness) => Ok(Some(loudness)),
+        None => measure(path, job),
+    };
+    let (a, b) = rayon::join(|| measure_missing(cached.0, path_a), || measure_missing(cached.1, path_b));
+    let (Some(a), Some(b)) = (a?, b?) else { return Ok(false) };
+    if cached.0.is_none() { cache.insert(path_a, a); }
+    if cached.1.is_none() { cache.insert(path_b, b); }
+    if cached.0.is_none() || cached.1.is_none() { cache.save(app); }
+
+    let sink = Sink::try_new(stream).map_err(|e| e.to_string())?;
+    let segments = [
+        ("a_raw", path_a, a, 0.0),
+        ("b_raw", path_b, b, 0.0),
+        ("a_normalized", path_a, a, a.normalize_gain_db()),
+        ("b_normalized", path_b, b, b.normalize_gain_db()),
+    ];
+    let total = segments.len();
+    for (index, (which, path, loudness, gain_db)) in segments.into_iter().enumerate() {
+        if job.is_cancelled() { sink.stop(); return Ok(false); }
+        sink.append(excerpt(path, gain_db)?);
+        let _ = events::PREVIEW_SEGMENT.emit(app, &PreviewSegment { which, index, path: path.to_string(), gain_db, loudness_lufs: loudness.integrated_lufs });
+        job.set_progress(index as f32 / total as f32);
+
+        let deadline = Instant::now() + PREVIEW_EXCERPT + PREVIEW_SEGMENT_GRACE;
+        while !sink.empty() && Instant::now() < deadline {
+            if job.is_cancelled() || !threads::sleep(Duration::from_millis(20)) { sink.stop(); return Ok(false); }
+        }
+    }
+    Ok(true)
+}
+
+pub fn start_preview(app: AppHandle, stream: OutputStreamHandle, path_a: String, path_b: String, job: JobHandle) {
+    threads::spawn("loudness-preview", move || {
+        let finish = match preview_sequence(&app, &stream, &path_a, &path_b, &job) {
+            Ok(completed) => PreviewFinish { cancelled: !completed, error: None },
+            Err(e) => PreviewFinish { cancelled: false, error: Some(e) },
+        };
+        let _ = events::PREVIEW_FINISH.emit(&app, &finish);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::galaxy::tests::{pcm16, wav_bytes};
+    use crate::util::jobs::{JobKind, JobRegistry};
+
+    fn temp_tone(name: &str, frames: usize) -> String {
+        let dir = std::env::temp_dir().join(format!("agm_loudness_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tone.wav");
+        fs::write(&path, wav_bytes(44100, 2, frames)).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    fn remove_tone(path: &str) { let _ = fs::remove_dir_all(std::path::Path::new(path).parent().unwrap()); }
+
+    #[test]
+    fn normalize_gain_moves_toward_the_reference_within_range() {
+        let gain = |lufs| Loudness { integrated_lufs: lufs, sample_peak: 1.0, true_peak: None }.normalize_gain_db();
+        assert_eq!(gain(-18.0), 0.0);
+        assert_eq!(gain(-9.5), -8.5);
+        assert_eq!(gain(-23.0), 5.0);
+        assert_eq!(gain(-40.0), 12.0);
+        assert_eq!(gain(5.0), -20.0);
+    }
+
+    #[test]
+    fn measure_matches_the_meter_on_the_decoded_pcm() {
+        let path = temp_tone("measure", 44100 * 3);
+        let jobs = JobRegistry::new();
+        let measured = measure(&path, &jobs.register("loudness_measure_test", JobKind::LoudnessPreview, "test")).unwrap().unwrap();
+
+        let mut meter = LoudnessMeter::new(2, 44100);
+        for s in pcm16(44100, 2, 44100 * 3) { meter.push(s as f32 / 32768.0); }
+        assert!((measured.integrated_lufs - meter.integrated_lufs().unwrap()).abs() < 1e-9, "{:?}", measured);
+        assert_eq!(measured.sample_peak, meter.sample_peak());
+        // 440Hz 远低于奈奎斯特，样间峰值几乎不高于样本峰值
+        let true_peak = measured.true_peak.unwrap();
+        assert!(true_peak >= measured.sample_peak && true_peak < measured.sample_peak * 1.01, "{:?}", measured);
+        remove_tone(&path);
+    }
+
+    #[test]
+    fn cancelled_measurement_returns_nothing() {
+        let path = temp_tone("cancel", 44100 * 3);
+        let jobs = JobRegistry::new();
+        let job = jobs.register("loudness_cancel_test", JobKind::LoudnessPreview, "test");
+        jobs.cancel("loudness_cancel_test").unwrap();
+        assert!(matches!(measure(&path, &job), Ok(None)));
+        remove_tone(&path);
+    }
+
+    #[test]
+    fn cache_entries_expire_when_the_file_changes() {
+        let path = temp_tone("cache", 44100);
+        let loudness = Loudness { integrated_lufs: -12.0, sample_peak: 0.5, true_peak: Some(0.6) };
+        let mut cache = LoudnessCache::default();
+        assert!(cache.lookup(&path).is_none());
+        cache.insert(&path, loudness);
+        assert_eq!(cache.lookup(&path).map(|l| l.integrated_lufs), Some(-12.0));
+
+        // 大小变了就重新测量
+        fs::write(&path, wav_bytes(44100, 2, 4410)).unwrap();
+        assert!(cache.lookup(&path).is_none());
+        // 读不到的文件不进缓存
+        cache.insert("/no/such/track.wav", loudness);
+        assert!(!cache.entries.contains_key("/no/such/track.wav"));
+        // 早期缓存条目没有 true_peak
+        let old: Loudness = serde_json::from_str(r#"{"integrated_lufs":-14.0,"sample_peak":0.9}"#).unwrap();
+        assert_eq!(old.true_peak, None);
+        remove_tone(&path);
+    }
+}
@@ -0,0 +1,235 @@
+// src/audio/memory_source.rs
+
+use rodio::Source;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use super::silence::SilenceCursor;
+
+// =================================================================
+// 后台零拷贝内存源引擎 (最核心的 O(1) 瞬切基石)
+// =================================================================
+// 两个引擎共用：整曲 PCM 以 Arc 共享，seek / 换设备只新建游标，不复制样本。
+// 起点由时间直接换算成样本下标，不经 rodio 的 skip_duration 逐个样本空转。
+#[derive(Clone)]
+pub struct ArcSliceSource {
+    data: Arc<Vec<f32>>, pos: usize, channels: u16, sample_rate: u32,
+    silence: Option<SilenceCursor>,
+}
+
+impl ArcSliceSource {
+    pub fn new(data: Arc<Vec<f32>>, channels: u16, sample_rate: u32) -> Self {
+        Self { data, pos: 0, channels, sample_rate, silence: None }
+    }
+    // 须在 starting_at 之前挂上，游标才能随之定位
+    pub fn with_silence(mut self, silence: Option<SilenceCursor>) -> Self {
+        self.silence = silence;
+        self
+    }
+    // 与 try_seek 同一套换算：先取整到帧再乘声道数，单声道 / 多声道都落在帧边界上
+    pub fn starting_at(mut self, time: Duration) -> Self {
+        let _ = self.try_seek(time);
+        self
+    }
+}
+
+impl Iterator for ArcSliceSource {
+    type Item = f32;
+    #[inline(always)]
+    fn next(&mut self) -> Option<f32> {
+        if let Some(to) = self.silence.as_mut().and_then(|s| s.jump_from(self.pos)) { self.pos = to; }
+        if self.pos < self.data.len() { let val = self.data[self.pos]; self.pos += 1; Some(val) } else { None }
+    }
+}
+
+impl Source for ArcSliceSource {
+    // 参数在整段内不变，帧即剩余样本；rodio 据此决定何时重新读取声道数与采样率
+    fn current_frame_len(&self) -> Option<usize> { Some(self.data.len() - self.pos) }
+    fn channels(&self) -> u16 { self.channels }
+    fn sample_rate(&self) -> u32 { self.sample_rate }
+    fn total_duration(&self) -> Option<Duration> {
+        let frames_left = (self.data.len() - self.pos) / self.channels as usize;
+        Some(Duration::from_secs_f64(frames_left as f64 / self.sample_rate as f64))
+    }
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        let offset = (pos.as_secs_f64() * self.sample_rate as f64) as usize * self.channels as usize;
+        self.pos = offset.min(self.data.len());
+        self.pos -= self.pos % self.channels as usize;
+        if let Some(silence) = self.silence.as_mut() { silence.reposition(self.pos); }
+        Ok(())
+    }
+}
+
+// =================================================================
+// 📈 渐进缓存：后台整曲解码边解边追加，seek 落在已解码范围内即可从内存起播
+// =================================================================
+// 解码完成时样本整块移交为 Arc<Vec<f32>> (即整曲缓存)，已挂上的渐进源随之改读这一份，全程不复制整曲。
+const BLOCK: usize = 4096; // 渐进源每次取出的样本数，音频回调里只短暂持锁
+
+struct Partial {
+    samples: Vec<f32>,
+    full: Option<Arc<Vec<f32>>>, // 解码完成后样本移到这里
+    done: bool, // 完成、取消或失败，不会再增长
+}
+
+impl Partial {
+    fn data(&self) -> &[f32] {
+        match &self.full { Some(full) => full.as_slice(), None => self.samples.as_slice() }
+    }
+}
+
+pub struct PartialPcm {
+    state: Mutex<Partial>,
+    grown: Condvar,
+}
+
+impl PartialPcm {
+    // expected 为预计样本数，预留容量避免边解边扩容时在锁内搬移整块内存
+    pub fn new(expected: usize) -> Arc<Self> {
+        Arc::new(Self { state: Mutex::new(Partial { samples: Vec::with_capacity(expected), full: None, done: false }), grown: Condvar::new() })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Partial> { self.state.lock().unwrap_or_else(|e| e.into_inner()) }
+
+    pub fn extend(&self, chunk: &[f32]) {
+        self.lock().samples.extend_from_slice(chunk);
+        self.grown.notify_all();
+    }
+
+    // 交出整曲样本作为最终缓存
+    pub fn finish(&self) -> Arc<Vec<f32>> {
+        let mut state = self.lock();
+        let full = Arc::new(std::mem::take(&mut state.samples));
+        state.full = Some(full.clone());
+        state.done = true;
+        drop(state);
+        self.grown.notify_all();
+        full
+    }
+
+    // 解码被取消或失败：不再增长，渐进源播到已解码的末尾即结束
+    pub fn abandon(&self) {
+        self.lock().done = true;
+        self.grown.notify_all();
+    }
+
+    // 等到该样本已解码；解码提前结束或超时返回 false
+    pub fn wait_for(&self, sample: usize, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        while sample >= state.data().len() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if state.done || left.is_zero() { return false; }
+            state = self.grown.wait_timeout(state, left).unwrap_or_else(|e| e.into_inner()).0;
+        }
+        true
+    }
+
+    pub fn source_at(self: &Arc<Self>, sample: usize, channels: u16, sample_rate: u32) -> ProgressiveSource {
+        ProgressiveSource { pcm: self.clone(), pos: sample - sample % channels as usize, block: Vec::with_capacity(BLOCK), block_pos: 0, channels, sample_rate }
+    }
+}
+
+pub struct ProgressiveSource {
+    pcm: Arc<PartialPcm>,
+    pos: usize, // 下一次取块的样本序号
+    block: Vec<f32>,
+    block_pos: usize,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl ProgressiveSource {
+    // Some(false) 表示解码暂未跟上
+    fn refill(&mut self) -> Option<bool> {
+        let state = self.pcm.lock();
+        let data = state.data();
+        if self.pos >= data.len() { return if state.done { None } else { Some(false) }; }
+        let to = (self.pos + BLOCK).min(data.len());
+        self.block.clear();
+        self.block.extend_from_slice(&data[self.pos..to]);
+        drop(state);
+        self.block_pos = 0;
+        self.pos = to;
+        Some(true)
+    }
+}
+
+impl Iterator for ProgressiveSource {
+    type Item = f32;
+    #[inline(always)]
+    fn next(&mut self) -> Option<f32> {
+        if self.block_pos >= self.block.len() {
+            // 欠载时输出静音而不在音频回调里阻塞等待
+            if !self.refill()? { return Some(0.0); }
+        }
+        let val = self.block[self.block_pos];
+        self.block_pos += 1;
+        Some(val)
+    }
+}
+
+impl Source for ProgressiveSource {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { self.channels }
+    fn sample_rate(&self) -> u32 { self.sample_rate }
+    fn total_duration(&self) -> Option<Duration> { None }
+    // 只在已解码的范围内跳转
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        let target = (pos.as_secs_f64() * self.sample_rate as f64) as usize * self.channels as usize;
+        if target >= self.pcm.lock().data().len() {
+            return Err(rodio::source::SeekError::NotSupported { underlying_source: std::any::type_name::<Self>() });
+        }
+        self.pos = target;
+        self.block.clear();
+        self.block_pos = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2 秒的已知缓冲：样本值即序号，便于核对起点
+    fn two_seconds(channels: u16, sample_rate: u32) -> Arc<Vec<f32>> {
+        Arc::new((0..2 * sample_rate as usize * channels as usize).map(|n| n as f32).collect())
+    }
+
+    #[test]
+    fn slice_duration_counts_frames_not_samples() {
+        for (channels, rate) in [(1, 44100), (2, 48000), (6, 48000)] {
+            let source = ArcSliceSource::new(two_seconds(channels, rate), channels, rate);
+            assert_eq!(source.total_duration(), Some(Duration::from_secs(2)));
+            let source = source.starting_at(Duration::from_millis(500));
+            assert_eq!(source.total_duration(), Some(Duration::from_millis(1500)));
+            assert_eq!(source.count(), rate as usize * 3 / 2 * channels as usize);
+        }
+    }
+
+    #[test]
+    fn slice_seek_lands_on_frame_boundary_and_clamps() {
+        let mut source = ArcSliceSource::new(two_seconds(2, 48000), 2, 48000);
+        source.try_seek(Duration::from_secs_f64(1.0 / 48000.0 * 10.5)).unwrap();
+        assert_eq!(source.next(), Some(20.0));
+        source.try_seek(Duration::from_secs(5)).unwrap();
+        assert_eq!(source.total_duration(), Some(Duration::ZERO));
+        assert_eq!(source.next(), None);
+    }
+
+    #[test]
+    fn progressive_source_follows_decode_and_hands_over() {
+        let pcm = PartialPcm::new(16);
+        pcm.extend(&[0.0, 1.0, 2.0, 3.0]);
+        let mut source = pcm.source_at(3, 2, 48000);
+        assert_eq!(source.total_duration(), None);
+        assert_eq!((source.next(), source.next()), (Some(2.0), Some(3.0)));
+        // 解码没跟上时输出静音而不是结束
+        assert_eq!(source.next(), Some(0.0));
+        assert!(!pcm.wait_for(4, Duration::from_millis(10)));
+        pcm.extend(&[4.0, 5.0]);
+        let full = pcm.finish();
+        assert_eq!(full.len(), 6);
+        assert_eq!(source.by_ref().collect::<Vec<_>>(), vec![4.0, 5.0]);
+        assert!(source.try_seek(Duration::from_secs(1)).is_err());
+    }
+}
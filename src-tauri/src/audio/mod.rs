@@ -2,11 +2,81 @@
 
 pub mod galaxy;
 pub mod ffmpeg;
+pub mod dsp_registry;
+pub mod analysis;
+pub mod coordinator;
+pub mod stretch;
+pub mod format_quirks;
+pub mod threads;
+pub mod dsp;
+pub mod device_caps;
+pub mod room;
+pub mod endpoint;
+pub mod recovery;
+pub mod loudness;
+pub mod replaygain;
+pub mod silence;
+pub mod spectrum;
+pub mod level_meter;
+pub mod playback_error;
+pub mod null_engine;
+pub mod hotplug;
+pub mod devices;
+pub mod output_mode;
+pub mod pcm_stream;
+pub mod decode_progress;
+pub mod ffprobe;
+pub mod memory_source;
+pub mod pcm_spill;
 
 use tokio::sync::oneshot;
-use std::sync::mpsc::{self, Sender};
-use rodio::{OutputStream, OutputStreamHandle};
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use rodio::{OutputStream, OutputStreamHandle, Sink};
 use rodio::cpal::traits::{HostTrait, DeviceTrait};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use crate::util::jobs::{JobKind, JobRegistry};
+use playback_error::{ErrorReporter, PlaybackError, PlaybackErrorCode};
+use decode_progress::{ProgressEvent, ProgressReporter};
+use crate::modules::api_schema::events;
+
+const TRACK_END_POLL: Duration = Duration::from_millis(250);
+const SHUTDOWN_JOIN_TIMEOUT: Duration = Duration::from_millis(400);
+const FADE_SETTLE: Duration = Duration::from_millis(10); // 淡变结束后再多等一点，确保最后一帧已离开 UpmixSource
+const PAUSE_SINK_GRACE: Duration = Duration::from_millis(1000); // 暂停淡出后 Sink 延后停下，让输出缓冲里的尾音放完
+pub const NO_AUDIO_DEVICE: &str = "NO_AUDIO_DEVICE";
+const SILENCE_BOUNDS_JOB: &str = "silence_bounds";
+
+// 是否已有可用的输出流；无输出时由占位引擎顶替，不经 actor 回执的指令据此直接报错
+static OUTPUT_READY: AtomicBool = AtomicBool::new(false);
+
+pub fn require_output() -> Result<(), String> {
+    if OUTPUT_READY.load(Ordering::Acquire) { Ok(()) } else { Err(NO_AUDIO_DEVICE.to_string()) }
+}
+
+// 当前输出流的实际采样率，引擎按它决定重采样目标；0 表示未知，引擎退回系统默认设备的配置
+static STREAM_RATE: AtomicU32 = AtomicU32::new(0);
+
+pub fn stream_sample_rate() -> Option<u32> {
+    match STREAM_RATE.load(Ordering::Acquire) { 0 => None, rate => Some(rate) }
+}
+
+fn note_stream_rate(device: Option<&rodio::cpal::Device>) {
+    let rate = device.and_then(|d| d.default_output_config().ok()).map_or(0, |c| c.sample_rate().0);
+    STREAM_RATE.store(rate, Ordering::Release);
+}
+
+// 扩展名在引擎的原生格式表里；没有扩展名时交给解码器自己探测
+fn has_format(formats: &[&str], path: &str) -> bool {
+    let Some(ext) = std::path::Path::new(path).extension().map(|e| e.to_string_lossy().to_ascii_lowercase()) else { return true };
+    formats.contains(&ext.as_str())
+}
 
 // Wrapper 强制实现 Send/Sync
 struct StreamHolder(OutputStream);
@@ -17,60 +87,367 @@ pub trait AudioEngine: Send + Sync {
     fn load(&mut self, path: &str) -> Result<f64, String>;
     fn play(&mut self);
     fn pause(&mut self);
+    fn stop(&mut self); // 彻底复位：清空队列与 PCM 缓存，等待下一次 load()
+    fn preload(&mut self, _path: &str) -> Result<(), String> { Ok(()) } // 预读下一首到副缓存槽，不触碰当前 Sink
     fn seek(&mut self, time: f64);
-    fn set_volume(&mut self, vol: f32);
-    fn name(&self) -> &str;
-    fn set_channel_mode(&mut self, _mode: u16) {}
+    fn set_volume(&mut self, vol: f32) -> Result<(), String>;
+    fn name(&self) -> &str; // 展示用名称，可能随版本变化；判断引擎一律用 id()
+    fn id(&self) -> &'static str;
+    fn capabilities(&self) -> EngineCapabilities;
+    fn set_channel_mode(&mut self, _mode: u16) -> Result<(), String> { Ok(()) }
+    fn set_resampler_quality(&mut self, _quality: galaxy::ResamplerQuality) {}
     fn update_output_stream(&mut self, _handle: OutputStreamHandle) {} 
     fn get_current_time(&self) -> f64; // 对齐物理时间戳接口
+    fn is_playing(&self) -> bool;
+    fn current_path(&self) -> Option<String>;
+    fn cache_strategy(&self) -> pcm_spill::CacheStrategy { pcm_spill::CacheStrategy::None }
+    fn clip_report(&self) -> Option<analysis::ClipReport> { None }
+    fn set_loop(&mut self, _start: f64, _end: f64) -> Result<(), String> { Err("LOOP_UNSUPPORTED".to_string()) }
+    fn clear_loop(&mut self) {}
+    fn set_rate_preserve_pitch(&mut self, _rate: f32) -> Result<(), String> { Err("RATE_UNSUPPORTED".to_string()) }
+    fn set_parametric_eq(&mut self, _bands: Vec<dsp::EqBand>) -> Result<(), String> { Err("EQ_UNSUPPORTED".to_string()) }
+    fn set_bass_boost(&mut self, _bass: dsp::BassBoost) -> Result<(), String> { Err("BASS_UNSUPPORTED".to_string()) }
+    fn set_stereo_width(&mut self, _width: f32) -> Result<(), String> { Err("WIDTH_UNSUPPORTED".to_string()) }
+    fn set_preamp(&mut self, _gain_db: f32) -> Result<(), String> { Err("PREAMP_UNSUPPORTED".to_string()) }
+    fn set_balance(&mut self, _balance: f32) -> Result<(), String> { Err("BALANCE_UNSUPPORTED".to_string()) }
+    fn set_downmix_lfe(&mut self, _enabled: bool) -> Result<(), String> { Err("DOWNMIX_UNSUPPORTED".to_string()) }
+    fn set_channel_layout(&mut self, _layout: dsp::ChannelLayout) -> Result<(), String> { Err("LAYOUT_UNSUPPORTED".to_string()) }
+    fn set_auto_level(&mut self, _auto_level: dsp::AutoLevel) -> Result<(), String> { Err("AUTO_LEVEL_UNSUPPORTED".to_string()) }
+    fn set_fade_config(&mut self, _fades: dsp::FadeConfig) -> Result<(), String> { Err("FADE_UNSUPPORTED".to_string()) }
+    fn set_skip_silence(&mut self, _prefs: silence::SkipSilence) -> Result<(), String> { Err("SKIP_SILENCE_UNSUPPORTED".to_string()) }
+    fn take_skipped_silence(&self) -> f64 { 0.0 } // 自上次取走以来跳过的静音秒数
+    fn set_replaygain_db(&mut self, _gain_db: f32) -> Result<(), String> { Err("REPLAYGAIN_UNSUPPORTED".to_string()) } // 与前级增益叠加
+    fn set_bitperfect(&mut self, _enabled: bool) -> Result<(), String> { Err("BITPERFECT_UNSUPPORTED".to_string()) } // 开启后音量与声道模式锁定，相关调用返回错误
+    fn set_crossfeed(&mut self, _crossfeed: dsp::Crossfeed) -> Result<(), String> { Err("CROSSFEED_UNSUPPORTED".to_string()) }
+    fn set_lfe_crossover(&mut self, _crossover: dsp::LfeCrossover) -> Result<(), String> { Err("LFE_UNSUPPORTED".to_string()) }
+    fn set_upmix_params(&mut self, _params: dsp::UpmixParams) -> Result<(), String> { Err("UPMIX_UNSUPPORTED".to_string()) }
+    fn set_limiter_enabled(&mut self, _enabled: bool) {}
+    fn limiter_reduction_db(&self) -> f32 { 0.0 }
+    fn set_dither(&mut self, _dither: dsp::Dither) -> Result<(), String> { Err("DITHER_UNSUPPORTED".to_string()) }
+    fn start_generator(&mut self, _request: galaxy::GeneratorRequest) -> Result<(), String> { Err("GENERATOR_UNSUPPORTED".to_string()) }
+    fn identify_channels(&mut self) -> Result<Vec<galaxy::ChannelIdent>, String> { Err("IDENTIFY_UNSUPPORTED".to_string()) }
+}
+
+// =================================================================
+// 🌗 转场淡变：两个引擎共用
+// =================================================================
+// 淡变本身由 UpmixSource 的状态增益完成，与主音量的平滑相互独立，淡变途中调音量不会被覆盖。
+// 引擎每次 load / seek 都会换掉 Sink，延后停 Sink 的线程只持有 Arc<Mutex<Sink>> 与令牌：
+// 期间任何新的转场都会递增令牌，旧线程醒来发现代次不符即放弃，不会停掉换上的新 Sink。
+
+// 把本次转场的时长交给 UpmixSource，返回调用方需要等待的淡变时间
+pub(crate) fn begin_fade(dsp: &dsp::DspSettings, fade_ms: u32) -> Duration {
+    dsp.fade_ms.store(fade_ms, Ordering::Relaxed);
+    if fade_ms == 0 { Duration::ZERO } else { Duration::from_millis(fade_ms as u64) + FADE_SETTLE }
+}
+
+// 淡出结束后停下 Sink；不淡出时当场停下，不再起线程
+pub(crate) fn pause_after_fade(sink: &Arc<Mutex<Sink>>, fade_token: &Arc<AtomicUsize>, is_playing: &Arc<AtomicBool>, fade: Duration) {
+    let my_token = fade_token.fetch_add(1, Ordering::SeqCst) + 1;
+    if fade.is_zero() {
+        lock_sink(sink).pause();
+        return;
+    }
+    let (sink, token_ref, is_playing_flag) = (sink.clone(), fade_token.clone(), is_playing.clone());
+    threads::spawn("pause-fade", move || {
+        if !threads::sleep(fade + PAUSE_SINK_GRACE) { return; }
+        if token_ref.load(Ordering::SeqCst) == my_token && !is_playing_flag.load(Ordering::SeqCst) {
+            lock_sink(&sink).pause();
+        }
+    });
+}
+
+// 持锁线程 panic 会让锁中毒：取回内部的 Sink 继续用，但其中途被打断的队列已不可信，
+// 清空后交给下一次 load / seek 重建，而不是让此后每条指令都跟着 panic
+pub(crate) fn lock_sink(sink: &Mutex<Sink>) -> MutexGuard<'_, Sink> {
+    sink.lock().unwrap_or_else(|poisoned| {
+        sink.clear_poison();
+        let guard = poisoned.into_inner();
+        guard.stop();
+        guard
+    })
+}
+
+// 引擎能力：前端据此隐藏当前引擎用不上的控件，而不是调用后才发现无效
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct EngineCapabilities {
+    pub gapless: bool, // 预读下一首并裁掉编码器延迟
+    pub fades: bool,
+    pub upmix: bool, // 5.1 / 7.1 声道模式
+    pub accurate_seek: bool, // 在解码后的 PCM 上定位，精确到采样
+    pub bitperfect: bool,
+    pub time_stretch: bool,
+    pub generator: bool, // 测试音与声道识别
+    pub formats: Vec<&'static str>, // 可直接载入的扩展名
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct EngineInfo {
+    pub id: &'static str,
+    pub display_name: String,
+    pub capabilities: EngineCapabilities,
+}
+
+// 操作回执：供前端乐观更新后对账，applied 反映钳制/回退后的真实结果
+#[derive(Serialize, Clone, Debug)]
+pub struct OpResult {
+    pub op: &'static str,
+    pub requested: Value,
+    pub applied: Value,
+    pub state_delta: Vec<&'static str>,
+    pub phase: ManagerPhase, // 该操作执行时管理层所处的阶段
+    pub engine: &'static str, // 执行该操作的引擎 id，发出时填入
+}
+
+impl OpResult {
+    pub fn new(op: &'static str, requested: Value, applied: Value, state_delta: Vec<&'static str>) -> Self {
+        Self { op, requested, applied, state_delta, phase: ManagerPhase::Idle, engine: "" }
+    }
+}
+
+// 切换引擎后新引擎接手的曲目现场
+#[derive(Serialize, Clone, Debug)]
+pub struct EngineSwitched {
+    pub engine_id: String,
+    pub path: Option<String>,
+    pub position: f64,
+    pub is_playing: bool,
+    pub duration: f64,
+}
+
+// 管理层状态机：过渡阶段内收到的指令不直接落到引擎上，而是记为意图
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ManagerPhase {
+    #[default]
+    Idle,
+    Loading,
+    SwitchingEngine,
+    SwitchingDevice,
+    ApplyingRoomPreset,
+}
+
+// 过渡期间积攒的意图：音量/播放暂停取最后一次，seek 覆盖恢复位置，load/stop 取消过渡
+#[derive(Default)]
+struct PendingIntents {
+    volume: Option<f32>,
+    volume_replies: Vec<oneshot::Sender<Result<(), String>>>,
+    playing: Option<bool>,
+    seek: Option<f64>,
+    seek_replies: Vec<oneshot::Sender<()>>,
+    cancelled: bool,
+}
+
+// 发生器接管 Sink 前的曲目现场，停止发生器时据此重新载入
+struct GeneratorResume {
+    path: Option<String>,
+    position: f64,
+    was_playing: bool,
+}
+
+// 播放状态快照：前端重连/引擎切换后一次性对齐
+#[derive(Serialize, Clone, Debug)]
+pub struct PlayerState {
+    pub engine_id: String,
+    pub output_ready: bool, // false 表示没有可用的输出设备，需 retry_audio_init 或接入设备
+    pub is_playing: bool,
+    pub is_paused: bool,
+    pub loaded_path: Option<String>,
+    pub position: f64,
+    pub duration: f64,
+    pub volume: f32,
+    pub muted: bool, // 静音时 volume 仍是静音前的音量
+    pub volume_curve: dsp::VolumeCurve,
+    pub fades: dsp::FadeConfig,
+    pub skip_silence: silence::SkipSilence,
+    pub auto_trim: bool,
+    pub trim: Option<(f64, f64)>, // 当前曲目修剪后的起止时间 (秒)
+    pub channel_mode: u16,
+    pub output_device: String,
+    pub match_source_format: bool,
+    pub output_format: Option<output_mode::TrackFormat>, // 按目标格式打开成功时输出流的格式；否则为 None
+    pub output_rate: output_mode::OutputRate,
+    pub max_output_rate: Option<u32>,
+    pub output_sample_rate: Option<u32>, // 输出流的实际采样率
+    pub resampling: bool, // 当前曲目的采样率与输出流不同
+    pub phase: ManagerPhase,
+    pub room_preset: String, // 当前房间预设名；任一项偏离后为 "custom"
+    pub limiter_enabled: bool,
+    pub limiter_reduction_db: f32, // 最终限幅器当前的增益衰减，供界面显示压限指示
+    pub preamp_db: f32,
+    pub balance: f32,
+    pub bitperfect: bool,
+    pub downmix_lfe: bool,
+    pub channel_layout: dsp::ChannelLayout,
+    pub replaygain: replaygain::ReplayGainPrefs,
+    pub replaygain_db: f32, // 当前曲目实际应用的增益
+    pub auto_level: dsp::AutoLevel,
+    pub cache_strategy: pcm_spill::CacheStrategy, // 当前曲目 PCM 存在内存、临时文件还是边解边播
 }
 
 // 定义所有的异步指令小纸条
 pub enum AudioCommand {
+    AttachApp(AppHandle),
     Load(String, oneshot::Sender<Result<f64, String>>),
+    Preload(String, oneshot::Sender<Result<(), String>>),
     Play,
     Pause,
+    Stop,
     Seek(f64, oneshot::Sender<()>),
-    SetVolume(f32),
-    SetChannels(u16),
+    SeekRelative(f64, oneshot::Sender<f64>),
+    SetLoop(f64, f64, oneshot::Sender<Result<(), String>>),
+    ClearLoop,
+    SetVolume(f32, oneshot::Sender<Result<(), String>>),
+    SetMute(bool, oneshot::Sender<Result<(), String>>),
+    ToggleMute(oneshot::Sender<Result<bool, String>>),
+    SetVolumeCurve(dsp::VolumeCurve, oneshot::Sender<Result<(), String>>),
+    SetSleepGain(f32), // 睡眠定时的额外衰减 0–1，不改用户音量
+    SetFadeConfig(dsp::FadeConfig, oneshot::Sender<Result<(), String>>),
+    SetSkipSilence(silence::SkipSilence, oneshot::Sender<Result<(), String>>),
+    SetAutoTrim(bool),
+    SetRate(f32, oneshot::Sender<Result<(), String>>),
+    SetParametricEq(Vec<dsp::EqBand>, oneshot::Sender<Result<(), String>>),
+    SetBassBoost(dsp::BassBoost, oneshot::Sender<Result<(), String>>),
+    SetStereoWidth(f32, oneshot::Sender<Result<(), String>>),
+    SetPreamp(f32, oneshot::Sender<Result<(), String>>),
+    SetBalance(f32, oneshot::Sender<Result<(), String>>),
+    SetCrossfeed(dsp::Crossfeed, oneshot::Sender<Result<(), String>>),
+    SetLfeCrossover(dsp::LfeCrossover, oneshot::Sender<Result<(), String>>),
+    SetUpmixParams(dsp::UpmixParams, oneshot::Sender<Result<(), String>>),
+    SetOutputPrefs(endpoint::OutputPrefs),
+    SetLimiterEnabled(bool),
+    SetDownmixLfe(bool, oneshot::Sender<Result<(), String>>),
+    SetChannelLayout(dsp::ChannelLayout, oneshot::Sender<Result<(), String>>),
+    SetReplayGain(replaygain::ReplayGainPrefs, oneshot::Sender<Result<(), String>>),
+    SetAutoLevel(dsp::AutoLevel, oneshot::Sender<Result<(), String>>),
+    SetDither(dsp::Dither, oneshot::Sender<Result<(), String>>),
+    RestoreSnapshot(recovery::EngineSection, f64, oneshot::Sender<recovery::RecoveryReport>),
+    StartGenerator(galaxy::GeneratorRequest, oneshot::Sender<Result<(), String>>),
+    StartLoudnessPreview(String, String, oneshot::Sender<Result<String, String>>),
+    StopGenerator(oneshot::Sender<Result<(), String>>),
+    IdentifyChannels(oneshot::Sender<Result<Vec<galaxy::ChannelIdent>, String>>),
+    SetChannels(u16, oneshot::Sender<Result<(), String>>),
+    SetBitperfect(bool, oneshot::Sender<Result<(), String>>),
+    SetResampler(String, oneshot::Sender<Result<(), String>>),
     GetDevices(oneshot::Sender<Vec<String>>),
+    GetDevicesDetailed(oneshot::Sender<Result<Vec<devices::OutputDeviceInfo>, String>>),
     SetDevice(String, oneshot::Sender<Result<String, String>>),
+    SetMatchSourceFormat(bool, oneshot::Sender<Result<(), String>>),
+    SetOutputSampleRate(Option<u32>, oneshot::Sender<Result<(), String>>),
+    SetMaxOutputRate(Option<u32>, oneshot::Sender<Result<(), String>>),
+    RetryOutput(oneshot::Sender<Result<(), String>>),
+    ResetAudio(oneshot::Sender<Result<(), String>>),
     SwitchEngine(String, oneshot::Sender<Result<String, String>>),
-    GetCurrentEngine(oneshot::Sender<String>),
+    GetCurrentEngine(oneshot::Sender<EngineInfo>),
     CheckDeviceStatus(oneshot::Sender<Option<String>>),
     GetCurrentTime(oneshot::Sender<f64>),
+    GetState(oneshot::Sender<PlayerState>),
+    GetClipReport(oneshot::Sender<Option<analysis::ClipReport>>),
+    GetDeviceCapabilities(Option<String>, bool, oneshot::Sender<Result<device_caps::DeviceCapabilities, String>>),
+    GetDeviceProbeReport(oneshot::Sender<Vec<device_caps::DeviceCapabilities>>),
+    CaptureRoomPreset(String, oneshot::Sender<room::RoomPreset>),
+    ApplyRoomPreset(room::RoomPreset, oneshot::Sender<Result<(), String>>),
+    Shutdown(oneshot::Sender<Vec<&'static str>>),
 }
 
+// 后台首尾静音测量的回报：(载入代次, 路径, 修剪后的起止时间)
+type TrimResult = (u64, String, Option<(f64, f64)>);
+
 pub struct AudioManager {
     pub active_engine: Box<dyn AudioEngine>,
     _stream: Option<StreamHolder>, 
-    stream_handle: OutputStreamHandle,
+    stream_handle: Option<OutputStreamHandle>, // None 表示启动时没能打开任何输出设备
+    engine_choice: &'static str, // 无输出期间要求切换的引擎，拿到设备后按它建起
+    engine_fallback: bool, // 当前的 FFmpeg 引擎是替 Galaxy 临时顶上的，下一首载入前换回
     pub current_device_mode: String,
     pub last_resolved_default: String,
     pub current_volume: f32, // 新增：用于在引擎切换间隙暂存音量
+    pub muted: bool, // 引擎实际音量为 0，current_volume 保留静音前的值
+    pub current_volume_curve: dsp::VolumeCurve,
+    sleep_gain: f32,
+    pub current_fades: dsp::FadeConfig,
+    pub current_skip_silence: silence::SkipSilence,
+    pub current_auto_trim: bool,
+    current_trim: Option<(f64, f64)>,
+    trim_generation: u64, // 每次载入 / 停止递增，后台测出的首尾静音按它认领
+    pub current_channel_mode: u16,
+    pub current_duration: f64,
+    pub current_resampler: galaxy::ResamplerQuality,
+    pub current_rate: f32,
+    pub current_eq: Vec<dsp::EqBand>,
+    pub current_bass: dsp::BassBoost,
+    pub current_width: f32,
+    pub current_preamp: f32, // dB，独立于 current_volume
+    pub current_balance: f32,
+    pub current_bitperfect: bool,
+    pub current_downmix_lfe: bool,
+    pub current_channel_layout: dsp::ChannelLayout,
+    pub current_replaygain: replaygain::ReplayGainPrefs,
+    pub current_replaygain_db: f32,
+    pub current_auto_level: dsp::AutoLevel,
+    pub current_crossfeed: dsp::Crossfeed,
+    pub current_lfe: dsp::LfeCrossover,
+    pub current_upmix: dsp::UpmixParams,
+    pub current_limiter: bool,
+    pub current_dither: dsp::Dither,
+    track_end_notified: bool,
+    app: Option<AppHandle>,
+    phase: ManagerPhase,
+    device_caps: device_caps::CapabilityCache,
+    jobs: Arc<JobRegistry>,
+    active_room: Option<room::RoomPreset>,
+    generator: Option<GeneratorResume>, // Some 表示发生器正在占用主 Sink
+    output_prefs: endpoint::OutputPrefs,
+    output_pause: Option<endpoint::InactivePause>, // Some 表示因端点失活而暂停，等待其恢复
+    endpoint_seen_ok: Instant, // 最近一次确认端点仍然在线的时刻
+    lost_device: Option<String>, // 拔出后回退到默认设备前选中的设备，prefer_device 时等它回来
+    track_format: Option<output_mode::TrackFormat>, // 当前曲目的原始格式
+    target_format: Option<output_mode::TrackFormat>, // 最近一次打开设备时要求的格式；None 为设备默认配置
+    output_format: Option<output_mode::TrackFormat>, // 按目标格式打开成功时的实际格式
+    snapshot: Option<recovery::SnapshotWriter>,
+    errors_tx: Sender<PlaybackError>, // 交给新建的引擎
+    device_watch: hotplug::DeviceListWatch,
+    errors_rx: Receiver<PlaybackError>,
+    progress_tx: Sender<ProgressEvent>,
+    progress_rx: Receiver<ProgressEvent>,
+    trim_tx: Sender<TrimResult>, // 交给后台测量首尾静音的任务
+    trim_rx: Receiver<TrimResult>,
 }
 
 impl AudioManager {
-    pub fn start_actor() -> Sender<AudioCommand> {
+    pub fn start_actor(jobs: Arc<JobRegistry>) -> Sender<AudioCommand> {
         let (tx, rx) = mpsc::channel::<AudioCommand>();
         
         std::thread::spawn(move || {
-            let mut manager = AudioManager::new();
+            let mut manager = AudioManager::new(jobs);
+            // 过渡期间被顺延的非意图类指令，优先于通道中的新指令处理
+            let mut deferred: VecDeque<AudioCommand> = VecDeque::new();
             
-            while let Ok(cmd) = rx.recv() {
-                match cmd {
-                    AudioCommand::Load(path, reply) => { let _ = reply.send(manager.load(&path)); }
-                    AudioCommand::Play => manager.play(),
-                    AudioCommand::Pause => manager.pause(),
-                    AudioCommand::Seek(time, reply) => { manager.seek(time); let _ = reply.send(()); }
-                    AudioCommand::SetVolume(vol) => manager.set_volume(vol),
-                    AudioCommand::SetChannels(mode) => manager.set_channels(mode),
-                    AudioCommand::GetDevices(reply) => { let _ = reply.send(manager.get_audio_devices()); }
-                    AudioCommand::SetDevice(device, reply) => { let _ = reply.send(manager.set_audio_device(&device)); }
-                    AudioCommand::SwitchEngine(engine_id, reply) => { let _ = reply.send(manager.switch_engine(&engine_id)); }
-                    AudioCommand::GetCurrentEngine(reply) => { let _ = reply.send(manager.active_engine.name().to_string()); }
-                    AudioCommand::CheckDeviceStatus(reply) => { let _ = reply.send(manager.check_device_status()); }
-                    AudioCommand::GetCurrentTime(reply) => { let _ = reply.send(manager.active_engine.get_current_time()); }
+            let mut next_poll = Instant::now() + TRACK_END_POLL;
+            loop {
+                manager.write_snapshot_if_due();
+                manager.flush_playback_errors();
+                manager.flush_decode_progress();
+                manager.flush_trim_results();
+                // 按截止时刻巡检曲目是否播完，驱动后端队列自动续播；指令再密集也不会把巡检饿死
+                if Instant::now() >= next_poll {
+                    manager.poll_track_end();
+                    manager.poll_silence_skips();
+                    next_poll = Instant::now() + TRACK_END_POLL;
+                }
+                let cmd = match deferred.pop_front() {
+                    Some(cmd) => cmd,
+                    None => match rx.recv_timeout(next_poll.saturating_duration_since(Instant::now())) {
+                        Ok(cmd) => cmd,
+                        Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    },
+                };
+                // 指令执行中的 panic 不拖垮 actor：该指令的回执随栈展开被丢弃，调用方得到错误；随后重建输出与引擎
+                match panic::catch_unwind(AssertUnwindSafe(|| manager.dispatch(cmd, &rx, &mut deferred))) {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(_) => manager.recover_from_panic(),
                 }
             }
         });
@@ -78,82 +455,863 @@ impl AudioManager {
         tx
     }
 
-    pub fn new() -> Self {
+    // 单条指令内的 panic 由调用方截住；返回 false 表示 actor 应当退出
+    fn dispatch(&mut self, cmd: AudioCommand, rx: &Receiver<AudioCommand>, deferred: &mut VecDeque<AudioCommand>) -> bool {
+        match cmd {
+            AudioCommand::AttachApp(app) => self.attach_app(app),
+            AudioCommand::Load(path, reply) => {
+                let res = self.transition(ManagerPhase::Loading, rx, deferred, |m| m.load(&path));
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| self.current_duration));
+            }
+            AudioCommand::Preload(path, reply) => {
+                // 当前引擎原生解不了的曲目届时会改由 FFmpeg 载入，不必预读
+                let _ = reply.send(if self.decodes_natively(&path) { self.active_engine.preload(&path) } else { Ok(()) });
+            }
+            AudioCommand::Play => { let op = self.play(); self.emit_op(&op); }
+            AudioCommand::Pause => { let op = self.pause(); self.emit_op(&op); }
+            AudioCommand::Stop => { let op = self.stop(); self.emit_op(&op); }
+            AudioCommand::Seek(time, reply) => { let op = self.seek(time); self.emit_op(&op); let _ = reply.send(()); }
+            AudioCommand::SeekRelative(delta, reply) => {
+                let op = self.seek_relative(delta);
+                self.emit_op(&op);
+                let _ = reply.send(op.applied["time"].as_f64().unwrap_or(0.0));
+            }
+            AudioCommand::SetLoop(start, end, reply) => {
+                let res = self.set_loop(start, end);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::ClearLoop => { let op = self.clear_loop(); self.emit_op(&op); }
+            AudioCommand::SetVolume(vol, reply) => {
+                let res = self.set_volume(vol);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetMute(muted, reply) => {
+                let res = self.set_mute(muted);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::ToggleMute(reply) => {
+                let res = self.set_mute(!self.muted);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| self.muted));
+            }
+            AudioCommand::SetVolumeCurve(curve, reply) => {
+                let res = self.set_volume_curve(curve);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetSleepGain(gain) => self.set_sleep_gain(gain),
+            AudioCommand::SetAutoTrim(enabled) => { let op = self.set_auto_trim(enabled); self.emit_op(&op); }
+            AudioCommand::SetSkipSilence(prefs, reply) => {
+                let res = self.set_skip_silence(prefs);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetFadeConfig(fades, reply) => {
+                let res = self.set_fade_config(fades);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetRate(rate, reply) => {
+                let res = self.set_rate(rate);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetParametricEq(bands, reply) => {
+                let res = self.set_parametric_eq(bands);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetBassBoost(bass, reply) => {
+                let res = self.set_bass_boost(bass);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetStereoWidth(width, reply) => {
+                let res = self.set_stereo_width(width);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetPreamp(gain_db, reply) => {
+                let res = self.set_preamp(gain_db);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetBalance(balance, reply) => {
+                let res = self.set_balance(balance);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetCrossfeed(crossfeed, reply) => {
+                let res = self.set_crossfeed(crossfeed);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetLfeCrossover(crossover, reply) => {
+                let res = self.set_lfe_crossover(crossover);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetOutputPrefs(prefs) => self.output_prefs = prefs,
+            AudioCommand::RestoreSnapshot(section, position, reply) => {
+                let report = self.restore_engine_section(section, position);
+                let _ = reply.send(report);
+            }
+            AudioCommand::SetDither(dither, reply) => {
+                let res = self.set_dither(dither);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetLimiterEnabled(enabled) => { let op = self.set_limiter_enabled(enabled); self.emit_op(&op); }
+            AudioCommand::SetDownmixLfe(enabled, reply) => {
+                let res = self.set_downmix_lfe(enabled);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetChannelLayout(layout, reply) => {
+                let res = self.set_channel_layout(layout);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetAutoLevel(auto_level, reply) => {
+                let res = self.set_auto_level(auto_level);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetReplayGain(prefs, reply) => {
+                let res = self.set_replaygain(prefs);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetUpmixParams(params, reply) => {
+                let res = self.set_upmix_params(params);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::StartLoudnessPreview(path_a, path_b, reply) => {
+                let _ = reply.send(self.start_loudness_preview(path_a, path_b));
+            }
+            AudioCommand::StartGenerator(request, reply) => {
+                let res = self.start_generator(request);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::IdentifyChannels(reply) => {
+                let res = self.identify_channels();
+                if let Ok((op, _)) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|(_, plan)| plan));
+            }
+            AudioCommand::StopGenerator(reply) => {
+                let res = self.transition(ManagerPhase::Loading, rx, deferred, |m| m.stop_generator());
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetChannels(mode, reply) => {
+                let res = self.set_channels(mode);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetBitperfect(enabled, reply) => {
+                let res = self.set_bitperfect(enabled);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetResampler(id, reply) => {
+                let res = self.set_resampler(&id);
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::GetDevices(reply) => { let _ = reply.send(self.get_audio_devices()); }
+            AudioCommand::GetDevicesDetailed(reply) => {
+                let probe = device_caps::CpalProbe;
+                let _ = reply.send(devices::list(&rodio::cpal::default_host(), &self.current_device_mode, |id| self.device_caps.get(&probe, id)));
+            }
+            AudioCommand::SetDevice(device, reply) => {
+                let res = self.transition(ManagerPhase::SwitchingDevice, rx, deferred, |m| m.set_audio_device(&device));
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|op| format!("Switched to {}", op.applied["device"].as_str().unwrap_or("Default"))));
+            }
+            AudioCommand::SetMatchSourceFormat(enabled, reply) => {
+                let res = self.transition(ManagerPhase::SwitchingDevice, rx, deferred, |m| m.set_match_source_format(enabled));
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetOutputSampleRate(rate, reply) => {
+                let res = self.transition(ManagerPhase::SwitchingDevice, rx, deferred, |m| m.set_output_sample_rate(rate));
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SetMaxOutputRate(rate, reply) => {
+                let res = self.transition(ManagerPhase::SwitchingDevice, rx, deferred, |m| m.set_max_output_rate(rate));
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::RetryOutput(reply) => {
+                let res = self.transition(ManagerPhase::SwitchingDevice, rx, deferred, |m| m.retry_output());
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::ResetAudio(reply) => {
+                let res = self.transition(ManagerPhase::SwitchingEngine, rx, deferred, |m| m.reset_backend());
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::SwitchEngine(engine_id, reply) => {
+                let res = self.transition(ManagerPhase::SwitchingEngine, rx, deferred, |m| m.switch_engine(&engine_id));
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|op| op.applied["status"].as_str().unwrap_or_default().to_string()));
+            }
+            AudioCommand::GetCurrentEngine(reply) => { let _ = reply.send(self.engine_info()); }
+            AudioCommand::CheckDeviceStatus(reply) => { let _ = reply.send(self.check_device_status()); }
+            AudioCommand::GetCurrentTime(reply) => { let _ = reply.send(self.active_engine.get_current_time()); }
+            AudioCommand::GetState(reply) => { let _ = reply.send(self.get_state()); }
+            AudioCommand::GetClipReport(reply) => { let _ = reply.send(self.active_engine.clip_report()); }
+            AudioCommand::GetDeviceCapabilities(device, refresh, reply) => { let _ = reply.send(self.device_capabilities(device.as_deref(), refresh)); }
+            AudioCommand::GetDeviceProbeReport(reply) => { let _ = reply.send(self.device_caps.report()); }
+            AudioCommand::CaptureRoomPreset(name, reply) => {
+                let preset = self.room_snapshot(&name);
+                self.active_room = Some(preset.clone());
+                let _ = reply.send(preset);
+            }
+            AudioCommand::ApplyRoomPreset(preset, reply) => {
+                let res = self.transition(ManagerPhase::ApplyingRoomPreset, rx, deferred, |m| m.apply_room_preset(preset));
+                if let Ok(op) = &res { self.emit_op(op); }
+                let _ = reply.send(res.map(|_| ()));
+            }
+            AudioCommand::Shutdown(reply) => {
+                let _ = reply.send(self.shutdown());
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn new(jobs: Arc<JobRegistry>) -> Self {
         let host = rodio::cpal::default_host();
         let default_name = host.default_output_device()
             .and_then(|d| d.name().ok())
             .unwrap_or_else(|| "Unknown".to_string());
 
-        let (stream, stream_handle) = OutputStream::try_default().unwrap();
-        let default_engine = galaxy::GalaxyEngine::new(stream_handle.clone());
-        
+        // 没有声卡或驱动异常 (虚拟机、远程桌面常见) 时不崩溃，以占位引擎进入无输出状态
+        let (errors_tx, errors_rx) = mpsc::channel();
+        let (progress_tx, progress_rx) = mpsc::channel();
+        let (stream, stream_handle, default_engine): (_, _, Box<dyn AudioEngine>) = match OutputStream::try_default() {
+            Ok((stream, handle)) => {
+                note_stream_rate(host.default_output_device().as_ref());
+                let engine = galaxy::GalaxyEngine::new(handle.clone(), jobs.clone(), ErrorReporter::new(errors_tx.clone(), "galaxy"), ProgressReporter::new(progress_tx.clone(), "galaxy"));
+                (Some(StreamHolder(stream)), Some(handle), Box::new(engine))
+            }
+            Err(e) => {
+                eprintln!("[AUDIO] No output device available ({}). Starting without audio output.", e);
+                (None, None, Box::new(null_engine::NullEngine))
+            }
+        };
+        OUTPUT_READY.store(stream_handle.is_some(), Ordering::Release);
+        Self::assemble(jobs, default_engine, stream, stream_handle, default_name, (errors_tx, errors_rx), (progress_tx, progress_rx))
+    }
+
+    // 无输出的管理层：不碰声卡，直接以给定引擎组装，供测试驱动状态机
+    #[cfg(test)]
+    fn headless(engine: Box<dyn AudioEngine>) -> Self {
+        Self::assemble(JobRegistry::new(), engine, None, None, "Unknown".to_string(), mpsc::channel(), mpsc::channel())
+    }
+
+    fn assemble(
+        jobs: Arc<JobRegistry>,
+        engine: Box<dyn AudioEngine>,
+        stream: Option<StreamHolder>,
+        stream_handle: Option<OutputStreamHandle>,
+        default_name: String,
+        (errors_tx, errors_rx): (Sender<PlaybackError>, Receiver<PlaybackError>),
+        (progress_tx, progress_rx): (Sender<ProgressEvent>, Receiver<ProgressEvent>),
+    ) -> Self {
+        let (trim_tx, trim_rx) = mpsc::channel();
         Self {
-            active_engine: Box::new(default_engine),
-            _stream: Some(StreamHolder(stream)),
+            active_engine: engine,
+            _stream: stream,
             stream_handle,
+            engine_choice: "galaxy",
+            engine_fallback: false,
             current_device_mode: "Default".to_string(),
             last_resolved_default: default_name,
             current_volume: 0.8, // 新增：初始化默认音量为 80%
+            muted: false,
+            current_volume_curve: dsp::VolumeCurve::default(),
+            sleep_gain: 1.0,
+            current_fades: dsp::FadeConfig::default(),
+            current_skip_silence: silence::SkipSilence::default(),
+            current_auto_trim: false,
+            current_trim: None,
+            trim_generation: 0,
+            current_channel_mode: 2,
+            current_duration: 0.0,
+            current_resampler: galaxy::ResamplerQuality::HighQuality,
+            current_rate: 1.0,
+            current_eq: Vec::new(),
+            current_bass: dsp::BassBoost::default(),
+            current_width: 1.0,
+            current_preamp: 0.0,
+            current_balance: 0.0,
+            current_bitperfect: false,
+            current_downmix_lfe: false,
+            current_channel_layout: dsp::ChannelLayout::default(),
+            current_replaygain: replaygain::ReplayGainPrefs::default(),
+            current_replaygain_db: 0.0,
+            current_auto_level: dsp::AutoLevel::default(),
+            current_crossfeed: dsp::Crossfeed::default(),
+            current_lfe: dsp::LfeCrossover::default(),
+            current_upmix: dsp::UpmixParams::default(),
+            current_limiter: true,
+            current_dither: dsp::Dither::default(),
+            track_end_notified: false,
+            app: None,
+            phase: ManagerPhase::Idle,
+            device_caps: device_caps::CapabilityCache::default(),
+            jobs,
+            active_room: None,
+            generator: None,
+            output_prefs: endpoint::OutputPrefs::default(),
+            output_pause: None,
+            endpoint_seen_ok: Instant::now(),
+            lost_device: None,
+            track_format: None,
+            target_format: None,
+            output_format: None,
+            snapshot: None,
+            errors_tx,
+            errors_rx,
+            progress_tx,
+            progress_rx,
+            trim_tx,
+            trim_rx,
+            device_watch: hotplug::DeviceListWatch::default(),
+        }
+    }
+
+    // 退出前的有序拆除：先停引擎释放 Sink，再通知并限时回收全部后台线程
+    pub fn shutdown(&mut self) -> Vec<&'static str> {
+        self.active_engine.stop();
+        self._stream = None;
+        let stragglers = threads::shutdown_and_join(SHUTDOWN_JOIN_TIMEOUT);
+        pcm_spill::purge();
+        println!("[AUDIO] Shutdown complete ({} thread(s) still running).", stragglers.len());
+        stragglers
+    }
+
+    // 无输出期间报告拿到设备后将要建起的引擎
+    pub fn engine_id(&self) -> &'static str {
+        match self.active_engine.id() {
+            null_engine::ID => self.engine_choice,
+            id => id,
+        }
+    }
+
+    pub fn engine_info(&self) -> EngineInfo {
+        EngineInfo { id: self.engine_id(), display_name: self.active_engine.name().to_string(), capabilities: self.active_engine.capabilities() }
+    }
+
+    pub fn get_state(&self) -> PlayerState {
+        let is_playing = self.active_engine.is_playing();
+        let loaded_path = self.active_engine.current_path();
+        PlayerState {
+            engine_id: self.engine_id().to_string(),
+            output_ready: self.stream_handle.is_some(),
+            is_playing,
+            is_paused: loaded_path.is_some() && !is_playing,
+            loaded_path,
+            position: self.active_engine.get_current_time(),
+            duration: self.current_duration,
+            volume: self.current_volume,
+            muted: self.muted,
+            volume_curve: self.current_volume_curve,
+            fades: self.current_fades,
+            skip_silence: self.current_skip_silence,
+            auto_trim: self.current_auto_trim,
+            trim: self.current_trim,
+            channel_mode: self.current_channel_mode,
+            output_device: self.current_device_mode.clone(),
+            match_source_format: self.output_prefs.match_source_format,
+            output_format: self.output_format,
+            output_rate: self.output_prefs.sample_rate,
+            max_output_rate: self.output_prefs.max_output_rate,
+            output_sample_rate: stream_sample_rate(),
+            resampling: matches!((self.track_format, stream_sample_rate()), (Some(track), Some(rate)) if track.sample_rate != rate),
+            phase: self.phase,
+            room_preset: self.active_room.as_ref()
+                .filter(|p| p.matches(&self.room_snapshot(&p.name)))
+                .map(|p| p.name.clone())
+                .unwrap_or_else(|| "custom".to_string()),
+            limiter_enabled: self.current_limiter,
+            limiter_reduction_db: self.active_engine.limiter_reduction_db(),
+            preamp_db: self.current_preamp,
+            balance: self.current_balance,
+            bitperfect: self.current_bitperfect,
+            downmix_lfe: self.current_downmix_lfe,
+            channel_layout: self.current_channel_layout,
+            replaygain: self.current_replaygain,
+            replaygain_db: self.current_replaygain_db,
+            auto_level: self.current_auto_level,
+            cache_strategy: self.active_engine.cache_strategy(),
+        }
+    }
+
+    pub fn room_snapshot(&self, name: &str) -> room::RoomPreset {
+        room::RoomPreset {
+            name: name.to_string(),
+            device: self.current_device_mode.clone(),
+            channel_mode: self.current_channel_mode,
+            volume: self.current_volume,
+            dsp: room::DspSnapshot { eq: self.current_eq.clone(), bass: self.current_bass, stereo_width: self.current_width, preamp_db: self.current_preamp, balance: self.current_balance, crossfeed: self.current_crossfeed, lfe_crossover: self.current_lfe, upmix: self.current_upmix, dither: self.current_dither, downmix_lfe: self.current_downmix_lfe, auto_level: self.current_auto_level },
+        }
+    }
+
+    fn emit_room_progress(&self, name: &str, step: &'static str) {
+        let index = room::ROOM_STEPS.iter().position(|s| *s == step).unwrap_or(0);
+        if let Some(app) = &self.app {
+            let _ = events::ROOM_PRESET_PROGRESS.emit(app, &room::RoomProgress { name: name.to_string(), step, index, total: room::ROOM_STEPS.len() });
+        }
+    }
+
+    fn apply_dsp_snapshot(&mut self, dsp: &room::DspSnapshot) -> Result<(), String> {
+        self.set_parametric_eq(dsp.eq.clone())?;
+        self.set_bass_boost(dsp.bass)?;
+        self.set_stereo_width(dsp.stereo_width)?;
+        self.set_preamp(dsp.preamp_db)?;
+        self.set_balance(dsp.balance)?;
+        self.set_crossfeed(dsp.crossfeed)?;
+        self.set_lfe_crossover(dsp.lfe_crossover)?;
+        self.set_upmix_params(dsp.upmix)?;
+        self.set_dither(dsp.dither)?;
+        self.set_downmix_lfe(dsp.downmix_lfe)?;
+        self.set_auto_level(dsp.auto_level)?;
+        Ok(())
+    }
+
+    fn apply_room_steps(&mut self, preset: &room::RoomPreset) -> Result<(), String> {
+        self.emit_room_progress(&preset.name, "device");
+        if preset.device != self.current_device_mode { self.set_audio_device(&preset.device)?; }
+        self.emit_room_progress(&preset.name, "channel_mode");
+        self.set_channels(preset.channel_mode)?;
+        self.emit_room_progress(&preset.name, "dsp");
+        self.apply_dsp_snapshot(&preset.dsp)
+    }
+
+    // 任一步失败都回滚到应用前的快照，并把音量淡回原值，不留下半套配置
+    pub fn apply_room_preset(&mut self, preset: room::RoomPreset) -> Result<OpResult, String> {
+        let previous = self.room_snapshot("");
+        self.emit_room_progress(&preset.name, "fade_out");
+        if self.active_engine.is_playing() {
+            let _ = self.active_engine.set_volume(0.0);
+            threads::sleep(room::ROOM_FADE);
+        }
+
+        if let Err(e) = self.apply_room_steps(&preset) {
+            if previous.device != self.current_device_mode { let _ = self.set_audio_device(&previous.device); }
+            let _ = self.set_channels(previous.channel_mode);
+            let _ = self.apply_dsp_snapshot(&previous.dsp);
+            let _ = self.set_volume(previous.volume);
+            return Err(e);
+        }
+
+        self.emit_room_progress(&preset.name, "fade_in");
+        self.set_volume(preset.volume)?;
+        let applied = self.room_snapshot(&preset.name);
+        self.active_room = Some(applied.clone());
+        Ok(OpResult::new("apply_room_preset", json!({ "name": preset.name }), json!(applied),
+            vec!["output_device", "channel_mode", "volume", "dsp", "room_preset"]))
+    }
+
+    fn poll_track_end(&mut self) {
+        if self.track_end_notified || self.current_duration <= 0.0 || !self.active_engine.is_playing() { return; }
+        let end = self.current_trim.map(|(_, end)| end).unwrap_or(self.current_duration);
+        if self.active_engine.get_current_time() >= end {
+            self.track_end_notified = true;
+            if let Some(app) = &self.app { let _ = events::TRACK_ENDED.emit(app, &()); }
         }
     }
 
+    fn poll_silence_skips(&mut self) {
+        let skipped = self.active_engine.take_skipped_silence();
+        if skipped <= 0.0 { return; }
+        if let Some(app) = &self.app { let _ = events::SILENCE_SKIPPED.emit(app, &skipped); }
+    }
+
+    // 引擎在各自线程上报的错误，在 actor 线程统一发出
+    fn flush_playback_errors(&self) {
+        while let Ok(error) = self.errors_rx.try_recv() {
+            if let Some(app) = &self.app { let _ = events::PLAYBACK_ERROR.emit(app, &error); }
+        }
+    }
+
+    fn flush_decode_progress(&mut self) {
+        while let Ok(progress) = self.progress_rx.try_recv() {
+            if progress.is_stale() { continue; }
+            // 解码得出的精确时长接替载入时的估算，seek 上限与播完判定随之更新
+            if let ProgressEvent::Duration(update) = &progress { self.current_duration = update.duration; }
+            let Some(app) = &self.app else { continue };
+            let _ = match &progress {
+                ProgressEvent::Decode(event, decode) => event.emit(app, decode),
+                ProgressEvent::Cache(event, cache) => event.emit(app, cache),
+                ProgressEvent::Duration(update) => events::DURATION_UPDATED.emit(app, update),
+            };
+        }
+    }
+
+    fn emit_op(&self, op: &OpResult) {
+        recovery::mark_dirty();
+        if let Some(app) = &self.app {
+            let _ = events::PLAYER_OP_RESULT.emit(app, &OpResult { engine: self.engine_id(), ..op.clone() });
+        }
+    }
+
+    fn attach_app(&mut self, app: AppHandle) {
+        if let Ok(dir) = app.path().app_local_data_dir() {
+            pcm_spill::init_dir(dir.join("pcm_spill"));
+            self.snapshot = Some(recovery::SnapshotWriter::new(dir));
+        }
+        self.app = Some(app);
+    }
+
+    fn engine_section(&self) -> recovery::EngineSection {
+        recovery::EngineSection {
+            track: self.active_engine.current_path(),
+            engine_id: self.engine_id().to_string(),
+            device: self.current_device_mode.clone(),
+            channel_mode: self.current_channel_mode,
+            volume: self.current_volume,
+            resampler: self.current_resampler.id().to_string(),
+            rate: self.current_rate,
+            limiter_enabled: self.current_limiter,
+            dsp: self.room_snapshot("").dsp,
+        }
+    }
+
+    // 发生器占用主 Sink 时不落盘，快照始终指向真实曲目
+    fn write_snapshot_if_due(&mut self) {
+        if self.generator.is_some() { return; }
+        let playing = self.active_engine.is_playing();
+        let Some(refresh) = self.snapshot.as_ref().and_then(|w| w.due(playing)) else { return };
+        let section = refresh.then(|| self.engine_section());
+        let position = self.active_engine.get_current_time();
+        if let Some(writer) = &mut self.snapshot {
+            if let Some(section) = &section { writer.set_engine(section); }
+            writer.write(position, playing);
+        }
+    }
+
+    // 每个字段独立恢复，任何一项失败只记入 skipped，不影响其余字段
+    pub fn restore_engine_section(&mut self, section: recovery::EngineSection, position: f64) -> recovery::RecoveryReport {
+        let mut report = recovery::RecoveryReport { position, ..Default::default() };
+        if section.engine_id != self.engine_id() { report.record("engine", self.switch_engine(&section.engine_id)); }
+        if section.device != self.current_device_mode {
+            let previous = self.current_device_mode.clone();
+            let res = self.set_audio_device(&section.device).map_err(|e| format!("DEVICE_UNAVAILABLE: {}", e));
+            // 打不开时原输出流并未拆掉，只把设备模式改回去，不必重开
+            if !report.record("device", res) { self.current_device_mode = previous; }
+        }
+        report.record("channel_mode", self.set_channels(section.channel_mode));
+        report.record("volume", self.set_volume(section.volume));
+        report.record("resampler", self.set_resampler(&section.resampler));
+        report.record("rate", self.set_rate(section.rate));
+        report.record("parametric_eq", self.set_parametric_eq(section.dsp.eq));
+        report.record("bass_boost", self.set_bass_boost(section.dsp.bass));
+        report.record("stereo_width", self.set_stereo_width(section.dsp.stereo_width));
+        report.record("preamp", self.set_preamp(section.dsp.preamp_db));
+        report.record("balance", self.set_balance(section.dsp.balance));
+        report.record("crossfeed", self.set_crossfeed(section.dsp.crossfeed));
+        report.record("lfe_crossover", self.set_lfe_crossover(section.dsp.lfe_crossover));
+        report.record("upmix_params", self.set_upmix_params(section.dsp.upmix));
+        report.record("dither", self.set_dither(section.dsp.dither));
+        report.record("downmix_lfe", self.set_downmix_lfe(section.dsp.downmix_lfe));
+        report.record("auto_level", self.set_auto_level(section.dsp.auto_level));
+        self.set_limiter_enabled(section.limiter_enabled);
+        report.restored.push("limiter_enabled");
+
+        if let Some(path) = section.track {
+            let res = if std::path::Path::new(&path).exists() { self.load(&path) } else { Err("TRACK_MISSING".to_string()) };
+            if report.record("track", res) {
+                let end = if self.current_duration > 0.0 { (self.current_duration - 1.0).max(0.0) } else { f64::MAX };
+                self.seek(position.min(end));
+                report.track = Some(path);
+            }
+        }
+        recovery::mark_dirty();
+        report
+    }
+
+    fn set_phase(&mut self, phase: ManagerPhase) {
+        self.phase = phase;
+        if let Some(app) = &self.app { let _ = events::PLAYER_PHASE.emit(app, &phase); }
+    }
+
+    // 执行一次过渡：期间到达的指令被收拢为意图，过渡完成后统一作用于（可能已更换的）引擎
+    fn transition(
+        &mut self,
+        phase: ManagerPhase,
+        rx: &Receiver<AudioCommand>,
+        deferred: &mut VecDeque<AudioCommand>,
+        step: impl FnOnce(&mut Self) -> Result<OpResult, String>,
+    ) -> Result<OpResult, String> {
+        self.set_phase(phase);
+        let mut res = step(self);
+        if let Ok(op) = &mut res { op.phase = phase; }
+        let pending = self.absorb_pending(rx, deferred);
+        self.set_phase(ManagerPhase::Idle);
+        self.apply_intents(pending, res.is_ok());
+        res
+    }
+
+    fn absorb_pending(&self, rx: &Receiver<AudioCommand>, deferred: &mut VecDeque<AudioCommand>) -> PendingIntents {
+        let mut pending = PendingIntents::default();
+        while let Ok(cmd) = rx.try_recv() {
+            match cmd {
+                AudioCommand::SetVolume(vol, reply) => { pending.volume = Some(vol); pending.volume_replies.push(reply); }
+                AudioCommand::Play => pending.playing = Some(true),
+                AudioCommand::Pause => pending.playing = Some(false),
+                AudioCommand::Seek(time, reply) => { pending.seek = Some(time); pending.seek_replies.push(reply); }
+                // 只读查询立即作答，让前端能观察到过渡阶段
+                AudioCommand::GetState(reply) => { let _ = reply.send(self.get_state()); }
+                AudioCommand::GetCurrentTime(reply) => { let _ = reply.send(self.active_engine.get_current_time()); }
+                cmd @ (AudioCommand::Load(..) | AudioCommand::Stop) => {
+                    pending.cancelled = true;
+                    deferred.push_back(cmd);
+                    break;
+                }
+                other => deferred.push_back(other),
+            }
+        }
+        pending
+    }
+
+    fn apply_intents(&mut self, pending: PendingIntents, succeeded: bool) {
+        if let Some(vol) = pending.volume {
+            let res = self.set_volume(vol);
+            if let Ok(op) = &res { self.emit_op(op); }
+            for reply in pending.volume_replies { let _ = reply.send(res.clone().map(|_| ())); }
+        }
+        if succeeded && !pending.cancelled {
+            if let Some(time) = pending.seek { let op = self.seek(time); self.emit_op(&op); }
+            match pending.playing {
+                Some(true) => { let op = self.play(); self.emit_op(&op); }
+                Some(false) => { let op = self.pause(); self.emit_op(&op); }
+                None => {}
+            }
+        }
+        for reply in pending.seek_replies { let _ = reply.send(()); }
+    }
+
     pub fn check_device_status(&mut self) -> Option<String> {
         let host = rodio::cpal::default_host();
+        let names = devices::ids(&host);
+        if let Some(changed) = names.clone().and_then(|names| self.device_watch.observe(names)) {
+            println!("[AUDIO] Output devices changed (+{:?} -{:?}).", changed.added, changed.removed);
+            if let Some(app) = &self.app { let _ = events::AUDIO_DEVICES_CHANGED.emit(app, &changed); }
+        }
+        // 无输出状态下只在出现默认设备时提请接入，避免每轮巡检都徒劳地重开
+        if self.stream_handle.is_none() {
+            return host.default_output_device().map(|_| "Default".to_string());
+        }
         let mut device_exists = false;
         
-        if let Ok(mut devices) = host.output_devices() {
+        if let Some(names) = names {
             let target_name = if self.current_device_mode == "Default" {
-                &self.last_resolved_default
+                self.last_resolved_default.clone()
             } else {
-                &self.current_device_mode
+                self.current_device_mode.clone()
             };
-            device_exists = devices.any(|d| d.name().ok().as_ref() == Some(target_name));
+            device_exists = names.contains(&target_name);
+            // 被拔掉的设备重新出现且用户偏好它时，走与其它迁移相同的流程切回去
+            let returned = self.lost_device.clone().filter(|lost| self.current_device_mode == "Default" && names.contains(lost));
+            if self.device_caps.sync_device_list(names) { println!("[AUDIO] Device list changed. Capability cache invalidated."); }
+            // 明确选中的设备从列表里消失：不守着等它，带着位置与播放状态回退到默认设备
+            if !device_exists && self.current_device_mode != "Default" {
+                self.fall_back_to_default();
+                return None;
+            }
+            self.watch_output_endpoint(&target_name, device_exists);
+            if let Some(lost) = returned.filter(|_| self.output_prefs.prefer_device && self.output_pause.is_none()) {
+                println!("[AUDIO] Preferred device '{}' is back. Switching back.", lost);
+                return Some(lost);
+            }
         }
+
+        // 因端点失活暂停期间守着原设备等它回来，不往其它设备迁移
+        if self.output_pause.is_some() { return None; }
         
         if !device_exists {
             return Some("Default".to_string());
         }
         
-        if self.current_device_mode == "Default" {
-            if let Some(current_default) = host.default_output_device().and_then(|d| d.name().ok()) {
-                if current_default != self.last_resolved_default {
-                    return Some("Default".to_string());
-                }
-            }
-        }
+        // 系统默认设备变了：在 actor 内直接迁移输出流，引擎保留位置与播放状态，前端无需暂停再续播
+        self.check_and_recover_default_device();
         
         None
     }
 
+    // 端点能报告状态时以其为准，否则退回到设备是否仍在枚举列表中
+    fn watch_output_endpoint(&mut self, device: &str, listed: bool) {
+        let active = endpoint::endpoint_active(device).unwrap_or(listed);
+        match &self.output_pause {
+            None if active => self.endpoint_seen_ok = Instant::now(),
+            None if self.active_engine.is_playing() => self.pause_for_inactive_output(device),
+            Some(pause) if active && pause.device == device => self.resume_after_output_returns(),
+            _ => {}
+        }
+    }
+
+    // 书签取最后一次确认端点在线时的位置，而不是之后对着死端点空转到的位置
+    fn pause_for_inactive_output(&mut self, device: &str) {
+        let silent_for = self.endpoint_seen_ok.elapsed().as_secs_f64() * self.current_rate as f64;
+        let position = (self.active_engine.get_current_time() - silent_for).max(0.0);
+        self.active_engine.pause();
+        self.active_engine.seek(position);
+        let pause = endpoint::InactivePause { device: device.to_string(), path: self.active_engine.current_path(), position };
+        println!("[AUDIO] Output endpoint '{}' became inactive. Paused at {:.2}s.", device, position);
+        if let Some(app) = &self.app { let _ = events::OUTPUT_INACTIVE_PAUSED.emit(app, &pause); }
+        self.output_pause = Some(pause);
+    }
+
+    // 书签与端点失活暂停同样取最后确认在线的位置；回退后按原先的播放状态继续
+    fn fall_back_to_default(&mut self) {
+        let lost = self.current_device_mode.clone();
+        let silent_for = self.endpoint_seen_ok.elapsed().as_secs_f64() * self.current_rate as f64;
+        let position = (self.active_engine.get_current_time() - silent_for).max(0.0);
+        let was_playing = self.active_engine.is_playing();
+        self.active_engine.pause();
+        self.active_engine.seek(position);
+        self.output_pause = None;
+        match self.set_audio_device("Default") {
+            Ok(op) => self.emit_op(&op),
+            Err(e) => println!("[AUDIO] Failed to fall back to the default device: {}", e),
+        }
+        if was_playing { self.active_engine.play(); }
+        self.endpoint_seen_ok = Instant::now();
+        println!("[AUDIO] Output device '{}' disappeared. Fell back to '{}' at {:.2}s.", lost, self.last_resolved_default, position);
+        if let Some(app) = &self.app {
+            let _ = events::DEVICE_LOST.emit(app, &hotplug::DeviceLost { device: lost.clone(), fallback: self.last_resolved_default.clone(), position, resumed: was_playing });
+        }
+        self.lost_device = Some(lost);
+    }
+
+    // 原端点的流已随设备失效，先在其上重建输出流再决定是否续播
+    fn resume_after_output_returns(&mut self) {
+        let Some(pause) = self.output_pause.take() else { return };
+        self.endpoint_seen_ok = Instant::now();
+        let mode = self.current_device_mode.clone();
+        if let Err(e) = self.set_audio_device(&mode) { println!("[AUDIO] Failed to reopen '{}' after it returned: {}", pause.device, e); }
+        let resumed = self.output_prefs.auto_resume && self.active_engine.current_path() == pause.path;
+        if resumed { self.active_engine.play(); }
+        println!("[AUDIO] Output endpoint '{}' is back (resumed: {}).", pause.device, resumed);
+        if let Some(app) = &self.app { let _ = events::OUTPUT_REACTIVATED.emit(app, &endpoint::OutputReactivated { device: pause.device, position: pause.position, resumed }); }
+    }
+
+    // 关闭 follow_default 时钉在当初打开的设备上，直到它消失才迁移
     pub fn check_and_recover_default_device(&mut self) {
-        if self.current_device_mode == "Default" {
+        if self.current_device_mode == "Default" && self.stream_handle.is_some() && self.output_prefs.follow_default {
             let host = rodio::cpal::default_host();
             if let Some(current_default) = host.default_output_device().and_then(|d| d.name().ok()) {
                 if current_default != self.last_resolved_default {
                     println!("[AUDIO] Default hardware changed: {} -> {}. Auto-recovering...", self.last_resolved_default, current_default);
-                    self.last_resolved_default = current_default.clone();
+                    let previous = std::mem::replace(&mut self.last_resolved_default, current_default.clone());
                     
-                    if let Ok((new_stream, new_handle)) = OutputStream::try_default() {
-                        self.active_engine.update_output_stream(new_handle.clone());
-                        self._stream = Some(StreamHolder(new_stream));
-                        self.stream_handle = new_handle;
+                    if let Ok((new_stream, new_handle)) = self.open_stream(None) {
+                        self.install_stream(new_stream, new_handle);
                         println!("[AUDIO] Stream successfully migrated to new default device.");
+                        if let Some(app) = &self.app { let _ = events::OUTPUT_DEVICE_SWITCHED.emit(app, &hotplug::DeviceSwitched { from: previous, to: current_default.clone() }); }
+                        self.emit_op(&OpResult::new("set_device", json!({ "device": "Default" }), json!({ "device": "Default", "resolved": current_default }), vec!["output_device"]));
                     }
                 }
             }
         }
     }
 
+    // 声卡能力统一出口："Default" 解析为当前默认设备；refresh 强制重新探测
+    pub fn device_capabilities(&mut self, device: Option<&str>, refresh: bool) -> Result<device_caps::DeviceCapabilities, String> {
+        let name = match device.unwrap_or(&self.current_device_mode) {
+            "Default" => self.last_resolved_default.clone(),
+            other => other.to_string(),
+        };
+        let probe = device_caps::CpalProbe;
+        if refresh { self.device_caps.refresh(&probe, &name) } else { self.device_caps.get(&probe, &name) }
+    }
+
+    // 返回设备 id；名字不重复时 id 就是名字
     pub fn get_audio_devices(&self) -> Vec<String> {
-        let host = rodio::cpal::default_host();
-        match host.output_devices() {
-            Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
-            Err(_) => vec!["Default Device".to_string()],
+        match devices::ids(&rodio::cpal::default_host()) {
+            Some(ids) => ids,
+            None if self.stream_handle.is_none() => Vec::new(),
+            None => vec!["Default Device".to_string()],
+        }
+    }
+
+    // 换上新的输出流；此前处于无输出状态时顺带按所选引擎建起真正的引擎并注入全部设置
+    fn install_stream(&mut self, stream: OutputStream, handle: OutputStreamHandle) {
+        let had_output = self.stream_handle.is_some();
+        self.active_engine.update_output_stream(handle.clone());
+        self._stream = Some(StreamHolder(stream));
+        self.stream_handle = Some(handle);
+        OUTPUT_READY.store(true, Ordering::Release);
+        if !had_output {
+            println!("[AUDIO] Output device acquired. Starting {} engine.", self.engine_choice);
+            if let Err(e) = self.switch_engine(self.engine_choice) { eprintln!("[AUDIO] Failed to start engine after acquiring output: {}", e); }
         }
     }
 
-    pub fn set_audio_device(&mut self, device_name: &str) -> Result<String, String> {
+    // 最后手段：丢掉输出流与引擎 (连同其中可能中毒的锁和缓存)，按当前设置重新打开设备、建起同一引擎；曲目需重新载入
+    pub fn reset_backend(&mut self) -> Result<OpResult, String> {
+        if self.stream_handle.is_some() { self.engine_choice = self.engine_id(); }
+        // 旧引擎可能正处于不一致状态，停它时再 panic 也不影响重建
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| self.active_engine.stop()));
+        self.active_engine = Box::new(null_engine::NullEngine);
+        self._stream = None;
+        self.stream_handle = None;
+        OUTPUT_READY.store(false, Ordering::Release);
+        dsp_registry::clear_chain();
+        self.generator = None;
+        self.output_pause = None;
+        self.current_trim = None;
+        self.trim_generation += 1;
+        self.current_duration = 0.0;
+        self.track_end_notified = false;
+        let mode = self.current_device_mode.clone();
+        if let Err(e) = self.set_audio_device(&mode) {
+            if mode == "Default" { return Err(format!("{}: {}", NO_AUDIO_DEVICE, e)); }
+            self.set_audio_device("Default").map_err(|e| format!("{}: {}", NO_AUDIO_DEVICE, e))?;
+        }
+        Ok(OpResult::new("reset_audio", json!({}), json!({ "engine_id": self.engine_id(), "device": self.current_device_mode }),
+            vec!["engine_id", "output_ready", "output_device", "loaded_path", "is_playing", "position", "duration", "trim"]))
+    }
+
+    fn recover_from_panic(&mut self) {
+        eprintln!("[AUDIO] A command panicked. Rebuilding the output stream and engine.");
+        let engine = self.engine_id();
+        let _ = self.errors_tx.send(PlaybackError { code: PlaybackErrorCode::EnginePanicked, message: "audio backend rebuilt after a panic".to_string(), path: None, engine });
+        self.set_phase(ManagerPhase::Idle);
+        match panic::catch_unwind(AssertUnwindSafe(|| self.reset_backend())) {
+            Ok(Ok(op)) => self.emit_op(&op),
+            Ok(Err(e)) => eprintln!("[AUDIO] Rebuild after panic failed: {}", e),
+            Err(_) => eprintln!("[AUDIO] Rebuild after panic panicked again. Staying in no-output state."),
+        }
+    }
+
+    // 无输出状态下重新尝试打开设备 (当前选定的设备或默认设备)
+    pub fn retry_output(&mut self) -> Result<OpResult, String> {
+        if self.stream_handle.is_none() {
+            let mode = self.current_device_mode.clone();
+            self.set_audio_device(&mode).map_err(|e| format!("{}: {}", NO_AUDIO_DEVICE, e))?;
+        }
+        Ok(OpResult::new("retry_output", json!({}), json!({ "output_ready": true, "engine_id": self.engine_id(), "device": self.current_device_mode }),
+            vec!["output_ready", "engine_id", "output_device"]))
+    }
+
+    pub fn set_audio_device(&mut self, device_name: &str) -> Result<OpResult, String> {
+        let previous_mode = self.current_device_mode.clone();
+        let previous_default = self.last_resolved_default.clone();
         self.current_device_mode = device_name.to_string();
+        self.lost_device = None; // 无论是用户手动选择还是切回原设备，都不再等它
 
         if device_name == "Default" {
             let host = rodio::cpal::default_host();
@@ -161,24 +1319,20 @@ impl AudioManager {
                 .and_then(|d| d.name().ok())
                 .unwrap_or_else(|| "Unknown".to_string());
 
-            let (stream, stream_handle) = OutputStream::try_default().map_err(|e| e.to_string())?;
-            self.active_engine.update_output_stream(stream_handle.clone());
-            self._stream = Some(StreamHolder(stream));
-            self.stream_handle = stream_handle;
-            return Ok("Switched to Default".to_string());
+            let (stream, stream_handle) = self.open_stream(None)?;
+            self.install_stream(stream, stream_handle);
+            let delta = if previous_mode != "Default" || previous_default != self.last_resolved_default { vec!["output_device"] } else { vec![] };
+            return Ok(OpResult::new("set_device", json!({ "device": device_name }), json!({ "device": "Default", "resolved": self.last_resolved_default }), delta));
         }
 
-        let host = rodio::cpal::default_host();
-        let device = host.output_devices().map_err(|e| e.to_string())?
-            .find(|d| d.name().map(|n| n == device_name).unwrap_or(false));
+        let device = devices::find(&rodio::cpal::default_host(), device_name)?;
 
         if let Some(device) = device {
-            match OutputStream::try_from_device(&device) {
+            match self.open_stream(Some(&device)) {
                 Ok((new_stream, new_handle)) => {
-                    self.active_engine.update_output_stream(new_handle.clone());
-                    self._stream = Some(StreamHolder(new_stream)); 
-                    self.stream_handle = new_handle;
-                    Ok(format!("Switched to {}", device_name))
+                    self.install_stream(new_stream, new_handle);
+                    let delta = if previous_mode != device_name { vec!["output_device"] } else { vec![] };
+                    Ok(OpResult::new("set_device", json!({ "device": device_name }), json!({ "device": device_name, "resolved": device_name }), delta))
                 },
                 Err(e) => Err(format!("Failed to init device: {}", e)),
             }
@@ -187,44 +1341,926 @@ impl AudioManager {
         }
     }
 
-    pub fn switch_engine(&mut self, engine_id: &str) -> Result<String, String> {
-        self.check_and_recover_default_device();
-        let res = match engine_id {
-            "galaxy" => {
-                self.active_engine = Box::new(galaxy::GalaxyEngine::new(self.stream_handle.clone()));
-                Ok("ENGINE_GALAXY_READY".to_string())
-            }
-            "ffmpeg" => {
-                self.active_engine = Box::new(ffmpeg::FFmpegEngine::new(self.stream_handle.clone()));
-                Ok("ENGINE_FFMPEG_READY".to_string())
+    // 打开输出流 (None 为系统默认设备)：有目标格式时先按它打开，设备不接受则退回默认配置；登记实际采样率
+    fn open_stream(&mut self, device: Option<&rodio::cpal::Device>) -> Result<(OutputStream, OutputStreamHandle), String> {
+        let default_device = rodio::cpal::default_host().default_output_device();
+        let target = device.or(default_device.as_ref());
+        self.output_format = None;
+        if let (Some(target), Some(format)) = (target, self.target_format) {
+            match output_mode::open(target, format) {
+                Ok(opened) => {
+                    println!("[AUDIO] Output opened at {}Hz / {:?} bit.", format.sample_rate, format.bits);
+                    self.output_format = Some(format);
+                    STREAM_RATE.store(format.sample_rate, Ordering::Release);
+                    return Ok(opened);
+                }
+                Err(e) => {
+                    println!("[AUDIO] Device refused {}Hz ({}). Falling back to its shared default.", format.sample_rate, e);
+                    if let Some(app) = &self.app { let _ = events::OUTPUT_MODE_FALLBACK.emit(app, &output_mode::ModeFallback { device: self.current_device_mode.clone(), format, reason: e }); }
+                }
             }
-            _ => Err("UNKNOWN_ENGINE".to_string())
+        }
+        let opened = match device {
+            Some(device) => OutputStream::try_from_device(device),
+            None => OutputStream::try_default(),
+        }.map_err(|e| e.to_string())?;
+        note_stream_rate(target);
+        Ok(opened)
+    }
+
+    // 跟随曲目 (match_source_format 或 "匹配音源" 采样率) 时取曲目格式并受 max_output_rate 约束，指定采样率时取该值，否则交给设备默认配置
+    fn desired_format(&self) -> Option<output_mode::TrackFormat> {
+        let follow_track = || self.track_format.map(|t| output_mode::TrackFormat { sample_rate: output_mode::cap_rate(t.sample_rate, self.output_prefs.max_output_rate), ..t });
+        match self.output_prefs.sample_rate {
+            output_mode::OutputRate::Fixed(sample_rate) => Some(output_mode::TrackFormat { sample_rate, bits: self.track_format.and_then(|t| t.bits) }),
+            output_mode::OutputRate::Source => follow_track(),
+            output_mode::OutputRate::Device if self.output_prefs.match_source_format => follow_track(),
+            output_mode::OutputRate::Device => None,
+        }
+    }
+
+    // 要求的输出格式随新曲目变化时，在载入前按新格式重开设备
+    fn match_output_format(&mut self, path: &str) {
+        self.track_format = output_mode::track_format(path);
+        let desired = self.desired_format();
+        if desired == self.target_format || self.stream_handle.is_none() { return; }
+        self.target_format = desired;
+        let mode = self.current_device_mode.clone();
+        if let Err(e) = self.set_audio_device(&mode) { println!("[AUDIO] Failed to reopen '{}' for the new track format: {}", mode, e); }
+    }
+
+    // 按新的输出设置重开设备；整曲缓存是按旧输出采样率解码的，当前曲目原位重新载入
+    fn reopen_output(&mut self) -> Result<(), String> {
+        self.target_format = self.desired_format();
+        if self.stream_handle.is_none() { return Ok(()); }
+        let mode = self.current_device_mode.clone();
+        self.set_audio_device(&mode)?;
+        let Some(path) = self.active_engine.current_path() else { return Ok(()) };
+        let (position, was_playing) = (self.active_engine.get_current_time(), self.active_engine.is_playing());
+        self.active_engine.load(&path)?;
+        self.active_engine.seek(position);
+        if was_playing { self.active_engine.play(); }
+        Ok(())
+    }
+
+    pub fn set_match_source_format(&mut self, enabled: bool) -> Result<OpResult, String> {
+        let previous = self.output_prefs.match_source_format;
+        self.output_prefs.match_source_format = enabled;
+        if let Err(e) = self.reopen_output() {
+            self.output_prefs.match_source_format = previous;
+            return Err(e);
+        }
+        let delta = if previous != enabled { vec!["match_source_format", "output_format", "output_sample_rate", "resampling"] } else { vec![] };
+        Ok(OpResult::new("set_match_source_format", json!({ "enabled": enabled }), json!({ "enabled": enabled, "format": self.output_format }), delta))
+    }
+
+    // None 为匹配音源，每首按其采样率打开设备；指定值时设备固定在该采样率，由引擎的 Sinc 重采样器转换
+    pub fn set_output_sample_rate(&mut self, rate: Option<u32>) -> Result<OpResult, String> {
+        let policy = match rate {
+            None => output_mode::OutputRate::Source,
+            Some(rate) if (output_mode::RATE_RANGE.0..=output_mode::RATE_RANGE.1).contains(&rate) => output_mode::OutputRate::Fixed(rate),
+            Some(_) => return Err(format!("INVALID_SAMPLE_RATE: must be within {}–{}", output_mode::RATE_RANGE.0, output_mode::RATE_RANGE.1)),
         };
+        let previous = self.output_prefs.sample_rate;
+        self.output_prefs.sample_rate = policy;
+        if let Err(e) = self.reopen_output() {
+            self.output_prefs.sample_rate = previous;
+            return Err(e);
+        }
+        let delta = if previous != policy { vec!["output_rate", "output_format", "output_sample_rate", "resampling"] } else { vec![] };
+        Ok(OpResult::new("set_output_sample_rate", json!({ "rate": rate }), json!({ "rate": policy, "output_sample_rate": stream_sample_rate() }), delta))
+    }
 
-        // 核心增量：给新引擎注入旧音量，防止切换后归零或震耳欲聋
-        if res.is_ok() {
-            self.active_engine.set_volume(self.current_volume);
+    // 跟随曲目采样率时的上限，给打不开高采样率流的设备用；None 为不设上限
+    pub fn set_max_output_rate(&mut self, rate: Option<u32>) -> Result<OpResult, String> {
+        if rate.is_some_and(|r| !(output_mode::RATE_RANGE.0..=output_mode::RATE_RANGE.1).contains(&r)) {
+            return Err(format!("INVALID_SAMPLE_RATE: must be within {}–{}", output_mode::RATE_RANGE.0, output_mode::RATE_RANGE.1));
+        }
+        let previous = self.output_prefs.max_output_rate;
+        self.output_prefs.max_output_rate = rate;
+        if let Err(e) = self.reopen_output() {
+            self.output_prefs.max_output_rate = previous;
+            return Err(e);
         }
+        let delta = if previous != rate { vec!["max_output_rate", "output_format", "output_sample_rate", "resampling"] } else { vec![] };
+        Ok(OpResult::new("set_max_output_rate", json!({ "rate": rate }), json!({ "rate": rate, "output_sample_rate": stream_sample_rate() }), delta))
+    }
 
-        res
+    // 切换引擎时带上当前曲目：新引擎载入同一首并回到原位置、原播放状态；载入失败则保留旧引擎
+    pub fn switch_engine(&mut self, engine_id: &str) -> Result<OpResult, String> {
+        self.check_and_recover_default_device();
+        self.engine_fallback = false;
+        let engine_choice = match engine_id {
+            "galaxy" => "galaxy",
+            "ffmpeg" => "ffmpeg",
+            _ => return Err("UNKNOWN_ENGINE".to_string())
+        };
+        let previous_choice = std::mem::replace(&mut self.engine_choice, engine_choice);
+        let handle = self.stream_handle.clone().ok_or_else(|| NO_AUDIO_DEVICE.to_string())?;
+        let previous_engine = self.active_engine.id();
+        let (mut engine, status): (Box<dyn AudioEngine>, _) = if engine_choice == "galaxy" {
+            (Box::new(galaxy::GalaxyEngine::new(handle, self.jobs.clone(), ErrorReporter::new(self.errors_tx.clone(), "galaxy"), ProgressReporter::new(self.progress_tx.clone(), "galaxy"))), "ENGINE_GALAXY_READY")
+        } else {
+            (Box::new(ffmpeg::FFmpegEngine::new(handle, ErrorReporter::new(self.errors_tx.clone(), "ffmpeg"), ProgressReporter::new(self.progress_tx.clone(), "ffmpeg"))), "ENGINE_FFMPEG_READY")
+        };
+
+        // 核心增量：给新引擎注入旧音量，防止切换后归零或震耳欲聋
+        let _ = engine.set_volume(self.effective_volume());
+        let _ = engine.set_channel_mode(self.current_channel_mode);
+        engine.set_resampler_quality(self.current_resampler);
+        let _ = engine.set_rate_preserve_pitch(self.current_rate);
+        let _ = engine.set_parametric_eq(self.current_eq.clone());
+        let _ = engine.set_bass_boost(self.current_bass);
+        let _ = engine.set_stereo_width(self.current_width);
+        let _ = engine.set_preamp(self.current_preamp);
+        let _ = engine.set_balance(self.current_balance);
+        let _ = engine.set_crossfeed(self.current_crossfeed);
+        let _ = engine.set_lfe_crossover(self.current_lfe);
+        let _ = engine.set_upmix_params(self.current_upmix);
+        engine.set_limiter_enabled(self.current_limiter);
+        let _ = engine.set_dither(self.current_dither);
+        let _ = engine.set_downmix_lfe(self.current_downmix_lfe);
+        let _ = engine.set_channel_layout(self.current_channel_layout);
+        let _ = engine.set_replaygain_db(self.current_replaygain_db);
+        let _ = engine.set_auto_level(self.current_auto_level);
+        let _ = engine.set_fade_config(self.current_fades);
+        let _ = engine.set_skip_silence(self.current_skip_silence);
+
+        let mut delta = vec!["is_playing", "position", "duration"];
+        if previous_engine != engine.id() { delta.insert(0, "engine_id"); }
+        // 新引擎不支持比特完美时如实退出该模式
+        let keep_bitperfect = !self.current_bitperfect || engine.set_bitperfect(true).is_ok();
+
+        // 旧引擎先暂停而不停止，新引擎载入失败时原样续上
+        let path = self.active_engine.current_path();
+        let (position, was_playing) = (self.active_engine.get_current_time(), self.active_engine.is_playing());
+        let mut duration = 0.0;
+        if let Some(path) = &path {
+            self.active_engine.pause();
+            match engine.load(path) {
+                Ok(loaded) => duration = loaded,
+                Err(e) => {
+                    self.engine_choice = previous_choice;
+                    if was_playing { self.active_engine.play(); }
+                    return Err(format!("ENGINE_SWITCH_FAILED: {}", e));
+                }
+            }
+        }
+        // 旧引擎停下时会清空 DSP 链登记，新引擎随后的 seek 重挂链路并重新登记
+        let mut previous = std::mem::replace(&mut self.active_engine, engine);
+        previous.stop();
+        self.current_duration = duration;
+        if path.is_some() {
+            self.active_engine.seek(position);
+            if was_playing { self.active_engine.play(); }
+        }
+        if !keep_bitperfect {
+            self.current_bitperfect = false;
+            delta.push("bitperfect");
+        }
+        let restored = EngineSwitched { engine_id: engine_id.to_string(), is_playing: was_playing && path.is_some(), path, position, duration };
+        if let Some(app) = &self.app { let _ = events::ENGINE_SWITCHED.emit(app, &restored); }
+        Ok(OpResult::new("switch_engine", json!({ "engine_id": engine_id }), json!({ "engine_id": engine_id, "status": status, "restored": restored }), delta))
     }
 
-    pub fn load(&mut self, path: &str) -> Result<f64, String> { 
+    pub fn load(&mut self, path: &str) -> Result<OpResult, String> { 
         self.check_and_recover_default_device();
-        self.active_engine.load(path) 
+        self.generator = None;
+        self.output_pause = None;
+        let fallback = self.fall_back_for(path)?;
+        // 增益先于载入下发，新曲目的前级从第一帧起就是目标电平；载入失败时还原
+        self.match_output_format(path);
+        let previous_gain = self.current_replaygain_db;
+        self.apply_replaygain(Some(path));
+        let duration = match self.active_engine.load(path) {
+            Ok(duration) => duration,
+            Err(e) => {
+                self.current_replaygain_db = previous_gain;
+                let _ = self.active_engine.set_replaygain_db(previous_gain);
+                return Err(e);
+            }
+        };
+        self.current_duration = duration;
+        self.track_end_notified = false;
+        // 首尾静音命中缓存时当场生效；未命中则交给后台任务整曲解码，测出后再跳过片头
+        self.trim_generation += 1;
+        self.current_trim = None;
+        if let (Some(app), true) = (&self.app, self.current_auto_trim) {
+            match silence::cached_bounds(app, path) {
+                Some(bounds) => self.current_trim = bounds.trim_points(),
+                None => self.measure_trim(app.clone(), path),
+            }
+        }
+        if let Some((start, _)) = self.current_trim.filter(|(start, _)| *start > 0.0) { self.active_engine.seek(start); }
+        let mut delta = vec!["loaded_path", "position", "duration", "replaygain_db", "trim"];
+        if fallback { delta.insert(0, "engine_id"); }
+        Ok(OpResult::new("load", json!({ "path": path }), json!({ "path": path, "duration": duration, "replaygain_db": self.current_replaygain_db, "trim": self.current_trim, "engine_id": self.engine_id() }),
+            delta))
+    }
+    // 同 id 登记会取消上一首尚未测完的任务
+    fn measure_trim(&self, app: AppHandle, path: &str) {
+        let job = self.jobs.register(SILENCE_BOUNDS_JOB, JobKind::SilenceBounds, path);
+        let (tx, generation, path) = (self.trim_tx.clone(), self.trim_generation, path.to_string());
+        threads::spawn("silence-bounds", move || {
+            let trim = silence::bounds(&app, &path, Some(job.token())).ok().and_then(|b| b.trim_points());
+            if !job.is_cancelled() { let _ = tx.send((generation, path, trim)); }
+        });
+    }
+
+    // 只认当前这次载入的结果；用户已自行 seek 越过片头时不再往回跳
+    fn flush_trim_results(&mut self) {
+        while let Ok((generation, path, trim)) = self.trim_rx.try_recv() {
+            if generation != self.trim_generation || !self.current_auto_trim { continue; }
+            self.current_trim = trim;
+            if let Some((start, _)) = trim.filter(|(start, _)| *start > self.active_engine.get_current_time()) { self.active_engine.seek(start); }
+            self.emit_op(&OpResult::new("auto_trim", json!({ "path": path }), json!({ "path": path, "trim": trim }), vec!["trim"]));
+        }
+    }
+    fn decodes_natively(&self, path: &str) -> bool { has_format(&self.active_engine.capabilities().formats, path) }
+    // Galaxy 原生解不了的格式 (APE / WavPack / Opus / DSD 等) 在装了 FFmpeg 时临时换它来播，下一首能原生解码时再换回。
+    // 旧曲目先停掉，切换时不必在新引擎里重载它。返回引擎是否因此变动
+    fn fall_back_for(&mut self, path: &str) -> Result<bool, String> {
+        if !self.engine_fallback && self.active_engine.id() != "galaxy" { return Ok(false); }
+        let wanted = if !has_format(galaxy::NATIVE_FORMATS, path) && ffmpeg::FFmpegEngine::installed_exe().is_some() { "ffmpeg" } else { "galaxy" };
+        if wanted == self.active_engine.id() { return Ok(false); }
+        self.active_engine.stop();
+        self.switch_engine(wanted)?;
+        self.engine_fallback = wanted == "ffmpeg";
+        if self.engine_fallback { println!("[AUDIO] Galaxy cannot decode {} natively, falling back to FFmpeg for this track.", path); }
+        Ok(true)
+    }
+    fn apply_replaygain(&mut self, path: Option<&str>) {
+        let tags = match self.current_replaygain.mode {
+            replaygain::ReplayGainMode::Off => None,
+            _ => path.and_then(replaygain::ReplayGainTags::read),
+        };
+        let gain_db = if path.is_some() { self.current_replaygain.gain_db(tags.as_ref()) } else { 0.0 };
+        if self.active_engine.set_replaygain_db(gain_db).is_ok() { self.current_replaygain_db = gain_db; }
+    }
+    // 修改模式后立即按当前曲目重新计算
+    pub fn set_replaygain(&mut self, prefs: replaygain::ReplayGainPrefs) -> Result<OpResult, String> {
+        prefs.validate()?;
+        let previous = (self.current_replaygain, self.current_replaygain_db);
+        self.current_replaygain = prefs;
+        let path = self.active_engine.current_path();
+        self.apply_replaygain(path.as_deref());
+        let mut delta = vec![];
+        if prefs != previous.0 { delta.push("replaygain"); }
+        if self.current_replaygain_db != previous.1 { delta.push("replaygain_db"); }
+        Ok(OpResult::new("set_replaygain", json!(prefs), json!({ "prefs": prefs, "gain_db": self.current_replaygain_db }), delta))
     }
-    pub fn play(&mut self) { 
+    pub fn play(&mut self) -> OpResult { 
+        self.output_pause = None; // 用户主动接管，不再等原端点
+        self.endpoint_seen_ok = Instant::now();
         self.check_and_recover_default_device();
-        self.active_engine.play() 
+        if self.sleep_gain < 1.0 { self.set_sleep_gain(1.0); } // 睡眠定时压低的音量在下一次播放时复位
+        let was_playing = self.active_engine.is_playing();
+        self.active_engine.play();
+        let delta = if was_playing { vec![] } else { vec!["is_playing"] };
+        OpResult::new("play", Value::Null, json!({ "is_playing": self.active_engine.is_playing() }), delta)
+    }
+    pub fn pause(&mut self) -> OpResult {
+        let was_playing = self.active_engine.is_playing();
+        self.active_engine.pause();
+        let delta = if was_playing { vec!["is_playing", "position"] } else { vec![] };
+        OpResult::new("pause", Value::Null, json!({ "is_playing": self.active_engine.is_playing() }), delta)
     }
-    pub fn pause(&mut self) { self.active_engine.pause() }
-    pub fn seek(&mut self, time: f64) { 
+    pub fn stop(&mut self) -> OpResult {
+        let was_playing = self.active_engine.is_playing();
+        self.active_engine.stop();
+        self.generator = None;
+        self.output_pause = None;
+        self.current_duration = 0.0;
+        self.trim_generation += 1;
+        let mut delta = vec!["loaded_path", "position", "duration"];
+        if was_playing { delta.insert(0, "is_playing"); }
+        OpResult::new("stop", Value::Null, json!({ "is_playing": false, "position": 0.0 }), delta)
+    }
+    pub fn seek(&mut self, time: f64) -> OpResult { 
         self.check_and_recover_default_device();
-        self.active_engine.seek(time) 
+        let upper = if self.current_duration > 0.0 { self.current_duration } else { f64::MAX };
+        let applied = if time.is_finite() { time.clamp(0.0, upper) } else { 0.0 };
+        self.active_engine.seek(applied);
+        self.track_end_notified = false;
+        OpResult::new("seek", json!({ "time": time }), json!({ "time": applied }), vec!["position"])
+    }
+    // 基于引擎真实物理位置的相对跳转；越过结尾直接结束曲目，而不是挂一个空音源
+    pub fn seek_relative(&mut self, delta: f64) -> OpResult {
+        let position = self.active_engine.get_current_time();
+        let target = if delta.is_finite() { (position + delta).max(0.0) } else { position };
+
+        if self.current_duration > 0.0 && target >= self.current_duration {
+            let duration = self.current_duration;
+            self.stop();
+            if let Some(app) = &self.app { let _ = events::TRACK_ENDED.emit(app, &()); }
+            return OpResult::new("seek_relative", json!({ "delta": delta }), json!({ "time": duration, "ended": true }), vec!["is_playing", "position"]);
+        }
+
+        let seek_op = self.seek(target);
+        OpResult::new("seek_relative", json!({ "delta": delta }), seek_op.applied, seek_op.state_delta)
+    }
+    pub fn set_loop(&mut self, start: f64, end: f64) -> Result<OpResult, String> {
+        let end_applied = if self.current_duration > 0.0 { end.min(self.current_duration) } else { end };
+        self.active_engine.set_loop(start, end_applied)?;
+        self.track_end_notified = false;
+        Ok(OpResult::new("set_loop", json!({ "start": start, "end": end }), json!({ "start": start, "end": end_applied }), vec!["loop", "position"]))
+    }
+    pub fn clear_loop(&mut self) -> OpResult {
+        self.active_engine.clear_loop();
+        OpResult::new("clear_loop", Value::Null, Value::Null, vec!["loop"])
+    }
+    pub fn set_rate(&mut self, rate: f32) -> Result<OpResult, String> {
+        self.active_engine.set_rate_preserve_pitch(rate)?;
+        let changed = rate != self.current_rate;
+        self.current_rate = rate;
+        let delta = if changed { vec!["rate", "position"] } else { vec![] };
+        Ok(OpResult::new("set_rate", json!({ "rate": rate }), json!({ "rate": rate, "preserve_pitch": true }), delta))
+    }
+    pub fn set_parametric_eq(&mut self, bands: Vec<dsp::EqBand>) -> Result<OpResult, String> {
+        self.active_engine.set_parametric_eq(bands.clone())?;
+        let delta = if bands != self.current_eq { vec!["parametric_eq"] } else { vec![] };
+        self.current_eq = bands;
+        Ok(OpResult::new("set_parametric_eq", json!({ "bands": self.current_eq }), json!({ "bands": self.current_eq }), delta))
+    }
+    pub fn set_bass_boost(&mut self, bass: dsp::BassBoost) -> Result<OpResult, String> {
+        self.active_engine.set_bass_boost(bass)?;
+        let delta = if bass != self.current_bass { vec!["bass_boost"] } else { vec![] };
+        self.current_bass = bass;
+        let limited = bass.gain_db > dsp::BASS_LIMIT_THRESHOLD_DB;
+        Ok(OpResult::new("set_bass_boost", json!(bass), json!({ "gain_db": bass.gain_db, "freq": bass.freq, "limiter": limited }), delta))
+    }
+    pub fn set_stereo_width(&mut self, width: f32) -> Result<OpResult, String> {
+        self.active_engine.set_stereo_width(width)?;
+        let delta = if width != self.current_width { vec!["stereo_width"] } else { vec![] };
+        self.current_width = width;
+        Ok(OpResult::new("set_stereo_width", json!({ "width": width }), json!({ "width": width }), delta))
+    }
+    pub fn set_preamp(&mut self, gain_db: f32) -> Result<OpResult, String> {
+        self.active_engine.set_preamp(gain_db)?;
+        let delta = if gain_db != self.current_preamp { vec!["preamp_db"] } else { vec![] };
+        self.current_preamp = gain_db;
+        Ok(OpResult::new("set_preamp", json!({ "gain_db": gain_db }), json!({ "gain_db": gain_db }), delta))
+    }
+    pub fn set_balance(&mut self, balance: f32) -> Result<OpResult, String> {
+        self.active_engine.set_balance(balance)?;
+        let delta = if balance != self.current_balance { vec!["balance"] } else { vec![] };
+        self.current_balance = balance;
+        Ok(OpResult::new("set_balance", json!({ "balance": balance }), json!({ "balance": balance }), delta))
+    }
+    pub fn set_crossfeed(&mut self, crossfeed: dsp::Crossfeed) -> Result<OpResult, String> {
+        self.active_engine.set_crossfeed(crossfeed)?;
+        let delta = if crossfeed != self.current_crossfeed { vec!["crossfeed"] } else { vec![] };
+        self.current_crossfeed = crossfeed;
+        // 环绕模式下该级自动旁路，回执里如实标出
+        let active = crossfeed.enabled && self.current_channel_mode == 2;
+        Ok(OpResult::new("set_crossfeed", json!(crossfeed), json!({ "enabled": crossfeed.enabled, "level": crossfeed.level, "active": active }), delta))
+    }
+    pub fn set_auto_level(&mut self, auto_level: dsp::AutoLevel) -> Result<OpResult, String> {
+        self.active_engine.set_auto_level(auto_level)?;
+        let delta = if auto_level != self.current_auto_level { vec!["auto_level"] } else { vec![] };
+        self.current_auto_level = auto_level;
+        Ok(OpResult::new("set_auto_level", json!(auto_level), json!(auto_level), delta))
+    }
+    pub fn set_lfe_crossover(&mut self, crossover: dsp::LfeCrossover) -> Result<OpResult, String> {
+        self.active_engine.set_lfe_crossover(crossover)?;
+        let delta = if crossover != self.current_lfe { vec!["lfe_crossover"] } else { vec![] };
+        self.current_lfe = crossover;
+        Ok(OpResult::new("set_lfe_crossover", json!(crossover), json!(crossover), delta))
+    }
+    pub fn set_dither(&mut self, dither: dsp::Dither) -> Result<OpResult, String> {
+        self.active_engine.set_dither(dither)?;
+        let delta = if dither != self.current_dither { vec!["dither"] } else { vec![] };
+        self.current_dither = dither;
+        Ok(OpResult::new("set_dither", json!(dither), json!(dither), delta))
+    }
+    pub fn set_downmix_lfe(&mut self, enabled: bool) -> Result<OpResult, String> {
+        self.active_engine.set_downmix_lfe(enabled)?;
+        let delta = if enabled != self.current_downmix_lfe { vec!["downmix_lfe"] } else { vec![] };
+        self.current_downmix_lfe = enabled;
+        Ok(OpResult::new("set_downmix_lfe", json!({ "enabled": enabled }), json!({ "enabled": enabled }), delta))
+    }
+    pub fn set_limiter_enabled(&mut self, enabled: bool) -> OpResult {
+        self.active_engine.set_limiter_enabled(enabled);
+        let delta = if enabled != self.current_limiter { vec!["limiter_enabled"] } else { vec![] };
+        self.current_limiter = enabled;
+        OpResult::new("set_limiter_enabled", json!({ "enabled": enabled }), json!({ "enabled": enabled }), delta)
+    }
+    pub fn set_channel_layout(&mut self, layout: dsp::ChannelLayout) -> Result<OpResult, String> {
+        self.active_engine.set_channel_layout(layout)?;
+        let delta = if layout != self.current_channel_layout { vec!["channel_layout"] } else { vec![] };
+        self.current_channel_layout = layout;
+        Ok(OpResult::new("set_channel_layout", json!(layout), json!(layout), delta))
     }
-    pub fn set_volume(&mut self, vol: f32) { 
-        self.current_volume = vol; // 新增：记录当前音量到管理层
-        self.active_engine.set_volume(vol) 
+    pub fn set_upmix_params(&mut self, params: dsp::UpmixParams) -> Result<OpResult, String> {
+        self.active_engine.set_upmix_params(params)?;
+        let delta = if params != self.current_upmix { vec!["upmix_params"] } else { vec![] };
+        self.current_upmix = params;
+        Ok(OpResult::new("set_upmix_params", json!(params), json!(params), delta))
     }
-    pub fn set_channels(&mut self, mode: u16) { self.active_engine.set_channel_mode(mode); }
-}
\ No newline at end of file
+    // 试听走独立 Sink，这里只借用当前输出流；新的试听会顶替并取消仍在进行的上一轮
+    pub fn start_loudness_preview(&mut self, path_a: String, path_b: String) -> Result<String, String> {
+        let app = self.app.clone().ok_or_else(|| "APP_NOT_ATTACHED".to_string())?;
+        let handle = self.stream_handle.clone().ok_or_else(|| NO_AUDIO_DEVICE.to_string())?;
+        for path in [&path_a, &path_b] {
+            if !std::path::Path::new(path).exists() { return Err(format!("FILE_NOT_FOUND: {}", path)); }
+        }
+        let job = self.jobs.register(loudness::PREVIEW_JOB_ID, JobKind::LoudnessPreview, "A/B loudness preview");
+        loudness::start_preview(app, handle, path_a, path_b, job);
+        Ok(loudness::PREVIEW_JOB_ID.to_string())
+    }
+    fn capture_resume(&self) -> GeneratorResume {
+        GeneratorResume {
+            path: self.active_engine.current_path(),
+            position: self.active_engine.get_current_time(),
+            was_playing: self.active_engine.is_playing(),
+        }
+    }
+    // 连续切换信号时只记录第一次接管前的曲目现场
+    pub fn start_generator(&mut self, request: galaxy::GeneratorRequest) -> Result<OpResult, String> {
+        let resume = self.capture_resume();
+        // 引擎先校验参数再停曲目，失败时现场原样保留
+        self.active_engine.start_generator(request)?;
+        if self.generator.is_none() { self.generator = Some(resume); }
+        self.current_duration = 0.0;
+        Ok(OpResult::new("start_generator", json!({ "level_db": request.level_db }), json!({ "level_db": request.level_db.min(galaxy::GENERATOR_MAX_DB) }),
+            vec!["is_playing", "loaded_path", "position", "duration"]))
+    }
+    // 识别音与发生器共用接管 / 恢复流程，结束后同样经 stop_generator 回到原曲目
+    pub fn identify_channels(&mut self) -> Result<(OpResult, Vec<galaxy::ChannelIdent>), String> {
+        let resume = self.capture_resume();
+        let plan = self.active_engine.identify_channels()?;
+        if self.generator.is_none() { self.generator = Some(resume); }
+        self.current_duration = 0.0;
+        let op = OpResult::new("identify_channels", Value::Null, json!({ "channels": plan.len() }),
+            vec!["is_playing", "loaded_path", "position", "duration"]);
+        Ok((op, plan))
+    }
+    pub fn stop_generator(&mut self) -> Result<OpResult, String> {
+        let resume = self.generator.take().ok_or_else(|| "GENERATOR_NOT_RUNNING".to_string())?;
+        self.active_engine.stop();
+        self.current_duration = 0.0;
+        if let Some(path) = &resume.path {
+            self.load(path)?;
+            self.seek(resume.position);
+            if resume.was_playing { self.play(); }
+        }
+        Ok(OpResult::new("stop_generator", Value::Null, json!({ "restored": resume.path, "position": resume.position }),
+            vec!["is_playing", "loaded_path", "position", "duration"]))
+    }
+    // current_volume 是滑块值；交给引擎的是按音量曲线换算后的增益，引擎内的淡入淡出都基于它
+    fn effective_volume(&self) -> f32 { if self.muted { 0.0 } else { self.current_volume_curve.gain(self.current_volume) * self.sleep_gain } }
+    // 静音期间调音量只更新记忆值，取消静音时恢复到它
+    pub fn set_volume(&mut self, vol: f32) -> Result<OpResult, String> { 
+        let applied = if vol.is_finite() { vol.clamp(0.0, 1.0) } else { self.current_volume };
+        self.active_engine.set_volume(if self.muted { 0.0 } else { self.current_volume_curve.gain(applied) * self.sleep_gain })?;
+        let changed = applied != self.current_volume;
+        self.current_volume = applied; // 新增：记录当前音量到管理层
+        Ok(OpResult::new("set_volume", json!({ "volume": vol }), json!({ "volume": applied }), if changed { vec!["volume"] } else { vec![] }))
+    }
+    // 音量经 UpmixSource 的主音量平滑逼近，静音与恢复都带短淡变
+    pub fn set_mute(&mut self, muted: bool) -> Result<OpResult, String> {
+        self.active_engine.set_volume(if muted { 0.0 } else { self.current_volume_curve.gain(self.current_volume) * self.sleep_gain })?;
+        let changed = muted != self.muted;
+        self.muted = muted;
+        Ok(OpResult::new("set_mute", json!({ "muted": muted }), json!({ "muted": muted, "volume": self.current_volume }), if changed { vec!["muted"] } else { vec![] }))
+    }
+    pub fn set_volume_curve(&mut self, curve: dsp::VolumeCurve) -> Result<OpResult, String> {
+        let previous = self.current_volume_curve;
+        self.current_volume_curve = curve;
+        if let Err(e) = self.active_engine.set_volume(self.effective_volume()) {
+            self.current_volume_curve = previous;
+            return Err(e);
+        }
+        let delta = if curve != previous { vec!["volume_curve"] } else { vec![] };
+        Ok(OpResult::new("set_volume_curve", json!(curve), json!({ "curve": curve, "gain": self.effective_volume() }), delta))
+    }
+    pub fn set_sleep_gain(&mut self, gain: f32) {
+        self.sleep_gain = if gain.is_finite() { gain.clamp(0.0, 1.0) } else { 1.0 };
+        let _ = self.active_engine.set_volume(self.effective_volume());
+    }
+    // 从下一次载入起生效
+    pub fn set_auto_trim(&mut self, enabled: bool) -> OpResult {
+        let delta = if enabled != self.current_auto_trim { vec!["auto_trim"] } else { vec![] };
+        self.current_auto_trim = enabled;
+        OpResult::new("set_auto_trim", json!({ "enabled": enabled }), json!({ "enabled": enabled }), delta)
+    }
+    pub fn set_skip_silence(&mut self, prefs: silence::SkipSilence) -> Result<OpResult, String> {
+        self.active_engine.set_skip_silence(prefs)?;
+        let delta = if prefs != self.current_skip_silence { vec!["skip_silence", "position"] } else { vec![] };
+        self.current_skip_silence = prefs;
+        Ok(OpResult::new("set_skip_silence", json!(prefs), json!(prefs), delta))
+    }
+    pub fn set_fade_config(&mut self, fades: dsp::FadeConfig) -> Result<OpResult, String> {
+        self.active_engine.set_fade_config(fades)?;
+        let delta = if fades != self.current_fades { vec!["fades"] } else { vec![] };
+        self.current_fades = fades;
+        Ok(OpResult::new("set_fade_config", json!(fades), json!(fades), delta))
+    }
+    pub fn set_channels(&mut self, mode: u16) -> Result<OpResult, String> {
+        let applied = match mode { 6 | 8 | 106 | 108 => mode, _ => 2 };
+        self.active_engine.set_channel_mode(applied)?;
+        let changed = applied != self.current_channel_mode;
+        self.current_channel_mode = applied;
+        Ok(OpResult::new("set_channels", json!({ "mode": mode }), json!({ "mode": applied }), if changed { vec!["channel_mode"] } else { vec![] }))
+    }
+    pub fn set_bitperfect(&mut self, enabled: bool) -> Result<OpResult, String> {
+        self.active_engine.set_bitperfect(enabled)?;
+        let delta = if enabled != self.current_bitperfect { vec!["bitperfect"] } else { vec![] };
+        self.current_bitperfect = enabled;
+        Ok(OpResult::new("set_bitperfect", json!({ "enabled": enabled }), json!({ "enabled": enabled }), delta))
+    }
+    pub fn set_resampler(&mut self, id: &str) -> Result<OpResult, String> {
+        let quality = galaxy::ResamplerQuality::from_id(id).ok_or_else(|| "UNKNOWN_RESAMPLER".to_string())?;
+        let changed = quality != self.current_resampler;
+        self.current_resampler = quality;
+        self.active_engine.set_resampler_quality(quality);
+        Ok(OpResult::new("set_resampler", json!({ "resampler": id }), json!({ "resampler": quality.id() }), if changed { vec!["resampler"] } else { vec![] }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager() -> AudioManager { AudioManager::headless(Box::new(null_engine::NullEngine)) }
+
+    #[test]
+    fn seek_reports_the_clamped_position() {
+        let mut m = manager();
+        m.current_duration = 120.0;
+        for (requested, applied) in [(500.0, 120.0), (-3.0, 0.0), (42.5, 42.5), (f64::NAN, 0.0), (f64::INFINITY, 0.0)] {
+            let op = m.seek(requested);
+            assert_eq!(op.requested["time"], json!(requested));
+            assert_eq!(op.applied["time"], json!(applied), "seek({requested})");
+            assert_eq!(op.state_delta, vec!["position"]);
+        }
+        // 时长未知时只钳下限
+        m.current_duration = 0.0;
+        assert_eq!(m.seek(1e6).applied["time"], json!(1e6));
+    }
+
+    #[test]
+    fn volume_while_muted_reports_the_remembered_level() {
+        let mut m = manager();
+        m.set_mute(true).unwrap();
+        let op = m.set_volume(1.7).unwrap();
+        assert_eq!((op.requested["volume"].clone(), op.applied["volume"].clone()), (json!(1.7f32), json!(1.0f32)));
+        assert_eq!(op.state_delta, vec!["volume"]);
+        // 记住的是钳制后的滑块值，实际输出仍为静音
+        let state = m.get_state();
+        assert!(state.muted);
+        assert_eq!(state.volume, 1.0);
+        assert_eq!(m.effective_volume(), 0.0);
+
+        let op = m.set_volume(1.0).unwrap();
+        assert!(op.state_delta.is_empty());
+        let op = m.set_volume(f32::NAN).unwrap();
+        assert_eq!(op.applied["volume"], json!(1.0f32));
+
+        let op = m.set_mute(false).unwrap();
+        assert_eq!((op.applied["muted"].clone(), op.applied["volume"].clone()), (json!(false), json!(1.0f32)));
+        assert_eq!(m.effective_volume(), 1.0);
+    }
+
+    // 能载入成功的替身引擎：记录引擎侧最终看到的状态，过渡成功后的意图才有处可落
+    #[derive(Default)]
+    struct Seen { path: Option<String>, position: f64, playing: bool, volume: f32 }
+
+    struct ScriptedEngine(Arc<Mutex<Seen>>);
+
+    impl AudioEngine for ScriptedEngine {
+        fn load(&mut self, path: &str) -> Result<f64, String> {
+            if path.contains("missing") { return Err("FILE_NOT_FOUND".to_string()); }
+            let mut s = self.0.lock().unwrap();
+            (s.path, s.position, s.playing) = (Some(path.to_string()), 0.0, false);
+            Ok(180.0)
+        }
+        fn play(&mut self) { let mut s = self.0.lock().unwrap(); s.playing = s.path.is_some(); }
+        fn pause(&mut self) { self.0.lock().unwrap().playing = false; }
+        fn stop(&mut self) { let mut s = self.0.lock().unwrap(); (s.path, s.position, s.playing) = (None, 0.0, false); }
+        fn seek(&mut self, time: f64) { self.0.lock().unwrap().position = time; }
+        fn set_volume(&mut self, vol: f32) -> Result<(), String> { self.0.lock().unwrap().volume = vol; Ok(()) }
+        fn name(&self) -> &str { "Scripted" }
+        fn id(&self) -> &'static str { "scripted" }
+        fn capabilities(&self) -> EngineCapabilities { EngineCapabilities::default() }
+        fn get_current_time(&self) -> f64 { self.0.lock().unwrap().position }
+        fn is_playing(&self) -> bool { self.0.lock().unwrap().playing }
+        fn current_path(&self) -> Option<String> { self.0.lock().unwrap().path.clone() }
+    }
+
+    // 与 actor 循环相同的取指顺序：先清顺延队列，再取通道
+    fn drain(m: &mut AudioManager, rx: &Receiver<AudioCommand>) {
+        let mut deferred = VecDeque::new();
+        while let Some(cmd) = deferred.pop_front().or_else(|| rx.try_recv().ok()) {
+            assert!(m.dispatch(cmd, rx, &mut deferred));
+            assert_eq!(m.phase, ManagerPhase::Idle);
+        }
+    }
+
+    struct Replies {
+        loads: Vec<oneshot::Receiver<Result<f64, String>>>,
+        volumes: Vec<oneshot::Receiver<Result<(), String>>>,
+        seeks: Vec<oneshot::Receiver<()>>,
+        states: Vec<oneshot::Receiver<PlayerState>>,
+        last_volume: Option<f32>,
+    }
+
+    // 随机排列过渡类指令 (载入 / 停止发生器) 与意图类指令，一次性塞进通道，让意图落在过渡期间
+    fn enqueue_random(tx: &Sender<AudioCommand>, seed: u64, count: usize) -> Replies {
+        let mut state = seed | 1;
+        let mut next = move |n: u64| { state ^= state << 13; state ^= state >> 7; state ^= state << 17; state % n };
+        let mut replies = Replies { loads: vec![], volumes: vec![], seeks: vec![], states: vec![], last_volume: None };
+        for i in 0..count {
+            let cmd = match next(9) {
+                0 => { let (r, rx) = oneshot::channel(); replies.loads.push(rx); AudioCommand::Load(format!("/music/{}{}.flac", if next(4) == 0 { "missing" } else { "track" }, i), r) }
+                1 => { let (r, _rx) = oneshot::channel(); AudioCommand::StopGenerator(r) }
+                2 => AudioCommand::Play,
+                3 => AudioCommand::Pause,
+                4 => AudioCommand::Stop,
+                5 => { let (r, rx) = oneshot::channel(); replies.seeks.push(rx); AudioCommand::Seek(next(400) as f64 - 100.0, r) }
+                6 => { let (r, rx) = oneshot::channel(); replies.states.push(rx); AudioCommand::GetState(r) }
+                _ => {
+                    let vol = next(150) as f32 / 100.0;
+                    replies.last_volume = Some(vol);
+                    let (r, rx) = oneshot::channel();
+                    replies.volumes.push(rx);
+                    AudioCommand::SetVolume(vol, r)
+                }
+            };
+            tx.send(cmd).unwrap();
+        }
+        replies
+    }
+
+    #[test]
+    fn random_interleavings_answer_every_intent() {
+        for seed in 1..=200u64 {
+            for scripted in [false, true] {
+                let seen = Arc::new(Mutex::new(Seen::default()));
+                let engine: Box<dyn AudioEngine> = if scripted { Box::new(ScriptedEngine(seen.clone())) } else { Box::new(null_engine::NullEngine) };
+                let mut m = AudioManager::headless(engine);
+                let (tx, rx) = mpsc::channel();
+                let mut replies = enqueue_random(&tx, seed * 0x9E37_79B9, 60);
+                drain(&mut m, &rx);
+
+                for r in replies.loads.iter_mut() { assert!(r.try_recv().is_ok(), "seed {seed}: load left unanswered"); }
+                for r in replies.volumes.iter_mut() { assert_eq!(r.try_recv(), Ok(Ok(())), "seed {seed}: volume intent lost"); }
+                for r in replies.seeks.iter_mut() { assert!(r.try_recv().is_ok(), "seed {seed}: seek left unanswered"); }
+                for r in replies.states.iter_mut() { assert!(r.try_recv().is_ok(), "seed {seed}: state query left unanswered"); }
+                // 音量意图不论过渡成败都生效：最终取最后一次请求
+                if let Some(vol) = replies.last_volume {
+                    assert_eq!(m.current_volume, vol.clamp(0.0, 1.0), "seed {seed}");
+                    if scripted { assert_eq!(seen.lock().unwrap().volume, m.effective_volume()); }
+                }
+                if scripted {
+                    let seen = seen.lock().unwrap();
+                    // 曲目在位时引擎拿到的位置一定落在时长之内
+                    if seen.path.is_some() { assert!((0.0..=180.0).contains(&seen.position), "seed {seed}: position {}", seen.position); }
+                    assert!(!seen.playing || seen.path.is_some());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn intents_queued_behind_a_load_land_on_the_new_track() {
+        let seen = Arc::new(Mutex::new(Seen::default()));
+        let mut m = AudioManager::headless(Box::new(ScriptedEngine(seen.clone())));
+        let (tx, rx) = mpsc::channel();
+        let (load, mut loaded) = oneshot::channel();
+        tx.send(AudioCommand::Load("/music/a.flac".to_string(), load)).unwrap();
+        let (seek, _) = oneshot::channel();
+        tx.send(AudioCommand::Seek(500.0, seek)).unwrap();
+        tx.send(AudioCommand::Pause).unwrap();
+        tx.send(AudioCommand::Play).unwrap();
+        let (vol, _) = oneshot::channel();
+        tx.send(AudioCommand::SetVolume(0.3, vol)).unwrap();
+        drain(&mut m, &rx);
+        assert_eq!(loaded.try_recv(), Ok(Ok(180.0)));
+        let seen = seen.lock().unwrap();
+        assert_eq!((seen.path.as_deref(), seen.position, seen.playing), (Some("/music/a.flac"), 180.0, true));
+        assert_eq!((m.current_volume, seen.volume), (0.3, m.effective_volume()));
+    }
+
+    #[test]
+    fn failed_transition_keeps_volume_but_drops_playback_intents() {
+        let seen = Arc::new(Mutex::new(Seen::default()));
+        let mut m = AudioManager::headless(Box::new(ScriptedEngine(seen.clone())));
+        let (tx, rx) = mpsc::channel();
+        let (load, _) = oneshot::channel();
+        tx.send(AudioCommand::Load("/music/missing.flac".to_string(), load)).unwrap();
+        tx.send(AudioCommand::Play).unwrap();
+        let (vol, mut vol_reply) = oneshot::channel();
+        tx.send(AudioCommand::SetVolume(0.6, vol)).unwrap();
+        drain(&mut m, &rx);
+        assert_eq!(vol_reply.try_recv(), Ok(Ok(())));
+        let seen = seen.lock().unwrap();
+        assert_eq!((seen.path.clone(), seen.playing), (None, false));
+        assert_eq!((m.current_volume, seen.volume), (0.6, m.effective_volume()));
+    }
+
+    use rodio::Source;
+
+    // 模拟某个解码线程持锁时 panic：之后的 lock() 全部得到 PoisonError
+    fn poisoned_sink() -> Arc<Mutex<Sink>> {
+        let (sink, _output) = Sink::new_idle();
+        let sink = Arc::new(Mutex::new(sink));
+        let held = sink.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = held.lock().unwrap();
+            panic!("decoder died while holding the sink");
+        }).join();
+        assert!(sink.is_poisoned());
+        sink
+    }
+
+    // strict 为真时按修复前的写法直接 unwrap，用来演练 actor 对漏网 panic 的兜底
+    struct SinkEngine { sink: Arc<Mutex<Sink>>, strict: bool, path: Option<String> }
+
+    impl SinkEngine {
+        fn with_sink<R>(&self, f: impl FnOnce(&Sink) -> R) -> R {
+            if self.strict { f(&self.sink.lock().unwrap()) } else { f(&lock_sink(&self.sink)) }
+        }
+    }
+
+    impl AudioEngine for SinkEngine {
+        fn load(&mut self, path: &str) -> Result<f64, String> {
+            self.with_sink(|s| { s.stop(); s.append(rodio::source::SineWave::new(440.0).take_duration(Duration::from_secs(10))); s.pause(); });
+            self.path = Some(path.to_string());
+            Ok(10.0)
+        }
+        fn play(&mut self) { self.with_sink(|s| s.play()) }
+        fn pause(&mut self) { self.with_sink(|s| s.pause()) }
+        fn stop(&mut self) { self.with_sink(|s| s.stop()); self.path = None; }
+        fn seek(&mut self, _time: f64) { self.with_sink(|_| ()) }
+        fn set_volume(&mut self, vol: f32) -> Result<(), String> { self.with_sink(|s| s.set_volume(vol)); Ok(()) }
+        fn name(&self) -> &str { "Sink" }
+        fn id(&self) -> &'static str { "sink" }
+        fn capabilities(&self) -> EngineCapabilities { EngineCapabilities::default() }
+        fn get_current_time(&self) -> f64 { 0.0 }
+        fn is_playing(&self) -> bool { self.with_sink(|s| !s.is_paused() && !s.empty()) }
+        fn current_path(&self) -> Option<String> { self.path.clone() }
+    }
+
+    #[test]
+    fn lock_sink_clears_poison_and_keeps_the_sink_usable() {
+        let sink = poisoned_sink();
+        lock_sink(&sink).set_volume(0.5);
+        assert!(!sink.is_poisoned());
+        // 恢复后不再需要兜底：普通 lock() 也能拿到
+        assert_eq!(sink.lock().unwrap().volume(), 0.5);
+    }
+
+    #[test]
+    fn commands_on_a_poisoned_sink_are_answered_without_panicking() {
+        let engine = SinkEngine { sink: poisoned_sink(), strict: false, path: None };
+        let mut m = AudioManager::headless(Box::new(engine));
+        let (tx, rx) = mpsc::channel();
+        let (load, mut loaded) = oneshot::channel();
+        tx.send(AudioCommand::Load("/music/a.flac".to_string(), load)).unwrap();
+        tx.send(AudioCommand::Play).unwrap();
+        let (seek, mut sought) = oneshot::channel();
+        tx.send(AudioCommand::Seek(3.0, seek)).unwrap();
+        let (vol, mut vol_reply) = oneshot::channel();
+        tx.send(AudioCommand::SetVolume(0.4, vol)).unwrap();
+        tx.send(AudioCommand::Pause).unwrap();
+        drain(&mut m, &rx);
+        assert_eq!(loaded.try_recv(), Ok(Ok(10.0)));
+        assert_eq!(sought.try_recv(), Ok(()));
+        assert_eq!(vol_reply.try_recv(), Ok(Ok(())));
+        assert!(!m.get_state().is_playing);
+    }
+
+    #[test]
+    fn crash_restore_skips_missing_resources_field_by_field() {
+        let dir = std::env::temp_dir().join(format!("agm_restore_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let track_file = dir.join("track.flac");
+        std::fs::write(&track_file, b"").unwrap();
+        let track = track_file.to_string_lossy().to_string();
+
+        // 崩溃前：载入、调过音量与声道，快照落盘后不再正常退出
+        let mut before = AudioManager::headless(Box::new(ScriptedEngine(Arc::default())));
+        before.load(&track).unwrap();
+        before.set_volume(0.4).unwrap();
+        before.set_channels(106).unwrap();
+        let mut writer = recovery::SnapshotWriter::new(dir.clone());
+        writer.set_engine(&before.engine_section());
+        writer.write(42.0, true);
+        let snapshot = recovery::load_snapshot(&dir).unwrap();
+        assert!(snapshot.was_playing);
+        let saved = snapshot.engine.unwrap();
+        assert_eq!(saved.track.as_deref(), Some(track.as_str()));
+
+        let restore = |section: recovery::EngineSection, position: f64| {
+            let seen = Arc::new(Mutex::new(Seen::default()));
+            let mut m = AudioManager::headless(Box::new(ScriptedEngine(seen.clone())));
+            let report = m.restore_engine_section(section, position);
+            (m, seen, report)
+        };
+        let skipped = |report: &recovery::RecoveryReport, field: &str| report.skipped.iter().find(|s| s.field == field).map(|s| s.reason.clone());
+
+        let (m, seen, report) = restore(saved.clone(), snapshot.position);
+        assert_eq!(report.track.as_deref(), Some(track.as_str()));
+        assert_eq!(seen.lock().unwrap().position, 42.0);
+        assert_eq!((m.current_volume, m.current_channel_mode), (0.4, 106));
+        for field in ["channel_mode", "volume", "resampler", "limiter_enabled", "track"] {
+            assert!(report.restored.contains(&field), "{field} should be restored");
+        }
+        // 引擎与设备未变不算一项；替身引擎不支持的设置逐项跳过，不拖累其余字段
+        assert!(skipped(&report, "engine").is_none() && skipped(&report, "device").is_none());
+        assert_eq!(skipped(&report, "rate").as_deref(), Some("RATE_UNSUPPORTED"));
+
+        // 位置超出曲长时停在末尾前 1 秒
+        let (_, seen, _) = restore(saved.clone(), 500.0);
+        assert_eq!(seen.lock().unwrap().position, 179.0);
+
+        // 设备与引擎都不可用：两项跳过，原输出模式保留，其余照常恢复
+        let section = recovery::EngineSection { device: "AGM Absent Output".to_string(), engine_id: "ffmpeg".to_string(), ..saved.clone() };
+        let (m, seen, report) = restore(section, 42.0);
+        assert!(skipped(&report, "device").is_some_and(|r| r.starts_with("DEVICE_UNAVAILABLE")));
+        assert_eq!(skipped(&report, "engine").as_deref(), Some(NO_AUDIO_DEVICE));
+        assert_eq!(m.current_device_mode, "Default");
+        assert_eq!((m.current_volume, m.current_channel_mode), (0.4, 106));
+        assert_eq!(report.track.as_deref(), Some(track.as_str()));
+        assert_eq!(seen.lock().unwrap().position, 42.0);
+
+        // 曲目文件已被删除：只跳过曲目，不去载入
+        std::fs::remove_file(&track_file).unwrap();
+        let (m, seen, report) = restore(saved, 42.0);
+        assert_eq!(skipped(&report, "track").as_deref(), Some("TRACK_MISSING"));
+        assert_eq!(report.track, None);
+        assert_eq!(seen.lock().unwrap().path, None);
+        assert_eq!(m.current_volume, 0.4);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // 退出信号是进程级的，置位后同一进程里的解码 / 睡眠都会立即收手：在子进程里单独跑，不波及其它测试
+    #[test]
+    fn actor_shutdown_stops_a_long_decode_and_fades_within_budget() {
+        let name = "actor_shutdown_stops_a_long_decode_and_fades_within_budget";
+        if std::env::var_os("AGM_SHUTDOWN_CHILD").is_none() {
+            let test = format!("{}::{}", module_path!().split_once("::").map_or(module_path!(), |(_, rest)| rest), name);
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args([test.as_str(), "--exact", "--nocapture", "--test-threads=1"])
+                .env("AGM_SHUTDOWN_CHILD", "1")
+                .status().unwrap();
+            assert!(status.success(), "{test} failed in the child process");
+            return;
+        }
+
+        let jobs = JobRegistry::new();
+        let tx = AudioManager::start_actor(jobs.clone());
+        // 5 分钟 44.1kHz 立体声重采样到 48kHz：整曲解码远长于本测试
+        let decoded = galaxy::GalaxyEngine::decode_detached(galaxy::tests::wav_bytes(44100, 2, 44100 * 300), 48000, &jobs);
+        // 两次淡出后延迟停 Sink 的线程，睡满要 30 秒
+        let (sink, _output) = Sink::new_idle();
+        let sink = Arc::new(Mutex::new(sink));
+        let (fade_token, is_playing) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicBool::new(false)));
+        for _ in 0..2 { pause_after_fade(&sink, &fade_token, &is_playing, Duration::from_secs(30)); }
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!decoded.load(Ordering::Acquire), "decode finished before shutdown; use a longer fixture");
+
+        let started = Instant::now();
+        let (reply, mut stragglers) = oneshot::channel();
+        tx.send(AudioCommand::Shutdown(reply)).unwrap();
+        let stragglers = loop {
+            match stragglers.try_recv() {
+                Ok(names) => break names,
+                Err(oneshot::error::TryRecvError::Empty) if started.elapsed() < Duration::from_secs(5) => std::thread::sleep(Duration::from_millis(5)),
+                Err(e) => panic!("no shutdown reply: {e:?}"),
+            }
+        };
+        assert!(stragglers.is_empty(), "stragglers: {stragglers:?}");
+        assert!(started.elapsed() < Duration::from_millis(500), "shutdown took {:?}", started.elapsed());
+        assert!(!decoded.load(Ordering::Acquire));
+        // actor 已退出，通道随之断开
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(tx.send(AudioCommand::Play).is_err());
+    }
+
+    #[test]
+    fn panicking_command_drops_its_reply_and_the_actor_keeps_serving() {
+        let engine = SinkEngine { sink: poisoned_sink(), strict: true, path: None };
+        let mut m = AudioManager::headless(Box::new(engine));
+        let (_tx, rx) = mpsc::channel();
+        let mut deferred = VecDeque::new();
+        // 与 actor 循环一致：catch_unwind 包住单条指令
+        let mut run = |m: &mut AudioManager, cmd| panic::catch_unwind(AssertUnwindSafe(|| m.dispatch(cmd, &rx, &mut deferred)));
+
+        let (load, mut loaded) = oneshot::channel();
+        assert!(run(&mut m, AudioCommand::Load("/music/a.flac".to_string(), load)).is_err());
+        // 回执随栈展开被丢弃，调用方收到的是错误而不是永远挂起
+        assert!(loaded.try_recv().is_err());
+        let (vol, mut vol_reply) = oneshot::channel();
+        assert!(run(&mut m, AudioCommand::SetVolume(0.4, vol)).is_err());
+        assert!(vol_reply.try_recv().is_err());
+
+        m.phase = ManagerPhase::Idle;
+        m.active_engine = Box::new(null_engine::NullEngine);
+        let (state, mut got) = oneshot::channel();
+        assert!(matches!(run(&mut m, AudioCommand::GetState(state)), Ok(true)));
+        assert!(got.try_recv().is_ok());
+    }
+}
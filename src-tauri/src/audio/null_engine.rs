@@ -0,0 +1,50 @@
+// src/audio/null_engine.rs
+
+use super::{dsp, galaxy, silence, AudioEngine, EngineCapabilities, NO_AUDIO_DEVICE};
+
+// =================================================================
+// 🔇 无输出占位引擎：启动时找不到任何输出设备时顶替真正的引擎
+// =================================================================
+// 载入与发声类操作返回 NO_AUDIO_DEVICE，播放控制空转；DSP 设置一律接受，
+// 让管理层照常记下，拿到设备后建起真正的引擎时再统一注入。
+pub struct NullEngine;
+
+pub const ID: &str = "none";
+
+fn no_device<T>() -> Result<T, String> { Err(NO_AUDIO_DEVICE.to_string()) }
+
+impl AudioEngine for NullEngine {
+    fn load(&mut self, _path: &str) -> Result<f64, String> { no_device() }
+    fn preload(&mut self, _path: &str) -> Result<(), String> { no_device() }
+    fn play(&mut self) {}
+    fn pause(&mut self) {}
+    fn stop(&mut self) {}
+    fn seek(&mut self, _time: f64) {}
+    fn set_volume(&mut self, _vol: f32) -> Result<(), String> { Ok(()) }
+    fn name(&self) -> &str { "No Output" }
+    fn id(&self) -> &'static str { ID }
+    fn capabilities(&self) -> EngineCapabilities { EngineCapabilities::default() }
+    fn get_current_time(&self) -> f64 { 0.0 }
+    fn is_playing(&self) -> bool { false }
+    fn current_path(&self) -> Option<String> { None }
+    fn set_loop(&mut self, _start: f64, _end: f64) -> Result<(), String> { no_device() }
+    fn set_rate_preserve_pitch(&mut self, _rate: f32) -> Result<(), String> { Ok(()) }
+    fn set_parametric_eq(&mut self, _bands: Vec<dsp::EqBand>) -> Result<(), String> { Ok(()) }
+    fn set_bass_boost(&mut self, _bass: dsp::BassBoost) -> Result<(), String> { Ok(()) }
+    fn set_stereo_width(&mut self, _width: f32) -> Result<(), String> { Ok(()) }
+    fn set_preamp(&mut self, _gain_db: f32) -> Result<(), String> { Ok(()) }
+    fn set_balance(&mut self, _balance: f32) -> Result<(), String> { Ok(()) }
+    fn set_downmix_lfe(&mut self, _enabled: bool) -> Result<(), String> { Ok(()) }
+    fn set_channel_layout(&mut self, _layout: dsp::ChannelLayout) -> Result<(), String> { Ok(()) }
+    fn set_auto_level(&mut self, _auto_level: dsp::AutoLevel) -> Result<(), String> { Ok(()) }
+    fn set_fade_config(&mut self, _fades: dsp::FadeConfig) -> Result<(), String> { Ok(()) }
+    fn set_skip_silence(&mut self, _prefs: silence::SkipSilence) -> Result<(), String> { Ok(()) }
+    fn set_replaygain_db(&mut self, _gain_db: f32) -> Result<(), String> { Ok(()) }
+    fn set_bitperfect(&mut self, _enabled: bool) -> Result<(), String> { Ok(()) }
+    fn set_crossfeed(&mut self, _crossfeed: dsp::Crossfeed) -> Result<(), String> { Ok(()) }
+    fn set_lfe_crossover(&mut self, _crossover: dsp::LfeCrossover) -> Result<(), String> { Ok(()) }
+    fn set_upmix_params(&mut self, _params: dsp::UpmixParams) -> Result<(), String> { Ok(()) }
+    fn set_dither(&mut self, _dither: dsp::Dither) -> Result<(), String> { Ok(()) }
+    fn start_generator(&mut self, _request: galaxy::GeneratorRequest) -> Result<(), String> { no_device() }
+    fn identify_channels(&mut self) -> Result<Vec<galaxy::ChannelIdent>, String> { no_device() }
+}
@@ -0,0 +1,69 @@
+// src/audio/output_mode.rs
+
+use lofty::{read_from_path, AudioFile};
+use rodio::cpal::traits::DeviceTrait;
+use rodio::cpal::{Device, SampleFormat, SampleRate, SupportedStreamConfigRange};
+use rodio::{OutputStream, OutputStreamHandle};
+use serde::{Serialize, Deserialize};
+
+// =================================================================
+// 🎯 输出格式：匹配音源时按曲目的采样率与位深打开设备，或固定在指定采样率
+// =================================================================
+// 这里不做独占：cpal 的 WASAPI 后端只有共享模式，设备只报告混音格式，其它采样率会被拒绝，
+// 由调用方退回共享模式的默认配置并发 output-mode-fallback；CoreAudio / ALSA 可直接按要求打开。
+// 音量始终在 Sink 上以软件增益完成，不经系统混音器。
+pub const RATE_RANGE: (u32, u32) = (8000, 768_000);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputRate {
+    #[default]
+    Device,     // 交给设备默认配置
+    Source,     // 匹配音源，每首按其采样率重开设备
+    Fixed(u32), // 固定采样率，引擎重采样到该值
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct TrackFormat {
+    pub sample_rate: u32,
+    pub bits: Option<u8>, // 有损格式没有位深
+}
+
+// 设备拒绝按曲目格式打开，已退回其共享默认格式
+#[derive(Serialize, Clone, Debug)]
+pub struct ModeFallback {
+    pub device: String,
+    pub format: TrackFormat,
+    pub reason: String,
+}
+
+// 超过上限时逐次减半，176.4k 落到 88.2k 而不是 96k，保持整数倍关系
+pub fn cap_rate(rate: u32, max: Option<u32>) -> u32 {
+    let Some(max) = max else { return rate };
+    let mut capped = rate;
+    while capped > max && capped.is_multiple_of(2) { capped /= 2; }
+    if capped > max { max } else { capped }
+}
+
+pub fn track_format(path: &str) -> Option<TrackFormat> {
+    let file = read_from_path(path).ok()?;
+    let props = file.properties();
+    Some(TrackFormat { sample_rate: props.sample_rate()?, bits: props.bit_depth() })
+}
+
+// 同等条件下优先：声道数与设备默认一致 → 整数格式且容器位宽能装下曲目位深 (24 位放进 32 位) → F32
+fn score(range: &SupportedStreamConfigRange, channels: u16, bits: Option<u8>) -> (bool, bool, bool) {
+    let format = range.sample_format();
+    let container = bits.map(|b| if b <= 16 { 2 } else { 4 });
+    (range.channels() == channels, format.is_int() && Some(format.sample_size()) == container, format == SampleFormat::F32)
+}
+
+pub fn open(device: &Device, format: TrackFormat) -> Result<(OutputStream, OutputStreamHandle), String> {
+    let channels = device.default_output_config().map(|c| c.channels()).unwrap_or(2);
+    let config = device.supported_output_configs().map_err(|e| e.to_string())?
+        .filter(|r| (r.min_sample_rate().0..=r.max_sample_rate().0).contains(&format.sample_rate))
+        .max_by_key(|r| score(r, channels, format.bits))
+        .ok_or_else(|| format!("SOURCE_FORMAT_UNSUPPORTED: device does not accept {}Hz", format.sample_rate))?
+        .with_sample_rate(SampleRate(format.sample_rate));
+    OutputStream::try_from_device_config(device, config).map_err(|e| format!("SOURCE_FORMAT_OPEN_FAILED: {}", e))
+}
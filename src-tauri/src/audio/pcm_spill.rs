@@ -0,0 +1,182 @@
+// src/audio/pcm_spill.rs
+
+use rodio::Source;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+// =================================================================
+// 💾 PCM 落盘缓存：整曲解码预计超出内存预算时改写临时文件，seek 时从任意偏移读回
+// =================================================================
+// 预算按 时长 × 采样率 × 声道 × 4 字节 估算。临时文件放在应用数据目录的 pcm_spill 下，
+// 最后一个引用释放时删除；启动与退出时再清一遍，崩溃残留的文件不会越积越多。
+pub const DEFAULT_BUDGET_BYTES: usize = 512 * 1024 * 1024;
+const BLOCK: usize = 4096; // 文件源每次读取的样本数
+
+static BUDGET: AtomicUsize = AtomicUsize::new(DEFAULT_BUDGET_BYTES);
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// 当前曲目的缓存方式，供界面解释 seek 延迟的差异
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheStrategy {
+    None,     // 没有载入曲目
+    Memory,   // 整曲 PCM 在内存里，seek 即时
+    TempFile, // 超出内存预算，PCM 写在临时文件里，seek 需读盘
+    Stream,   // 边解码边播放，不保留整曲，seek 可能要重起解码
+}
+
+pub fn set_budget(bytes: usize) { BUDGET.store(bytes.max(1), Ordering::Relaxed); }
+
+pub fn exceeds_budget(expected_samples: usize) -> bool {
+    expected_samples.saturating_mul(4) > BUDGET.load(Ordering::Relaxed)
+}
+
+fn dir() -> &'static PathBuf { DIR.get_or_init(|| std::env::temp_dir().join("astral_pcm_spill")) }
+
+// 启动时定下目录并清掉上次残留；只有第一次调用生效
+pub fn init_dir(dir: PathBuf) {
+    let _ = DIR.set(dir);
+    purge();
+}
+
+pub fn purge() {
+    let Ok(entries) = fs::read_dir(dir()) else { return };
+    for entry in entries.flatten() {
+        if entry.path().extension().is_some_and(|ext| ext == "f32") { let _ = fs::remove_file(entry.path()); }
+    }
+}
+
+struct State {
+    written: usize, // 已写入的样本数
+    done: bool,     // 完成、取消或写入失败，不会再增长
+}
+
+pub struct SpillFile {
+    path: PathBuf,
+    writer: Mutex<Option<File>>,
+    state: Mutex<State>,
+    grown: Condvar,
+}
+
+impl SpillFile {
+    pub fn create() -> Result<Arc<Self>, String> {
+        fs::create_dir_all(dir()).map_err(|e| e.to_string())?;
+        let path = dir().join(format!("pcm-{}-{}.f32", std::process::id(), NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+        let writer = File::create(&path).map_err(|e| e.to_string())?;
+        Ok(Arc::new(Self { path, writer: Mutex::new(Some(writer)), state: Mutex::new(State { written: 0, done: false }), grown: Condvar::new() }))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> { self.state.lock().unwrap_or_else(|e| e.into_inner()) }
+
+    pub fn append(&self, chunk: &[f32]) -> Result<(), String> {
+        let bytes: Vec<u8> = chunk.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        writer.as_mut().ok_or("SPILL_CLOSED")?.write_all(&bytes).map_err(|e| e.to_string())?;
+        drop(writer);
+        self.lock().written += chunk.len();
+        self.grown.notify_all();
+        Ok(())
+    }
+
+    // 解码结束 (完成或放弃)：关闭写句柄，已写入的部分照常可读
+    pub fn finish(&self) {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner()).take();
+        self.lock().done = true;
+        self.grown.notify_all();
+    }
+
+    pub fn is_done(&self) -> bool { self.lock().done }
+    pub fn written(&self) -> usize { self.lock().written }
+
+    // 等到该样本已写入；写入提前结束或超时返回 false
+    pub fn wait_for(&self, sample: usize, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        while sample >= state.written {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if state.done || left.is_zero() { return false; }
+            state = self.grown.wait_timeout(state, left).unwrap_or_else(|e| e.into_inner()).0;
+        }
+        true
+    }
+
+    pub fn source_at(self: &Arc<Self>, sample: usize, channels: u16, sample_rate: u32) -> Result<FileBackedSource, String> {
+        let file = File::open(&self.path).map_err(|e| e.to_string())?;
+        Ok(FileBackedSource { file, spill: self.clone(), pos: sample - sample % channels as usize, block: Vec::with_capacity(BLOCK), block_pos: 0, bytes: vec![0u8; BLOCK * 4], channels, sample_rate })
+    }
+}
+
+// 写句柄先关掉再删，Windows 上打开着的文件删不掉
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// 读句柄排在 spill 之前，先于最后一个引用释放，删除时文件已关闭
+pub struct FileBackedSource {
+    file: File,
+    spill: Arc<SpillFile>,
+    pos: usize, // 下一次读取的样本序号
+    block: Vec<f32>,
+    block_pos: usize,
+    bytes: Vec<u8>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl FileBackedSource {
+    // Some(false) 表示解码暂未写到这里
+    fn refill(&mut self) -> Option<bool> {
+        let (written, done) = { let state = self.spill.lock(); (state.written, state.done) };
+        if self.pos >= written { return if done { None } else { Some(false) }; }
+        let count = (written - self.pos).min(BLOCK);
+        let bytes = &mut self.bytes[..count * 4];
+        self.file.seek(SeekFrom::Start(self.pos as u64 * 4)).ok()?;
+        self.file.read_exact(bytes).ok()?;
+        self.block.clear();
+        self.block.extend(bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])));
+        self.block_pos = 0;
+        self.pos += count;
+        Some(true)
+    }
+}
+
+impl Iterator for FileBackedSource {
+    type Item = f32;
+    #[inline(always)]
+    fn next(&mut self) -> Option<f32> {
+        if self.block_pos >= self.block.len() {
+            // 欠载时输出静音而不在音频回调里阻塞等待
+            if !self.refill()? { return Some(0.0); }
+        }
+        let val = self.block[self.block_pos];
+        self.block_pos += 1;
+        Some(val)
+    }
+}
+
+impl Source for FileBackedSource {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { self.channels }
+    fn sample_rate(&self) -> u32 { self.sample_rate }
+    fn total_duration(&self) -> Option<Duration> { None }
+    // 只在已写入的范围内跳转
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        let target = (pos.as_secs_f64() * self.sample_rate as f64) as usize * self.channels as usize;
+        if target >= self.spill.lock().written {
+            return Err(rodio::source::SeekError::NotSupported { underlying_source: std::any::type_name::<Self>() });
+        }
+        self.pos = target;
+        self.block.clear();
+        self.block_pos = 0;
+        Ok(())
+    }
+}
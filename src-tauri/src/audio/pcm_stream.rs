@@ -0,0 +1,349 @@
+// src/audio/pcm_stream.rs
+
+use rodio::Source;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+use std::process::Child;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use super::playback_error::{ErrorReporter, PlaybackErrorCode};
+use super::decode_progress::DecodeTracker;
+use super::threads;
+
+// =================================================================
+// 🌊 流式 PCM：后台线程分块读取解码器输出 (f32le)，播放源边读边播，首块到达即可起播
+// =================================================================
+// 读线程最多领先播放游标 READ_AHEAD_SECS 秒 (有界窗口)，暂停时随之停下，不会把整曲读进内存。
+// 已读样本默认留作缓存供内存 seek；累计超过 CACHE_LIMIT_BYTES 后放弃缓存，只保留窗口内的样本。
+// 流可以从曲中任意位置 (origin) 开始，样本序号一律按整曲计，多条流各自覆盖一段时间范围。
+// 流持有解码子进程：取消时杀掉，读线程退出前 wait 回收，不留僵尸进程。
+const READ_AHEAD_SECS: usize = 4;
+const CACHE_LIMIT_BYTES: usize = 512 * 1024 * 1024;
+const CHUNK_BYTES: usize = 64 * 1024;
+const BLOCK: usize = 4096; // 播放源每次从共享缓冲取出的样本数，音频回调里只短暂持锁
+const LOG_LINES: usize = 8; // 保留的子进程 stderr 末尾行数，失败时附进错误信息
+
+// 尚未回收的解码子进程数；更新 FFmpeg 时据此拒绝替换正在运行的程序
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+pub fn live_decoders() -> usize { LIVE.load(Ordering::Acquire) }
+
+struct State {
+    samples: Vec<f32>,
+    base: usize,  // samples[0] 在流中的样本序号；放弃缓存后随播放前移
+    cached: bool, // 仍保留全部已读样本
+    done: bool,
+}
+
+impl State {
+    fn end(&self) -> usize { self.base + self.samples.len() }
+}
+
+pub struct PcmStream {
+    state: Mutex<State>,
+    changed: Condvar,
+    cursor: AtomicUsize, // 当前播放源的读位置，读线程据此限速
+    owner: AtomicUsize,  // 当前播放源的编号；被换下的旧源不再推动游标
+    cancelled: AtomicBool,
+    child: Mutex<Option<Child>>,
+    log: Mutex<VecDeque<String>>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl PcmStream {
+    // 子进程的 stdout 须为管道；stderr 由调用方读取并经 log_line 记下
+    pub fn spawn(mut child: Child, origin: usize, channels: u16, sample_rate: u32, errors: ErrorReporter, mut progress: DecodeTracker, path: String) -> Result<Arc<Self>, String> {
+        let Some(mut reader) = child.stdout.take() else {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err("Stdout failed".to_string());
+        };
+        let stream = Arc::new(Self {
+            state: Mutex::new(State { samples: Vec::new(), base: origin, cached: true, done: false }),
+            changed: Condvar::new(),
+            cursor: AtomicUsize::new(origin),
+            owner: AtomicUsize::new(0),
+            cancelled: AtomicBool::new(false),
+            child: Mutex::new(Some(child)),
+            log: Mutex::new(VecDeque::new()),
+            channels,
+            sample_rate,
+        });
+        let shared = stream.clone();
+        LIVE.fetch_add(1, Ordering::AcqRel);
+        threads::spawn("pcm-stream", move || {
+            let mut buf = vec![0u8; CHUNK_BYTES];
+            let mut carry = 0;
+            // 读到结尾才算解码完成；被取消或读失败都不发 decode-complete
+            let finished = loop {
+                match reader.read(&mut buf[carry..]) {
+                    Ok(0) => break !shared.is_cancelled(),
+                    Ok(n) => {
+                        // 一次 read 不一定落在 4 字节边界上，余下的半个样本留到下一轮
+                        let filled = carry + n;
+                        let whole = filled - filled % 4;
+                        let chunk: Vec<f32> = buf[..whole].chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+                        buf.copy_within(whole..filled, 0);
+                        carry = filled - whole;
+                        if !shared.push(&chunk) { break false; }
+                        progress.advance(shared.produced());
+                    }
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        errors.report(PlaybackErrorCode::DecodeFailed, e.to_string(), Some(&path));
+                        break false;
+                    }
+                }
+            };
+            let status = shared.child.lock().unwrap_or_else(|e| e.into_inner()).take().map(|mut child| {
+                // 取消后管道已关，进程可能还卡在解码上，先杀再回收
+                if !finished { let _ = child.kill(); }
+                child.wait()
+            });
+            LIVE.fetch_sub(1, Ordering::AcqRel);
+            shared.lock().done = true;
+            shared.changed.notify_all();
+            match status {
+                Some(Ok(status)) if finished && !status.success() => {
+                    errors.report(PlaybackErrorCode::DecodeFailed, format!("ffmpeg exited with {}: {}", status, shared.log_tail()), Some(&path));
+                }
+                _ if finished => progress.complete(),
+                _ => {}
+            }
+        });
+        Ok(stream)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> { self.state.lock().unwrap_or_else(|e| e.into_inner()) }
+
+    // 返回 false 表示流已取消，读线程应退出
+    fn push(&self, chunk: &[f32]) -> bool {
+        let ahead = READ_AHEAD_SECS * self.sample_rate as usize * self.channels as usize;
+        let mut state = self.lock();
+        while !self.is_cancelled() && state.end().saturating_sub(self.cursor.load(Ordering::Acquire)) >= ahead {
+            state = self.changed.wait_timeout(state, Duration::from_millis(100)).unwrap_or_else(|e| e.into_inner()).0;
+        }
+        if self.is_cancelled() { return false; }
+        state.samples.extend_from_slice(chunk);
+        if state.cached && state.samples.len() * 4 > CACHE_LIMIT_BYTES {
+            state.cached = false;
+            println!("[AUDIO] PCM cache exceeds {} MB, keeping only the read-ahead window", CACHE_LIMIT_BYTES / 1024 / 1024);
+        }
+        if !state.cached {
+            let played = self.cursor.load(Ordering::Acquire).saturating_sub(state.base).min(state.samples.len());
+            state.samples.drain(..played);
+            state.base += played;
+        }
+        drop(state);
+        self.changed.notify_all();
+        true
+    }
+
+    fn is_cancelled(&self) -> bool { self.cancelled.load(Ordering::Acquire) }
+
+    // 立即杀掉子进程，读线程随即读到结尾并负责 wait 回收
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+        if let Some(child) = self.child.lock().unwrap_or_else(|e| e.into_inner()).as_mut() { let _ = child.kill(); }
+        self.changed.notify_all();
+    }
+
+    pub fn log_line(&self, line: String) {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        if log.len() == LOG_LINES { log.pop_front(); }
+        log.push_back(line);
+    }
+
+    pub fn log_tail(&self) -> String {
+        self.log.lock().unwrap_or_else(|e| e.into_inner()).iter().map(String::as_str).collect::<Vec<_>>().join(" | ")
+    }
+
+    pub fn produced(&self) -> usize { self.lock().end() } // 读到的位置 (整曲样本序号)
+    pub fn is_done(&self) -> bool { self.lock().done }
+
+    // 该样本仍在内存里 (已读且未被丢弃)
+    pub fn holds(&self, sample: usize) -> bool {
+        let state = self.lock();
+        sample >= state.base && (sample < state.end() || (state.done && !self.is_cancelled()))
+    }
+
+    // 该样本在内存里，或在读线程前方 lead 个样本以内，顺读即可到达
+    pub fn covers(&self, sample: usize, lead: usize) -> bool {
+        let state = self.lock();
+        sample >= state.base && (sample < state.end() || (!state.done && !self.is_cancelled() && sample < state.end() + lead))
+    }
+
+    // 等到该样本读到或流结束；超时返回 false
+    pub fn wait_for(&self, sample: usize, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        while sample >= state.end() && !state.done {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() { return false; }
+            state = self.changed.wait_timeout(state, left).unwrap_or_else(|e| e.into_inner()).0;
+        }
+        true
+    }
+
+    // 从曲首开始、整曲读完且缓存完整时交出全部样本，之后的 seek / 循环 / 静音跳过走整曲内存路径
+    pub fn take_samples(&self) -> Option<Arc<Vec<f32>>> {
+        let mut state = self.lock();
+        if !state.done || !state.cached || state.base != 0 || state.samples.is_empty() || self.is_cancelled() { return None; }
+        Some(Arc::new(std::mem::take(&mut state.samples)))
+    }
+
+    // 从该样本起新建播放源并接管读线程的限速游标
+    pub fn source_at(self: &Arc<Self>, sample: usize) -> StreamSource {
+        let sample = sample - sample % self.channels as usize;
+        let id = self.owner.fetch_add(1, Ordering::AcqRel) + 1;
+        self.cursor.store(sample, Ordering::Release);
+        self.changed.notify_all();
+        StreamSource { stream: self.clone(), id, pos: sample, block: Vec::with_capacity(BLOCK), block_pos: 0 }
+    }
+}
+
+pub struct StreamSource {
+    stream: Arc<PcmStream>,
+    id: usize,
+    pos: usize, // 下一次取块的流内样本序号
+    block: Vec<f32>,
+    block_pos: usize,
+}
+
+impl StreamSource {
+    fn publish(&self) {
+        if self.stream.owner.load(Ordering::Acquire) != self.id { return; }
+        self.stream.cursor.store(self.pos, Ordering::Release);
+        self.stream.changed.notify_all();
+    }
+
+    // Some(false) 表示读线程暂未跟上
+    fn refill(&mut self) -> Option<bool> {
+        let state = self.stream.lock();
+        if self.pos < state.base { return None; }
+        if self.pos >= state.end() { return if state.done { None } else { Some(false) }; }
+        let from = self.pos - state.base;
+        let to = (from + BLOCK).min(state.samples.len());
+        self.block.clear();
+        self.block.extend_from_slice(&state.samples[from..to]);
+        drop(state);
+        self.block_pos = 0;
+        self.pos += to - from;
+        self.publish();
+        Some(true)
+    }
+}
+
+impl Iterator for StreamSource {
+    type Item = f32;
+    #[inline(always)]
+    fn next(&mut self) -> Option<f32> {
+        if self.block_pos >= self.block.len() {
+            // 欠载时输出静音而不在音频回调里阻塞等待
+            if !self.refill()? { return Some(0.0); }
+        }
+        let val = self.block[self.block_pos];
+        self.block_pos += 1;
+        Some(val)
+    }
+}
+
+impl Source for StreamSource {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { self.stream.channels }
+    fn sample_rate(&self) -> u32 { self.stream.sample_rate }
+    fn total_duration(&self) -> Option<Duration> { None }
+    // 只在内存里仍有的范围内跳转，供 A-B 循环回跳
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        let channels = self.stream.channels as usize;
+        let target = (pos.as_secs_f64() * self.stream.sample_rate as f64) as usize * channels;
+        if !self.stream.holds(target) {
+            return Err(rodio::source::SeekError::NotSupported { underlying_source: std::any::type_name::<Self>() });
+        }
+        self.pos = target;
+        self.block.clear();
+        self.block_pos = 0;
+        self.publish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::memory_source::ArcSliceSource;
+
+    const RATE: u32 = 48000;
+
+    // 读线程已跑完的流：不起子进程，直接摆好状态
+    fn finished(samples: Vec<f32>, origin: usize) -> Arc<PcmStream> {
+        Arc::new(PcmStream {
+            state: Mutex::new(State { samples, base: origin, cached: true, done: true }),
+            changed: Condvar::new(),
+            cursor: AtomicUsize::new(origin),
+            owner: AtomicUsize::new(0),
+            cancelled: AtomicBool::new(false),
+            child: Mutex::new(None),
+            log: Mutex::new(VecDeque::new()),
+            channels: 2,
+            sample_rate: RATE,
+        })
+    }
+
+    fn ramp(len: usize) -> Vec<f32> { (0..len).map(|i| i as f32).collect() }
+
+    #[test]
+    fn finished_stream_hands_over_its_buffer_without_copying() {
+        let samples = ramp(RATE as usize * 2 * 10);
+        let original = samples.as_ptr();
+        let stream = finished(samples, 0);
+        let taken = stream.take_samples().unwrap();
+        assert_eq!(taken.as_ptr(), original);
+        assert_eq!(taken.len(), RATE as usize * 2 * 10);
+        // 交出后流里不再留一份
+        assert!(stream.take_samples().is_none());
+        assert_eq!(stream.lock().samples.capacity(), 0);
+
+        // 之后每次 seek 只新建游标，所有源共享同一块内存
+        let sources: Vec<ArcSliceSource> = [0.0, 3.5, 9.99, 42.0].iter()
+            .map(|&t| ArcSliceSource::new(taken.clone(), 2, RATE).starting_at(Duration::from_secs_f64(t)))
+            .collect();
+        assert_eq!(Arc::strong_count(&taken), 1 + sources.len());
+        let mut at = sources[1].clone();
+        assert_eq!((at.next(), at.next()), (Some((3.5 * RATE as f64 * 2.0) as f32), Some((3.5 * RATE as f64 * 2.0) as f32 + 1.0)));
+        drop((sources, at));
+        assert_eq!(Arc::strong_count(&taken), 1);
+    }
+
+    #[test]
+    fn partial_or_cancelled_streams_never_hand_over() {
+        // 从曲中起的流只覆盖一段，不能当整曲用
+        assert!(finished(ramp(1024), 960).take_samples().is_none());
+        let cancelled = finished(ramp(1024), 0);
+        cancelled.cancel();
+        assert!(cancelled.take_samples().is_none());
+        let reading = finished(ramp(1024), 0);
+        reading.lock().done = false;
+        assert!(reading.take_samples().is_none());
+    }
+
+    #[test]
+    fn stream_source_copies_one_block_at_a_time() {
+        let stream = finished(ramp(RATE as usize * 2 * 10), 0);
+        let offset = RATE as usize * 2 * 7 + 1; // 落在帧中间，取整到帧首
+        let mut source = stream.source_at(offset);
+        assert_eq!(source.next(), Some((offset - 1) as f32));
+        assert_eq!(source.block.len(), BLOCK);
+        assert_eq!(source.block.capacity(), BLOCK);
+        // 读完一整块后才取下一块，缓冲不会增长
+        for _ in 0..BLOCK { source.next(); }
+        assert_eq!(source.block.capacity(), BLOCK);
+        assert_eq!(source.pos, offset - 1 + 2 * BLOCK);
+
+        // 流内 seek 同样只移动游标
+        source.try_seek(Duration::from_secs(2)).unwrap();
+        assert_eq!(source.next(), Some((RATE * 2 * 2) as f32));
+        assert_eq!(source.block.capacity(), BLOCK);
+    }
+}
@@ -0,0 +1,60 @@
+// src/audio/playback_error.rs
+
+use std::sync::mpsc::Sender;
+use rodio::{OutputStreamHandle, Sink};
+use serde::Serialize;
+
+// =================================================================
+// 🚨 播放错误：引擎里原本只打日志的失败路径统一上报为 playback-error
+// =================================================================
+// 引擎构造时拿到发送端，在任意线程上报；接收端留在音频 actor，每轮循环取出后发给前端，
+// 前端据此提示并跳到下一首。
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PlaybackErrorCode {
+    DecodeFailed,      // 后台整曲解码无法开始或中途崩溃
+    DeviceLost,        // 输出流已失效，建不出 Sink
+    FfmpegSpawnFailed,
+    FfmpegFailed,      // 进程已启动但没有产出音频
+    SeekFailed,        // seek 时整曲缓存不可用
+    LoadFailed,        // 其余载入失败 (读文件、探测格式等)
+    EnginePanicked,    // 指令执行中 panic，输出与引擎已重建
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PlaybackError {
+    pub code: PlaybackErrorCode,
+    pub message: String,
+    pub path: Option<String>,
+    pub engine: &'static str, // 与 PlayerState.engine_id 一致
+}
+
+#[derive(Clone)]
+pub struct ErrorReporter {
+    tx: Sender<PlaybackError>,
+    engine: &'static str,
+}
+
+impl ErrorReporter {
+    pub fn new(tx: Sender<PlaybackError>, engine: &'static str) -> Self { Self { tx, engine } }
+
+    pub fn report(&self, code: PlaybackErrorCode, message: impl Into<String>, path: Option<&str>) {
+        let error = PlaybackError { code, message: message.into(), path: path.map(str::to_string), engine: self.engine };
+        eprintln!("[AUDIO] {:?} ({}): {}", error.code, error.engine, error.message);
+        let _ = self.tx.send(error);
+    }
+
+    // 原样透传结果，失败时顺带上报
+    pub fn check<T>(&self, code: PlaybackErrorCode, path: &str, result: Result<T, String>) -> Result<T, String> {
+        if let Err(e) = &result { self.report(code, e.clone(), Some(path)); }
+        result
+    }
+
+    // 建不出 Sink 时上报 DEVICE_LOST，换上不接设备的空闲 Sink，引擎状态保持一致，等设备恢复或切换
+    pub fn new_sink(&self, handle: &OutputStreamHandle, path: Option<&str>) -> Sink {
+        Sink::try_new(handle).unwrap_or_else(|e| {
+            self.report(PlaybackErrorCode::DeviceLost, e.to_string(), path);
+            Sink::new_idle().0
+        })
+    }
+}
@@ -0,0 +1,191 @@
+// src/audio/recovery.rs
+
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use super::room::DspSnapshot;
+
+// =================================================================
+// 🩹 崩溃恢复快照：播放中每 15 秒、状态变动后防抖落盘，原子改名写入
+// =================================================================
+// 引擎段只在状态变动时重新序列化，周期写入只拼接新的播放位置；
+// 队列段由队列持有方预先序列化好交过来，这里不关心其结构。
+// 会话锁文件在正常退出时删除，启动时仍存在即视为上次崩溃。
+
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(15);
+const SNAPSHOT_DEBOUNCE: Duration = Duration::from_secs(1);
+const SNAPSHOT_FILE: &str = "player_snapshot.json";
+const SESSION_LOCK: &str = "session.lock";
+
+static DIRTY_SINCE: Mutex<Option<Instant>> = Mutex::new(None);
+static QUEUE_SECTION: Mutex<Option<String>> = Mutex::new(None);
+static PREVIOUS_CRASHED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EngineSection {
+    pub track: Option<String>,
+    pub engine_id: String,
+    pub device: String,
+    pub channel_mode: u16,
+    pub volume: f32,
+    pub resampler: String,
+    pub rate: f32,
+    pub limiter_enabled: bool,
+    pub dsp: DspSnapshot,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PlayerSnapshot {
+    pub position: f64,
+    pub was_playing: bool,
+    pub saved_at: i64,
+    #[serde(default)]
+    pub engine: Option<EngineSection>,
+    #[serde(default)]
+    pub queue: Option<serde_json::Value>,
+}
+
+pub fn mark_dirty() {
+    let mut since = DIRTY_SINCE.lock().unwrap_or_else(|e| e.into_inner());
+    if since.is_none() { *since = Some(Instant::now()); }
+}
+
+// 防抖窗口内的连续变动只落盘一次
+fn take_settled_dirty() -> bool {
+    let mut since = DIRTY_SINCE.lock().unwrap_or_else(|e| e.into_inner());
+    match *since {
+        Some(t) if t.elapsed() >= SNAPSHOT_DEBOUNCE => { *since = None; true }
+        _ => false,
+    }
+}
+
+pub fn note_queue<T: Serialize>(section: &T) {
+    if let Ok(json) = serde_json::to_string(section) {
+        *QUEUE_SECTION.lock().unwrap_or_else(|e| e.into_inner()) = Some(json);
+        mark_dirty();
+    }
+}
+
+pub struct SnapshotWriter {
+    dir: PathBuf,
+    engine_body: String,
+    last_write: Instant,
+}
+
+impl SnapshotWriter {
+    pub fn new(dir: PathBuf) -> Self { Self { dir, engine_body: "null".to_string(), last_write: Instant::now() } }
+
+    // 返回是否需要重新提供引擎段；位置与播放状态每次写入时现取
+    pub fn due(&self, playing: bool) -> Option<bool> {
+        let dirty = take_settled_dirty();
+        if dirty || (playing && self.last_write.elapsed() >= SNAPSHOT_INTERVAL) { Some(dirty) } else { None }
+    }
+
+    pub fn set_engine(&mut self, section: &EngineSection) {
+        if let Ok(json) = serde_json::to_string(section) { self.engine_body = json; }
+    }
+
+    pub fn write(&mut self, position: f64, was_playing: bool) {
+        self.last_write = Instant::now();
+        let queue = QUEUE_SECTION.lock().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_else(|| "null".to_string());
+        let body = format!(
+            "{{\"position\":{:.3},\"was_playing\":{},\"saved_at\":{},\"engine\":{},\"queue\":{}}}",
+            if position.is_finite() { position } else { 0.0 }, was_playing, chrono::Local::now().timestamp(), self.engine_body, queue
+        );
+        let tmp = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        let result = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&tmp, body))
+            .and_then(|_| fs::rename(&tmp, self.dir.join(SNAPSHOT_FILE)));
+        if let Err(e) = result { eprintln!("[RECOVERY] Failed to write snapshot: {}", e); }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SkippedField {
+    pub field: &'static str,
+    pub reason: String,
+}
+
+// 逐字段恢复的结果：缺失的文件 / 设备只跳过对应字段，其余照常恢复
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct RecoveryReport {
+    pub restored: Vec<&'static str>,
+    pub skipped: Vec<SkippedField>,
+    pub track: Option<String>,
+    pub position: f64,
+    pub was_playing: bool,
+}
+
+impl RecoveryReport {
+    pub fn record<T>(&mut self, field: &'static str, result: Result<T, String>) -> bool {
+        match result {
+            Ok(_) => { self.restored.push(field); true }
+            Err(reason) => { self.skipped.push(SkippedField { field, reason }); false }
+        }
+    }
+}
+
+pub fn load_snapshot(dir: &Path) -> Result<PlayerSnapshot, String> {
+    let json = fs::read_to_string(dir.join(SNAPSHOT_FILE)).map_err(|_| "NO_SNAPSHOT".to_string())?;
+    serde_json::from_str(&json).map_err(|e| format!("SNAPSHOT_CORRUPT: {}", e))
+}
+
+// 启动时调用：锁文件仍在说明上次没有正常退出
+pub fn begin_session(dir: &Path) -> bool {
+    let lock = dir.join(SESSION_LOCK);
+    let crashed = lock.exists();
+    PREVIOUS_CRASHED.store(crashed, Ordering::SeqCst);
+    let _ = fs::create_dir_all(dir);
+    let _ = fs::write(lock, chrono::Local::now().timestamp().to_string());
+    crashed
+}
+
+pub fn end_session(dir: &Path) { let _ = fs::remove_file(dir.join(SESSION_LOCK)); }
+
+pub fn previous_crashed() -> bool { PREVIOUS_CRASHED.load(Ordering::SeqCst) }
+
+// 恢复过一次后不再提示
+pub fn clear_crash_flag() { PREVIOUS_CRASHED.store(false, Ordering::SeqCst); }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crash_cycle_and_damaged_snapshots() {
+        let dir = std::env::temp_dir().join(format!("agm_recovery_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        // 首次启动：没有锁文件，也没有快照
+        assert!(!begin_session(&dir));
+        assert_eq!(load_snapshot(&dir).unwrap_err(), "NO_SNAPSHOT");
+
+        // 从未提供引擎段 / 队列段时按缺失读回，而不是整体失败；非有限位置写成 0
+        let mut writer = SnapshotWriter::new(dir.clone());
+        writer.write(f64::NAN, false);
+        let snapshot = load_snapshot(&dir).unwrap();
+        assert_eq!(snapshot.position, 0.0);
+        assert!(snapshot.engine.is_none());
+        assert!(snapshot.queue.is_none());
+
+        note_queue(&serde_json::json!({ "paths": ["/music/a.flac"], "index": 0 }));
+        writer.write(61.25, true);
+        let snapshot = load_snapshot(&dir).unwrap();
+        assert_eq!((snapshot.position, snapshot.was_playing), (61.25, true));
+        assert_eq!(snapshot.queue.unwrap()["paths"][0], "/music/a.flac");
+        assert!(!dir.join(format!("{}.tmp", SNAPSHOT_FILE)).exists());
+
+        // 没有 end_session 就再次启动即为崩溃；正常退出后不再提示
+        assert!(begin_session(&dir));
+        assert!(previous_crashed());
+        end_session(&dir);
+        assert!(!begin_session(&dir));
+        end_session(&dir);
+
+        fs::write(dir.join(SNAPSHOT_FILE), "{\"position\":").unwrap();
+        assert!(load_snapshot(&dir).unwrap_err().starts_with("SNAPSHOT_CORRUPT"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
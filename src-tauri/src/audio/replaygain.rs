@@ -0,0 +1,201 @@
+// src/audio/replaygain.rs
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use lofty::{read_from_path, ItemKey, Tag, TagExt, TaggedFile, TaggedFileExt};
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+use super::loudness::{self, REFERENCE_LUFS};
+use crate::util::jobs::JobHandle;
+
+// =================================================================
+// 🎚️ ReplayGain：读取曲目 / 专辑增益标签，载入时作为前级增益叠加
+// =================================================================
+// Opus 的 R128_* 以 -23 LUFS 为参考、Q7.8 定点整数存储，换算到 ReplayGain 的 -18 LUFS 参考需再加 5dB。
+// 标签带峰值时，增益不会把峰值推到满幅以上。
+pub const FALLBACK_MAX_DB: f32 = 15.0;
+const R128_TO_REPLAYGAIN_DB: f32 = 5.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReplayGainTags {
+    pub track_gain_db: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain_db: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+// "-6.54 dB" / "+1.2dB" / "0.98765"
+fn parse_number(raw: &str) -> Option<f32> {
+    let trimmed = raw.trim();
+    let numeric = trimmed.strip_suffix("dB").or_else(|| trimmed.strip_suffix("db")).unwrap_or(trimmed).trim();
+    numeric.parse::<f32>().ok().filter(|v| v.is_finite())
+}
+
+fn parse_r128(raw: &str) -> Option<f32> {
+    raw.trim().parse::<i16>().ok().map(|q| q as f32 / 256.0 + R128_TO_REPLAYGAIN_DB)
+}
+
+fn lookup(tag: &Tag, key: ItemKey, raw_key: &str) -> Option<String> {
+    tag.get_string(&key).or_else(|| tag.get_string(&ItemKey::Unknown(raw_key.to_string()))).map(str::to_string)
+}
+
+impl ReplayGainTags {
+    fn from_tag(tag: &Tag) -> Self {
+        let gain = |key, raw_key, r128_key| lookup(tag, key, raw_key).and_then(|v| parse_number(&v))
+            .or_else(|| tag.get_string(&ItemKey::Unknown(String::from(r128_key))).and_then(parse_r128));
+        let peak = |key, raw_key| lookup(tag, key, raw_key).and_then(|v| parse_number(&v)).filter(|p| *p > 0.0);
+        Self {
+            track_gain_db: gain(ItemKey::ReplayGainTrackGain, "REPLAYGAIN_TRACK_GAIN", "R128_TRACK_GAIN"),
+            track_peak: peak(ItemKey::ReplayGainTrackPeak, "REPLAYGAIN_TRACK_PEAK"),
+            album_gain_db: gain(ItemKey::ReplayGainAlbumGain, "REPLAYGAIN_ALBUM_GAIN", "R128_ALBUM_GAIN"),
+            album_peak: peak(ItemKey::ReplayGainAlbumPeak, "REPLAYGAIN_ALBUM_PEAK"),
+        }
+    }
+
+    // 同一文件可能同时带 ID3v2 与 APE 等多个标签，取第一个含增益的
+    pub fn from_file(file: &TaggedFile) -> Option<Self> {
+        file.tags().iter().map(Self::from_tag).find(|t| t.track_gain_db.is_some() || t.album_gain_db.is_some())
+    }
+
+    pub fn read(path: &str) -> Option<Self> {
+        read_from_path(path).ok().and_then(|f| Self::from_file(&f))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayGainMode { #[default] Off, Track, Album }
+
+impl ReplayGainMode {
+    pub fn parse(mode: &str) -> Result<Self, String> {
+        match mode {
+            "off" => Ok(Self::Off),
+            "track" => Ok(Self::Track),
+            "album" => Ok(Self::Album),
+            _ => Err(format!("UNKNOWN_REPLAYGAIN_MODE: {}", mode)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReplayGainPrefs {
+    pub mode: ReplayGainMode,
+    pub fallback_db: f32, // 无标签曲目使用的增益
+}
+
+impl ReplayGainPrefs {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.fallback_db.is_finite() || !(-FALLBACK_MAX_DB..=FALLBACK_MAX_DB).contains(&self.fallback_db) {
+            return Err(format!("REPLAYGAIN_INVALID_FALLBACK: gain must be within ±{}dB", FALLBACK_MAX_DB));
+        }
+        Ok(())
+    }
+
+    // 专辑模式缺专辑增益时退回曲目增益，反之亦然；两者都没有才用兜底增益
+    pub fn gain_db(&self, tags: Option<&ReplayGainTags>) -> f32 {
+        let (primary, secondary) = match self.mode {
+            ReplayGainMode::Off => return 0.0,
+            ReplayGainMode::Track => (tags.map(|t| (t.track_gain_db, t.track_peak)), tags.map(|t| (t.album_gain_db, t.album_peak))),
+            ReplayGainMode::Album => (tags.map(|t| (t.album_gain_db, t.album_peak)), tags.map(|t| (t.track_gain_db, t.track_peak))),
+        };
+        let chosen = [primary, secondary].into_iter().flatten().find_map(|(gain, peak)| gain.map(|g| (g, peak)));
+        match chosen {
+            Some((gain, Some(peak))) => gain.min(-20.0 * peak.log10()),
+            Some((gain, None)) => gain,
+            None => self.fallback_db,
+        }
+    }
+
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("replaygain.json")).ok()
+            .and_then(|json| serde_json::from_str::<Self>(&json).ok())
+            .filter(|p| p.validate().is_ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(dir.join("replaygain.json"), json).map_err(|e| e.to_string())
+    }
+}
+
+// =================================================================
+// 📏 ReplayGain 扫描：整曲解码测 R128 积分响度与真峰值，可选写回标签
+// =================================================================
+// 增益以 -18 LUFS 为参考 (ReplayGain 2.0)；无法解码的文件记一条错误继续下一个。
+pub const SCAN_JOB_ID: &str = "replaygain_scan";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ScanResult {
+    pub path: String,
+    pub track_gain_db: Option<f32>,
+    pub track_peak: Option<f32>, // 真峰值 (线性)
+    pub integrated_lufs: Option<f64>,
+    pub tags_written: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ScanProgress {
+    pub done: usize,
+    pub total: usize,
+    pub result: ScanResult,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ScanSummary {
+    pub results: Vec<ScanResult>,
+    pub cancelled: bool,
+}
+
+fn write_tags(path: &str, gain_db: f32, peak: f32) -> Result<(), String> {
+    let mut tagged_file = read_from_path(path).map_err(|e| e.to_string())?;
+    if tagged_file.primary_tag().is_none() {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let tag = tagged_file.primary_tag_mut().ok_or_else(|| "TAG_UNSUPPORTED".to_string())?;
+    let written = tag.insert_text(ItemKey::ReplayGainTrackGain, format!("{:.2} dB", gain_db))
+        && tag.insert_text(ItemKey::ReplayGainTrackPeak, format!("{:.6}", peak));
+    if !written { return Err("TAG_UNSUPPORTED".to_string()); }
+    tag.save_to_path(path).map_err(|e| e.to_string())
+}
+
+// 取消时返回 None
+fn scan_one(path: &str, write: bool, job: &JobHandle) -> Option<ScanResult> {
+    let mut result = ScanResult { path: path.to_string(), track_gain_db: None, track_peak: None, integrated_lufs: None, tags_written: false, error: None };
+    match loudness::measure(path, job) {
+        Ok(None) => return None,
+        Err(e) => result.error = Some(e),
+        Ok(Some(measured)) => {
+            let gain_db = (REFERENCE_LUFS - measured.integrated_lufs) as f32;
+            let peak = measured.true_peak.unwrap_or(measured.sample_peak);
+            result.track_gain_db = Some(gain_db);
+            result.track_peak = Some(peak);
+            result.integrated_lufs = Some(measured.integrated_lufs);
+            if write {
+                match write_tags(path, gain_db, peak) {
+                    Ok(()) => result.tags_written = true,
+                    Err(e) => result.error = Some(format!("TAG_WRITE_FAILED: {}", e)),
+                }
+            }
+        }
+    }
+    Some(result)
+}
+
+pub fn scan(paths: &[String], write: bool, job: &JobHandle, on_progress: impl Fn(ScanProgress) + Sync) -> ScanSummary {
+    let done = AtomicUsize::new(0);
+    let total = paths.len();
+    let results: Vec<ScanResult> = paths.par_iter().filter_map(|path| {
+        if job.is_cancelled() { return None; }
+        let result = scan_one(path, write, job)?;
+        let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+        job.set_progress(done as f32 / total.max(1) as f32);
+        on_progress(ScanProgress { done, total, result: result.clone() });
+        Some(result)
+    }).collect();
+    ScanSummary { results, cancelled: job.is_cancelled() }
+}
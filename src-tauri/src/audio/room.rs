@@ -0,0 +1,67 @@
+// src/audio/room.rs
+
+use serde::{Serialize, Deserialize};
+use std::time::Duration;
+use super::dsp::{AutoLevel, BassBoost, Crossfeed, Dither, EqBand, LfeCrossover, UpmixParams};
+
+// =================================================================
+// 🛋️ 房间预设：输出设备 + 声道模式 + 音量 + DSP 的整体快照
+// =================================================================
+// 应用时按 淡出 → 切设备 → 切声道 → 换 DSP → 淡入 顺序执行，
+// 全程处于 ApplyingRoomPreset 阶段，期间的前端指令照常收拢为意图。
+
+pub const ROOM_FADE: Duration = Duration::from_millis(120);
+
+pub const ROOM_STEPS: &[&str] = &["fade_out", "device", "channel_mode", "dsp", "fade_in"];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DspSnapshot {
+    #[serde(default)]
+    pub eq: Vec<EqBand>,
+    #[serde(default)]
+    pub bass: BassBoost,
+    #[serde(default = "unit_width")]
+    pub stereo_width: f32,
+    #[serde(default)]
+    pub preamp_db: f32,
+    #[serde(default)]
+    pub balance: f32,
+    #[serde(default)]
+    pub crossfeed: Crossfeed,
+    #[serde(default)]
+    pub lfe_crossover: LfeCrossover,
+    #[serde(default)]
+    pub upmix: UpmixParams,
+    #[serde(default)]
+    pub dither: Dither,
+    #[serde(default)]
+    pub downmix_lfe: bool,
+    #[serde(default)]
+    pub auto_level: AutoLevel,
+}
+
+fn unit_width() -> f32 { 1.0 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RoomPreset {
+    pub name: String,
+    pub device: String,
+    pub channel_mode: u16,
+    pub volume: f32,
+    pub dsp: DspSnapshot,
+}
+
+impl RoomPreset {
+    // 名称之外的内容全部一致才算仍处于该预设
+    pub fn matches(&self, other: &RoomPreset) -> bool {
+        self.device == other.device && self.channel_mode == other.channel_mode && self.volume == other.volume && self.dsp == other.dsp
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RoomProgress {
+    pub name: String,
+    pub step: &'static str,
+    pub index: usize,
+    pub total: usize,
+}
@@ -0,0 +1,285 @@
+// src/audio/silence.rs
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
+use rodio::{Decoder, Source};
+use serde::{Serialize, Deserialize};
+use tauri::{AppHandle, Manager};
+use super::galaxy::LoopRegion;
+use super::loudness::file_stamp;
+use crate::util::cancel::CancellationToken;
+
+// =================================================================
+// 🤫 跳过曲中静音：在整曲 PCM 缓存上预先标出静音段，播放源越过它们
+// =================================================================
+// 静音段两端各保留一小段，衔接不显突兀；A-B 循环生效期间不跳，避免循环源的计数错位。
+// 跳过的时长计入播放位置，并累计成待通知量，由管理层巡检时推给前端。
+const KEEP_MS: u32 = 150;
+pub const THRESHOLD_RANGE_DB: (f32, f32) = (-90.0, -20.0);
+pub const MIN_LEN_RANGE_MS: (u32, u32) = (500, 60_000);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SkipSilence {
+    pub enabled: bool,
+    pub threshold_db: f32, // 各声道都低于此电平才算静音
+    pub min_len_ms: u32,
+}
+
+impl Default for SkipSilence {
+    fn default() -> Self { Self { enabled: false, threshold_db: -50.0, min_len_ms: 2000 } }
+}
+
+impl SkipSilence {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.threshold_db.is_finite() || !(THRESHOLD_RANGE_DB.0..=THRESHOLD_RANGE_DB.1).contains(&self.threshold_db) {
+            return Err(format!("SKIP_SILENCE_INVALID_THRESHOLD: must be within {}–{}dB", THRESHOLD_RANGE_DB.0, THRESHOLD_RANGE_DB.1));
+        }
+        if !(MIN_LEN_RANGE_MS.0..=MIN_LEN_RANGE_MS.1).contains(&self.min_len_ms) {
+            return Err(format!("SKIP_SILENCE_INVALID_LENGTH: must be within {}–{}ms", MIN_LEN_RANGE_MS.0, MIN_LEN_RANGE_MS.1));
+        }
+        Ok(())
+    }
+
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("skip_silence.json")).ok()
+            .and_then(|json| serde_json::from_str::<Self>(&json).ok())
+            .filter(|s| s.validate().is_ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(dir.join("skip_silence.json"), json).map_err(|e| e.to_string())
+    }
+}
+
+// 以样本下标表示、按帧对齐的待跳区间，升序且互不重叠
+pub struct SilenceMap {
+    regions: Vec<Range<usize>>,
+}
+
+impl SilenceMap {
+    pub fn scan(samples: &[f32], channels: u16, sample_rate: u32, prefs: &SkipSilence) -> Self {
+        let channels = channels.max(1) as usize;
+        let threshold = 10f32.powf(prefs.threshold_db / 20.0);
+        let min_frames = prefs.min_len_ms as usize * sample_rate as usize / 1000;
+        let keep = KEEP_MS as usize * sample_rate as usize / 1000;
+        let mut regions = Vec::new();
+        let mut push = |start: usize, end: usize| {
+            if end - start >= min_frames && end - start > 2 * keep { regions.push((start + keep) * channels..(end - keep) * channels); }
+        };
+        let mut run_start = None;
+        let mut frames = 0;
+        for (frame, chunk) in samples.chunks_exact(channels).enumerate() {
+            let silent = chunk.iter().all(|s| s.abs() < threshold);
+            match (silent, run_start) {
+                (true, None) => run_start = Some(frame),
+                (false, Some(start)) => { push(start, frame); run_start = None; }
+                _ => {}
+            }
+            frames = frame + 1;
+        }
+        if let Some(start) = run_start { push(start, frames); }
+        Self { regions }
+    }
+}
+
+// 同一份 PCM 缓存与设置只扫描一次；只弱引用缓存，不延长其生命周期
+#[derive(Default)]
+pub struct SilenceCache {
+    entry: Option<(Weak<Vec<f32>>, SkipSilence, Arc<SilenceMap>)>,
+}
+
+impl SilenceCache {
+    pub fn get(&mut self, samples: &Arc<Vec<f32>>, channels: u16, sample_rate: u32, prefs: SkipSilence) -> Option<Arc<SilenceMap>> {
+        if !prefs.enabled { return None; }
+        if let Some((cached, cached_prefs, map)) = &self.entry {
+            if cached.as_ptr() == Arc::as_ptr(samples) && *cached_prefs == prefs { return Some(map.clone()); }
+        }
+        let map = Arc::new(SilenceMap::scan(samples, channels, sample_rate, &prefs));
+        self.entry = Some((Arc::downgrade(samples), prefs, map.clone()));
+        Some(map)
+    }
+}
+
+fn add_secs(slot: &AtomicU64, secs: f64) {
+    let _ = slot.fetch_update(Ordering::AcqRel, Ordering::Relaxed, |bits| Some((f64::from_bits(bits) + secs).to_bits()));
+}
+
+// since_anchor 随播放位置的每次绝对定位 (load / seek) 清零；pending 由管理层取走后发 silence-skipped
+#[derive(Default)]
+pub struct SkipLog {
+    since_anchor: AtomicU64,
+    pending: AtomicU64,
+}
+
+impl SkipLog {
+    fn record(&self, secs: f64) {
+        add_secs(&self.since_anchor, secs);
+        add_secs(&self.pending, secs);
+    }
+    pub fn since_anchor(&self) -> f64 { f64::from_bits(self.since_anchor.load(Ordering::Acquire)) }
+    pub fn reset_anchor(&self) { self.since_anchor.store(0f64.to_bits(), Ordering::Release); }
+    pub fn take_pending(&self) -> f64 { f64::from_bits(self.pending.swap(0f64.to_bits(), Ordering::AcqRel)) }
+}
+
+// 挂在内存源上的游标：读到静音段起点时给出跳转目标
+#[derive(Clone)]
+pub struct SilenceCursor {
+    map: Arc<SilenceMap>,
+    next: usize,
+    log: Arc<SkipLog>,
+    loop_region: Arc<LoopRegion>,
+    samples_per_sec: f64,
+}
+
+impl SilenceCursor {
+    pub fn new(map: Arc<SilenceMap>, log: Arc<SkipLog>, loop_region: Arc<LoopRegion>, channels: u16, sample_rate: u32) -> Self {
+        Self { map, next: 0, log, loop_region, samples_per_sec: channels.max(1) as f64 * sample_rate as f64 }
+    }
+
+    // 源被 seek 后重新定位到第一个尚未越过的区间
+    pub fn reposition(&mut self, pos: usize) {
+        self.next = self.map.regions.partition_point(|r| r.end <= pos);
+    }
+
+    #[inline(always)]
+    pub fn jump_from(&mut self, pos: usize) -> Option<usize> {
+        let region = self.map.regions.get(self.next)?;
+        if pos < region.start { return None; }
+        self.next += 1;
+        if pos >= region.end || self.loop_region.bounds().is_some() { return None; }
+        self.log.record((region.end - pos) as f64 / self.samples_per_sec);
+        Some(region.end)
+    }
+}
+
+// =================================================================
+// ✂️ 首尾静音：整曲解码一次测出片头 / 片尾的静音长度，按 (大小, mtime) 缓存
+// =================================================================
+// 自动修剪开启时，载入后从片头静音结束处起播，播到片尾静音开始处即视为曲终。
+// 缓存未命中时由管理层放到后台任务里测量，结果回到 actor 后再生效，载入本身不等整曲解码。
+const BOUNDS_THRESHOLD_DB: f32 = -60.0;
+const MIN_TRIM_MS: u32 = 50; // 更短的首尾静音不值得为之 seek
+const MIN_TRIMMED_SECS: f64 = 1.0; // 修剪后至少留下这么长，几乎全静音的曲目不修剪
+const MAX_CACHED_BOUNDS: usize = 4096;
+const CANCEL_CHECK_SAMPLES: usize = 1 << 16;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SilenceBounds {
+    pub lead_in_ms: u32,
+    pub lead_out_ms: u32,
+    pub duration: f64, // 秒，按解码出的样本数计
+}
+
+impl SilenceBounds {
+    // 修剪后的起止时间 (秒)；首尾都不足 MIN_TRIM_MS 时为 None
+    pub fn trim_points(&self) -> Option<(f64, f64)> {
+        if self.lead_in_ms < MIN_TRIM_MS && self.lead_out_ms < MIN_TRIM_MS { return None; }
+        let start = if self.lead_in_ms >= MIN_TRIM_MS { self.lead_in_ms as f64 / 1000.0 } else { 0.0 };
+        let end = if self.lead_out_ms >= MIN_TRIM_MS { self.duration - self.lead_out_ms as f64 / 1000.0 } else { self.duration };
+        Some((start, end)).filter(|(s, e)| e - s >= MIN_TRIMMED_SECS)
+    }
+}
+
+// 可随 token 中途放弃 (自动修剪时被切歌顶替)，放弃时返回 CANCELLED 且不写缓存
+pub fn analyze(path: &str, cancel: Option<&CancellationToken>) -> Result<SilenceBounds, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let source = Decoder::new(BufReader::new(file)).map_err(|e| e.to_string())?.convert_samples::<f32>();
+    let channels = source.channels().max(1) as usize;
+    let sample_rate = source.sample_rate().max(1) as f64;
+    let threshold = 10f32.powf(BOUNDS_THRESHOLD_DB / 20.0);
+    let (mut first_loud, mut last_loud, mut samples) = (None, 0usize, 0usize);
+    for sample in source {
+        if sample.abs() >= threshold {
+            let frame = samples / channels;
+            first_loud.get_or_insert(frame);
+            last_loud = frame + 1;
+        }
+        samples += 1;
+        if samples % CANCEL_CHECK_SAMPLES == 0 && cancel.is_some_and(|c| c.is_cancelled()) { return Err("CANCELLED".to_string()); }
+    }
+    let frames = samples / channels;
+    if frames == 0 { return Err("DECODE_EMPTY".to_string()); }
+    let ms = |f: usize| (f as f64 * 1000.0 / sample_rate).round() as u32;
+    let (lead_in, lead_out) = match first_loud {
+        Some(first) => (ms(first), ms(frames.saturating_sub(last_loud))),
+        None => (ms(frames), 0),
+    };
+    Ok(SilenceBounds { lead_in_ms: lead_in, lead_out_ms: lead_out, duration: frames as f64 / sample_rate })
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CachedBounds {
+    size: u64,
+    mtime: i64,
+    bounds: SilenceBounds,
+    #[serde(default)]
+    used: u64, // 最近一次命中的逻辑时钟，超出上限时淘汰最久未用的
+}
+
+// 常驻内存，首次使用时从磁盘读入；只在新增条目时写回，条目数有上限
+#[derive(Serialize, Deserialize, Default)]
+pub struct BoundsCache {
+    entries: HashMap<String, CachedBounds>,
+    #[serde(skip)]
+    clock: u64,
+}
+
+static CACHE: OnceLock<Mutex<BoundsCache>> = OnceLock::new();
+
+impl BoundsCache {
+    fn path(app: &AppHandle) -> PathBuf {
+        app.path().app_local_data_dir().unwrap_or_else(|_| PathBuf::from("./data")).join("silence_bounds_cache.json")
+    }
+    fn load(app: &AppHandle) -> Self {
+        let mut cache: Self = fs::read_to_string(Self::path(app)).ok().and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default();
+        cache.clock = cache.entries.values().map(|e| e.used).max().unwrap_or(0);
+        cache
+    }
+    fn save(&self, app: &AppHandle) {
+        let path = Self::path(app);
+        if let Some(dir) = path.parent() { let _ = fs::create_dir_all(dir); }
+        if let Ok(json) = serde_json::to_string(self) { let _ = fs::write(path, json); }
+    }
+    fn shared(app: &AppHandle) -> MutexGuard<'static, BoundsCache> {
+        CACHE.get_or_init(|| Mutex::new(Self::load(app))).lock().unwrap_or_else(|e| e.into_inner())
+    }
+    fn lookup(&mut self, path: &str) -> Option<SilenceBounds> {
+        let (size, mtime) = file_stamp(path)?;
+        self.clock += 1;
+        let entry = self.entries.get_mut(path).filter(|e| e.size == size && e.mtime == mtime)?;
+        entry.used = self.clock;
+        Some(entry.bounds)
+    }
+    fn insert(&mut self, path: &str, bounds: SilenceBounds) {
+        let Some((size, mtime)) = file_stamp(path) else { return };
+        self.clock += 1;
+        self.entries.insert(path.to_string(), CachedBounds { size, mtime, bounds, used: self.clock });
+        while self.entries.len() > MAX_CACHED_BOUNDS {
+            let Some(oldest) = self.entries.iter().min_by_key(|(_, e)| e.used).map(|(p, _)| p.clone()) else { break };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+// 只查缓存，不解码；actor 线程上载入时用它决定是否需要后台测量
+pub fn cached_bounds(app: &AppHandle, path: &str) -> Option<SilenceBounds> {
+    BoundsCache::shared(app).lookup(path)
+}
+
+// 命中缓存直接返回，否则整曲解码测量并写回缓存；解码期间不持有缓存锁
+pub fn bounds(app: &AppHandle, path: &str, cancel: Option<&CancellationToken>) -> Result<SilenceBounds, String> {
+    if let Some(bounds) = cached_bounds(app, path) { return Ok(bounds); }
+    let bounds = analyze(path, cancel)?;
+    let mut cache = BoundsCache::shared(app);
+    cache.insert(path, bounds);
+    cache.save(app);
+    Ok(bounds)
+}
@@ -0,0 +1,177 @@
+// src/audio/spectrum.rs
+
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::time::Duration;
+use realfft::RealFftPlanner;
+use serde::Serialize;
+use tauri::AppHandle;
+use crate::modules::api_schema::events;
+use super::threads;
+
+// =================================================================
+// 🌈 可视化：UpmixSource 末端的旁路抽头 + 按需运行的频谱 / 示波器线程
+// =================================================================
+// 抽头把每帧 L/R 写进预分配的无锁环形缓冲，没有读者时只多一次原子读；
+// 两个引擎共用这一个全局抽头，切换引擎不影响可视化。读端偶尔读到正在被覆盖的样本，对显示无妨。
+// 比特完美模式不经 UpmixSource，没有可视化数据。
+const FFT_SIZE: usize = 1024;
+const RING_SIZE: usize = 4096; // 帧数，2 的幂，按位与取模
+pub const BANDS: usize = 32;
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+const BAND_RANGE_HZ: (f32, f32) = (20.0, 20_000.0);
+const FLOOR_DB: f32 = -100.0;
+const SCOPE_INTERVAL: Duration = Duration::from_millis(16);
+pub const SCOPE_FRAMES_RANGE: (usize, usize) = (16, RING_SIZE);
+
+// 抽头的读者，按位登记
+const SPECTRUM: u32 = 1;
+const SCOPE: u32 = 2;
+
+struct Tap {
+    readers: AtomicU32,
+    write: AtomicUsize, // 已写入的帧数
+    sample_rate: AtomicU32,
+    ring: Box<[AtomicU32]>, // 交错的 L/R，f32 bits
+}
+
+static TAP: OnceLock<Tap> = OnceLock::new();
+// 各读者线程的停止信号
+static SPECTRUM_WORKER: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+static SCOPE_WORKER: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+static SCOPE_FRAMES: AtomicUsize = AtomicUsize::new(512);
+
+fn tap() -> &'static Tap {
+    TAP.get_or_init(|| Tap {
+        readers: AtomicU32::new(0),
+        write: AtomicUsize::new(0),
+        sample_rate: AtomicU32::new(48000),
+        ring: (0..RING_SIZE * 2).map(|_| AtomicU32::new(0)).collect(),
+    })
+}
+
+impl Tap {
+    #[inline]
+    fn frame(&self, index: usize) -> (f32, f32) {
+        let slot = (index & (RING_SIZE - 1)) * 2;
+        (f32::from_bits(self.ring[slot].load(Ordering::Relaxed)), f32::from_bits(self.ring[slot + 1].load(Ordering::Relaxed)))
+    }
+}
+
+// 音源链建立时登记采样率，不在音频回调里做
+pub fn set_sample_rate(sample_rate: u32) { tap().sample_rate.store(sample_rate, Ordering::Relaxed); }
+
+#[inline(always)]
+pub fn push(left: f32, right: f32) {
+    let Some(tap) = TAP.get() else { return };
+    if tap.readers.load(Ordering::Relaxed) == 0 { return; }
+    let slot = (tap.write.load(Ordering::Relaxed) & (RING_SIZE - 1)) * 2;
+    tap.ring[slot].store(left.to_bits(), Ordering::Relaxed);
+    tap.ring[slot + 1].store(right.to_bits(), Ordering::Relaxed);
+    tap.write.fetch_add(1, Ordering::Release);
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SpectrumFrame {
+    pub bands: Vec<f32>, // dBFS，低频在前，满幅正弦约为 0
+}
+
+// 已在运行则什么都不做
+fn launch(worker: &Mutex<Option<Arc<AtomicBool>>>, reader: u32, name: &'static str, run: impl FnOnce(Arc<AtomicBool>) + Send + 'static) {
+    let mut worker = worker.lock().unwrap_or_else(|e| e.into_inner());
+    if worker.is_some() { return; }
+    let stop = Arc::new(AtomicBool::new(false));
+    *worker = Some(stop.clone());
+    tap().readers.fetch_or(reader, Ordering::Relaxed);
+    threads::spawn(name, move || run(stop));
+}
+
+fn halt(worker: &Mutex<Option<Arc<AtomicBool>>>, reader: u32) {
+    if let Some(stop) = worker.lock().unwrap_or_else(|e| e.into_inner()).take() { stop.store(true, Ordering::Relaxed); }
+    if let Some(tap) = TAP.get() { tap.readers.fetch_and(!reader, Ordering::Relaxed); }
+}
+
+pub fn start(app: AppHandle) { launch(&SPECTRUM_WORKER, SPECTRUM, "spectrum-fft", move |stop| run(app, stop)); }
+
+pub fn stop() { halt(&SPECTRUM_WORKER, SPECTRUM); }
+
+// 运行中再次调用只更新帧数
+pub fn scope_start(app: AppHandle, frames: usize) -> Result<(), String> {
+    if !(SCOPE_FRAMES_RANGE.0..=SCOPE_FRAMES_RANGE.1).contains(&frames) {
+        return Err(format!("SCOPE_INVALID_FRAME_SIZE: must be within {}–{}", SCOPE_FRAMES_RANGE.0, SCOPE_FRAMES_RANGE.1));
+    }
+    SCOPE_FRAMES.store(frames, Ordering::Relaxed);
+    launch(&SCOPE_WORKER, SCOPE, "scope-feed", move |stop| run_scope(app, stop));
+    Ok(())
+}
+
+pub fn scope_stop() { halt(&SCOPE_WORKER, SCOPE); }
+
+// 对数等分的频带边界，上限不超过奈奎斯特频率
+fn band_edges(sample_rate: f32) -> [f32; BANDS + 1] {
+    let (low, high) = (BAND_RANGE_HZ.0, BAND_RANGE_HZ.1.min(sample_rate / 2.0));
+    let ratio = (high / low).powf(1.0 / BANDS as f32);
+    let mut edges = [low; BANDS + 1];
+    for i in 1..=BANDS { edges[i] = edges[i - 1] * ratio; }
+    edges
+}
+
+// 每个频带取区间内的最大幅度；低频带窄于一个 bin 时取中心频率所在的 bin
+fn band_levels(bins: &[realfft::num_complex::Complex<f32>], sample_rate: f32, norm: f32) -> Vec<f32> {
+    let bin_hz = sample_rate / FFT_SIZE as f32;
+    let edges = band_edges(sample_rate);
+    let last_bin = bins.len() - 1;
+    edges.windows(2).map(|edge| {
+        let (lo, hi) = ((edge[0] / bin_hz).ceil() as usize, ((edge[1] / bin_hz).floor() as usize).min(last_bin));
+        let magnitude = if lo <= hi {
+            bins[lo..=hi].iter().map(|c| c.norm()).fold(0.0, f32::max)
+        } else {
+            bins[((edge[0] * edge[1]).sqrt() / bin_hz).round().min(last_bin as f32) as usize].norm()
+        };
+        (20.0 * (magnitude / norm).max(1e-9).log10()).max(FLOOR_DB)
+    }).collect()
+}
+
+fn run(app: AppHandle, stop: Arc<AtomicBool>) {
+    let tap = tap();
+    let fft = RealFftPlanner::<f32>::new().plan_fft_forward(FFT_SIZE);
+    let window: Vec<f32> = (0..FFT_SIZE).map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (FFT_SIZE - 1) as f32).cos()).collect();
+    let norm = window.iter().sum::<f32>() / 2.0;
+    let (mut input, mut bins, mut scratch) = (fft.make_input_vec(), fft.make_output_vec(), fft.make_scratch_vec());
+    let mut last_write = usize::MAX;
+    while threads::sleep(FRAME_INTERVAL) && !stop.load(Ordering::Relaxed) {
+        // 暂停 / 停止后不再有新样本，不重复推送同一帧
+        let write = tap.write.load(Ordering::Acquire);
+        if write == last_write { continue; }
+        last_write = write;
+        let start = write.wrapping_sub(FFT_SIZE);
+        for (i, slot) in input.iter_mut().enumerate() {
+            let (left, right) = tap.frame(start.wrapping_add(i));
+            *slot = (left + right) * 0.5 * window[i];
+        }
+        if fft.process_with_scratch(&mut input, &mut bins, &mut scratch).is_err() { continue; }
+        let sample_rate = tap.sample_rate.load(Ordering::Relaxed).max(1) as f32;
+        let _ = events::SPECTRUM_DATA.emit(&app, &SpectrumFrame { bands: band_levels(&bins, sample_rate, norm) });
+    }
+}
+
+// 推送最近 N 帧交错的 L/R；两次推送之间积累的帧多于 N 时，把这一整段等距抽取成 N 帧
+fn run_scope(app: AppHandle, stop: Arc<AtomicBool>) {
+    let tap = tap();
+    let mut last_write = tap.write.load(Ordering::Acquire);
+    while threads::sleep(SCOPE_INTERVAL) && !stop.load(Ordering::Relaxed) {
+        let write = tap.write.load(Ordering::Acquire);
+        if write == last_write { continue; }
+        let frames = SCOPE_FRAMES.load(Ordering::Relaxed);
+        let span = write.wrapping_sub(last_write).clamp(frames, RING_SIZE);
+        last_write = write;
+        let start = write.wrapping_sub(span);
+        let mut samples = Vec::with_capacity(frames * 2);
+        for i in 0..frames {
+            let (left, right) = tap.frame(start.wrapping_add(i * span / frames));
+            samples.push(left);
+            samples.push(right);
+        }
+        let _ = events::SCOPE_DATA.emit(&app, &samples);
+    }
+}
@@ -0,0 +1,221 @@
+// src/audio/stretch.rs
+
+use rodio::Source;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use super::dsp_registry::{hash_params, DspStage, StageMeter};
+use std::sync::Arc;
+
+// =================================================================
+// ⏩ WSOLA 变速不变调：有声书/播客倍速播放不再“花栗鼠化”
+// =================================================================
+pub const MIN_RATE: f32 = 0.5;
+pub const MAX_RATE: f32 = 3.0;
+
+const FRAME_MS: f32 = 40.0;   // 分析窗长
+const SEEK_MS: f32 = 10.0;    // 相似度搜索半径
+
+pub struct TimeStretchSource<I: Source<Item = f32>> {
+    input: I,
+    channels: usize,
+    sample_rate: u32,
+    rate: f32,
+    bypass: bool,
+
+    win_len: usize,      // 帧数
+    hop_out: usize,      // 合成步长（半窗，Hann 50% 重叠恒等相加）
+    tolerance: usize,
+    window: Vec<f32>,
+
+    buffer: Vec<f32>,    // 交错输入缓冲
+    buffer_base: usize,  // buffer[0] 对应的输入帧号
+    input_done: bool,
+
+    ana_pos: f64,        // 下一帧的名义分析位置
+    prev_pos: Option<usize>,
+    overlap: Vec<f32>,   // 交错的叠加累积区，长度 win_len * channels
+    output: VecDeque<f32>,
+    finished: bool,
+    meter: StageMeter,
+}
+
+impl<I: Source<Item = f32>> TimeStretchSource<I> {
+    pub fn new(input: I, rate: f32) -> Self {
+        let channels = input.channels().max(1) as usize;
+        let sample_rate = input.sample_rate();
+        let rate = rate.clamp(MIN_RATE, MAX_RATE);
+        let bypass = (rate - 1.0).abs() < 1e-3 || channels > 2;
+
+        let mut win_len = (sample_rate as f32 * FRAME_MS / 1000.0) as usize;
+        win_len += win_len % 2;
+        let win_len = win_len.max(64);
+        let window = (0..win_len)
+            .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / win_len as f32).cos())
+            .collect();
+
+        Self {
+            input, channels, sample_rate, rate, bypass,
+            win_len, hop_out: win_len / 2,
+            tolerance: (sample_rate as f32 * SEEK_MS / 1000.0) as usize,
+            window,
+            buffer: Vec::new(), buffer_base: 0, input_done: false,
+            ana_pos: 0.0, prev_pos: None,
+            overlap: vec![0.0; win_len * channels],
+            output: VecDeque::new(),
+            finished: false,
+            meter: StageMeter::new("time_stretch", !bypass, hash_params(rate.to_bits())),
+        }
+    }
+
+    pub fn stage(&self) -> Arc<DspStage> { self.meter.stage() }
+
+    fn buffered_frames(&self) -> usize { self.buffer.len() / self.channels }
+
+    // 保证输入帧 [.., end) 已进入缓冲；源耗尽后不再补零，由调用方判断
+    fn fill_until(&mut self, end: usize) {
+        while !self.input_done && self.buffer_base + self.buffered_frames() < end {
+            match self.input.next() {
+                Some(s) => self.buffer.push(s),
+                None => self.input_done = true,
+            }
+        }
+    }
+
+    #[inline]
+    fn frame_sample(&self, frame: usize, ch: usize) -> f32 {
+        if frame < self.buffer_base { return 0.0; }
+        self.buffer.get((frame - self.buffer_base) * self.channels + ch).copied().unwrap_or(0.0)
+    }
+
+    #[inline]
+    fn mono(&self, frame: usize) -> f32 {
+        if self.channels == 1 { self.frame_sample(frame, 0) } else { 0.5 * (self.frame_sample(frame, 0) + self.frame_sample(frame, 1)) }
+    }
+
+    // 在名义位置附近寻找与上一帧“自然延续”最相似的起点，消除相位跳变
+    fn best_offset(&self, nominal: usize) -> usize {
+        let Some(prev) = self.prev_pos else { return nominal; };
+        let target = prev + self.hop_out;
+        let lo = nominal.saturating_sub(self.tolerance).max(self.buffer_base);
+        let hi = nominal + self.tolerance;
+        let span = self.hop_out;
+
+        let mut best = nominal.max(lo);
+        let mut best_score = f32::MIN;
+        for cand in (lo..=hi).step_by(2) {
+            let mut score = 0.0;
+            for k in (0..span).step_by(4) {
+                score += self.mono(target + k) * self.mono(cand + k);
+            }
+            if score > best_score { best_score = score; best = cand; }
+        }
+        best
+    }
+
+    fn process_block(&mut self) -> bool {
+        let nominal = self.ana_pos as usize;
+        let needed = nominal + self.tolerance + self.win_len + self.hop_out;
+        self.fill_until(needed);
+        if self.input_done && nominal >= self.buffer_base + self.buffered_frames() {
+            // 输入耗尽：把累积区剩余的半窗吐出后结束
+            self.output.extend(self.overlap.drain(..self.hop_out * self.channels));
+            self.finished = true;
+            return !self.output.is_empty();
+        }
+
+        let pos = self.best_offset(nominal);
+        let mut overlap = std::mem::take(&mut self.overlap);
+        for (n, w) in self.window.iter().enumerate() {
+            for ch in 0..self.channels {
+                overlap[n * self.channels + ch] += self.frame_sample(pos + n, ch) * w;
+            }
+        }
+
+        let hop = self.hop_out * self.channels;
+        self.output.extend(overlap.drain(..hop));
+        overlap.resize(self.win_len * self.channels, 0.0);
+        self.overlap = overlap;
+
+        self.prev_pos = Some(pos);
+        self.ana_pos += self.hop_out as f64 * self.rate as f64;
+
+        // 丢弃后续不会再被访问的输入
+        let keep_from = (self.ana_pos as usize).saturating_sub(self.tolerance).min(pos + self.hop_out);
+        if keep_from > self.buffer_base {
+            let drop_frames = (keep_from - self.buffer_base).min(self.buffered_frames());
+            self.buffer.drain(..drop_frames * self.channels);
+            self.buffer_base += drop_frames;
+        }
+        true
+    }
+}
+
+impl<I: Source<Item = f32>> Iterator for TimeStretchSource<I> {
+    type Item = f32;
+    fn next(&mut self) -> Option<f32> {
+        if self.bypass { return self.input.next(); }
+        if self.output.is_empty() {
+            if self.finished { return None; }
+            let started = Instant::now();
+            let produced = self.process_block();
+            self.meter.record(started.elapsed());
+            if !produced { return None; }
+        }
+        self.output.pop_front()
+    }
+}
+
+impl<I: Source<Item = f32>> Source for TimeStretchSource<I> {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { self.channels as u16 }
+    fn sample_rate(&self) -> u32 { self.sample_rate }
+    fn total_duration(&self) -> Option<Duration> {
+        if self.bypass { return self.input.total_duration(); }
+        self.input.total_duration().map(|d| d.div_f32(self.rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    const RATE: u32 = 16000;
+
+    fn tone(frames: usize) -> SamplesBuffer<f32> {
+        SamplesBuffer::new(1, RATE, (0..frames).map(|n| (2.0 * std::f32::consts::PI * 440.0 * n as f32 / RATE as f32).sin() * 0.5).collect::<Vec<_>>())
+    }
+
+    // 过零计数估计主频
+    fn dominant_hz(samples: &[f32]) -> f32 {
+        let rising = samples.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+        rising as f32 * RATE as f32 / samples.len() as f32
+    }
+
+    #[test]
+    fn length_scales_with_rate_and_pitch_holds() {
+        let frames = RATE as usize * 2;
+        for rate in [0.5, 1.5, 2.0, MAX_RATE] {
+            let stretched = TimeStretchSource::new(tone(frames), rate);
+            let win_len = stretched.win_len;
+            assert_eq!(stretched.total_duration(), Some(Duration::from_secs(2).div_f32(rate)));
+            let out: Vec<f32> = stretched.collect();
+            // 末尾多出的只是最后一窗的衰减尾巴
+            let expected = frames as f32 / rate;
+            assert!((out.len() as f32 - expected).abs() <= 2.0 * win_len as f32, "rate {rate}: {} frames, expected ~{expected}", out.len());
+            let body = &out[out.len() / 4..out.len() * 3 / 4];
+            let hz = dominant_hz(body);
+            assert!((hz - 440.0).abs() < 3.0, "rate {rate}: pitch moved to {hz}Hz");
+            let peak = body.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+            assert!((peak - 0.5).abs() < 0.05, "rate {rate}: level {peak}");
+        }
+    }
+
+    #[test]
+    fn unity_rate_and_surround_bypass() {
+        let input: Vec<f32> = tone(1600).collect();
+        assert_eq!(TimeStretchSource::new(tone(1600), 1.0).collect::<Vec<_>>(), input);
+        let surround = SamplesBuffer::new(6, RATE, vec![0.25f32; 600]);
+        assert_eq!(TimeStretchSource::new(surround, 2.0).count(), 600);
+    }
+}
@@ -0,0 +1,127 @@
+// src/audio/threads.rs
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// =================================================================
+// 🧵 音频后台线程登记表：统一的退出信号与限时回收
+// =================================================================
+// 所有解码 / 淡出 / 巡检线程都经由这里创建，长睡眠一律改用可中断的 sleep，
+// 退出时 AudioManager::shutdown() 置位信号并在限时内逐个回收。
+// 进程内只有 REGISTRY 一份；单独成型是为了测试能在私有实例上演练退出而不波及其它测试
+struct Registry {
+    shutdown: AtomicBool,
+    handles: Mutex<Vec<(&'static str, JoinHandle<()>)>>,
+}
+
+static REGISTRY: Registry = Registry::new();
+
+const SLEEP_SLICE: Duration = Duration::from_millis(20);
+
+pub fn shutting_down() -> bool { REGISTRY.shutting_down() }
+
+pub fn spawn<F>(name: &'static str, f: F)
+where F: FnOnce() + Send + 'static {
+    REGISTRY.spawn(name, f)
+}
+
+// 分片睡眠，收到退出信号立即返回 false
+pub fn sleep(duration: Duration) -> bool { REGISTRY.sleep(duration) }
+
+// 置位退出信号并在 timeout 内回收全部线程，返回未能按时结束的线程名
+pub fn shutdown_and_join(timeout: Duration) -> Vec<&'static str> { REGISTRY.shutdown_and_join(timeout) }
+
+impl Registry {
+    const fn new() -> Self { Self { shutdown: AtomicBool::new(false), handles: Mutex::new(Vec::new()) } }
+
+    fn shutting_down(&self) -> bool { self.shutdown.load(Ordering::SeqCst) }
+
+    fn spawn<F>(&self, name: &'static str, f: F)
+    where F: FnOnce() + Send + 'static {
+        let handle = match thread::Builder::new().name(name.to_string()).spawn(f) {
+            Ok(h) => h,
+            Err(e) => { eprintln!("[AUDIO] Failed to spawn {} thread: {}", name, e); return; }
+        };
+        let mut handles = self.handles.lock().unwrap_or_else(|e| e.into_inner());
+        handles.retain(|(_, h)| !h.is_finished());
+        handles.push((name, handle));
+    }
+
+    fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        loop {
+            if self.shutting_down() { return false; }
+            let now = Instant::now();
+            if now >= deadline { return true; }
+            thread::sleep((deadline - now).min(SLEEP_SLICE));
+        }
+    }
+
+    fn shutdown_and_join(&self, timeout: Duration) -> Vec<&'static str> {
+        self.shutdown.store(true, Ordering::SeqCst);
+        let handles: Vec<_> = std::mem::take(&mut *self.handles.lock().unwrap_or_else(|e| e.into_inner()));
+        let deadline = Instant::now() + timeout;
+
+        let mut pending = handles;
+        while !pending.is_empty() && Instant::now() < deadline {
+            let (done, rest): (Vec<_>, Vec<_>) = pending.into_iter().partition(|(_, h)| h.is_finished());
+            for (_, h) in done { let _ = h.join(); }
+            pending = rest;
+            if !pending.is_empty() { thread::sleep(Duration::from_millis(5)); }
+        }
+
+        let stragglers: Vec<&'static str> = pending.iter().map(|(name, _)| *name).collect();
+        for name in &stragglers { eprintln!("[AUDIO] Thread '{}' did not stop within {:?}", name, timeout); }
+        stragglers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    // 模拟各类后台线程：长睡眠的巡检、分片干活的解码、早已结束的淡出
+    fn spawn_workers(registry: &'static Registry) {
+        registry.spawn("watchdog", move || { while registry.sleep(Duration::from_secs(60)) {} });
+        registry.spawn("decode", move || {
+            while !registry.shutting_down() {
+                let mut acc = 0u64;
+                for i in 0..10_000u64 { acc = acc.wrapping_mul(31).wrapping_add(i); }
+                std::hint::black_box(acc);
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+        registry.spawn("fade", || {});
+        for _ in 0..8 { registry.spawn("prefetch", move || { registry.sleep(Duration::from_secs(30)); }); }
+    }
+
+    #[test]
+    fn shutdown_joins_every_thread_within_budget() {
+        static REGISTRY: Registry = Registry::new();
+        spawn_workers(&REGISTRY);
+        thread::sleep(Duration::from_millis(50));
+        let started = Instant::now();
+        let stragglers = REGISTRY.shutdown_and_join(Duration::from_millis(500));
+        assert!(stragglers.is_empty(), "stragglers: {:?}", stragglers);
+        assert!(started.elapsed() < Duration::from_millis(500), "took {:?}", started.elapsed());
+        assert!(REGISTRY.handles.lock().unwrap().is_empty());
+        // 退出后再睡眠立即返回
+        assert!(!REGISTRY.sleep(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn thread_ignoring_the_signal_is_reported_after_the_timeout() {
+        static REGISTRY: Registry = Registry::new();
+        let (tx, rx) = mpsc::channel::<()>();
+        REGISTRY.spawn("stubborn", move || { let _ = rx.recv_timeout(Duration::from_secs(5)); });
+        REGISTRY.spawn("watchdog", || { while REGISTRY.sleep(Duration::from_secs(60)) {} });
+        let started = Instant::now();
+        assert_eq!(REGISTRY.shutdown_and_join(Duration::from_millis(100)), vec!["stubborn"]);
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(100) && elapsed < Duration::from_millis(500), "took {:?}", elapsed);
+        drop(tx);
+    }
+}
@@ -2,13 +2,19 @@
 
 mod audio;
 mod modules;
+mod util;
 
 use std::sync::Mutex;
 use audio::AudioManager;
 use modules::state::AppState;
+use modules::queue::{PlayQueue, QueuePrefs};
+use modules::collation::{self, SortPrefs};
+use modules::metadata_policy::{self, MetadataPolicy};
+use modules::path_policy::{self, PathPolicy};
+use modules::api_schema::events;
 use modules::commands::*; 
 
-use tauri::{Manager, Emitter, Listener, WindowEvent}; 
+use tauri::{Manager, Listener, WindowEvent}; 
 use souvlaki::{MediaControlEvent, MediaControls, MediaPlayback, PlatformConfig};
 use raw_window_handle::{HasWindowHandle, RawWindowHandle};
 use base64::{Engine as _, engine::general_purpose};
@@ -92,12 +98,12 @@ async fn load_astral_data(app: tauri::AppHandle) -> Result<AstralData, String> {
 
 #[tauri::command]
 fn update_persistence_snapshot(data: AstralData) {
-    let mut snapshot = PERSISTENCE_SNAPSHOT.lock().unwrap();
+    let mut snapshot = PERSISTENCE_SNAPSHOT.lock().unwrap_or_else(|e| e.into_inner());
     *snapshot = Some(data);
 }
 
 fn perform_final_save(app: &tauri::AppHandle) {
-    let snapshot = PERSISTENCE_SNAPSHOT.lock().unwrap();
+    let snapshot = PERSISTENCE_SNAPSHOT.lock().unwrap_or_else(|e| e.into_inner());
     if let Some(data) = snapshot.as_ref() {
         if let Ok(config_dir) = app.path().app_config_dir() {
             let _ = fs::create_dir_all(&config_dir);
@@ -187,7 +193,7 @@ async fn sync_smtc_metadata(app: tauri::AppHandle, handle: tauri::State<'_, Smtc
     log_smtc("---------- SMTC Metadata Sync ----------");
     
     {
-        let mut controls_guard = handle.controls.lock().unwrap();
+        let mut controls_guard = handle.controls.lock().unwrap_or_else(|e| e.into_inner());
         if controls_guard.is_none() {
             log_smtc("[NATIVE] First track played. Lazy initializing SMTC controls...");
             let config = PlatformConfig { 
@@ -201,10 +207,10 @@ async fn sync_smtc_metadata(app: tauri::AppHandle, handle: tauri::State<'_, Smtc
                 new_controls.attach(move |event| {
                     match event {
                         MediaControlEvent::Play | MediaControlEvent::Pause | MediaControlEvent::Toggle => { 
-                            let _ = app_clone.emit("smtc-toggle", ()); 
+                            let _ = events::SMTC_TOGGLE.emit(&app_clone, &()); 
                         },
-                        MediaControlEvent::Next => { let _ = app_clone.emit("smtc-next", ()); },
-                        MediaControlEvent::Previous => { let _ = app_clone.emit("smtc-prev", ()); },
+                        MediaControlEvent::Next => { let _ = events::SMTC_NEXT.emit(&app_clone, &()); },
+                        MediaControlEvent::Previous => { let _ = events::SMTC_PREV.emit(&app_clone, &()); },
                         _ => {}
                     }
                 }).unwrap();
@@ -255,7 +261,7 @@ async fn sync_smtc_metadata(app: tauri::AppHandle, handle: tauri::State<'_, Smtc
 
 #[tauri::command]
 async fn sync_smtc_status(handle: tauri::State<'_, SmtcHandle>, is_playing: bool) -> Result<(), String> {
-    let mut controls_guard = handle.controls.lock().unwrap();
+    let mut controls_guard = handle.controls.lock().unwrap_or_else(|e| e.into_inner());
     if let Some(controls) = controls_guard.as_mut() {
         let playback = if is_playing { 
             MediaPlayback::Playing { progress: None } 
@@ -267,29 +273,53 @@ async fn sync_smtc_status(handle: tauri::State<'_, SmtcHandle>, is_playing: bool
     Ok(())
 }
 
+// 注册表与 get_api_schema 共用 modules::api_schema 的指令表
+macro_rules! handler_from {
+    ($($stability:ident $name:ident ($($param:ident : $ty:ty),*) -> $ret:ty;)*) => {
+        tauri::generate_handler![$($name),*]
+    };
+}
+
 // ==========================================
 // 🚀 系统主进程入口
 // ==========================================
 fn main() {
     log_smtc(">>> Astral Galaxy Music Player Backend Started <<<");
     
-    let audio_tx = AudioManager::start_actor();
+    let jobs = util::jobs::JobRegistry::new();
+    let audio_tx = AudioManager::start_actor(jobs.clone());
     let tx_monitor = audio_tx.clone();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
-        .manage(AppState { audio_tx })
+        .manage(AppState { audio_tx, queue: Mutex::new(PlayQueue::default()), jobs })
         .on_window_event(|window, event| {
+            // 拖进窗口的文件 / 文件夹作为导入来源
+            if let WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) = event {
+                import_dropped(window.clone(), paths.clone());
+            }
             if let WindowEvent::CloseRequested { .. } = event {
                 // 物理级强制保存：从静态内存快照中瞬间提取并同步写入硬盘
                 perform_final_save(window.app_handle());
+                if let Ok(dir) = window.app_handle().path().app_local_data_dir() { audio::recovery::end_session(&dir); }
                 println!("[CORE] Final snapshot sync completed. Exiting.");
+
+                // 叫停下载、导入等后台任务，再有序拆除音频线程，避免进程在窗口关闭后继续滞留
+                window.state::<AppState>().jobs.cancel_all();
+                let (tx, rx) = tokio::sync::oneshot::channel();
+                if window.state::<AppState>().audio_tx.send(audio::AudioCommand::Shutdown(tx)).is_ok() {
+                    let _ = tauri::async_runtime::block_on(tokio::time::timeout(std::time::Duration::from_millis(800), rx));
+                }
             }
         })
         .setup(move |app| {
             let main_window = app.get_webview_window("main").unwrap();
             let app_handle = app.handle().clone();
+            let _ = tx_monitor.send(audio::AudioCommand::AttachApp(app_handle.clone()));
+            if let Ok(dir) = app.path().app_local_data_dir() {
+                if audio::recovery::begin_session(&dir) { println!("[RECOVERY] Previous session did not exit cleanly. Recovery snapshot offered."); }
+            }
             
             let hwnd_ptr = match main_window.window_handle().unwrap().as_raw() {
                 RawWindowHandle::Win32(h) => h.hwnd.get() as isize,
@@ -304,40 +334,64 @@ fn main() {
             });
 
             // 🛠️ 硬件拓扑守护线程：实时监控声卡设备变动
-            std::thread::spawn(move || {
+            audio::threads::spawn("device-monitor", move || {
                 loop {
-                    std::thread::sleep(std::time::Duration::from_millis(1500));
+                    if !audio::threads::sleep(std::time::Duration::from_millis(1500)) { break; }
                     
                     let (req_tx, req_rx) = tokio::sync::oneshot::channel();
                     if tx_monitor.send(audio::AudioCommand::CheckDeviceStatus(req_tx)).is_ok() {
                         if let Ok(Some(device)) = req_rx.blocking_recv() {
                             println!("[AUDIO] Hardware Topology Changed! Syncing to frontend for Safe Migration to: {}", device);
                             
-                            let _ = app_handle.emit("force-pause", ());
+                            let _ = events::FORCE_PAUSE.emit(&app_handle, &());
                             std::thread::sleep(std::time::Duration::from_millis(250)); 
                             
                             let (set_tx, set_rx) = tokio::sync::oneshot::channel();
                             let _ = tx_monitor.send(audio::AudioCommand::SetDevice(device, set_tx));
                             let _ = set_rx.blocking_recv(); 
                             
-                            let _ = app_handle.emit("force-play", ());
+                            let _ = events::FORCE_PLAY.emit(&app_handle, &());
                         }
                     }
                 }
             });
 
+            if let Ok(config_dir) = app.path().app_config_dir() {
+                let prefs = QueuePrefs::load(&config_dir);
+                app.state::<AppState>().queue().end_action = prefs.end_action;
+                collation::set_active(SortPrefs::load(&config_dir));
+                metadata_policy::set_active(MetadataPolicy::load(&config_dir));
+                path_policy::set_active(PathPolicy::load(&config_dir));
+                let (upmix_tx, _) = tokio::sync::oneshot::channel();
+                let _ = app.state::<AppState>().audio_tx.send(audio::AudioCommand::SetUpmixParams(audio::dsp::UpmixParams::load(&config_dir), upmix_tx));
+                let (curve_tx, _) = tokio::sync::oneshot::channel();
+                let _ = app.state::<AppState>().audio_tx.send(audio::AudioCommand::SetVolumeCurve(audio::dsp::VolumeCurve::load(&config_dir), curve_tx));
+                let (silence_tx, _) = tokio::sync::oneshot::channel();
+                let _ = app.state::<AppState>().audio_tx.send(audio::AudioCommand::SetSkipSilence(audio::silence::SkipSilence::load(&config_dir), silence_tx));
+                let (fade_tx, _) = tokio::sync::oneshot::channel();
+                let _ = app.state::<AppState>().audio_tx.send(audio::AudioCommand::SetFadeConfig(audio::dsp::FadeConfig::load(&config_dir), fade_tx));
+                let (replaygain_tx, _) = tokio::sync::oneshot::channel();
+                let _ = app.state::<AppState>().audio_tx.send(audio::AudioCommand::SetReplayGain(audio::replaygain::ReplayGainPrefs::load(&config_dir), replaygain_tx));
+                let (layout_tx, _) = tokio::sync::oneshot::channel();
+                let _ = app.state::<AppState>().audio_tx.send(audio::AudioCommand::SetChannelLayout(audio::dsp::ChannelLayout::load(&config_dir), layout_tx));
+                let _ = app.state::<AppState>().audio_tx.send(audio::AudioCommand::SetOutputPrefs(audio::endpoint::OutputPrefs::load(&config_dir)));
+            }
+
+            // 曲目自然播完：由后端队列直接续播，不再依赖 WebView 调度
+            let advance_handle = app.handle().clone();
+            app.listen("track-ended", move |_| {
+                let handle = advance_handle.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = advance_queue(&handle, true).await {
+                        println!("[QUEUE] Auto-advance failed: {}", e);
+                    }
+                });
+            });
+
             app.manage(SmtcHandle { controls: Mutex::new(None), hwnd_ptr });
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
-            import_music, check_file_exists, init_audio_engine, 
-            player_load_track, player_play, player_pause, player_seek, player_set_volume,
-            player_set_channels, get_output_devices, set_output_device,
-            get_lyrics, get_current_engine, get_current_time,
-            sync_smtc_metadata, sync_smtc_status,
-            toggle_smtc_active, init_persistence_layer, load_astral_data,
-            update_persistence_snapshot, check_ffmpeg_exists, start_ffmpeg_download
-        ])
+        .invoke_handler(modules::api_schema::api_commands!(handler_from))
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
\ No newline at end of file
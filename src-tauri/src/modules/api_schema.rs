@@ -0,0 +1,452 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::marker::PhantomData;
+use serde::Serialize;
+use tauri::{Emitter, Runtime};
+use crate::audio::{EngineSwitched, ManagerPhase, OpResult};
+use crate::audio::decode_progress::{CacheProgress, DecodeProgress, DurationUpdate};
+use crate::audio::endpoint::{InactivePause, OutputReactivated};
+use crate::audio::hotplug::{DeviceLost, DeviceSwitched, DevicesChanged};
+use crate::audio::level_meter::LevelFrame;
+use crate::audio::loudness::{PreviewFinish, PreviewSegment};
+use crate::audio::output_mode::ModeFallback;
+use crate::audio::playback_error::PlaybackError;
+use crate::audio::replaygain::{ScanProgress, ScanSummary};
+use crate::audio::room::RoomProgress;
+use crate::audio::spectrum::SpectrumFrame;
+use super::commands::{ImportBatch, ImportCancelled, ImportError, ImportSession, ImportSkipped, ImportSummary, RekeyedTrack};
+use super::integrity::IntegritySummary;
+use super::queue::EndAction;
+use super::sleep_timer::SleepMode;
+use super::storage_report::StorageReport;
+use super::utils::TrackMetadata;
+
+// ==========================================
+// 📜 对外指令 / 事件清单：前端、外部工具按 version 协商载荷形状
+// ==========================================
+// 指令表是唯一来源：main.rs 的 invoke_handler 由同一张表展开，
+// 没写进表里的指令根本注册不上。参数名按 Tauri 的约定转换为 camelCase。
+// 任何指令或事件的载荷形状发生不兼容变化时递增 SCHEMA_VERSION。
+pub const SCHEMA_VERSION: u32 = 6;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ParamSpec {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub stability: &'static str,
+    pub params: Vec<ParamSpec>,
+    pub returns: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct EventSpec {
+    pub name: &'static str,
+    pub stability: &'static str,
+    pub payload: String,
+}
+
+// 事件名与载荷类型绑在一起：只能经由 events::* 发出，载荷按 Borrow 放宽 (String 事件可直接传 &str)
+pub struct Event<T: ?Sized> {
+    pub name: &'static str,
+    payload: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized> Event<T> {
+    const fn new(name: &'static str) -> Self { Self { name, payload: PhantomData } }
+}
+
+impl<T: ?Sized + Serialize> Event<T> {
+    pub fn emit<R: Runtime, P: ?Sized + Serialize>(&self, target: &impl Emitter<R>, payload: &P) -> tauri::Result<()>
+    where
+        T: Borrow<P>,
+    {
+        target.emit(self.name, payload)
+    }
+}
+
+impl<T: ?Sized> Clone for Event<T> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<T: ?Sized> Copy for Event<T> {}
+
+impl<T: ?Sized> fmt::Debug for Event<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "Event({})", self.name) }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ApiSchema {
+    pub version: u32,
+    pub commands: Vec<CommandSpec>,
+    pub events: Vec<EventSpec>,
+}
+
+// stringify! 产出的类型带空格 ("Vec < String >")，统一压紧
+fn compact_type(ty: &str) -> String { ty.split_whitespace().collect::<String>().replace(",", ", ") }
+
+fn camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' { upper = true; continue; }
+        if upper { out.extend(c.to_uppercase()); upper = false; } else { out.push(c); }
+    }
+    out
+}
+
+impl CommandSpec {
+    fn new(name: &'static str, stability: &'static str, params: &[(&str, &str)], returns: &str) -> Self {
+        Self {
+            name, stability,
+            params: params.iter().map(|(n, t)| ParamSpec { name: camel_case(n), ty: compact_type(t) }).collect(),
+            returns: compact_type(returns),
+        }
+    }
+}
+
+// 以回调宏的形式交出指令表，调用方决定展开成 handler 还是描述
+macro_rules! api_commands {
+    ($callback:ident) => {
+        $callback! {
+            stable import_music(force: Option<bool>) -> Result<(), String>;
+            stable get_interrupted_imports() -> Vec<ImportJob>;
+            stable resume_import(job_id: String) -> Result<(), String>;
+            stable discard_import(job_id: String) -> ();
+            experimental cancel_import(job_id: Option<String>) -> Result<(), String>;
+            experimental pause_import(job_id: Option<String>) -> Result<(), String>;
+            experimental continue_import(job_id: Option<String>) -> Result<(), String>;
+            stable library_set_sort_locale(locale: String, strip_articles: bool, paths: Vec<String>) -> Result<(), String>;
+            experimental library_set_metadata_policy(policy: MetadataPolicy) -> Result<(), String>;
+            experimental library_get_metadata_policy() -> MetadataPolicy;
+            experimental library_set_path_policy(policy: PathPolicy) -> Result<(), String>;
+            experimental library_get_path_policy() -> PathPolicy;
+            experimental get_track_metadata(path: String, verbose: bool) -> Result<TrackMetadata, String>;
+            experimental promote_metadata(paths: Vec<String>) -> Result<Vec<PromoteResult>, String>;
+            stable get_cover_full(path: String, allow_oversized: bool) -> Result<String, String>;
+            stable set_cover_size_cap(megabytes: f64) -> ();
+            experimental set_pcm_cache_budget(megabytes: f64) -> ();
+            stable check_file_exists(path: String) -> bool;
+            stable init_audio_engine(engine_id: String) -> Result<String, String>;
+            stable player_load_track(path: String) -> Result<f64, String>;
+            stable player_preload(path: String) -> Result<(), String>;
+            stable player_play() -> Result<(), String>;
+            stable player_pause() -> ();
+            stable player_stop() -> ();
+            stable player_seek(time: f64) -> Result<(), String>;
+            stable player_seek_relative(delta: f64) -> Result<f64, String>;
+            stable player_set_loop(start: f64, end: f64) -> Result<(), String>;
+            stable player_clear_loop() -> ();
+            stable player_set_volume(vol: f32) -> Result<(), String>;
+            experimental player_set_mute(muted: bool) -> Result<(), String>;
+            experimental player_toggle_mute() -> Result<bool, String>;
+            experimental player_set_volume_curve(curve: String) -> Result<(), String>;
+            experimental player_set_fade_config(play_ms: u32, pause_ms: u32, seek_ms: u32, load_ms: u32) -> Result<(), String>;
+            experimental player_set_sleep_timer(minutes: u32) -> ();
+            experimental player_set_skip_silence(enabled: bool, threshold_db: f32, min_len_ms: u32) -> Result<(), String>;
+            experimental player_set_auto_trim(enabled: bool) -> Result<(), String>;
+            experimental analyze_silence(path: String) -> Result<SilenceBounds, String>;
+            experimental player_cancel_sleep_timer() -> Result<(), String>;
+            experimental visualizer_start() -> ();
+            experimental visualizer_stop() -> ();
+            experimental scope_start(samples_per_frame: usize) -> Result<(), String>;
+            experimental scope_stop() -> ();
+            experimental metering_start() -> ();
+            experimental metering_stop() -> ();
+            experimental metering_ack_clip() -> ();
+            stable player_set_rate_preserve_pitch(rate: f32) -> Result<(), String>;
+            stable set_parametric_eq(bands: Vec<EqBand>) -> Result<(), String>;
+            stable player_set_bass_boost(gain_db: f32, freq: Option<f32>) -> Result<(), String>;
+            stable player_set_stereo_width(width: f32) -> Result<(), String>;
+            experimental player_set_preamp(gain_db: f32) -> Result<(), String>;
+            experimental player_set_balance(balance: f32) -> Result<(), String>;
+            stable player_set_crossfeed(enabled: bool, level: u8) -> Result<(), String>;
+            stable player_set_lfe_crossover(freq: f32, slope_db: u8) -> Result<(), String>;
+            experimental player_set_upmix_params(rear_delay_ms: f32, rear_level: f32, center_level: f32, lfe_level: f32) -> Result<(), String>;
+            experimental player_set_output_auto_resume(enabled: bool) -> Result<(), String>;
+            experimental player_set_prefer_device(enabled: bool) -> Result<(), String>;
+            experimental player_set_limiter_enabled(enabled: bool) -> Result<(), String>;
+            experimental player_set_downmix_lfe(enabled: bool) -> Result<(), String>;
+            experimental player_set_auto_level(enabled: bool, target_lufs: f32) -> Result<(), String>;
+            experimental player_set_replaygain(mode: String, fallback_db: Option<f32>) -> Result<(), String>;
+            experimental player_set_channel_layout(layout: String, map: Option<Vec<u8>>) -> Result<(), String>;
+            experimental player_set_dither(enabled: bool, bits: u8) -> Result<(), String>;
+            experimental recovery_status() -> RecoveryStatus;
+            experimental recover_last_state() -> Result<RecoveryReport, String>;
+            stable get_running_jobs() -> Vec<JobInfo>;
+            stable cancel_job(job_id: String) -> Result<(), String>;
+            stable room_preset_save(name: String) -> Result<RoomPreset, String>;
+            stable room_preset_apply(name: String) -> Result<(), String>;
+            stable room_preset_list() -> Vec<RoomPreset>;
+            experimental generator_start(kind: String, params: Option<GeneratorParams>) -> Result<(), String>;
+            experimental generator_stop() -> Result<(), String>;
+            experimental player_identify_channels() -> Result<Vec<ChannelIdent>, String>;
+            experimental library_storage_report(paths: Vec<String>, roots: Option<Vec<String>>, largest_n: Option<usize>) -> Result<String, String>;
+            experimental get_last_storage_report() -> Option<StorageReport>;
+            experimental export_storage_report(path: String) -> Result<(), String>;
+            experimental library_verify_integrity(paths: Option<Vec<String>>, deep: bool) -> Result<String, String>;
+            experimental library_resume_integrity() -> Result<String, String>;
+            experimental library_get_integrity_failures() -> Vec<IntegrityRecord>;
+            experimental loudness_match_preview(path_a: String, path_b: String) -> Result<String, String>;
+            experimental scan_replaygain(paths: Vec<String>, write_tags: Option<bool>) -> Result<String, String>;
+            experimental cancel_scan() -> Result<(), String>;
+            experimental chapters_get(path: String) -> Result<ChapterSet, String>;
+            experimental chapters_set(path: String, chapters: Vec<Chapter>) -> Result<ChapterWriteResult, String>;
+            experimental chapters_add_at_current_position(title: String) -> Result<ChapterWriteResult, String>;
+            stable eq_save_preset(name: String, bands: Vec<EqBand>) -> Result<(), String>;
+            stable eq_list_presets() -> Vec<EqPreset>;
+            stable eq_load_preset(name: String) -> Result<Vec<EqBand>, String>;
+            stable eq_delete_preset(name: String) -> Result<(), String>;
+            stable player_set_channels(mode: u16) -> Result<(), String>;
+            experimental player_set_bitperfect(enabled: bool) -> Result<(), String>;
+            stable player_set_resampler(mode: String) -> Result<(), String>;
+            stable get_output_devices() -> Result<Vec<String>, String>;
+            experimental get_output_devices_detailed() -> Result<Vec<OutputDeviceInfo>, String>;
+            stable set_output_device(device: String) -> Result<String, String>;
+            experimental set_follow_system_default(enabled: bool) -> Result<(), String>;
+            experimental set_match_source_format(enabled: bool) -> Result<(), String>;
+            experimental set_output_sample_rate(rate: Option<u32>) -> Result<(), String>;
+            experimental set_max_output_rate(rate: Option<u32>) -> Result<(), String>;
+            experimental retry_audio_init() -> Result<(), String>;
+            experimental reset_audio() -> Result<(), String>;
+            stable get_device_capabilities(device: Option<String>) -> Result<DeviceCapabilities, String>;
+            stable refresh_device_capabilities(device: String) -> Result<DeviceCapabilities, String>;
+            stable get_device_probe_report() -> Result<Vec<DeviceCapabilities>, String>;
+            stable get_lyrics(path: String) -> Result<String, String>;
+            stable get_current_engine() -> Result<EngineInfo, String>;
+            experimental get_supported_formats() -> Result<SupportedFormats, String>;
+            stable get_current_time() -> Result<f64, String>;
+            stable player_get_state() -> Result<PlayerState, String>;
+            stable player_get_clipping_report() -> Result<Option<ClipReport>, String>;
+            stable get_dsp_chain() -> Vec<DspStageInfo>;
+            stable get_audio_stats() -> AudioStats;
+            stable sync_smtc_metadata(title: String, artist: String, cover: String) -> Result<(), String>;
+            stable sync_smtc_status(is_playing: bool) -> Result<(), String>;
+            stable toggle_smtc_active(enable: bool) -> Result<(), String>;
+            stable init_persistence_layer() -> Result<String, String>;
+            stable load_astral_data() -> Result<AstralData, String>;
+            stable update_persistence_snapshot(data: AstralData) -> ();
+            stable check_ffmpeg_exists() -> bool;
+            stable start_ffmpeg_download() -> ();
+            experimental set_download_proxy(url: Option<String>) -> Result<(), String>;
+            experimental get_ffmpeg_info() -> FFmpegInfo;
+            experimental update_ffmpeg() -> Result<(), String>;
+            stable queue_set(paths: Vec<String>) -> ();
+            stable queue_add(path: String) -> ();
+            stable queue_remove(index: usize) -> Result<(), String>;
+            stable queue_set_mode(shuffle: bool, repeat: RepeatMode) -> ();
+            stable queue_set_end_action(action: EndAction) -> Result<(), String>;
+            stable player_next() -> Result<Option<TrackMetadata>, String>;
+            stable player_previous() -> Result<Option<TrackMetadata>, String>;
+            stable get_api_schema() -> ApiSchema;
+        }
+    };
+}
+pub(crate) use api_commands;
+
+macro_rules! command_specs_from {
+    ($($stability:ident $name:ident ($($param:ident : $ty:ty),*) -> $ret:ty;)*) => {
+        vec![$(CommandSpec::new(stringify!($name), stringify!($stability), &[$((stringify!($param), stringify!($ty))),*], stringify!($ret))),*]
+    };
+}
+
+// 事件表同样是唯一来源：每个事件名绑定一个载荷类型，展开为 events::* 供发送方使用，
+// 载荷类型不符直接编译失败；对外描述由同一张表生成
+macro_rules! api_events {
+    ($($stability:ident $konst:ident = $name:literal: $ty:ty;)*) => {
+        pub mod events {
+            use super::*;
+            $(pub const $konst: Event<$ty> = Event::new($name);)*
+        }
+        const EVENTS: &[(&str, &str, &str)] = &[$(($name, stringify!($stability), stringify!($ty))),*];
+        #[cfg(test)]
+        const EVENT_CONSTS: &[&str] = &[$(stringify!($konst)),*];
+    };
+}
+
+api_events! {
+    stable PLAYER_OP_RESULT = "player-op-result": OpResult;
+    stable PLAYER_PHASE = "player-phase": ManagerPhase;
+    stable TRACK_CHANGED = "track-changed": TrackMetadata;
+    stable TRACK_ENDED = "track-ended": ();
+    stable QUEUE_FINISHED = "queue-finished": EndAction;
+    stable SEEK_START = "seek-start": ();
+    stable SEEK_END = "seek-end": f64;
+    stable FORCE_PAUSE = "force-pause": ();
+    stable FORCE_PLAY = "force-play": ();
+    stable SMTC_NEXT = "smtc-next": ();
+    stable SMTC_PREV = "smtc-prev": ();
+    stable SMTC_TOGGLE = "smtc-toggle": ();
+    stable IMPORT_START = "import-start": usize;
+    stable IMPORT_TRACKS_BATCH = "import-tracks-batch": ImportBatch;
+    stable IMPORT_FINISH = "import-finish": ImportSummary;
+    stable IMPORT_CANCEL = "import-cancel": ();
+    stable IMPORT_CANCELLING = "import-cancelling": String;
+    experimental IMPORT_SESSION = "import-session": ImportSession;
+    experimental IMPORT_CANCELLED = "import-cancelled": ImportCancelled;
+    experimental IMPORT_SKIPPED = "import-skipped": ImportSkipped;
+    experimental IMPORT_ERROR = "import-error": ImportError;
+    stable LIBRARY_REKEY_TRACK = "library-rekey-track": RekeyedTrack;
+    stable LIBRARY_REKEY_PROGRESS = "library-rekey-progress": (usize, usize);
+    stable LIBRARY_REKEY_FINISH = "library-rekey-finish": ();
+    stable FFMPEG_STATUS = "ffmpeg-status": String;
+    stable FFMPEG_PROGRESS = "ffmpeg-progress": f64;
+    stable ROOM_PRESET_PROGRESS = "room-preset-progress": RoomProgress;
+    experimental OUTPUT_INACTIVE_PAUSED = "output-inactive-paused": InactivePause;
+    experimental OUTPUT_REACTIVATED = "output-reactivated": OutputReactivated;
+    experimental STORAGE_REPORT_PROGRESS = "storage-report-progress": (usize, usize);
+    experimental STORAGE_REPORT_FINISH = "storage-report-finish": StorageReport;
+    experimental STORAGE_REPORT_CANCELLED = "storage-report-cancelled": String;
+    experimental INTEGRITY_PROGRESS = "integrity-progress": (usize, usize);
+    experimental INTEGRITY_FINISH = "integrity-finish": IntegritySummary;
+    experimental PREVIEW_SEGMENT = "preview-segment": PreviewSegment;
+    experimental PREVIEW_FINISH = "preview-finish": PreviewFinish;
+    experimental REPLAYGAIN_SCAN_PROGRESS = "replaygain-scan-progress": ScanProgress;
+    experimental REPLAYGAIN_SCAN_FINISH = "replaygain-scan-finish": ScanSummary;
+    experimental SLEEP_TIMER_FIRED = "sleep-timer-fired": SleepMode;
+    experimental SILENCE_SKIPPED = "silence-skipped": f64;
+    experimental SPECTRUM_DATA = "spectrum-data": SpectrumFrame;
+    experimental SCOPE_DATA = "scope-data": Vec<f32>;
+    experimental LEVEL_METER = "level-meter": LevelFrame;
+    experimental PLAYBACK_ERROR = "playback-error": PlaybackError;
+    experimental AUDIO_DEVICES_CHANGED = "audio-devices-changed": DevicesChanged;
+    experimental DEVICE_LOST = "device-lost": DeviceLost;
+    experimental OUTPUT_DEVICE_SWITCHED = "output-device-switched": DeviceSwitched;
+    experimental OUTPUT_MODE_FALLBACK = "output-mode-fallback": ModeFallback;
+    experimental DECODE_START = "decode-start": DecodeProgress;
+    experimental DECODE_PROGRESS = "decode-progress": DecodeProgress;
+    experimental DECODE_COMPLETE = "decode-complete": DecodeProgress;
+    experimental CACHE_PROGRESS = "cache-progress": CacheProgress;
+    experimental CACHE_READY = "cache-ready": CacheProgress;
+    experimental DURATION_UPDATED = "duration-updated": DurationUpdate;
+    experimental ENGINE_SWITCHED = "engine-switched": EngineSwitched;
+}
+
+pub fn schema() -> ApiSchema {
+    ApiSchema {
+        version: SCHEMA_VERSION,
+        commands: api_commands!(command_specs_from),
+        events: EVENTS.iter().map(|&(name, stability, payload)| EventSpec { name, stability, payload: compact_type(payload) }).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn sources() -> Vec<(PathBuf, String)> {
+        fn walk(dir: &Path, out: &mut Vec<(PathBuf, String)>) {
+            for entry in fs::read_dir(dir).unwrap().flatten() {
+                let path = entry.path();
+                if path.is_dir() { walk(&path, out); }
+                else if path.extension().is_some_and(|e| e == "rs") { out.push((path.clone(), fs::read_to_string(&path).unwrap())); }
+            }
+        }
+        let mut out = Vec::new();
+        walk(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), &mut out);
+        out
+    }
+
+    #[test]
+    fn event_names_are_unique() {
+        let mut seen = HashSet::new();
+        for (name, _, _) in EVENTS { assert!(seen.insert(name), "duplicate event {}", name); }
+    }
+
+    // 绕过 events::* 直接按字符串发出的事件也必须登记在表里
+    #[test]
+    fn raw_emit_sites_are_registered() {
+        let registered: HashSet<&str> = EVENTS.iter().map(|(name, _, _)| *name).collect();
+        let needle = ".emit(\"";
+        for (path, source) in sources() {
+            for (at, _) in source.match_indices(needle) {
+                let rest = &source[at + needle.len()..];
+                let name = &rest[..rest.find('"').unwrap()];
+                assert!(registered.contains(name), "{}: event '{}' is not in api_schema EVENTS", path.display(), name);
+            }
+        }
+    }
+
+    // 表里登记了却没有任何地方发出的事件说明描述已过时
+    #[test]
+    fn every_registered_event_is_emitted() {
+        let sources = sources();
+        for konst in EVENT_CONSTS {
+            let usage = format!("events::{}", konst);
+            assert!(
+                sources.iter().any(|(path, source)| !path.ends_with("api_schema.rs") && source.contains(&usage)),
+                "events::{} is registered but never emitted", konst,
+            );
+        }
+    }
+
+    // 由 Tauri 注入、不出现在前端调用参数里的类型
+    fn injected(ty: &str) -> bool {
+        let ty = ty.trim_start_matches("tauri::");
+        ty.starts_with("State<") || ty == "Window" || ty == "AppHandle"
+    }
+
+    fn split_top_level(list: &str) -> Vec<&str> {
+        let (mut depth, mut start, mut out) = (0, 0, Vec::new());
+        for (i, c) in list.char_indices() {
+            match c {
+                '<' | '(' | '[' => depth += 1,
+                '>' | ')' | ']' => depth -= 1,
+                ',' if depth == 0 => { out.push(list[start..i].trim()); start = i + 1; }
+                _ => {}
+            }
+        }
+        if !list[start..].trim().is_empty() { out.push(list[start..].trim()); }
+        out
+    }
+
+    // 指令名 -> (前端可见的参数, 返回类型)，取自 #[tauri::command] 下一行的函数签名
+    fn command_signatures() -> HashMap<String, (Vec<(String, String)>, String)> {
+        let mut out = HashMap::new();
+        for (_, source) in sources() {
+            let mut lines = source.lines();
+            while let Some(line) = lines.next() {
+                if line.trim() != "#[tauri::command]" { continue; }
+                let Some(rest) = lines.next().and_then(|sig| sig.split_once("fn ")).map(|(_, rest)| rest) else { continue };
+                let open = rest.find('(').unwrap();
+                let mut depth = 0;
+                let close = rest.char_indices().skip(open).find(|&(_, c)| {
+                    match c { '(' => depth += 1, ')' => depth -= 1, _ => {} }
+                    depth == 0
+                }).unwrap().0;
+                let params = split_top_level(&rest[open + 1..close]).into_iter()
+                    .filter_map(|param| param.split_once(':'))
+                    .map(|(name, ty)| (name.trim().trim_start_matches("mut ").to_string(), ty.trim().to_string()))
+                    .filter(|(_, ty)| !injected(ty))
+                    .collect();
+                let tail = rest[close + 1..].trim();
+                let returns = match tail.strip_prefix("->") {
+                    Some(ret) => ret[..ret.find('{').unwrap_or(ret.len())].trim().to_string(),
+                    None => "()".to_string(),
+                };
+                out.insert(rest[..open].trim().to_string(), (params, returns));
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn command_table_matches_signatures() {
+        let signatures = command_signatures();
+        for spec in api_commands!(command_specs_from) {
+            let (params, returns) = signatures.get(spec.name).unwrap_or_else(|| panic!("no #[tauri::command] fn for '{}'", spec.name));
+            let actual: Vec<(String, String)> = params.iter().map(|(name, ty)| (camel_case(name), compact_type(ty))).collect();
+            let declared: Vec<(String, String)> = spec.params.iter().map(|p| (p.name.clone(), p.ty.clone())).collect();
+            assert_eq!(declared, actual, "parameters of '{}' differ from its signature", spec.name);
+            assert_eq!(spec.returns, compact_type(returns), "return type of '{}' differs from its signature", spec.name);
+        }
+    }
+}
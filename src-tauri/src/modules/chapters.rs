@@ -0,0 +1,296 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use lofty::{read_from_path, AudioFile};
+use serde::{Serialize, Deserialize};
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+// ==========================================
+// 🔖 章节书签：外置 .chapters.json 总是写入，容器支持时再经 FFmpeg 无损重封装嵌入
+// ==========================================
+// 读取时两者都在且外置文件更新，以外置为准；否则取嵌入章节。
+// 重封装先写同目录临时文件，核对时长不变后再改名覆盖原文件。
+const EMBEDDABLE: &[&str] = &["m4a", "m4b", "mp4", "mka", "mkv", "mp3", "ogg", "opus"];
+const REMUX_DURATION_TOLERANCE_SECS: f64 = 0.1;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Chapter {
+    pub title: String,
+    pub start: f64, // 秒
+    #[serde(default)]
+    pub end: Option<f64>, // 缺省时延续到下一章开头或曲目结尾
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ChapterSource { Sidecar, Embedded, None }
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ChapterSet {
+    pub source: ChapterSource,
+    pub chapters: Vec<Chapter>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ChapterWriteResult {
+    pub sidecar: String,
+    pub embedded: bool,
+    pub embed_error: Option<String>, // 未嵌入的原因；容器不支持时同样给出
+}
+
+pub fn sidecar_path(path: &Path) -> PathBuf { path.with_extension("chapters.json") }
+
+fn modified(path: &Path) -> Option<std::time::SystemTime> { fs::metadata(path).and_then(|m| m.modified()).ok() }
+
+fn ffmpeg_command(exe: &Path) -> Command {
+    let mut cmd = Command::new(exe);
+    cmd.stdin(Stdio::null());
+    #[cfg(target_os = "windows")]
+    { cmd.creation_flags(0x08000000); }
+    cmd
+}
+
+// 排序并校验：起点有限且非负，结束点（若给出）晚于起点
+pub fn normalize(mut chapters: Vec<Chapter>) -> Result<Vec<Chapter>, String> {
+    for c in &chapters {
+        if !c.start.is_finite() || c.start < 0.0 { return Err(format!("CHAPTER_INVALID_START: {}", c.start)); }
+        if c.end.is_some_and(|e| !e.is_finite() || e <= c.start) { return Err(format!("CHAPTER_INVALID_END: '{}'", c.title)); }
+    }
+    chapters.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(chapters)
+}
+
+fn read_sidecar(path: &Path) -> Option<Vec<Chapter>> {
+    let json = fs::read_to_string(sidecar_path(path)).ok()?;
+    serde_json::from_str(&json).map_err(|e| eprintln!("[CHAPTERS] Ignoring corrupt sidecar for {:?}: {}", path, e)).ok()
+}
+
+pub fn write_sidecar(path: &Path, chapters: &[Chapter]) -> Result<PathBuf, String> {
+    let sidecar = sidecar_path(path);
+    let json = serde_json::to_string_pretty(chapters).map_err(|e| e.to_string())?;
+    fs::write(&sidecar, json).map_err(|e| e.to_string())?;
+    Ok(sidecar)
+}
+
+// FFMETADATA 的章节段：START/END 以 TIMEBASE 为单位，换算到秒
+struct RawChapter { timebase: f64, start: i64, end: Option<i64>, title: String }
+
+impl RawChapter {
+    fn into_chapter(self) -> Chapter {
+        Chapter { title: self.title, start: self.start as f64 * self.timebase, end: self.end.map(|e| e as f64 * self.timebase) }
+    }
+}
+
+fn parse_ffmetadata(text: &str) -> Vec<Chapter> {
+    let mut chapters = Vec::new();
+    let mut current: Option<RawChapter> = None;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            chapters.extend(current.take().map(RawChapter::into_chapter));
+            if line == "[CHAPTER]" { current = Some(RawChapter { timebase: 0.001, start: 0, end: None, title: String::new() }); }
+            continue;
+        }
+        let (Some(chapter), Some((key, value))) = (current.as_mut(), line.split_once('=')) else { continue };
+        match key {
+            "TIMEBASE" => {
+                let parsed = value.split_once('/').and_then(|(n, d)| Some((n.parse::<f64>().ok()?, d.parse::<f64>().ok()?)));
+                if let Some((n, d)) = parsed.filter(|(_, d)| *d > 0.0) { chapter.timebase = n / d; }
+            }
+            "START" => chapter.start = value.parse().unwrap_or(0),
+            "END" => chapter.end = value.parse().ok(),
+            "title" => chapter.title = unescape_meta(value),
+            _ => {}
+        }
+    }
+    chapters.extend(current.map(RawChapter::into_chapter));
+    chapters
+}
+
+fn read_embedded(path: &Path, ffmpeg: &Path) -> Option<Vec<Chapter>> {
+    let output = ffmpeg_command(ffmpeg).args(["-v", "error", "-i"]).arg(path).args(["-f", "ffmetadata", "-"]).output().ok()?;
+    if !output.status.success() { return None; }
+    Some(parse_ffmetadata(&String::from_utf8_lossy(&output.stdout))).filter(|c| !c.is_empty())
+}
+
+pub fn read(path: &Path, ffmpeg: Option<&Path>) -> ChapterSet {
+    let sidecar = read_sidecar(path);
+    let embedded = ffmpeg.and_then(|exe| read_embedded(path, exe));
+    let sidecar_newer = modified(&sidecar_path(path)).zip(modified(path)).is_some_and(|(s, a)| s >= a);
+    match (sidecar, embedded) {
+        (Some(chapters), Some(_)) if sidecar_newer => ChapterSet { source: ChapterSource::Sidecar, chapters },
+        (_, Some(chapters)) => ChapterSet { source: ChapterSource::Embedded, chapters },
+        (Some(chapters), None) => ChapterSet { source: ChapterSource::Sidecar, chapters },
+        (None, None) => ChapterSet { source: ChapterSource::None, chapters: Vec::new() },
+    }
+}
+
+fn unescape_meta(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() { out.push(if c == '\\' { chars.next().unwrap_or(c) } else { c }); }
+    out
+}
+
+fn escape_meta(value: &str) -> String {
+    value.chars().fold(String::with_capacity(value.len()), |mut out, c| {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') { out.push('\\'); }
+        out.push(c);
+        out
+    })
+}
+
+fn to_ffmetadata(chapters: &[Chapter], duration: f64) -> String {
+    let mut out = String::from(";FFMETADATA1\n");
+    for (i, c) in chapters.iter().enumerate() {
+        let end = c.end.or_else(|| chapters.get(i + 1).map(|n| n.start)).unwrap_or(duration).max(c.start);
+        out.push_str(&format!("[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n", (c.start * 1000.0).round() as i64, (end * 1000.0).round() as i64, escape_meta(&c.title)));
+    }
+    out
+}
+
+fn duration_of(path: &Path) -> Result<f64, String> {
+    read_from_path(path).map(|f| f.properties().duration().as_secs_f64()).map_err(|e| e.to_string())
+}
+
+// 只复制码流、替换章节表；任何一步失败都保证原文件不被改动
+pub fn embed(path: &Path, chapters: &[Chapter], ffmpeg: &Path) -> Result<(), String> {
+    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if !EMBEDDABLE.contains(&ext.as_str()) { return Err(format!("CONTAINER_UNSUPPORTED: .{}", ext)); }
+    let duration = duration_of(path)?;
+    let dir = path.parent().ok_or_else(|| "INVALID_PATH".to_string())?;
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let meta = dir.join(format!(".{}.chapters.ffmeta", stem));
+    let tmp = dir.join(format!(".{}.remux.{}", stem, ext));
+    fs::write(&meta, to_ffmetadata(chapters, duration)).map_err(|e| e.to_string())?;
+
+    let result = ffmpeg_command(ffmpeg)
+        .args(["-v", "error", "-y", "-i"]).arg(path).arg("-i").arg(&meta)
+        .args(["-map", "0", "-map_metadata", "0", "-map_chapters", "1", "-codec", "copy"]).arg(&tmp)
+        .output()
+        .map_err(|e| format!("FFMPEG_SPAWN_FAILED: {}", e))
+        .and_then(|out| if out.status.success() { Ok(()) } else { Err(format!("REMUX_FAILED: {}", String::from_utf8_lossy(&out.stderr).trim())) })
+        .and_then(|_| {
+            let remuxed = duration_of(&tmp)?;
+            if (remuxed - duration).abs() > REMUX_DURATION_TOLERANCE_SECS {
+                return Err(format!("REMUX_DURATION_CHANGED: {:.3}s -> {:.3}s", duration, remuxed));
+            }
+            fs::rename(&tmp, path).map_err(|e| e.to_string())
+        });
+    let _ = fs::remove_file(&meta);
+    if result.is_err() { let _ = fs::remove_file(&tmp); }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ch(title: &str, start: f64, end: Option<f64>) -> Chapter { Chapter { title: title.to_string(), start, end } }
+
+    fn temp_track(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("agm_chapters_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, b"not really audio").unwrap();
+        path
+    }
+
+    #[test]
+    fn normalize_sorts_and_rejects_bad_bounds() {
+        let sorted = normalize(vec![ch("B", 90.0, None), ch("A", 0.0, Some(90.0)), ch("C", 180.5, None)]).unwrap();
+        assert_eq!(sorted.iter().map(|c| c.title.as_str()).collect::<Vec<_>>(), ["A", "B", "C"]);
+        assert_eq!(normalize(vec![ch("x", -1.0, None)]), Err("CHAPTER_INVALID_START: -1".to_string()));
+        assert!(normalize(vec![ch("x", f64::NAN, None)]).unwrap_err().starts_with("CHAPTER_INVALID_START"));
+        assert_eq!(normalize(vec![ch("x", 10.0, Some(10.0))]), Err("CHAPTER_INVALID_END: 'x'".to_string()));
+        assert!(normalize(vec![ch("x", 10.0, Some(f64::INFINITY))]).is_err());
+        assert_eq!(normalize(vec![]), Ok(vec![]));
+    }
+
+    #[test]
+    fn sidecar_round_trip() {
+        let track = temp_track("book.m4b");
+        let chapters = normalize(vec![ch("第二章 · Été", 61.25, None), ch("Intro", 0.0, Some(12.5)), ch("🎧 Outro", 3599.999, None)]).unwrap();
+        let sidecar = write_sidecar(&track, &chapters).unwrap();
+        assert_eq!(sidecar, track.parent().unwrap().join("book.chapters.json"));
+
+        let set = read(&track, None);
+        assert!(matches!(set.source, ChapterSource::Sidecar));
+        assert_eq!(set.chapters, chapters);
+
+        // 覆盖写入后读到的是新内容
+        write_sidecar(&track, &chapters[..1]).unwrap();
+        assert_eq!(read(&track, None).chapters, chapters[..1]);
+        let _ = fs::remove_dir_all(track.parent().unwrap());
+    }
+
+    #[test]
+    fn missing_or_corrupt_sidecar_reads_as_no_chapters() {
+        let track = temp_track("plain.flac");
+        assert!(matches!(read(&track, None).source, ChapterSource::None));
+        fs::write(sidecar_path(&track), "{ not json").unwrap();
+        let set = read(&track, None);
+        assert!(matches!(set.source, ChapterSource::None));
+        assert!(set.chapters.is_empty());
+        let _ = fs::remove_dir_all(track.parent().unwrap());
+    }
+
+    #[test]
+    fn ffmetadata_round_trip_fills_implied_ends_and_escapes_titles() {
+        let chapters = vec![ch("a=b; c#d \\ e", 0.0, None), ch("Middle", 61.25, Some(100.0)), ch("Last", 150.0, None)];
+        let text = to_ffmetadata(&chapters, 200.0);
+        assert!(text.starts_with(";FFMETADATA1\n"));
+        let parsed = parse_ffmetadata(&text);
+        assert_eq!(parsed.iter().map(|c| c.title.as_str()).collect::<Vec<_>>(), ["a=b; c#d \\ e", "Middle", "Last"]);
+        // 缺省的结束点写成下一章开头，末章写成曲目时长
+        let bounds: Vec<(f64, f64)> = parsed.iter().map(|c| (c.start, c.end.unwrap())).collect();
+        for ((start, end), expected) in bounds.iter().zip([(0.0, 61.25), (61.25, 100.0), (150.0, 200.0)]) {
+            assert!((start - expected.0).abs() < 1e-9 && (end - expected.1).abs() < 1e-9, "{:?}", bounds);
+        }
+    }
+
+    #[test]
+    fn ffmetadata_parser_honours_timebase_and_ignores_other_sections() {
+        let text = ";FFMETADATA1\ntitle=Album\n[STREAM]\ntitle=ignored\n[CHAPTER]\nTIMEBASE=1/44100\nSTART=44100\nEND=88200\ntitle=One\n[CHAPTER]\nTIMEBASE=1/0\nSTART=5000\ntitle=Two\n";
+        let parsed = parse_ffmetadata(text);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].title, "One");
+        assert!((parsed[0].start - 1.0).abs() < 1e-9 && (parsed[0].end.unwrap() - 2.0).abs() < 1e-9);
+        // 非法 TIMEBASE 保持默认毫秒
+        assert_eq!((parsed[1].title.as_str(), parsed[1].end), ("Two", None));
+        assert!((parsed[1].start - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn embedding_into_an_unsupported_container_leaves_the_file_alone() {
+        let track = temp_track("lossless.flac");
+        let err = embed(&track, &[ch("A", 0.0, None)], Path::new("ffmpeg-that-does-not-exist")).unwrap_err();
+        assert_eq!(err, "CONTAINER_UNSUPPORTED: .flac");
+        assert_eq!(fs::read(&track).unwrap(), b"not really audio");
+        let _ = fs::remove_dir_all(track.parent().unwrap());
+    }
+
+    #[test]
+    fn embedding_into_an_m4b_remuxes_chapters_and_keeps_the_duration() {
+        // 需要已安装的 FFmpeg；没有时跳过
+        let Some(exe) = crate::audio::ffmpeg::FFmpegEngine::installed_exe() else { return };
+        let track = temp_track("remux.m4b");
+        let dir = track.parent().unwrap().to_path_buf();
+        let made = ffmpeg_command(&exe)
+            .args(["-v", "error", "-y", "-f", "lavfi", "-i", "sine=frequency=440:duration=5", "-c:a", "aac", "-f", "ipod"]).arg(&track)
+            .status().unwrap();
+        assert!(made.success());
+        let before = duration_of(&track).unwrap();
+
+        embed(&track, &[ch("Opening", 0.0, None), ch("Second", 2.5, None)], &exe).unwrap();
+        assert!((duration_of(&track).unwrap() - before).abs() <= REMUX_DURATION_TOLERANCE_SECS);
+        let embedded = read_embedded(&track, &exe).unwrap();
+        assert_eq!(embedded.iter().map(|c| c.title.as_str()).collect::<Vec<_>>(), ["Opening", "Second"]);
+        assert!((embedded[1].start - 2.5).abs() < 0.01, "{:?}", embedded);
+        // 临时的 .ffmeta 和重封装文件都已清理
+        let leftovers: Vec<_> = fs::read_dir(&dir).unwrap().flatten().map(|e| e.file_name()).filter(|n| n != "remux.m4b").collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
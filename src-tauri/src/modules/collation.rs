@@ -0,0 +1,152 @@
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+use lofty::{ItemKey, Tag};
+use pinyin::ToPinyin;
+use serde::{Serialize, Deserialize};
+
+// ==========================================
+// 🔤 曲库排序键：导入时一次性计算并随元数据落库，查询时不再重算
+// ==========================================
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SortPrefs {
+    pub locale: String,
+    pub strip_articles: bool,
+}
+
+impl Default for SortPrefs {
+    fn default() -> Self { Self { locale: "en".to_string(), strip_articles: true } }
+}
+
+impl SortPrefs {
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("sort_prefs.json")).ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(dir.join("sort_prefs.json"), json).map_err(|e| e.to_string())
+    }
+}
+
+// 导入线程池里并发读取，写入只发生在切换语言时
+static ACTIVE_PREFS: RwLock<Option<SortPrefs>> = RwLock::new(None);
+
+pub fn set_active(prefs: SortPrefs) { *ACTIVE_PREFS.write().unwrap_or_else(|e| e.into_inner()) = Some(prefs); }
+
+pub fn active() -> SortPrefs { ACTIVE_PREFS.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default() }
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SortKeys {
+    pub sort_title: String,
+    pub sort_artist: String,
+    pub sort_album: String,
+}
+
+impl SortKeys {
+    // 标签中的排序名（TSOT/TSOP/TSOA、ARTISTSORT 等）优先于显示名
+    pub fn compute(title: &str, artist: &str, album: &str, tag: Option<&Tag>, prefs: &SortPrefs) -> Self {
+        let pick = |key: ItemKey, fallback: &str| -> String {
+            let tagged = tag.and_then(|t| t.get_string(&key)).map(str::trim).filter(|s| !s.is_empty());
+            sort_key(tagged.unwrap_or(fallback), prefs)
+        };
+        Self {
+            sort_title: pick(ItemKey::TrackTitleSortOrder, title),
+            sort_artist: pick(ItemKey::TrackArtistSortOrder, artist),
+            sort_album: pick(ItemKey::AlbumTitleSortOrder, album),
+        }
+    }
+}
+
+fn articles_for(locale: &str) -> &'static [&'static str] {
+    match locale.split(['-', '_']).next().unwrap_or("") {
+        "en" => &["the ", "a ", "an "],
+        "fr" => &["le ", "la ", "les ", "l'", "un ", "une "],
+        "de" => &["der ", "die ", "das ", "ein ", "eine "],
+        "es" => &["el ", "la ", "los ", "las ", "un ", "una "],
+        "it" => &["il ", "lo ", "la ", "i ", "gli ", "le ", "l'"],
+        _ => &[],
+    }
+}
+
+pub fn sort_key(text: &str, prefs: &SortPrefs) -> String {
+    let lowered = text.trim().to_lowercase();
+    let mut body = lowered.as_str();
+    if prefs.strip_articles {
+        if let Some(article) = articles_for(&prefs.locale).iter().find(|a| body.starts_with(*a) && body.len() > a.len()) {
+            body = body[article.len()..].trim_start();
+        }
+    }
+
+    // 中文语境下汉字按拼音展开，使“周杰倫”与 Z 开头的拉丁名相邻
+    if !prefs.locale.starts_with("zh") { return body.to_string(); }
+    let mut key = String::with_capacity(body.len() * 2);
+    for c in body.chars() {
+        match c.to_pinyin() {
+            Some(p) => {
+                if !key.is_empty() && !key.ends_with(' ') { key.push(' '); }
+                key.push_str(p.plain());
+                key.push(' ');
+            }
+            None => key.push(c),
+        }
+    }
+    key.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lofty::TagType;
+
+    fn prefs(locale: &str, strip_articles: bool) -> SortPrefs { SortPrefs { locale: locale.to_string(), strip_articles } }
+
+    fn sorted<'a>(names: &[&'a str], prefs: &SortPrefs) -> Vec<&'a str> {
+        let mut names = names.to_vec();
+        names.sort_by_key(|n| sort_key(n, prefs));
+        names
+    }
+
+    #[test]
+    fn han_sorts_by_pinyin_among_latin_names() {
+        let zh = prefs("zh-CN", true);
+        assert_eq!(sort_key("周杰倫", &zh), "zhou jie lun");
+        assert_eq!(sort_key("Jay周", &zh), "jay zhou");
+        assert_eq!(sorted(&["周杰倫", "Zedd", "陈奕迅", "Adele", "阿杜"], &zh), vec!["阿杜", "Adele", "陈奕迅", "Zedd", "周杰倫"]);
+        // 非中文语境不展开拼音
+        assert_eq!(sort_key("周杰倫", &prefs("en", true)), "周杰倫");
+    }
+
+    #[test]
+    fn articles_are_stripped_per_locale() {
+        let en = prefs("en-US", true);
+        assert_eq!(sort_key("The Beatles", &en), "beatles");
+        assert_eq!(sort_key("  An Horse ", &en), "horse");
+        assert_eq!(sort_key("The", &en), "the");
+        assert_eq!(sort_key("Theatre of Tragedy", &en), "theatre of tragedy");
+        assert_eq!(sort_key("A-ha", &en), "a-ha");
+        assert_eq!(sort_key("L'Impératrice", &prefs("fr", true)), "impératrice");
+        assert_eq!(sort_key("Die Ärzte", &prefs("de_DE", true)), "ärzte");
+        assert_eq!(sort_key("Die Ärzte", &en), "die ärzte");
+        assert_eq!(sort_key("The Beatles", &prefs("en", false)), "the beatles");
+        assert_eq!(sorted(&["The Who", "Blur", "A Tribe Called Quest"], &en), vec!["Blur", "A Tribe Called Quest", "The Who"]);
+    }
+
+    #[test]
+    fn sort_tags_take_precedence_over_display_names() {
+        let en = prefs("en", true);
+        let mut tag = Tag::new(TagType::Id3v2);
+        assert!(tag.insert_text(ItemKey::TrackArtistSortOrder, "Bowie, David".to_string()));
+        assert!(tag.insert_text(ItemKey::AlbumTitleSortOrder, "   ".to_string()));
+        let keys = SortKeys::compute("Heroes", "David Bowie", "The Berlin Years", Some(&tag), &en);
+        assert_eq!(keys.sort_artist, "bowie, david");
+        // 空白的排序标签视同缺失，回落到显示名
+        assert_eq!(keys.sort_album, "berlin years");
+        assert_eq!(keys.sort_title, "heroes");
+        let untagged = SortKeys::compute("Heroes", "David Bowie", "The Berlin Years", None, &en);
+        assert_eq!(untagged.sort_artist, "david bowie");
+    }
+}
@@ -1,44 +1,385 @@
-use tauri::{State, Window, Emitter, Manager};
-use std::path::Path;
+use tauri::{AppHandle, State, Window, Manager};
+use std::path::{Path, PathBuf};
 use rfd::FileDialog;
 use rayon::prelude::*;
-use crate::audio::ffmpeg::FFmpegEngine;
-use crate::audio::AudioCommand; 
+use crate::audio::ffmpeg::{self, DownloadPrefs, FFmpegEngine, FFmpegInfo, FFMPEG_BUSY};
+use crate::audio::{pcm_spill, pcm_stream};
+use crate::audio::galaxy::{ChannelIdent, GeneratorParams, GeneratorRequest};
+use crate::audio::{self, AudioCommand, EngineInfo, PlayerState}; 
+use crate::audio::dsp_registry::{self, DspStageInfo};
+use crate::audio::analysis::ClipReport;
+use crate::audio::dsp::{AutoLevel, BassBoost, ChannelLayout, Crossfeed, Dither, EqBand, FadeConfig, LfeCrossover, UpmixParams, VolumeCurve};
+use crate::audio::device_caps::DeviceCapabilities;
+use crate::audio::endpoint::OutputPrefs;
+use crate::audio::output_mode::OutputRate;
+use crate::audio::devices::OutputDeviceInfo;
+use crate::audio::replaygain::{self, ReplayGainMode, ReplayGainPrefs};
+use crate::audio::silence::{self, SilenceBounds, SkipSilence};
+use crate::audio::{level_meter, spectrum};
+use crate::audio::coordinator::{CoordinatorStats, COORDINATOR};
 use super::state::AppState;
-use super::utils::{extract_metadata, parse_lyrics_file};
+use super::import_jobs::{self, ImportJob};
+use super::collation::{self, SortPrefs};
+use super::metadata_policy::{self, MetadataPolicy, PromoteResult};
+use super::eq_presets::{self, EqPreset};
+use super::room_presets;
+use super::storage_report::{self, StorageReport};
+use super::integrity::{self, IntegrityJob, IntegrityRecord, IntegrityStore};
+use super::chapters::{self, Chapter, ChapterSet, ChapterWriteResult};
+use super::sleep_timer::{self, SleepMode};
+use super::api_schema::{self, events, ApiSchema};
+use super::path_policy::{self, Access, PathPolicy};
+use crate::audio::room::RoomPreset;
+use super::queue::{Advance, EndAction, FinishStep, PlayQueue, QueuePrefs, QueueSection, RepeatMode};
+use crate::audio::recovery::{self, RecoveryReport};
+use super::utils::{self, extract_for_import, extract_metadata, extract_sort_keys, parse_lyrics_file, CoverIssue, TrackMetadata};
+use crate::util::jobs::{JobInfo, JobKind};
 use tokio::sync::oneshot;
 
+const FFMPEG_DOWNLOAD_JOB: &str = "ffmpeg_download";
+
 #[tauri::command]
 pub async fn get_lyrics(path: String) -> Result<String, String> {
+    path_policy::check(&path, Access::Read)?;
     parse_lyrics_file(path)
 }
 
 #[tauri::command]
-pub async fn import_music(window: Window) -> Result<(), String> {
+pub async fn import_music(window: Window, force: Option<bool>) -> Result<(), String> {
     let files = FileDialog::new()
-        .add_filter("Audio", &["mp3", "flac", "wav", "ogg", "m4a", "wma", "aac"])
+        .add_filter("Audio", import_jobs::dialog_extensions().as_slice())
         .set_directory("/")
         .set_parent(&window)
         .pick_files();
         
     if let Some(paths) = files {
+        let job = ImportJob::new(paths.iter().map(|p| p.to_string_lossy().to_string()).collect(), force.unwrap_or(false));
+        run_import_job(window, job);
+    } else {
+        let _ = events::IMPORT_CANCEL.emit(&window, &());
+    }
+    Ok(())
+}
+
+// 窗口收到的系统拖放：与导入对话框走同一条解析流水线，前端沿用 import-* 事件
+pub fn import_dropped(window: Window, paths: Vec<PathBuf>) {
+    tauri::async_runtime::spawn_blocking(move || {
+        let files = import_jobs::collect_audio_files(&paths);
+        if !files.is_empty() { run_import_job(window, ImportJob::new(files, false)); }
+    });
+}
+
+#[derive(serde::Serialize, Clone)]
+struct ImportIssue { path: String, cover: CoverIssue }
+
+#[derive(serde::Serialize, Clone, Default)]
+pub struct ImportSummary { job_id: String, imported: usize, skipped: usize, errors: usize, issues: Vec<ImportIssue>, cancelled: bool }
+
+#[derive(serde::Serialize, Clone)]
+pub struct ImportBatch { job_id: String, tracks: Vec<TrackMetadata>, processed: usize, total: usize }
+
+#[derive(serde::Serialize, Clone)]
+pub struct ImportError { path: String, reason: String }
+
+#[derive(serde::Serialize, Clone)]
+pub struct ImportSkipped { path: String, reason: &'static str }
+
+#[derive(serde::Serialize, Clone)]
+pub struct ImportSession { job_id: String, total: usize }
+
+#[derive(serde::Serialize, Clone)]
+pub struct ImportCancelled { job_id: String, processed: usize }
+
+// 同一时刻只跑一个导入任务，后来的排队等候，两批的 import-* 事件不会交错
+static IMPORT_LANE: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+// 把分批解析的进展转成 import-* 事件
+struct WindowSink<'a> { window: &'a Window, job_id: &'a str, total: usize }
+
+impl import_jobs::ImportSink for WindowSink<'_> {
+    fn skipped(&self, path: &str, reason: &'static str) {
+        let _ = events::IMPORT_SKIPPED.emit(self.window, &ImportSkipped { path: path.to_string(), reason });
+    }
+    fn failed(&self, path: &str, reason: String) {
+        let _ = events::IMPORT_ERROR.emit(self.window, &ImportError { path: path.to_string(), reason });
+    }
+    fn batch(&self, tracks: Vec<TrackMetadata>, handled: usize) {
+        let _ = events::IMPORT_TRACKS_BATCH.emit(self.window, &ImportBatch { job_id: self.job_id.to_string(), tracks, processed: handled, total: self.total });
+    }
+}
+
+// 分批解析与断点见 import_jobs::run_batches；这里负责任务登记、排队与首尾事件
+fn run_import_job(window: Window, mut job: ImportJob) {
+    let dir = import_jobs::jobs_dir(window.app_handle());
+    let handle = window.state::<AppState>().jobs.register(job.id.clone(), JobKind::Import, format!("{} files", job.paths.len()));
+    import_jobs::save_job(&dir, &job);
+    // 当前批次收尾前先通知前端，避免取消按钮看起来没反应
+    let (win_cancel, job_id) = (window.clone(), handle.id().to_string());
+    handle.token().on_cancel(move || { let _ = events::IMPORT_CANCELLING.emit(&win_cancel, &job_id); });
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let _lane = IMPORT_LANE.lock().unwrap_or_else(|e| e.into_inner());
+        let gate = import_jobs::activate(&job.id);
+        let (job_id, total) = (job.id.clone(), job.remaining().len());
+        let _ = events::IMPORT_START.emit(&window, &total);
+        let _ = events::IMPORT_SESSION.emit(&window, &ImportSession { job_id: job_id.clone(), total });
+        let policy = metadata_policy::active();
+        let sink = WindowSink { window: &window, job_id: &job_id, total };
+        let tally = import_jobs::run_batches(&mut job, &dir, handle.token(), &gate, &sink,
+            |path| extract_for_import(&path.to_path_buf(), &policy),
+            |job| handle.set_progress(job.completed as f32 / job.paths.len() as f32));
+        import_jobs::finish(&dir, &job.id);
+        if tally.cancelled {
+            let _ = events::IMPORT_CANCELLED.emit(&window, &ImportCancelled { job_id: job_id.clone(), processed: tally.imported });
+        }
+        let summary = ImportSummary {
+            job_id, imported: tally.imported, skipped: tally.skipped, errors: tally.errors, cancelled: tally.cancelled,
+            issues: tally.issues.into_iter().map(|(path, cover)| ImportIssue { path, cover }).collect(),
+        };
+        let _ = events::IMPORT_FINISH.emit(&window, &summary);
+    });
+}
+
+// 取消导入：指定 id 时只取消那一批，已结束的旧 id 报 IMPORT_NOT_FOUND 而不会误伤新开始的导入；不指定则取消当前这批
+#[tauri::command]
+pub fn cancel_import(state: State<AppState>, job_id: Option<String>) -> Result<(), String> {
+    let id = job_id.or_else(import_jobs::active_id).ok_or_else(|| "NO_ACTIVE_IMPORT".to_string())?;
+    if !state.jobs.list().iter().any(|job| job.id == id && job.kind == JobKind::Import) { return Err("IMPORT_NOT_FOUND".to_string()); }
+    state.jobs.cancel(&id)
+}
+
+// 暂停当前导入，腾出磁盘 IO；已在解析的文件照常完成
+#[tauri::command]
+pub fn pause_import(job_id: Option<String>) -> Result<(), String> { import_jobs::set_paused(job_id.as_deref(), true) }
+#[tauri::command]
+pub fn continue_import(job_id: Option<String>) -> Result<(), String> { import_jobs::set_paused(job_id.as_deref(), false) }
+
+#[tauri::command]
+pub fn get_interrupted_imports(window: Window) -> Vec<ImportJob> {
+    import_jobs::list_jobs(&import_jobs::jobs_dir(window.app_handle()))
+}
+
+#[tauri::command]
+pub fn resume_import(window: Window, job_id: String) -> Result<(), String> {
+    let job = import_jobs::load_job(&import_jobs::jobs_dir(window.app_handle()), &job_id)?;
+    run_import_job(window, job);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn discard_import(window: Window, job_id: String) {
+    import_jobs::remove_job(&import_jobs::jobs_dir(window.app_handle()), &job_id);
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct RekeyedTrack { path: String, sort_title: String, sort_artist: String, sort_album: String }
+
+// 切换排序语言：持久化偏好后在后台重算曲库排序键，逐首回推并汇报进度
+#[tauri::command]
+pub fn library_set_sort_locale(window: Window, locale: String, strip_articles: bool, paths: Vec<String>) -> Result<(), String> {
+    path_policy::check_all(&paths, Access::Read)?;
+    let prefs = SortPrefs { locale, strip_articles };
+    let dir = window.app_handle().path().app_config_dir().map_err(|e| e.to_string())?;
+    prefs.save(&dir)?;
+    collation::set_active(prefs);
+
+    tauri::async_runtime::spawn_blocking(move || {
         let total = paths.len();
-        let _ = window.emit("import-start", total);
-        tauri::async_runtime::spawn_blocking(move || {
-            paths.par_iter().for_each(|path| {
-                let track = extract_metadata(path);
-                let _ = window.emit("import-track", track);
+        let done = std::sync::atomic::AtomicUsize::new(0);
+        paths.par_iter().for_each(|path| {
+            let keys = extract_sort_keys(Path::new(path));
+            let _ = events::LIBRARY_REKEY_TRACK.emit(&window, &RekeyedTrack {
+                path: path.clone(), sort_title: keys.sort_title, sort_artist: keys.sort_artist, sort_album: keys.sort_album,
             });
-            let _ = window.emit("import-finish", ());
+            let n = done.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+            let _ = events::LIBRARY_REKEY_PROGRESS.emit(&window, &(n, total));
         });
-    } else {
-        let _ = window.emit("import-cancel", ());
-    }
+        let _ = events::LIBRARY_REKEY_FINISH.emit(&window, &());
+    });
     Ok(())
 }
 
+// 元数据来源优先级：持久化后立即对之后的导入 / 重新解析生效
+#[tauri::command]
+pub fn library_set_metadata_policy(app: AppHandle, policy: MetadataPolicy) -> Result<(), String> {
+    policy.validate()?;
+    let policy = policy.prepare();
+    policy.save(&app.path().app_config_dir().map_err(|e| e.to_string())?)?;
+    metadata_policy::set_active(policy);
+    Ok(())
+}
+#[tauri::command]
+pub fn library_get_metadata_policy() -> MetadataPolicy { metadata_policy::active() }
+// 路径策略：开启限制后删除 / 改名 / 写标签 / 整理等改动只允许落在曲库根目录内
+#[tauri::command]
+pub fn library_set_path_policy(app: AppHandle, policy: PathPolicy) -> Result<(), String> {
+    policy.validate()?;
+    let policy = policy.prepare();
+    policy.save(&app.path().app_config_dir().map_err(|e| e.to_string())?)?;
+    path_policy::set_active(policy);
+    Ok(())
+}
+#[tauri::command]
+pub fn library_get_path_policy() -> PathPolicy { path_policy::active() }
+// verbose 时附带每个字段的来源，供前端展示与核对
+#[tauri::command]
+pub async fn get_track_metadata(path: String, verbose: bool) -> Result<TrackMetadata, String> {
+    let path = path_policy::check(&path, Access::Read)?;
+    Ok(extract_metadata(&path, &metadata_policy::active(), verbose))
+}
+// 批量把文件名 / 目录推断出的字段写进真实标签
+#[tauri::command]
+pub async fn promote_metadata(paths: Vec<String>) -> Result<Vec<PromoteResult>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let policy = metadata_policy::active();
+        paths.par_iter().map(|p| match path_policy::check(p, Access::Modify) {
+            Ok(_) => metadata_policy::promote(Path::new(p), &policy),
+            Err(e) => PromoteResult { path: p.clone(), promoted: Vec::new(), error: Some(e) },
+        }).collect()
+    }).await.map_err(|e| e.to_string())
+}
+
+// 后台生成曲库存储报告，返回任务 id；进度与结果经事件推送，可通过 cancel_job 取消
+#[tauri::command]
+pub fn library_storage_report(window: Window, state: State<AppState>, paths: Vec<String>, roots: Option<Vec<String>>, largest_n: Option<usize>) -> Result<String, String> {
+    path_policy::check_all(&paths, Access::Read)?;
+    path_policy::check_all(roots.as_deref().unwrap_or_default(), Access::Read)?;
+    let job = state.jobs.register(format!("storage_report_{}", chrono::Local::now().timestamp_micros()), JobKind::StorageReport, format!("{} files", paths.len()));
+    let job_id = job.id().to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        let report = storage_report::build_report(window.app_handle(), &paths, roots, largest_n.unwrap_or(storage_report::DEFAULT_LARGEST_N), &job,
+            |done, total| { let _ = events::STORAGE_REPORT_PROGRESS.emit(&window, &(done, total)); });
+        match report {
+            Some(report) => { let _ = events::STORAGE_REPORT_FINISH.emit(&window, &report); }
+            None => { let _ = events::STORAGE_REPORT_CANCELLED.emit(&window, job.id()); }
+        }
+    });
+    Ok(job_id)
+}
+#[tauri::command]
+pub fn get_last_storage_report() -> Option<StorageReport> { storage_report::last_report() }
+#[tauri::command]
+pub fn export_storage_report(path: String) -> Result<(), String> {
+    let target = path_policy::check(&path, Access::Export)?;
+    let report = storage_report::last_report().ok_or_else(|| "NO_STORAGE_REPORT".to_string())?;
+    storage_report::export(&report, &target)
+}
+
+// 曲库完整性校验：paths 缺省时复检所有校验过的文件；deep 时全量解码比对 PCM 校验和
+#[tauri::command]
+pub fn library_verify_integrity(window: Window, paths: Option<Vec<String>>, deep: bool) -> Result<String, String> {
+    let paths = match paths {
+        Some(paths) => { path_policy::check_all(&paths, Access::Read)?; paths }
+        None => IntegrityStore::load(window.app_handle()).known_paths(),
+    };
+    if paths.is_empty() { return Err("NO_PATHS".to_string()); }
+    Ok(run_integrity_job(window, IntegrityJob::new(paths, deep)))
+}
+#[tauri::command]
+pub fn library_resume_integrity(window: Window) -> Result<String, String> {
+    let job = IntegrityJob::load_interrupted(window.app_handle()).ok_or_else(|| "NO_INTERRUPTED_CHECK".to_string())?;
+    Ok(run_integrity_job(window, job))
+}
+#[tauri::command]
+pub fn library_get_integrity_failures(app: AppHandle) -> Vec<IntegrityRecord> { IntegrityStore::load(&app).failures() }
+
+fn run_integrity_job(window: Window, mut job: IntegrityJob) -> String {
+    let handle = window.state::<AppState>().jobs.register(job.id.clone(), JobKind::Integrity, format!("{} files", job.paths.len()));
+    let job_id = job.id.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let summary = integrity::run(window.app_handle(), &mut job, &handle,
+            |done, total| { let _ = events::INTEGRITY_PROGRESS.emit(&window, &(done, total)); });
+        let _ = events::INTEGRITY_FINISH.emit(&window, &summary);
+    });
+    job_id
+}
+
+// A/B 响度对比试听：返回任务 id，可用 cancel_job 中途叫停；片段切换经 preview-segment 推送
+#[tauri::command]
+pub async fn loudness_match_preview(state: State<'_, AppState>, path_a: String, path_b: String) -> Result<String, String> {
+    path_policy::check(&path_a, Access::Read)?;
+    path_policy::check(&path_b, Access::Read)?;
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::StartLoudnessPreview(path_a, path_b, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+
+// 逐曲测量响度与真峰值，结果经 replaygain-scan-progress / replaygain-scan-finish 推送；write_tags 时写回曲目增益标签
+#[tauri::command]
+pub fn scan_replaygain(window: Window, state: State<AppState>, paths: Vec<String>, write_tags: Option<bool>) -> Result<String, String> {
+    let write_tags = write_tags.unwrap_or(false);
+    path_policy::check_all(&paths, if write_tags { Access::Modify } else { Access::Read })?;
+    if paths.is_empty() { return Err("NO_PATHS".to_string()); }
+    let job = state.jobs.register(replaygain::SCAN_JOB_ID, JobKind::ReplayGainScan, format!("{} files", paths.len()));
+    tauri::async_runtime::spawn_blocking(move || {
+        let summary = replaygain::scan(&paths, write_tags, &job, |progress| { let _ = events::REPLAYGAIN_SCAN_PROGRESS.emit(&window, &progress); });
+        let _ = events::REPLAYGAIN_SCAN_FINISH.emit(&window, &summary);
+    });
+    Ok(replaygain::SCAN_JOB_ID.to_string())
+}
+#[tauri::command]
+pub fn cancel_scan(state: State<AppState>) -> Result<(), String> { state.jobs.cancel(replaygain::SCAN_JOB_ID) }
+
+// ==========================================
+// 🔖 章节书签
+// ==========================================
+#[tauri::command]
+pub async fn chapters_get(path: String) -> Result<ChapterSet, String> {
+    let path = path_policy::check(&path, Access::Read)?;
+    Ok(chapters::read(&path, FFmpegEngine::installed_exe().as_deref()))
+}
+// 外置文件总是写入；正在播放的文件不做重封装，只写外置文件并在回执中说明
+#[tauri::command]
+pub async fn chapters_set(state: State<'_, AppState>, path: String, chapters: Vec<Chapter>) -> Result<ChapterWriteResult, String> {
+    let loaded = query_state(&state).await?.loaded_path;
+    write_chapters(path, chapters, loaded).await
+}
+// 听到位置按下快捷键即可打点：取当前曲目与播放位置追加一章
+#[tauri::command]
+pub async fn chapters_add_at_current_position(state: State<'_, AppState>, title: String) -> Result<ChapterWriteResult, String> {
+    let player = query_state(&state).await?;
+    let path = player.loaded_path.clone().ok_or_else(|| "NO_TRACK_LOADED".to_string())?;
+    let mut existing = chapters::read(Path::new(&path), FFmpegEngine::installed_exe().as_deref()).chapters;
+    existing.push(Chapter { title, start: player.position, end: None });
+    write_chapters(path, existing, player.loaded_path).await
+}
+
+// 会重封装音频文件本身，按改动曲库文件对待
+async fn write_chapters(path: String, chapters: Vec<Chapter>, loaded: Option<String>) -> Result<ChapterWriteResult, String> {
+    path_policy::check(&path, Access::Modify)?;
+    let chapters = chapters::normalize(chapters)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let file = Path::new(&path);
+        let embed = match FFmpegEngine::installed_exe() {
+            _ if loaded.as_deref() == Some(path.as_str()) => Err("FILE_LOADED".to_string()),
+            Some(exe) => chapters::embed(file, &chapters, &exe),
+            None => Err("FFMPEG_MISSING".to_string()),
+        };
+        // 外置文件最后写入，保证其时间戳不早于重封装后的音频文件
+        let sidecar = chapters::write_sidecar(file, &chapters)?;
+        Ok(ChapterWriteResult { sidecar: sidecar.to_string_lossy().to_string(), embedded: embed.is_ok(), embed_error: embed.err() })
+    }).await.map_err(|e| e.to_string())?
+}
+
 #[tauri::command]
-pub fn check_file_exists(path: String) -> bool { Path::new(&path).exists() }
+pub async fn get_cover_full(path: String, allow_oversized: bool) -> Result<String, String> {
+    let path = path_policy::check(&path, Access::Read)?;
+    utils::get_cover_full(&path, allow_oversized)
+}
+
+#[tauri::command]
+pub fn set_cover_size_cap(megabytes: f64) {
+    if megabytes.is_finite() && megabytes > 0.0 { utils::set_cover_cap((megabytes * 1024.0 * 1024.0) as usize); }
+}
+
+// Galaxy 整曲 PCM 的内存预算，预计超出的曲目改写临时文件；从下一次载入起生效
+#[tauri::command]
+pub fn set_pcm_cache_budget(megabytes: f64) {
+    if megabytes.is_finite() && megabytes > 0.0 { pcm_spill::set_budget((megabytes * 1024.0 * 1024.0) as usize); }
+}
+
+#[tauri::command]
+pub fn check_file_exists(path: String) -> bool { path_policy::check(&path, Access::Read).is_ok_and(|p| p.exists()) }
 
 #[tauri::command]
 pub async fn init_audio_engine(window: Window, state: State<'_, AppState>, engine_id: String) -> Result<String, String> {
@@ -49,13 +390,7 @@ pub async fn init_audio_engine(window: Window, state: State<'_, AppState>, engin
             state.audio_tx.send(AudioCommand::SwitchEngine(engine_id.clone(), tx)).map_err(|e| e.to_string())?;
             return rx.await.map_err(|e| e.to_string())?;
         } else {
-            let win_clone = window.clone();
-            tauri::async_runtime::spawn(async move {
-                if let Err(e) = FFmpegEngine::download_and_install(win_clone.clone()).await {
-                    println!("FFmpeg install failed: {}", e);
-                    let _ = win_clone.emit("ffmpeg-status", "error");
-                }
-            });
+            spawn_ffmpeg_install(window, &state);
             return Ok("DOWNLOADING".to_string());
         }
     }
@@ -66,6 +401,7 @@ pub async fn init_audio_engine(window: Window, state: State<'_, AppState>, engin
 
 #[tauri::command]
 pub async fn player_load_track(state: State<'_, AppState>, path: String) -> Result<f64, String> {
+    path_policy::check(&path, Access::Read)?;
     if !Path::new(&path).exists() { return Err("FILE_NOT_FOUND".to_string()); }
     let (tx, rx) = oneshot::channel();
     state.audio_tx.send(AudioCommand::Load(path, tx)).map_err(|e| e.to_string())?;
@@ -73,24 +409,337 @@ pub async fn player_load_track(state: State<'_, AppState>, path: String) -> Resu
 }
 
 #[tauri::command]
-pub fn player_play(state: State<AppState>) { let _ = state.audio_tx.send(AudioCommand::Play); }
+pub async fn player_preload(state: State<'_, AppState>, path: String) -> Result<(), String> {
+    path_policy::check(&path, Access::Read)?;
+    if !Path::new(&path).exists() { return Err("FILE_NOT_FOUND".to_string()); }
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::Preload(path, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn player_play(state: State<AppState>) -> Result<(), String> {
+    audio::require_output()?;
+    state.audio_tx.send(AudioCommand::Play).map_err(|e| e.to_string())
+}
 #[tauri::command]
 pub fn player_pause(state: State<AppState>) { let _ = state.audio_tx.send(AudioCommand::Pause); }
+#[tauri::command]
+pub fn player_stop(state: State<AppState>) { let _ = state.audio_tx.send(AudioCommand::Stop); }
 
 #[tauri::command]
 pub async fn player_seek(window: Window, state: State<'_, AppState>, time: f64) -> Result<(), String> {
-    let _ = window.emit("seek-start", ());
+    let _ = events::SEEK_START.emit(&window, &());
     let (tx, rx) = oneshot::channel();
     state.audio_tx.send(AudioCommand::Seek(time, tx)).map_err(|e| e.to_string())?;
     let _ = rx.await;
-    let _ = window.emit("seek-end", time);
+    let _ = events::SEEK_END.emit(&window, &time);
     Ok(())
 }
 
 #[tauri::command]
-pub fn player_set_volume(state: State<AppState>, vol: f32) { let _ = state.audio_tx.send(AudioCommand::SetVolume(vol)); }
+pub async fn player_seek_relative(window: Window, state: State<'_, AppState>, delta: f64) -> Result<f64, String> {
+    let _ = events::SEEK_START.emit(&window, &());
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SeekRelative(delta, tx)).map_err(|e| e.to_string())?;
+    let time = rx.await.map_err(|e| e.to_string())?;
+    let _ = events::SEEK_END.emit(&window, &time);
+    Ok(time)
+}
+
 #[tauri::command]
-pub fn player_set_channels(state: State<AppState>, mode: u16) { let _ = state.audio_tx.send(AudioCommand::SetChannels(mode)); }
+pub async fn player_set_loop(state: State<'_, AppState>, start: f64, end: f64) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetLoop(start, end, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+#[tauri::command]
+pub fn player_clear_loop(state: State<AppState>) { let _ = state.audio_tx.send(AudioCommand::ClearLoop); }
+
+#[tauri::command]
+pub async fn player_set_volume(state: State<'_, AppState>, vol: f32) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetVolume(vol, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+#[tauri::command]
+pub async fn player_set_mute(state: State<'_, AppState>, muted: bool) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetMute(muted, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// curve: "linear" / "logarithmic"（滑块 0–1 对应 -60dB…0dB）
+#[tauri::command]
+pub async fn player_set_volume_curve(app: AppHandle, state: State<'_, AppState>, curve: String) -> Result<(), String> {
+    let curve = VolumeCurve::parse(&curve)?;
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetVolumeCurve(curve, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())??;
+    curve.save(&app.path().app_config_dir().map_err(|e| e.to_string())?)
+}
+// 各项为淡变毫秒数，0 表示直接切换；pause_ms 同时用于停止
+#[tauri::command]
+pub async fn player_set_fade_config(app: AppHandle, state: State<'_, AppState>, play_ms: u32, pause_ms: u32, seek_ms: u32, load_ms: u32) -> Result<(), String> {
+    let fades = FadeConfig { play_ms, pause_ms, seek_ms, load_ms };
+    fades.validate()?;
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetFadeConfig(fades, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())??;
+    fades.save(&app.path().app_config_dir().map_err(|e| e.to_string())?)
+}
+// 低于 threshold_db 且长于 min_len_ms 的静音段被越过，每次跳过推送 silence-skipped（秒）
+#[tauri::command]
+pub async fn player_set_skip_silence(app: AppHandle, state: State<'_, AppState>, enabled: bool, threshold_db: f32, min_len_ms: u32) -> Result<(), String> {
+    let prefs = SkipSilence { enabled, threshold_db, min_len_ms };
+    prefs.validate()?;
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetSkipSilence(prefs, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())??;
+    prefs.save(&app.path().app_config_dir().map_err(|e| e.to_string())?)
+}
+// 开启后载入时从片头静音结束处起播、在片尾静音开始处判定曲终；从下一次载入起生效
+#[tauri::command]
+pub fn player_set_auto_trim(state: State<AppState>, enabled: bool) -> Result<(), String> {
+    state.audio_tx.send(AudioCommand::SetAutoTrim(enabled)).map_err(|e| e.to_string())
+}
+// 片头 / 片尾低于 -60dBFS 的静音长度，按文件缓存
+#[tauri::command]
+pub async fn analyze_silence(app: AppHandle, path: String) -> Result<SilenceBounds, String> {
+    path_policy::check(&path, Access::Read)?;
+    tauri::async_runtime::spawn_blocking(move || silence::bounds(&app, &path, None)).await.map_err(|e| e.to_string())?
+}
+// minutes = 0 表示播完当前曲目后暂停；重新设定会取消上一个定时
+#[tauri::command]
+pub fn player_set_sleep_timer(app: AppHandle, minutes: u32) { sleep_timer::arm(&app, minutes); }
+#[tauri::command]
+pub fn player_cancel_sleep_timer(app: AppHandle) -> Result<(), String> { sleep_timer::cancel(&app) }
+// 可视化可见时开启：约 30 次/秒推送 spectrum-data（32 个对数频带，dBFS）；关闭后不再做 FFT
+#[tauri::command]
+pub fn visualizer_start(app: AppHandle) { spectrum::start(app); }
+#[tauri::command]
+pub fn visualizer_stop() { spectrum::stop(); }
+// 约 60 次/秒推送 scope-data：最近 samples_per_frame 帧音量后的交错 L/R 样本
+#[tauri::command]
+pub fn scope_start(app: AppHandle, samples_per_frame: usize) -> Result<(), String> { spectrum::scope_start(app, samples_per_frame) }
+#[tauri::command]
+pub fn scope_stop() { spectrum::scope_stop(); }
+// 约 50ms 推送一次 level-meter：各输出声道的峰值 / RMS (dBFS) 与粘滞削波标记
+#[tauri::command]
+pub fn metering_start(app: AppHandle) { level_meter::start(app); }
+#[tauri::command]
+pub fn metering_stop() { level_meter::stop(); }
+// 清除削波标记
+#[tauri::command]
+pub fn metering_ack_clip() { level_meter::ack_clip(); }
+// 返回切换后的静音状态
+#[tauri::command]
+pub async fn player_toggle_mute(state: State<'_, AppState>) -> Result<bool, String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::ToggleMute(tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+#[tauri::command]
+pub async fn player_set_rate_preserve_pitch(state: State<'_, AppState>, rate: f32) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetRate(rate, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+#[tauri::command]
+pub async fn set_parametric_eq(state: State<'_, AppState>, bands: Vec<EqBand>) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetParametricEq(bands, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// 低架增益 0dB 时整级直通；频率缺省 80Hz
+#[tauri::command]
+pub async fn player_set_bass_boost(state: State<'_, AppState>, gain_db: f32, freq: Option<f32>) -> Result<(), String> {
+    let bass = BassBoost { gain_db, freq: freq.unwrap_or(BassBoost::default().freq) };
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetBassBoost(bass, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// 1.0 原样，0.0 合成单声道，最高 2.0 加宽；实时生效无需重建 Sink
+#[tauri::command]
+pub async fn player_set_stereo_width(state: State<'_, AppState>, width: f32) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetStereoWidth(width, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// -1.0 全左，1.0 全右；环绕模式下前后左右对称生效，中置与 LFE 不受影响
+#[tauri::command]
+pub async fn player_set_balance(state: State<'_, AppState>, balance: f32) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetBalance(balance, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// 前级增益 ±20dB：位于 EQ 之前，与音量滑块相乘而非替代
+#[tauri::command]
+pub async fn player_set_preamp(state: State<'_, AppState>, gain_db: f32) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetPreamp(gain_db, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// 耳机串音：level 1–3；5.1/7.1 输出时引擎自动旁路
+#[tauri::command]
+pub async fn player_set_crossfeed(state: State<'_, AppState>, enabled: bool, level: u8) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetCrossfeed(Crossfeed { enabled, level }, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// 实时响度拉平：按瞬时响度把电平缓慢推向 target_lufs，与 ReplayGain 独立叠加
+#[tauri::command]
+pub async fn player_set_auto_level(state: State<'_, AppState>, enabled: bool, target_lufs: f32) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetAutoLevel(AutoLevel { enabled, target_lufs }, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// 环绕模式的 LFE 分频点 (40–300Hz) 与斜率 (12/24 dB/oct)，播放中即时生效
+#[tauri::command]
+pub async fn player_set_lfe_crossover(state: State<'_, AppState>, freq: f32, slope_db: u8) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetLfeCrossover(LfeCrossover { freq, slope_db }, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// 量化到设备位深前的 TPDF 抖动，bits 8–24；满音量且无任何处理时自动旁路
+#[tauri::command]
+pub async fn player_set_dither(state: State<'_, AppState>, enabled: bool, bits: u8) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetDither(Dither { enabled, bits }, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// 最终前视限幅器开关；关闭后只保留静态软拐点
+#[tauri::command]
+pub fn player_set_limiter_enabled(state: State<AppState>, enabled: bool) -> Result<(), String> {
+    state.audio_tx.send(AudioCommand::SetLimiterEnabled(enabled)).map_err(|e| e.to_string())
+}
+// 5.1 / 7.1 源折叠为立体声时是否并入 LFE；小音箱吃不下低频时保持关闭
+#[tauri::command]
+pub async fn player_set_downmix_lfe(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetDownmixLfe(enabled, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// 输出端点 (功放 / HDMI) 失活暂停后，端点恢复时是否自动续播
+#[tauri::command]
+pub fn player_set_output_auto_resume(app: AppHandle, state: State<AppState>, enabled: bool) -> Result<(), String> {
+    let dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    let prefs = OutputPrefs { auto_resume: enabled, ..OutputPrefs::load(&dir) };
+    state.audio_tx.send(AudioCommand::SetOutputPrefs(prefs)).map_err(|e| e.to_string())?;
+    prefs.save(&dir)
+}
+// 选中的设备被拔出后自动回退到默认设备；开启后原设备重新插上时切回去
+#[tauri::command]
+pub fn player_set_prefer_device(app: AppHandle, state: State<AppState>, enabled: bool) -> Result<(), String> {
+    let dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    let prefs = OutputPrefs { prefer_device: enabled, ..OutputPrefs::load(&dir) };
+    state.audio_tx.send(AudioCommand::SetOutputPrefs(prefs)).map_err(|e| e.to_string())?;
+    prefs.save(&dir)
+}
+// 匹配音源格式：按曲目的采样率与位深打开设备 (共享模式，并非 WASAPI 独占)，设备不接受时退回其默认配置并发 output-mode-fallback；生效后落盘
+#[tauri::command]
+pub async fn set_match_source_format(app: AppHandle, state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    let dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetMatchSourceFormat(enabled, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())??;
+    OutputPrefs { match_source_format: enabled, ..OutputPrefs::load(&dir) }.save(&dir)
+}
+// 输出采样率：None 为匹配音源 (每首按其采样率重开设备)，指定值时固定在该采样率并由引擎高质量重采样；生效后落盘
+#[tauri::command]
+pub async fn set_output_sample_rate(app: AppHandle, state: State<'_, AppState>, rate: Option<u32>) -> Result<(), String> {
+    let dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetOutputSampleRate(rate, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())??;
+    let sample_rate = rate.map_or(OutputRate::Source, OutputRate::Fixed);
+    OutputPrefs { sample_rate, ..OutputPrefs::load(&dir) }.save(&dir)
+}
+// 跟随曲目采样率 (匹配音源 / 独占) 时的上限，超出时按整数倍降下来；None 取消上限
+#[tauri::command]
+pub async fn set_max_output_rate(app: AppHandle, state: State<'_, AppState>, rate: Option<u32>) -> Result<(), String> {
+    let dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetMaxOutputRate(rate, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())??;
+    OutputPrefs { max_output_rate: rate, ..OutputPrefs::load(&dir) }.save(&dir)
+}
+// "Default" 模式下是否跟随系统默认设备 (默认开启)；关闭后留在当前打开的设备上
+#[tauri::command]
+pub fn set_follow_system_default(app: AppHandle, state: State<AppState>, enabled: bool) -> Result<(), String> {
+    let dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    let prefs = OutputPrefs { follow_default: enabled, ..OutputPrefs::load(&dir) };
+    state.audio_tx.send(AudioCommand::SetOutputPrefs(prefs)).map_err(|e| e.to_string())?;
+    prefs.save(&dir)
+}
+// 环绕上混的后置延迟 (0–50ms) 与后置/中置/LFE 电平倍率 (0–2)；生效后落盘，下次启动自动恢复
+#[tauri::command]
+pub async fn player_set_upmix_params(app: AppHandle, state: State<'_, AppState>, rear_delay_ms: f32, rear_level: f32, center_level: f32, lfe_level: f32) -> Result<(), String> {
+    let params = UpmixParams { rear_delay_ms, rear_level, center_level, lfe_level };
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetUpmixParams(params, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())??;
+    params.save(&app.path().app_config_dir().map_err(|e| e.to_string())?)
+}
+// layout: "smpte" / "film" / "custom"；custom 时 map[角色] = 输出声道，角色顺序 L R C LFE Ls Rs Lb Rb
+#[tauri::command]
+pub async fn player_set_channel_layout(app: AppHandle, state: State<'_, AppState>, layout: String, map: Option<Vec<u8>>) -> Result<(), String> {
+    let layout = ChannelLayout::parse(&layout, map)?;
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetChannelLayout(layout, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())??;
+    layout.save(&app.path().app_config_dir().map_err(|e| e.to_string())?)
+}
+// mode: "off" / "track" / "album"；fallback_db 缺省时沿用已保存的兜底增益
+#[tauri::command]
+pub async fn player_set_replaygain(app: AppHandle, state: State<'_, AppState>, mode: String, fallback_db: Option<f32>) -> Result<(), String> {
+    let config_dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    let mut prefs = ReplayGainPrefs::load(&config_dir);
+    prefs.mode = ReplayGainMode::parse(&mode)?;
+    if let Some(fallback_db) = fallback_db { prefs.fallback_db = fallback_db; }
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetReplayGain(prefs, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())??;
+    prefs.save(&config_dir)
+}
+#[tauri::command]
+pub fn eq_save_preset(app: AppHandle, name: String, bands: Vec<EqBand>) -> Result<(), String> {
+    eq_presets::save(&eq_presets::presets_dir(&app), &name, bands)
+}
+#[tauri::command]
+pub fn eq_list_presets(app: AppHandle) -> Vec<EqPreset> { eq_presets::list(&eq_presets::presets_dir(&app)) }
+// 读取后立即下发到实时 DSP 链，并把频段回传给前端刷新滑块
+#[tauri::command]
+pub async fn eq_load_preset(app: AppHandle, state: State<'_, AppState>, name: String) -> Result<Vec<EqBand>, String> {
+    let preset = eq_presets::load(&eq_presets::presets_dir(&app), &name)?;
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetParametricEq(preset.bands.clone(), tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())??;
+    Ok(preset.bands)
+}
+#[tauri::command]
+pub fn eq_delete_preset(app: AppHandle, name: String) -> Result<(), String> {
+    eq_presets::delete(&eq_presets::presets_dir(&app), &name)
+}
+#[tauri::command]
+pub async fn player_set_channels(state: State<'_, AppState>, mode: u16) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetChannels(mode, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// 比特完美直通：开启期间音量锁定 1.0、声道模式不可改，DSP 设置保留待退出后恢复
+#[tauri::command]
+pub async fn player_set_bitperfect(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetBitperfect(enabled, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn player_set_resampler(state: State<'_, AppState>, mode: String) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::SetResampler(mode, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
 
 #[tauri::command]
 pub async fn get_output_devices(state: State<'_, AppState>) -> Result<Vec<String>, String> { 
@@ -98,6 +747,28 @@ pub async fn get_output_devices(state: State<'_, AppState>) -> Result<Vec<String
     state.audio_tx.send(AudioCommand::GetDevices(tx)).map_err(|e| e.to_string())?;
     rx.await.map_err(|e| e.to_string())
 }
+// 每个设备附带 id、默认/当前标记与声道数、采样率；查询失败的设备也列出，只缺相应字段
+#[tauri::command]
+pub async fn get_output_devices_detailed(state: State<'_, AppState>) -> Result<Vec<OutputDeviceInfo>, String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::GetDevicesDetailed(tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+
+// 最后手段：重建输出流与引擎，保留全部设置；当前曲目需重新载入
+#[tauri::command]
+pub async fn reset_audio(state: State<'_, AppState>) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::ResetAudio(tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// 启动时没有输出设备的情况下重新尝试打开；成功后按所选引擎恢复播放能力
+#[tauri::command]
+pub async fn retry_audio_init(state: State<'_, AppState>) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::RetryOutput(tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
 
 #[tauri::command]
 pub async fn set_output_device(state: State<'_, AppState>, device: String) -> Result<String, String> { 
@@ -106,12 +777,64 @@ pub async fn set_output_device(state: State<'_, AppState>, device: String) -> Re
     rx.await.map_err(|e| e.to_string())?
 }
 
+// 已弃用：请改用 player_get_state，保留仅为兼容旧前端
 #[tauri::command]
-pub async fn get_current_engine(state: State<'_, AppState>) -> Result<String, String> {
+pub async fn get_device_capabilities(state: State<'_, AppState>, device: Option<String>) -> Result<DeviceCapabilities, String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::GetDeviceCapabilities(device, false, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn refresh_device_capabilities(state: State<'_, AppState>, device: String) -> Result<DeviceCapabilities, String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::GetDeviceCapabilities(Some(device), true, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+
+// 缓存探测报告：包含每个条目的探测时间与是否过期
+#[tauri::command]
+pub async fn get_device_probe_report(state: State<'_, AppState>) -> Result<Vec<DeviceCapabilities>, String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::GetDeviceProbeReport(tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct SupportedFormats {
+    engine_id: &'static str,
+    native: Vec<&'static str>,     // 当前引擎可直接载入
+    playable: Vec<&'static str>,   // 含装了 FFmpeg 时由其代为解码的格式
+    importable: &'static [&'static str],
+}
+
+// 按当前引擎给出可播放 / 可导入的扩展名 (小写)
+#[tauri::command]
+pub async fn get_supported_formats(state: State<'_, AppState>) -> Result<SupportedFormats, String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::GetCurrentEngine(tx)).map_err(|e| e.to_string())?;
+    let engine = rx.await.map_err(|e| e.to_string())?;
+    let mut playable = engine.capabilities.formats.clone();
+    if engine.id == "galaxy" && FFmpegEngine::installed_exe().is_some() {
+        playable.extend(ffmpeg::FORMATS.iter().filter(|ext| !engine.capabilities.formats.contains(ext)));
+    }
+    Ok(SupportedFormats { engine_id: engine.id, native: engine.capabilities.formats, playable, importable: import_jobs::AUDIO_EXTENSIONS })
+}
+
+#[tauri::command]
+pub async fn get_current_engine(state: State<'_, AppState>) -> Result<EngineInfo, String> {
     let (tx, rx) = oneshot::channel();
     state.audio_tx.send(AudioCommand::GetCurrentEngine(tx)).map_err(|e| e.to_string())?;
-    let name = rx.await.map_err(|e| e.to_string())?;
-    if name.contains("FFmpeg") { Ok("ffmpeg".to_string()) } else { Ok("galaxy".to_string()) }
+    rx.await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn player_get_state(state: State<'_, AppState>) -> Result<PlayerState, String> { query_state(&state).await }
+
+async fn query_state(state: &AppState) -> Result<PlayerState, String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::GetState(tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -121,18 +844,261 @@ pub async fn get_current_time(state: State<'_, AppState>) -> Result<f64, String>
     rx.await.map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn player_get_clipping_report(state: State<'_, AppState>) -> Result<Option<ClipReport>, String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::GetClipReport(tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_dsp_chain() -> Vec<DspStageInfo> { dsp_registry::snapshot() }
+
+#[derive(serde::Serialize)]
+pub struct AudioStats {
+    pub dsp_chain: Vec<DspStageInfo>,
+    pub decode: CoordinatorStats,
+}
+
+#[tauri::command]
+pub fn get_audio_stats() -> AudioStats {
+    AudioStats { dsp_chain: dsp_registry::snapshot(), decode: COORDINATOR.stats() }
+}
+
+// ==========================================
+// 🔊 信号发生器：替换当前曲目输出测试信号，停止后回到原曲目与位置
+// ==========================================
+#[tauri::command]
+pub async fn generator_start(state: State<'_, AppState>, kind: String, params: Option<GeneratorParams>) -> Result<(), String> {
+    let request = GeneratorRequest::parse(&kind, &params.unwrap_or_default())?;
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::StartGenerator(request, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+// 调试用：按当前声道模式与排布逐个声道播放提示音，返回播放计划；generator_stop 结束并恢复原曲目
+#[tauri::command]
+pub async fn player_identify_channels(state: State<'_, AppState>) -> Result<Vec<ChannelIdent>, String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::IdentifyChannels(tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+#[tauri::command]
+pub async fn generator_stop(state: State<'_, AppState>) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::StopGenerator(tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+
+// ==========================================
+// 🛋️ 房间预设：保存当前设备/声道/音量/DSP，一键整体切换
+// ==========================================
+#[tauri::command]
+pub async fn room_preset_save(app: AppHandle, state: State<'_, AppState>, name: String) -> Result<RoomPreset, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() { return Err("PRESET_NAME_EMPTY".to_string()); }
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::CaptureRoomPreset(name, tx)).map_err(|e| e.to_string())?;
+    let preset = rx.await.map_err(|e| e.to_string())?;
+    room_presets::save(&room_presets::presets_dir(&app), &preset)?;
+    Ok(preset)
+}
+// 进度经 "room-preset-progress" 逐步推送，切设备可能耗时一秒左右
+#[tauri::command]
+pub async fn room_preset_apply(app: AppHandle, state: State<'_, AppState>, name: String) -> Result<(), String> {
+    let preset = room_presets::load(&room_presets::presets_dir(&app), &name)?;
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::ApplyRoomPreset(preset, tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+#[tauri::command]
+pub fn room_preset_list(app: AppHandle) -> Vec<RoomPreset> { room_presets::list(&room_presets::presets_dir(&app)) }
+
+// ==========================================
+// 📋 后台任务：统一列出与取消
+// ==========================================
+#[tauri::command]
+pub fn get_running_jobs(state: State<AppState>) -> Vec<JobInfo> { state.jobs.list() }
+#[tauri::command]
+pub fn cancel_job(state: State<AppState>, job_id: String) -> Result<(), String> { state.jobs.cancel(&job_id) }
+
 #[tauri::command]
 pub fn check_ffmpeg_exists(window: Window) -> bool {
     FFmpegEngine::check_availability(window.app_handle())
 }
 
-#[tauri::command]
-pub fn start_ffmpeg_download(window: Window) {
-    let win_clone = window.clone();
+fn spawn_ffmpeg_install(window: Window, state: &AppState) {
+    let job = state.jobs.register(FFMPEG_DOWNLOAD_JOB, JobKind::FfmpegDownload, "FFmpeg");
     tauri::async_runtime::spawn(async move {
-        if let Err(e) = FFmpegEngine::download_and_install(win_clone.clone()).await {
+        if let Err(e) = FFmpegEngine::download_and_install(window.clone(), job).await {
             println!("FFmpeg install failed: {}", e);
-            let _ = win_clone.emit("ffmpeg-status", "error");
+            let _ = events::FFMPEG_STATUS.emit(&window, FFmpegEngine::install_failure_status(&e));
         }
     });
-}
\ No newline at end of file
+}
+
+#[tauri::command]
+pub fn start_ffmpeg_download(window: Window, state: State<AppState>) { spawn_ffmpeg_install(window, &state); }
+
+#[tauri::command]
+pub fn get_ffmpeg_info() -> FFmpegInfo { FFmpegEngine::info() }
+
+// 重走下载安装流程：新版本在同目录暂存、跑通 -version 后才替换，任何一步失败旧版本都原样保留。
+// 正在运行的程序无法被替换，有解码进程时直接拒绝，需先停止播放
+#[tauri::command]
+pub fn update_ffmpeg(window: Window, state: State<AppState>) -> Result<(), String> {
+    if pcm_stream::live_decoders() > 0 { return Err(format!("{}: stop playback before updating", FFMPEG_BUSY)); }
+    spawn_ffmpeg_install(window, &state);
+    Ok(())
+}
+
+// FFmpeg 下载走的代理 (http:// https:// socks5://)；传 None 或空串清除，下次下载生效
+#[tauri::command]
+pub fn set_download_proxy(app: AppHandle, url: Option<String>) -> Result<(), String> {
+    DownloadPrefs::new(url)?.save(&app.path().app_config_dir().map_err(|e| e.to_string())?)
+}
+
+// ==========================================
+// 🎶 后端播放队列指令集
+// ==========================================
+// 所有改动队列的入口都经过这里，顺带刷新崩溃恢复快照里的队列段
+fn with_queue<R>(state: &AppState, f: impl FnOnce(&mut PlayQueue) -> R) -> R {
+    let mut queue = state.queue();
+    let result = f(&mut queue);
+    recovery::note_queue(&queue.section());
+    result
+}
+
+#[tauri::command]
+pub fn queue_set(state: State<AppState>, paths: Vec<String>) { with_queue(&state, |q| q.set(paths)); }
+#[tauri::command]
+pub fn queue_add(state: State<AppState>, path: String) { with_queue(&state, |q| q.add(path)); }
+#[tauri::command]
+pub fn queue_remove(state: State<AppState>, index: usize) -> Result<(), String> { with_queue(&state, |q| q.remove(index)) }
+
+// 队列条目同样来自前端，播放前再过一遍路径策略
+async fn play_queue_entry(app: &AppHandle, path: String, start: bool) -> Result<TrackMetadata, String> {
+    path_policy::check(&path, Access::Read)?;
+    let state = app.state::<AppState>();
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::Load(path.clone(), tx)).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())??;
+    if start { let _ = state.audio_tx.send(AudioCommand::Play); }
+
+    let track = extract_metadata(&PathBuf::from(&path), &metadata_policy::active(), false);
+    let _ = events::TRACK_CHANGED.emit(app, &track);
+    Ok(track)
+}
+
+// 供 track-ended 自动续播 (auto) 与 player_next 共用
+pub async fn advance_queue(app: &AppHandle, auto: bool) -> Result<Option<TrackMetadata>, String> {
+    // 设了「播完当前曲目」的睡眠定时：队列照常推进，下一首只载入不起播
+    let sleeping = auto && sleep_timer::take_after_track();
+    let advance = with_queue(&app.state::<AppState>(), |q| q.advance(auto));
+    let result = match advance {
+        Advance::Play(path) => play_queue_entry(app, path, !sleeping).await.map(Some),
+        Advance::Restart => {
+            let state = app.state::<AppState>();
+            let (tx, rx) = oneshot::channel();
+            state.audio_tx.send(AudioCommand::Seek(0.0, tx)).map_err(|e| e.to_string())?;
+            let _ = rx.await;
+            if !sleeping { let _ = state.audio_tx.send(AudioCommand::Play); }
+            Ok(None)
+        }
+        Advance::Finished(action) => {
+            for step in action.finish_steps() {
+                match step {
+                    FinishStep::Pause => { let _ = app.state::<AppState>().audio_tx.send(AudioCommand::Pause); }
+                    FinishStep::Notify(action) => { let _ = events::QUEUE_FINISHED.emit(app, &action); }
+                }
+            }
+            Ok(None)
+        }
+        Advance::End => Ok(None),
+    };
+    if sleeping { sleep_timer::fire(app, SleepMode::AfterTrack); }
+    result
+}
+
+#[tauri::command]
+pub async fn player_next(app: AppHandle) -> Result<Option<TrackMetadata>, String> {
+    advance_queue(&app, false).await
+}
+
+#[tauri::command]
+pub fn queue_set_mode(state: State<AppState>, shuffle: bool, repeat: RepeatMode) {
+    with_queue(&state, |q| q.set_mode(shuffle, repeat));
+}
+
+#[tauri::command]
+pub fn queue_set_end_action(app: AppHandle, state: State<AppState>, action: EndAction) -> Result<(), String> {
+    if !action.is_supported() { return Err(format!("END_ACTION_UNSUPPORTED: {:?}", action)); }
+    state.queue().end_action = action;
+    let config_dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    QueuePrefs { end_action: action }.save(&config_dir)
+}
+
+#[tauri::command]
+pub async fn player_previous(app: AppHandle, state: State<'_, AppState>) -> Result<Option<TrackMetadata>, String> {
+    let (tx, rx) = oneshot::channel();
+    state.audio_tx.send(AudioCommand::GetCurrentTime(tx)).map_err(|e| e.to_string())?;
+    let position = rx.await.map_err(|e| e.to_string())?;
+
+    // 播放超过 3 秒则回到本曲开头，否则退回上一首
+    if position > 3.0 {
+        let (tx, rx) = oneshot::channel();
+        state.audio_tx.send(AudioCommand::Seek(0.0, tx)).map_err(|e| e.to_string())?;
+        let _ = rx.await;
+        return Ok(None);
+    }
+
+    let prev = with_queue(&state, |q| q.previous());
+    match prev {
+        Some(path) => play_queue_entry(&app, path, true).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+// ==========================================
+// 🩹 崩溃恢复
+// ==========================================
+#[derive(serde::Serialize)]
+pub struct RecoveryStatus { crashed: bool, saved_at: Option<i64> }
+
+#[tauri::command]
+pub fn recovery_status(app: AppHandle) -> RecoveryStatus {
+    let saved_at = app.path().app_local_data_dir().ok().and_then(|dir| recovery::load_snapshot(&dir).ok()).map(|s| s.saved_at);
+    RecoveryStatus { crashed: recovery::previous_crashed(), saved_at }
+}
+
+// 引擎段交给音频线程逐字段恢复；队列段剔除已丢失的文件后整体还原
+#[tauri::command]
+pub async fn recover_last_state(app: AppHandle, state: State<'_, AppState>) -> Result<RecoveryReport, String> {
+    let dir = app.path().app_local_data_dir().map_err(|e| e.to_string())?;
+    let snapshot = recovery::load_snapshot(&dir)?;
+    let mut report = match snapshot.engine {
+        Some(section) => {
+            let (tx, rx) = oneshot::channel();
+            state.audio_tx.send(AudioCommand::RestoreSnapshot(section, snapshot.position, tx)).map_err(|e| e.to_string())?;
+            rx.await.map_err(|e| e.to_string())?
+        }
+        None => RecoveryReport { position: snapshot.position, ..Default::default() },
+    };
+    report.was_playing = snapshot.was_playing;
+
+    let queue = snapshot.queue.filter(|v| !v.is_null())
+        .ok_or_else(|| "NO_QUEUE".to_string())
+        .and_then(|v| serde_json::from_value::<QueueSection>(v).map_err(|e| format!("QUEUE_CORRUPT: {}", e)));
+    let queue = queue.map(|section| with_queue(&state, |q| q.restore(section, |p| Path::new(p).exists())));
+    if let Ok(dropped) = &queue {
+        if *dropped > 0 { println!("[RECOVERY] {} queued file(s) no longer exist and were dropped.", dropped); }
+    }
+    report.record("queue", queue);
+    recovery::clear_crash_flag();
+    Ok(report)
+}
+
+// ==========================================
+// 📜 接口清单
+// ==========================================
+#[tauri::command]
+pub fn get_api_schema() -> ApiSchema { api_schema::schema() }
@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use tauri::Manager;
+use crate::audio::dsp::{EqBand, EqBandType};
+
+// ==========================================
+// 🎚️ 均衡器预设：内置预设编译进二进制，用户预设落盘为 JSON
+// ==========================================
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EqPreset {
+    pub name: String,
+    pub bands: Vec<EqBand>,
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+const fn peak(freq: f32, gain_db: f32) -> EqBand { EqBand { kind: EqBandType::Peak, freq, q: 1.0, gain_db } }
+const fn low_shelf(freq: f32, gain_db: f32) -> EqBand { EqBand { kind: EqBandType::LowShelf, freq, q: 0.707, gain_db } }
+const fn high_shelf(freq: f32, gain_db: f32) -> EqBand { EqBand { kind: EqBandType::HighShelf, freq, q: 0.707, gain_db } }
+
+const BUILTIN: &[(&str, &[EqBand])] = &[
+    ("Flat", &[]),
+    ("Rock", &[low_shelf(100.0, 4.0), peak(400.0, -2.0), peak(2500.0, 2.0), high_shelf(8000.0, 3.5)]),
+    ("Classical", &[low_shelf(120.0, 2.0), peak(1000.0, -1.0), high_shelf(10000.0, 2.5)]),
+    ("Bass Boost", &[low_shelf(80.0, 6.0), peak(160.0, 2.0)]),
+    ("Vocal", &[low_shelf(120.0, -2.0), peak(1500.0, 3.0), peak(3500.0, 2.0), high_shelf(9000.0, -1.0)]),
+];
+
+fn builtin(name: &str) -> Option<EqPreset> {
+    BUILTIN.iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(n, bands)| EqPreset { name: n.to_string(), bands: bands.to_vec(), builtin: true })
+}
+
+pub fn presets_dir(app: &tauri::AppHandle) -> PathBuf {
+    app.path().app_local_data_dir().unwrap_or_else(|_| PathBuf::from("./data")).join("eq_presets")
+}
+
+// 预设名直接作文件名，剔除路径分隔符等非法字符
+fn preset_path(dir: &Path, name: &str) -> PathBuf {
+    let safe: String = name.chars().map(|c| if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' { c } else { '_' }).collect();
+    dir.join(format!("{}.json", safe.trim()))
+}
+
+pub fn save(dir: &Path, name: &str, bands: Vec<EqBand>) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() { return Err("PRESET_NAME_EMPTY".to_string()); }
+    if builtin(name).is_some() { return Err("PRESET_BUILTIN_READONLY".to_string()); }
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let preset = EqPreset { name: name.to_string(), bands, builtin: false };
+    let json = serde_json::to_string_pretty(&preset).map_err(|e| e.to_string())?;
+    fs::write(preset_path(dir, name), json).map_err(|e| e.to_string())
+}
+
+pub fn load(dir: &Path, name: &str) -> Result<EqPreset, String> {
+    if let Some(preset) = builtin(name) { return Ok(preset); }
+    let json = fs::read_to_string(preset_path(dir, name)).map_err(|_| "PRESET_NOT_FOUND".to_string())?;
+    serde_json::from_str(&json).map_err(|e| format!("PRESET_CORRUPT: {}", e))
+}
+
+pub fn delete(dir: &Path, name: &str) -> Result<(), String> {
+    if builtin(name).is_some() { return Err("PRESET_BUILTIN_READONLY".to_string()); }
+    fs::remove_file(preset_path(dir, name)).map_err(|_| "PRESET_NOT_FOUND".to_string())
+}
+
+// 损坏或格式不符的文件跳过并告警，不影响其余预设
+pub fn list(dir: &Path) -> Vec<EqPreset> {
+    let mut presets: Vec<EqPreset> = BUILTIN.iter().filter_map(|(n, _)| builtin(n)).collect();
+    let Ok(entries) = fs::read_dir(dir) else { return presets };
+    let mut user: Vec<EqPreset> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().map(|x| x == "json").unwrap_or(false))
+        .filter_map(|p| {
+            let parsed = fs::read_to_string(&p).map_err(|e| e.to_string())
+                .and_then(|json| serde_json::from_str::<EqPreset>(&json).map_err(|e| e.to_string()));
+            match parsed {
+                Ok(mut preset) => { preset.builtin = false; Some(preset) }
+                Err(e) => { eprintln!("[EQ] Skipping invalid preset {:?}: {}", p, e); None }
+            }
+        })
+        .collect();
+    user.sort_by_key(|p| p.name.to_lowercase());
+    presets.extend(user);
+    presets
+}
@@ -0,0 +1,370 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+use tauri::Manager;
+use crate::util::cancel::CancellationToken;
+use super::utils::{CoverIssue, TrackMetadata};
+
+// ==========================================
+// 📥 导入任务断点记录：应用中途退出后可续传
+// ==========================================
+pub const CHECKPOINT_BATCH: usize = 64;
+// 可导入的扩展名，导入对话框、拖放与文件夹遍历共用；一律小写，比较时先把文件扩展名转小写
+pub const AUDIO_EXTENSIONS: &[&str] = &["mp3", "flac", "wav", "ogg", "opus", "m4a", "wma", "aac", "aiff", "aif", "ape", "wv", "tta", "dsf", "dff", "mka", "webm"];
+
+// 文件对话框的过滤器：GTK 按原样区分大小写，同时列出大写形式，老翻录的 SONG.MP3 也能看到
+pub fn dialog_extensions() -> Vec<String> {
+    AUDIO_EXTENSIONS.iter().flat_map(|ext| [ext.to_string(), ext.to_ascii_uppercase()]).collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportJob {
+    pub id: String,
+    pub paths: Vec<String>,
+    pub completed: usize,
+    pub started_at: i64,
+    #[serde(default)]
+    pub force: bool, // 已导入过的文件也重新解析 (外部改过标签)
+}
+
+impl ImportJob {
+    pub fn new(paths: Vec<String>, force: bool) -> Self {
+        let now = chrono::Local::now();
+        Self { id: format!("import_{}", now.timestamp_micros()), paths, completed: 0, started_at: now.timestamp(), force }
+    }
+
+    pub fn remaining(&self) -> &[String] {
+        &self.paths[self.completed.min(self.paths.len())..]
+    }
+}
+
+fn is_audio(path: &Path) -> bool {
+    path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.as_str()))
+}
+
+// 拖放进来的文件与文件夹展开成音频文件列表：文件夹递归遍历 (不跟随符号链接，避免成环)，非音频文件直接跳过
+pub fn collect_audio_files(paths: &[PathBuf]) -> Vec<String> {
+    fn walk(path: &Path, out: &mut Vec<String>) {
+        if path.is_dir() {
+            let Ok(entries) = fs::read_dir(path) else { return };
+            let mut children: Vec<PathBuf> = entries.filter_map(|e| e.ok())
+                .filter(|e| e.file_type().map(|t| !t.is_symlink()).unwrap_or(false))
+                .map(|e| e.path()).collect();
+            children.sort(); // 同一专辑按文件名顺序进入曲库
+            for child in &children { walk(child, out); }
+        } else if is_audio(path) {
+            out.push(path.to_string_lossy().to_string());
+        }
+    }
+    let mut out = Vec::new();
+    for path in paths { walk(path, &mut out); }
+    out
+}
+
+// ==========================================
+// 📦 import-tracks-batch 攒批：满 BATCH_TRACKS 首或距上次发出超过 BATCH_INTERVAL 即交出一批，
+// 每首一个事件时上万次 IPC 序列化会把 webview 堵住
+// ==========================================
+const BATCH_TRACKS: usize = 50;
+const BATCH_INTERVAL: Duration = Duration::from_millis(200);
+
+pub struct TrackBatcher {
+    pending: Mutex<(Vec<TrackMetadata>, Instant)>,
+}
+
+impl Default for TrackBatcher {
+    fn default() -> Self { Self { pending: Mutex::new((Vec::with_capacity(BATCH_TRACKS), Instant::now())) } }
+}
+
+impl TrackBatcher {
+    // 到了该发的时候返回整批，由调用方发出
+    pub fn push(&self, track: TrackMetadata) -> Option<Vec<TrackMetadata>> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.0.push(track);
+        if pending.0.len() < BATCH_TRACKS && pending.1.elapsed() < BATCH_INTERVAL { return None; }
+        pending.1 = Instant::now();
+        Some(std::mem::replace(&mut pending.0, Vec::with_capacity(BATCH_TRACKS)))
+    }
+
+    // 每个断点批次结束、取消或完成时交出剩余的
+    pub fn flush(&self) -> Option<Vec<TrackMetadata>> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.1 = Instant::now();
+        (!pending.0.is_empty()).then(|| std::mem::take(&mut pending.0))
+    }
+}
+
+// ==========================================
+// 🧾 本次运行中已导入过的文件 (按规范化路径)，重复导入时发 import-skipped 而不是再推一遍 import-track
+// ==========================================
+static SEEN: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+// 首次见到返回 true
+pub fn remember(path: &Path) -> bool {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    SEEN.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert_with(HashSet::new).insert(canonical)
+}
+
+// ==========================================
+// ⏸️ 正在运行的导入：按任务 id 暂停 / 继续，id 对不上的旧指令不会误伤新一批
+// ==========================================
+#[derive(Default)]
+pub struct PauseGate {
+    paused: Mutex<bool>,
+    resumed: Condvar,
+}
+
+impl PauseGate {
+    fn lock(&self) -> MutexGuard<'_, bool> { self.paused.lock().unwrap_or_else(|e| e.into_inner()) }
+
+    fn set(&self, paused: bool) {
+        *self.lock() = paused;
+        self.resumed.notify_all();
+    }
+
+    // 暂停期间在此等候；期间被取消立即放行，由调用方随后检查取消
+    pub fn wait(&self, token: &CancellationToken) {
+        let mut paused = self.lock();
+        while *paused && !token.is_cancelled() {
+            paused = self.resumed.wait_timeout(paused, Duration::from_millis(100)).unwrap_or_else(|e| e.into_inner()).0;
+        }
+    }
+}
+
+static ACTIVE: Mutex<Option<(String, Arc<PauseGate>)>> = Mutex::new(None);
+
+fn active() -> MutexGuard<'static, Option<(String, Arc<PauseGate>)>> { ACTIVE.lock().unwrap_or_else(|e| e.into_inner()) }
+
+// 导入线程拿到执行权后登记，结束时注销
+pub fn activate(id: &str) -> Arc<PauseGate> {
+    let gate = Arc::new(PauseGate::default());
+    *active() = Some((id.to_string(), gate.clone()));
+    gate
+}
+
+pub fn deactivate(id: &str) {
+    let mut active = active();
+    if active.as_ref().is_some_and(|(current, _)| current == id) { *active = None; }
+}
+
+// 完成或取消都算正常结束：注销并删掉断点记录，可续传列表里只留下进程中断的任务
+pub fn finish(dir: &Path, id: &str) {
+    deactivate(id);
+    remove_job(dir, id);
+}
+
+pub fn active_id() -> Option<String> { active().as_ref().map(|(id, _)| id.clone()) }
+
+// 不指定 id 时作用于当前导入
+pub fn set_paused(id: Option<&str>, paused: bool) -> Result<(), String> {
+    let active = active();
+    let Some((current, gate)) = active.as_ref() else { return Err("NO_ACTIVE_IMPORT".to_string()) };
+    if id.is_some_and(|id| id != current) { return Err("IMPORT_NOT_FOUND".to_string()); }
+    gate.set(paused);
+    Ok(())
+}
+
+pub fn jobs_dir(app: &tauri::AppHandle) -> PathBuf {
+    app.path().app_local_data_dir().unwrap_or_else(|_| PathBuf::from("./data")).join("import_jobs")
+}
+
+pub fn save_job(dir: &Path, job: &ImportJob) {
+    let _ = fs::create_dir_all(dir);
+    if let Ok(json) = serde_json::to_string(job) {
+        // 先写临时文件再改名，避免退出瞬间留下半截记录
+        let tmp = dir.join(format!("{}.json.tmp", job.id));
+        if fs::write(&tmp, json).is_ok() {
+            let _ = fs::rename(tmp, dir.join(format!("{}.json", job.id)));
+        }
+    }
+}
+
+pub fn remove_job(dir: &Path, job_id: &str) {
+    let _ = fs::remove_file(dir.join(format!("{}.json", job_id)));
+}
+
+pub fn load_job(dir: &Path, job_id: &str) -> Result<ImportJob, String> {
+    let json = fs::read_to_string(dir.join(format!("{}.json", job_id))).map_err(|e| e.to_string())?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+pub fn list_jobs(dir: &Path) -> Vec<ImportJob> {
+    let Ok(entries) = fs::read_dir(dir) else { return vec![] };
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().map(|x| x == "json").unwrap_or(false))
+        .filter_map(|e| fs::read_to_string(e.path()).ok())
+        .filter_map(|json| serde_json::from_str::<ImportJob>(&json).ok())
+        .filter(|job| job.completed < job.paths.len())
+        .collect()
+}
+
+// ==========================================
+// 🔁 分批解析与断点：每批并行解析，批次结束后落盘一次断点，热循环内不做任何 IO；中途取消时断点停在上一批末尾，是否保留由调用方决定
+// ==========================================
+// 解析过程中的对外通知：命令层接到窗口事件上
+pub trait ImportSink: Sync {
+    fn skipped(&self, path: &str, reason: &'static str);
+    fn failed(&self, path: &str, reason: String);
+    // handled 为已推给前端 / 判为重复 / 读不了的曲目数，含被取消的半批
+    fn batch(&self, tracks: Vec<TrackMetadata>, handled: usize);
+}
+
+#[derive(Debug, Default)]
+pub struct ImportTally {
+    pub imported: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    pub issues: Vec<(String, CoverIssue)>,
+    pub cancelled: bool,
+}
+
+pub fn run_batches<S, P, C>(job: &mut ImportJob, dir: &Path, token: &CancellationToken, gate: &PauseGate, sink: &S, parse: P, mut on_checkpoint: C) -> ImportTally
+where S: ImportSink, P: Fn(&Path) -> Result<TrackMetadata, String> + Sync, C: FnMut(&ImportJob) {
+    let (imported, skipped, errors) = (AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0));
+    let batcher = TrackBatcher::default();
+    let emit_batch = |tracks: Option<Vec<TrackMetadata>>| {
+        let Some(tracks) = tracks else { return };
+        sink.batch(tracks, [&imported, &skipped, &errors].iter().map(|n| n.load(Ordering::Relaxed)).sum());
+    };
+    let force = job.force;
+    let mut issues = Vec::new();
+    while job.completed < job.paths.len() && !token.is_cancelled() {
+        let end = (job.completed + CHECKPOINT_BATCH).min(job.paths.len());
+        let batch_issues: Vec<(String, CoverIssue)> = job.paths[job.completed..end].par_iter().filter_map(|path| {
+            gate.wait(token);
+            if token.is_cancelled() { return None; }
+            if !remember(Path::new(path)) && !force {
+                skipped.fetch_add(1, Ordering::Relaxed);
+                sink.skipped(path, "duplicate");
+                return None;
+            }
+            let track = match parse(Path::new(path)) {
+                Ok(track) => track,
+                Err(reason) => {
+                    errors.fetch_add(1, Ordering::Relaxed);
+                    sink.failed(path, reason);
+                    return None;
+                }
+            };
+            let issue = track.cover_issue.map(|cover| (path.clone(), cover));
+            imported.fetch_add(1, Ordering::Relaxed);
+            emit_batch(batcher.push(track));
+            issue
+        }).collect();
+        emit_batch(batcher.flush());
+        // 批内中途取消时，未解析的文件无法区分，整批留待续传重做
+        if token.is_cancelled() { break; }
+        issues.extend(batch_issues);
+        job.completed = end;
+        save_job(dir, job);
+        on_checkpoint(job);
+    }
+    ImportTally {
+        imported: imported.into_inner(), skipped: skipped.into_inner(), errors: errors.into_inner(),
+        issues, cancelled: job.completed < job.paths.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::metadata_policy::MetadataPolicy;
+    use crate::modules::utils::extract_metadata;
+
+    #[derive(Default)]
+    struct Recorder {
+        tracks: Mutex<Vec<String>>,
+        skipped: Mutex<Vec<String>>,
+        failed: Mutex<Vec<String>>,
+    }
+
+    impl ImportSink for Recorder {
+        fn skipped(&self, path: &str, _reason: &'static str) { self.skipped.lock().unwrap().push(path.to_string()); }
+        fn failed(&self, path: &str, _reason: String) { self.failed.lock().unwrap().push(path.to_string()); }
+        fn batch(&self, tracks: Vec<TrackMetadata>, _handled: usize) { self.tracks.lock().unwrap().extend(tracks.into_iter().map(|t| t.path)); }
+    }
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("agm_import_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn files(dir: &Path, count: usize) -> Vec<String> {
+        (0..count).map(|i| {
+            let path = dir.join(format!("{:03} - Track.mp3", i));
+            fs::write(&path, b"").unwrap();
+            path.to_string_lossy().to_string()
+        }).collect()
+    }
+
+    fn parse(path: &Path) -> Result<TrackMetadata, String> { Ok(extract_metadata(&path.to_path_buf(), &MetadataPolicy::default(), false)) }
+
+    #[test]
+    fn kill_mid_batch_then_resume_imports_everything_once() {
+        let root = scratch("resume");
+        let (music, jobs) = (root.join("music"), root.join("jobs"));
+        fs::create_dir_all(&music).unwrap();
+        let paths = files(&music, CHECKPOINT_BATCH * 2 + 10);
+        let mut job = ImportJob::new(paths.clone(), false);
+        save_job(&jobs, &job);
+
+        // 第二批解析到一半时“杀掉”：断点只应停在第一批末尾
+        let token = CancellationToken::new();
+        let parsed = AtomicUsize::new(0);
+        let first = Recorder::default();
+        let tally = run_batches(&mut job, &jobs, &token, &PauseGate::default(), &first, |path| {
+            if parsed.fetch_add(1, Ordering::SeqCst) + 1 == CHECKPOINT_BATCH + 5 { token.cancel(); }
+            parse(path)
+        }, |_| {});
+        assert!(tally.cancelled);
+        let saved = load_job(&jobs, &job.id).unwrap();
+        assert_eq!(saved.completed, CHECKPOINT_BATCH);
+        assert_eq!(list_jobs(&jobs).len(), 1);
+
+        // 从磁盘上的断点续传：已推给前端的半批判为重复，其余全部导入
+        let mut resumed = saved;
+        let second = Recorder::default();
+        let mut checkpoints = Vec::new();
+        let tally = run_batches(&mut resumed, &jobs, &CancellationToken::new(), &PauseGate::default(), &second, parse, |job| checkpoints.push(job.completed));
+        assert!(!tally.cancelled);
+        assert_eq!(checkpoints, vec![CHECKPOINT_BATCH * 2, paths.len()]);
+        assert!(second.failed.lock().unwrap().is_empty());
+
+        let mut seen: Vec<String> = first.tracks.into_inner().unwrap();
+        seen.extend(second.tracks.into_inner().unwrap());
+        let total_emitted = seen.len();
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), total_emitted, "a track was pushed to the frontend twice");
+        assert_eq!(seen, { let mut all = paths.clone(); all.sort(); all });
+        assert_eq!(second.skipped.lock().unwrap().len() + tally.imported, paths.len() - CHECKPOINT_BATCH);
+
+        assert!(list_jobs(&jobs).is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn cancelled_job_leaves_no_record() {
+        let root = scratch("cancel");
+        let jobs = root.join("jobs");
+        let mut job = ImportJob::new(files(&root, 3), false);
+        save_job(&jobs, &job);
+        let token = CancellationToken::new();
+        token.cancel();
+        let tally = run_batches(&mut job, &jobs, &token, &PauseGate::default(), &Recorder::default(), parse, |_| panic!("no checkpoint expected"));
+        assert!(tally.cancelled);
+        assert_eq!((tally.imported, job.completed), (0, 0));
+        // 与 run_import_job 的收尾一致：取消后不会再出现在可续传列表里
+        finish(&jobs, &job.id);
+        assert!(list_jobs(&jobs).is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+use lofty::{read_from_path, AudioFile};
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::checksum::Md5;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_FLAC, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSourceStream, Monitor};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tauri::Manager;
+use crate::audio::coordinator::COORDINATOR;
+use crate::util::jobs::JobHandle;
+
+// ==========================================
+// 🩺 曲库完整性校验：表头快检 + 全量解码深检，捕捉静默损坏
+// ==========================================
+// 结果按路径落盘在 integrity.json；深检首次成功时记下 PCM 校验和作为基准，
+// 之后每次深检与基准比对。改标签不影响 PCM，校验和变化即视为内容损坏。
+// 运行断点与导入任务一样分批落盘，应用退出后可续传。
+pub const CHECKPOINT_BATCH: usize = 32;
+const DEEP_PARALLELISM: usize = 2;
+const DURATION_TOLERANCE_SECS: f64 = 0.5;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DeepCheck {
+    pub checked_at: i64,
+    pub decode_ok: bool,
+    pub decode_errors: usize,
+    pub pcm_checksum: Option<String>,
+    pub flac_md5_ok: Option<bool>, // 仅 FLAC 且 STREAMINFO 带 MD5 时有值
+    pub checksum_changed: bool,
+    pub error: Option<String>, // 无法开始解码时的原因
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IntegrityRecord {
+    pub path: String,
+    pub size: u64,
+    pub mtime: i64,
+    pub duration_secs: f64, // 首次通过表头检查时的基准时长
+    pub checked_at: i64,
+    pub header_issue: Option<String>,
+    pub deep: Option<DeepCheck>,
+    #[serde(default)]
+    pub baseline_checksum: Option<String>,
+    #[serde(default)]
+    pub reasons: Vec<String>, // 为空即通过
+}
+
+impl IntegrityRecord {
+    fn refresh_reasons(&mut self) {
+        let mut reasons: Vec<String> = self.header_issue.iter().cloned().collect();
+        if let Some(deep) = &self.deep {
+            if let Some(e) = &deep.error { reasons.push(e.clone()); }
+            else if !deep.decode_ok { reasons.push(format!("DECODE_ERRORS: {}", deep.decode_errors)); }
+            if deep.flac_md5_ok == Some(false) { reasons.push("FLAC_MD5_MISMATCH".to_string()); }
+            if deep.checksum_changed { reasons.push("PCM_CHECKSUM_CHANGED".to_string()); }
+        }
+        self.reasons = reasons;
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct IntegrityStore {
+    records: HashMap<String, IntegrityRecord>,
+}
+
+impl IntegrityStore {
+    fn path(app: &tauri::AppHandle) -> PathBuf {
+        app.path().app_local_data_dir().unwrap_or_else(|_| PathBuf::from("./data")).join("integrity.json")
+    }
+    pub fn load(app: &tauri::AppHandle) -> Self {
+        fs::read_to_string(Self::path(app)).ok().and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default()
+    }
+    pub fn save(&self, app: &tauri::AppHandle) {
+        let path = Self::path(app);
+        if let Some(dir) = path.parent() { let _ = fs::create_dir_all(dir); }
+        if let Ok(json) = serde_json::to_string(self) { let _ = fs::write(path, json); }
+    }
+    pub fn known_paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self.records.keys().cloned().collect();
+        paths.sort();
+        paths
+    }
+    pub fn failures(&self) -> Vec<IntegrityRecord> {
+        let mut failed: Vec<IntegrityRecord> = self.records.values().filter(|r| !r.reasons.is_empty()).cloned().collect();
+        failed.sort_by(|a, b| a.path.cmp(&b.path));
+        failed
+    }
+}
+
+// 断点记录：同一时间只保留一次校验运行
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IntegrityJob {
+    pub id: String,
+    pub paths: Vec<String>,
+    pub deep: bool,
+    pub completed: usize,
+    pub started_at: i64,
+}
+
+impl IntegrityJob {
+    pub fn new(paths: Vec<String>, deep: bool) -> Self {
+        let now = chrono::Local::now();
+        Self { id: format!("integrity_{}", now.timestamp_micros()), paths, deep, completed: 0, started_at: now.timestamp() }
+    }
+
+    fn checkpoint_path(app: &tauri::AppHandle) -> PathBuf {
+        app.path().app_local_data_dir().unwrap_or_else(|_| PathBuf::from("./data")).join("integrity_job.json")
+    }
+
+    pub fn load_interrupted(app: &tauri::AppHandle) -> Option<Self> {
+        let json = fs::read_to_string(Self::checkpoint_path(app)).ok()?;
+        serde_json::from_str::<Self>(&json).ok().filter(|job| job.completed < job.paths.len())
+    }
+
+    fn save(&self, app: &tauri::AppHandle) {
+        let path = Self::checkpoint_path(app);
+        if let Some(dir) = path.parent() { let _ = fs::create_dir_all(dir); }
+        if let Ok(json) = serde_json::to_string(self) {
+            let tmp = path.with_extension("json.tmp");
+            if fs::write(&tmp, json).is_ok() { let _ = fs::rename(tmp, path); }
+        }
+    }
+
+    fn remove(app: &tauri::AppHandle) { let _ = fs::remove_file(Self::checkpoint_path(app)); }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct IntegritySummary {
+    pub job_id: String,
+    pub deep: bool,
+    pub checked: usize,
+    pub failed: usize,
+    pub newly_failed: Vec<String>,
+    pub cancelled: bool,
+}
+
+fn mtime_of(meta: &fs::Metadata) -> i64 {
+    meta.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+// 表头快检：能否解析、时长与基准是否一致
+fn check_header(path: &Path, previous: Option<&IntegrityRecord>) -> Result<IntegrityRecord, ()> {
+    let now = chrono::Local::now().timestamp();
+    let Ok(meta) = fs::metadata(path) else { return Err(()) };
+    let mut record = previous.cloned().unwrap_or(IntegrityRecord {
+        path: path.to_string_lossy().to_string(), size: 0, mtime: 0, duration_secs: 0.0, checked_at: now,
+        header_issue: None, deep: None, baseline_checksum: None, reasons: Vec::new(),
+    });
+    // 文件有改动时上次深检的失败结论不再适用，等下次深检重新判定
+    let changed = record.size != meta.len() || record.mtime != mtime_of(&meta);
+    if changed && record.deep.as_ref().is_some_and(|d| !d.decode_ok) { record.deep = None; }
+    record.size = meta.len();
+    record.mtime = mtime_of(&meta);
+    record.checked_at = now;
+    record.header_issue = match read_from_path(path) {
+        Err(e) => Some(format!("HEADER_UNREADABLE: {}", e)),
+        Ok(tagged) => {
+            let duration = tagged.properties().duration().as_secs_f64();
+            if record.duration_secs <= 0.0 { record.duration_secs = duration; }
+            if (duration - record.duration_secs).abs() > DURATION_TOLERANCE_SECS {
+                Some(format!("DURATION_MISMATCH: expected {:.1}s, found {:.1}s", record.duration_secs, duration))
+            } else { None }
+        }
+    };
+    Ok(record)
+}
+
+struct Decoded {
+    errors: usize,
+    checksum: String,
+    flac_md5_ok: Option<bool>,
+}
+
+// 全量解码并对交错 f32 PCM 求 MD5；单帧解码失败计数后继续，容器读不下去则提前结束
+fn decode_all(path: &Path, job: &JobHandle) -> Result<Option<Decoded>, String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()) { hint.with_extension(&ext); }
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| format!("PROBE_FAILED: {}", e))?;
+    let mut format = probed.format;
+    let track = format.default_track().filter(|t| t.codec_params.codec != CODEC_TYPE_NULL).ok_or_else(|| "NO_AUDIO_TRACK".to_string())?;
+    let (track_id, is_flac) = (track.id, track.codec_params.codec == CODEC_TYPE_FLAC);
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions { verify: true })
+        .map_err(|e| format!("CODEC_UNSUPPORTED: {}", e))?;
+
+    let aux = COORDINATOR.register_aux();
+    let mut md5 = Md5::default();
+    let mut buffer: Option<SampleBuffer<f32>> = None;
+    let (mut errors, mut since_pace, mut next_pace) = (0usize, 0usize, 0usize);
+    loop {
+        if job.is_cancelled() { return Ok(None); }
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(_) => { errors += 1; break; }
+        };
+        if packet.track_id() != track_id { continue; }
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let needed = decoded.capacity() * decoded.spec().channels.count();
+                if buffer.as_ref().is_none_or(|b| b.capacity() < needed) {
+                    buffer = Some(SampleBuffer::new(decoded.capacity() as u64, *decoded.spec()));
+                }
+                let Some(buf) = buffer.as_mut() else { continue };
+                buf.copy_interleaved_ref(decoded);
+                for sample in buf.samples() { md5.process_buf_bytes(&sample.to_le_bytes()); }
+                since_pace += buf.samples().len();
+            }
+            Err(DecodeError::DecodeError(_)) => errors += 1,
+            Err(_) => { errors += 1; break; }
+        }
+        if since_pace >= next_pace { since_pace = 0; next_pace = aux.pace(); }
+    }
+    let flac_md5_ok = if is_flac { decoder.finalize().verify_ok } else { None };
+    let checksum = md5.md5().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(Some(Decoded { errors, checksum, flac_md5_ok }))
+}
+
+// 返回 false 表示中途被取消，结果作废
+fn check_deep(record: &mut IntegrityRecord, job: &JobHandle) -> bool {
+    let checked_at = chrono::Local::now().timestamp();
+    let decoded = match decode_all(Path::new(&record.path), job) {
+        Ok(Some(decoded)) => decoded,
+        Ok(None) => return false,
+        Err(e) => {
+            record.deep = Some(DeepCheck { checked_at, decode_ok: false, decode_errors: 0, pcm_checksum: None, flac_md5_ok: None, checksum_changed: false, error: Some(e) });
+            return true;
+        }
+    };
+    let decode_ok = decoded.errors == 0;
+    // 只有完整解码成功的校验和才可作为基准或参与比对
+    let checksum_changed = decode_ok && record.baseline_checksum.as_ref().is_some_and(|b| *b != decoded.checksum);
+    if decode_ok && record.baseline_checksum.is_none() { record.baseline_checksum = Some(decoded.checksum.clone()); }
+    record.deep = Some(DeepCheck {
+        checked_at, decode_ok, decode_errors: decoded.errors, pcm_checksum: Some(decoded.checksum),
+        flac_md5_ok: decoded.flac_md5_ok, checksum_changed, error: None,
+    });
+    true
+}
+
+pub fn run(app: &tauri::AppHandle, job: &mut IntegrityJob, handle: &JobHandle, on_progress: impl Fn(usize, usize) + Sync) -> IntegritySummary {
+    let mut store = IntegrityStore::load(app);
+    let mut summary = IntegritySummary { job_id: job.id.clone(), deep: job.deep, ..Default::default() };
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(DEEP_PARALLELISM).thread_name(|i| format!("integrity-{}", i)).build().ok();
+    let total = job.paths.len();
+    let done = AtomicUsize::new(job.completed);
+    job.save(app);
+
+    while job.completed < total {
+        if handle.is_cancelled() { summary.cancelled = true; return summary; }
+        let end = (job.completed + CHECKPOINT_BATCH).min(total);
+        let batch = &job.paths[job.completed..end];
+        let check = |path: &String| -> Option<(String, Option<IntegrityRecord>)> {
+            if handle.is_cancelled() { return None; }
+            let mut record = match check_header(Path::new(path), store.records.get(path)) {
+                Ok(record) => record,
+                Err(()) => return Some((path.clone(), None)),
+            };
+            if job.deep && !check_deep(&mut record, handle) { return None; }
+            record.refresh_reasons();
+            on_progress(done.fetch_add(1, Ordering::Relaxed) + 1, total);
+            Some((path.clone(), Some(record)))
+        };
+        let results: Vec<_> = match &pool {
+            Some(pool) => pool.install(|| batch.par_iter().filter_map(check).collect()),
+            None => batch.iter().filter_map(check).collect(),
+        };
+        // 批内中途取消时整批留待续传重做，已完成的结果也不写入
+        if handle.is_cancelled() { continue; }
+        for (path, record) in results {
+            summary.checked += 1;
+            let Some(record) = record else {
+                // 文件已不存在：不记为损坏，直接移出校验记录
+                store.records.remove(&path);
+                continue;
+            };
+            let was_ok = store.records.get(&path).is_none_or(|r| r.reasons.is_empty());
+            if !record.reasons.is_empty() && was_ok { summary.newly_failed.push(path.clone()); }
+            store.records.insert(path, record);
+        }
+        job.completed = end;
+        handle.set_progress(job.completed as f32 / total.max(1) as f32);
+        store.save(app);
+        job.save(app);
+    }
+    IntegrityJob::remove(app);
+    summary.failed = store.failures().len();
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::jobs::{JobKind, JobRegistry};
+
+    const RATE: u32 = 44100;
+    const BLOCK: usize = 4096;
+
+    fn samples() -> Vec<i16> {
+        (0..RATE as usize).map(|i| ((i as f64 * 440.0 * std::f64::consts::TAU / RATE as f64).sin() * 12000.0) as i16).collect()
+    }
+
+    fn crc8(data: &[u8]) -> u8 {
+        data.iter().fold(0u8, |mut crc, &b| {
+            crc ^= b;
+            for _ in 0..8 { crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 }; }
+            crc
+        })
+    }
+
+    fn crc16(data: &[u8]) -> u16 {
+        data.iter().fold(0u16, |mut crc, &b| {
+            crc ^= (b as u16) << 8;
+            for _ in 0..8 { crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 }; }
+            crc
+        })
+    }
+
+    // 最小 FLAC：单声道 16 bit，VERBATIM 子帧，STREAMINFO 带 PCM 的 MD5；返回 (文件, 各帧起始偏移)
+    fn flac_bytes(pcm: &[i16]) -> (Vec<u8>, Vec<usize>) {
+        let mut md5 = Md5::default();
+        for s in pcm { md5.process_buf_bytes(&s.to_le_bytes()); }
+        let mut out = b"fLaC".to_vec();
+        out.extend_from_slice(&[0x80, 0, 0, 34]);
+        out.extend_from_slice(&(BLOCK as u16).to_be_bytes());
+        out.extend_from_slice(&(BLOCK as u16).to_be_bytes());
+        out.extend_from_slice(&[0; 6]);
+        out.extend_from_slice(&(((RATE as u64) << 44) | (15u64 << 36) | pcm.len() as u64).to_be_bytes());
+        out.extend_from_slice(&md5.md5());
+
+        let mut frames = Vec::new();
+        for (n, block) in pcm.chunks(BLOCK).enumerate() {
+            let start = out.len();
+            frames.push(start);
+            let size = (block.len() - 1) as u16;
+            out.extend_from_slice(&[0xFF, 0xF8, 0x79, 0x08, n as u8, (size >> 8) as u8, size as u8]);
+            out.push(crc8(&out[start..]));
+            out.push(0x02);
+            for s in block { out.extend_from_slice(&s.to_be_bytes()); }
+            let crc = crc16(&out[start..]);
+            out.extend_from_slice(&crc.to_be_bytes());
+        }
+        (out, frames)
+    }
+
+    fn wav_bytes(pcm: &[i16]) -> Vec<u8> {
+        let data_len = (pcm.len() * 2) as u32;
+        let mut out = b"RIFF".to_vec();
+        out.extend_from_slice(&(36 + data_len).to_le_bytes());
+        out.extend_from_slice(b"WAVEfmt ");
+        out.extend_from_slice(&16u32.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&RATE.to_le_bytes());
+        out.extend_from_slice(&(RATE * 2).to_le_bytes());
+        out.extend_from_slice(&2u16.to_le_bytes());
+        out.extend_from_slice(&16u16.to_le_bytes());
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&data_len.to_le_bytes());
+        for s in pcm { out.extend_from_slice(&s.to_le_bytes()); }
+        out
+    }
+
+    fn temp_file(name: &str, bytes: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("agm_integrity_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    // 不改体积、不碰表头，只把中段一小片 PCM 清零
+    fn corrupt(path: &Path, at: usize) {
+        let mut bytes = fs::read(path).unwrap();
+        bytes[at..at + 64].fill(0);
+        fs::write(path, bytes).unwrap();
+    }
+
+    fn shallow(path: &Path, previous: Option<&IntegrityRecord>) -> IntegrityRecord {
+        let mut record = check_header(path, previous).unwrap();
+        record.refresh_reasons();
+        record
+    }
+
+    fn deep(path: &Path, previous: Option<&IntegrityRecord>, job: &JobHandle) -> IntegrityRecord {
+        let mut record = check_header(path, previous).unwrap();
+        assert!(check_deep(&mut record, job));
+        record.refresh_reasons();
+        record
+    }
+
+    #[test]
+    fn flac_corrupted_mid_stream_passes_shallow_and_fails_deep() {
+        let jobs = JobRegistry::new();
+        let job = jobs.register("integrity", JobKind::Integrity, "");
+        let (bytes, frames) = flac_bytes(&samples());
+        let path = temp_file("mid.flac", &bytes);
+
+        let clean = deep(&path, None, &job);
+        assert!(clean.reasons.is_empty(), "{:?}", clean.reasons);
+        assert_eq!(clean.deep.as_ref().unwrap().flac_md5_ok, Some(true));
+        assert!((clean.duration_secs - 1.0).abs() < 1e-6);
+
+        corrupt(&path, frames[frames.len() / 2] + 200);
+        let quick = shallow(&path, Some(&clean));
+        assert!(quick.reasons.is_empty(), "shallow should not notice: {:?}", quick.reasons);
+        assert_eq!(quick.size, bytes.len() as u64);
+
+        // 没有历史基准也能查出：帧 CRC / STREAMINFO MD5 任一处报错
+        let fresh = deep(&path, None, &job);
+        assert!(fresh.header_issue.is_none());
+        assert!(!fresh.reasons.is_empty());
+        assert!(fresh.reasons.iter().any(|r| r == "FLAC_MD5_MISMATCH" || r.starts_with("DECODE_ERRORS")), "{:?}", fresh.reasons);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn wav_corruption_is_caught_against_the_pcm_baseline() {
+        let jobs = JobRegistry::new();
+        let job = jobs.register("integrity", JobKind::Integrity, "");
+        let bytes = wav_bytes(&samples());
+        let path = temp_file("mid.wav", &bytes);
+
+        let clean = deep(&path, None, &job);
+        assert!(clean.reasons.is_empty(), "{:?}", clean.reasons);
+        assert!(clean.baseline_checksum.is_some());
+
+        corrupt(&path, bytes.len() / 2);
+        assert!(shallow(&path, Some(&clean)).reasons.is_empty());
+        // PCM 无自校验，只能靠首次深检记下的基准
+        let checked = deep(&path, Some(&clean), &job);
+        assert_eq!(checked.reasons, vec!["PCM_CHECKSUM_CHANGED".to_string()]);
+        assert_eq!(checked.baseline_checksum, clean.baseline_checksum);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn cancelled_deep_check_discards_the_result() {
+        let jobs = JobRegistry::new();
+        let job = jobs.register("integrity", JobKind::Integrity, "");
+        let path = temp_file("cancel.wav", &wav_bytes(&samples()));
+        let mut record = check_header(&path, None).unwrap();
+        jobs.cancel("integrity").unwrap();
+        assert!(!check_deep(&mut record, &job));
+        assert!(record.deep.is_none());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
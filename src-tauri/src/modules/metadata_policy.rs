@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+use lofty::{read_from_path, Accessor, Tag, TagExt, TaggedFileExt};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use super::utils::repair_mojibake;
+
+// ==========================================
+// 🗂️ 元数据来源优先级：标签 / 文件名模式 / 目录结构，按字段取第一个非空值
+// ==========================================
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataSource { Tags, Filename, Folder, Default }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MetadataPolicy {
+    pub sources: Vec<MetadataSource>,
+    // 作用于不含扩展名的文件名，命名捕获组 artist / album / track / title
+    pub filename_patterns: Vec<String>,
+    #[serde(skip)]
+    compiled: Vec<Regex>,
+}
+
+// 默认只认标签，与引入来源优先级之前的行为一致
+impl Default for MetadataPolicy {
+    fn default() -> Self {
+        Self {
+            sources: vec![MetadataSource::Tags],
+            filename_patterns: vec![
+                r"^(?P<track>\d{1,3})\s*[-._ ]\s*(?P<artist>.+?) - (?P<title>.+)$".to_string(),
+                r"^(?P<track>\d{1,3})\s*[-._ ]\s*(?P<title>.+)$".to_string(),
+                r"^(?P<artist>.+?) - (?P<title>.+)$".to_string(),
+            ],
+            compiled: Vec::new(),
+        }.prepare()
+    }
+}
+
+impl MetadataPolicy {
+    // 非法的正则只告警并跳过，解析时自然落到下一个模式 / 来源
+    pub fn prepare(mut self) -> Self {
+        self.compiled = self.filename_patterns.iter().filter_map(|p| match Regex::new(p) {
+            Ok(re) => Some(re),
+            Err(e) => { eprintln!("[META] Skipping invalid filename pattern {:?}: {}", p, e); None }
+        }).collect();
+        self
+    }
+
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("metadata_policy.json")).ok()
+            .and_then(|json| serde_json::from_str::<Self>(&json).ok())
+            .map(Self::prepare)
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(dir.join("metadata_policy.json"), json).map_err(|e| e.to_string())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sources.is_empty() { return Err("POLICY_NO_SOURCES".to_string()); }
+        if let Some(bad) = self.filename_patterns.iter().find(|p| Regex::new(p).is_err()) {
+            return Err(format!("POLICY_INVALID_PATTERN: {}", bad));
+        }
+        Ok(())
+    }
+}
+
+static ACTIVE_POLICY: RwLock<Option<MetadataPolicy>> = RwLock::new(None);
+
+pub fn set_active(policy: MetadataPolicy) { *ACTIVE_POLICY.write().unwrap_or_else(|e| e.into_inner()) = Some(policy); }
+
+pub fn active() -> MetadataPolicy { ACTIVE_POLICY.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default() }
+
+#[derive(Debug, Clone, Default)]
+struct Candidates {
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value.map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+fn from_tags(tag: Option<&Tag>) -> Candidates {
+    let field = |v: Option<std::borrow::Cow<'_, str>>| non_empty(v.as_deref()).map(|v| repair_mojibake(&v));
+    Candidates {
+        title: field(tag.and_then(|t| t.title())),
+        artist: field(tag.and_then(|t| t.artist())),
+        album: field(tag.and_then(|t| t.album())),
+    }
+}
+
+fn from_filename(stem: &str, patterns: &[Regex]) -> Candidates {
+    let Some(caps) = patterns.iter().find_map(|re| re.captures(stem)) else { return Candidates::default() };
+    let group = |name: &str| non_empty(caps.name(name).map(|m| m.as_str()));
+    Candidates { title: group("title"), artist: group("artist"), album: group("album") }
+}
+
+// 上级目录 = 专辑，再上一级 = 艺术家
+fn from_folder(path: &Path) -> Candidates {
+    let name = |p: Option<&Path>| non_empty(p.and_then(|p| p.file_name()).map(|n| n.to_string_lossy()).as_deref());
+    let album_dir = path.parent();
+    Candidates { title: None, album: name(album_dir), artist: name(album_dir.and_then(Path::parent)) }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ResolvedFields {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub provenance: BTreeMap<&'static str, MetadataSource>,
+}
+
+pub fn resolve(path: &Path, tag: Option<&Tag>, policy: &MetadataPolicy) -> ResolvedFields {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let candidates: Vec<(MetadataSource, Candidates)> = policy.sources.iter().map(|source| (*source, match source {
+        MetadataSource::Tags => from_tags(tag),
+        MetadataSource::Filename => from_filename(&stem, &policy.compiled),
+        MetadataSource::Folder => from_folder(path),
+        MetadataSource::Default => Candidates::default(),
+    })).collect();
+
+    let mut provenance = BTreeMap::new();
+    let mut pick = |field: &'static str, get: fn(&Candidates) -> &Option<String>, fallback: &str| -> String {
+        let found = candidates.iter().find_map(|(source, c)| get(c).clone().map(|v| (*source, v)));
+        let (source, value) = found.unwrap_or_else(|| (MetadataSource::Default, fallback.to_string()));
+        provenance.insert(field, source);
+        value
+    };
+    let title = pick("title", |c| &c.title, &stem);
+    let artist = pick("artist", |c| &c.artist, "Unknown Artist");
+    let album = pick("album", |c| &c.album, "Unknown Album");
+    ResolvedFields { title, artist, album, provenance }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PromoteResult {
+    pub path: String,
+    pub promoted: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// 把来自文件名 / 目录结构的字段写回真正的标签；标签本身提供的字段保持不动
+pub fn promote(path: &Path, policy: &MetadataPolicy) -> PromoteResult {
+    let mut result = PromoteResult { path: path.to_string_lossy().to_string(), promoted: Vec::new(), error: None };
+    let mut tagged_file = match read_from_path(path) {
+        Ok(f) => f,
+        Err(e) => { result.error = Some(e.to_string()); return result; }
+    };
+    let resolved = resolve(path, tagged_file.primary_tag().or_else(|| tagged_file.first_tag()), policy);
+    let derived = |field: &str| matches!(resolved.provenance.get(field), Some(MetadataSource::Filename | MetadataSource::Folder));
+    let fields: Vec<&'static str> = ["title", "artist", "album"].into_iter().filter(|f| derived(f)).collect();
+    if fields.is_empty() { return result; }
+
+    if tagged_file.primary_tag().is_none() {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let Some(tag) = tagged_file.primary_tag_mut() else { result.error = Some("TAG_UNSUPPORTED".to_string()); return result };
+    for field in &fields {
+        match *field {
+            "title" => tag.set_title(resolved.title.clone()),
+            "artist" => tag.set_artist(resolved.artist.clone()),
+            _ => tag.set_album(resolved.album.clone()),
+        }
+    }
+    match tag.save_to_path(path) {
+        Ok(()) => result.promoted = fields,
+        Err(e) => result.error = Some(e.to_string()),
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lofty::TagType;
+
+    fn policy(sources: &[MetadataSource]) -> MetadataPolicy {
+        MetadataPolicy { sources: sources.to_vec(), ..Default::default() }.prepare()
+    }
+
+    fn titled(title: &str) -> Tag {
+        let mut tag = Tag::new(TagType::Id3v2);
+        tag.set_title(title.to_string());
+        tag
+    }
+
+    #[test]
+    fn sources_resolve_per_field_in_priority_order() {
+        use MetadataSource::*;
+        let path = Path::new("/music/Radiohead/OK Computer/02 - Paranoid Android.mp3");
+        let tag = titled("Paranoid Android (Remastered)");
+        let resolved = resolve(path, Some(&tag), &policy(&[Tags, Filename, Folder]));
+        assert_eq!((resolved.title.as_str(), resolved.artist.as_str(), resolved.album.as_str()), ("Paranoid Android (Remastered)", "Radiohead", "OK Computer"));
+        assert_eq!(resolved.provenance.get("title"), Some(&Tags));
+        assert_eq!(resolved.provenance.get("artist"), Some(&Folder));
+
+        let resolved = resolve(path, Some(&tag), &policy(&[Filename, Tags]));
+        assert_eq!(resolved.title, "Paranoid Android");
+        assert_eq!(resolved.provenance.get("title"), Some(&Filename));
+        assert_eq!((resolved.artist.as_str(), resolved.provenance.get("artist")), ("Unknown Artist", Some(&Default)));
+
+        // 空白标签视同缺失，落到下一个来源
+        let resolved = resolve(path, Some(&titled("   ")), &policy(&[Tags, Filename]));
+        assert_eq!(resolved.provenance.get("title"), Some(&Filename));
+
+        let resolved = resolve(path, None, &policy(&[Tags]));
+        assert_eq!((resolved.title.as_str(), resolved.album.as_str()), ("02 - Paranoid Android", "Unknown Album"));
+    }
+
+    #[test]
+    fn filename_patterns_capture_named_groups() {
+        let patterns = &MetadataPolicy::default().compiled;
+        let c = from_filename("03 - Björk - Jóga", patterns);
+        assert_eq!((c.artist.as_deref(), c.title.as_deref()), (Some("Björk"), Some("Jóga")));
+        let c = from_filename("07. Song", patterns);
+        assert_eq!((c.artist, c.title.as_deref()), (None, Some("Song")));
+        let c = from_filename("Daft Punk - One More Time", patterns);
+        assert_eq!((c.artist.as_deref(), c.title.as_deref()), (Some("Daft Punk"), Some("One More Time")));
+        assert!(from_filename("untitled", patterns).title.is_none());
+
+        let custom = MetadataPolicy { sources: vec![MetadataSource::Filename], filename_patterns: vec![r"^(?P<album>.+?) \[(?P<title>.+)\]$".to_string(), "(".to_string()], ..Default::default() }.prepare();
+        assert_eq!(custom.compiled.len(), 1);
+        assert!(custom.validate().unwrap_err().starts_with("POLICY_INVALID_PATTERN"));
+        let resolved = resolve(Path::new("/x/Kind of Blue [So What].flac"), None, &custom);
+        assert_eq!((resolved.album.as_str(), resolved.title.as_str()), ("Kind of Blue", "So What"));
+    }
+
+    #[test]
+    fn unicode_folders_supply_artist_and_album() {
+        use MetadataSource::*;
+        let path = Path::new("/音乐/周杰倫/范特西 🎵/01 - 愛在西元前.flac");
+        let resolved = resolve(path, None, &policy(&[Tags, Filename, Folder]));
+        assert_eq!(resolved.artist, "周杰倫");
+        assert_eq!(resolved.album, "范特西 🎵");
+        assert_eq!(resolved.title, "愛在西元前");
+        assert_eq!(resolved.provenance.get("album"), Some(&Folder));
+        // 文件直接放在根目录下时没有可用的上级目录
+        assert_eq!(from_folder(Path::new("/song.mp3")).artist, None);
+    }
+}
@@ -1,3 +1,15 @@
 pub mod state;
 pub mod utils;
-pub mod commands;
\ No newline at end of file
+pub mod commands;
+pub mod queue;
+pub mod import_jobs;
+pub mod collation;
+pub mod metadata_policy;
+pub mod path_policy;
+pub mod eq_presets;
+pub mod room_presets;
+pub mod storage_report;
+pub mod integrity;
+pub mod chapters;
+pub mod sleep_timer;
+pub mod api_schema;
@@ -0,0 +1,241 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf, Prefix};
+use std::sync::RwLock;
+use serde::{Serialize, Deserialize};
+
+// ==========================================
+// 🛡️ 路径策略：所有接收路径的指令统一在此把关
+// ==========================================
+// 读取类只做词法检查（批量扫描也不必逐个访问文件系统）；
+// 导出目标额外解析真实位置；改动曲库文件的操作（删除 / 改名 / 写标签 / 整理）
+// 在开启限制时必须落在用户配置的曲库根目录之内，未配置根目录时一律拒绝。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,   // 任意位置可读
+    Export, // 新建输出文件，位置由用户在保存对话框中选定
+    Modify, // 改写已有的曲库文件
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rule { EmptyPath, NulByte, RelativePath, DeviceNamespace, ReservedDeviceName, Unresolvable, NoLibraryRoots, OutsideLibraryRoots }
+
+impl Rule {
+    fn code(self) -> &'static str {
+        match self {
+            Rule::EmptyPath => "EMPTY_PATH",
+            Rule::NulByte => "NUL_BYTE",
+            Rule::RelativePath => "RELATIVE_PATH",
+            Rule::DeviceNamespace => "DEVICE_NAMESPACE",
+            Rule::ReservedDeviceName => "RESERVED_DEVICE_NAME",
+            Rule::Unresolvable => "UNRESOLVABLE",
+            Rule::NoLibraryRoots => "NO_LIBRARY_ROOTS",
+            Rule::OutsideLibraryRoots => "OUTSIDE_LIBRARY_ROOTS",
+        }
+    }
+}
+
+fn violation(rule: Rule, path: &str) -> String { format!("PATH_POLICY: {}: {}", rule.code(), path) }
+
+const RESERVED_NAMES: &[&str] = &["CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$"];
+
+// Windows 下 "nul.mp3"、"COM1 .txt" 同样指向设备：取首个点之前的部分并去掉尾随空格
+fn is_reserved_name(component: &str) -> bool {
+    let stem = component.split('.').next().unwrap_or_default().trim_end_matches(' ').to_ascii_uppercase();
+    let numbered = |prefix: &str| stem.strip_prefix(prefix).is_some_and(|n| n.len() == 1 && matches!(n.as_bytes()[0], b'1'..=b'9'));
+    RESERVED_NAMES.contains(&stem.as_str()) || numbered("COM") || numbered("LPT")
+}
+
+fn lexical_check(raw: &str) -> Result<(), Rule> {
+    if raw.trim().is_empty() { return Err(Rule::EmptyPath); }
+    if raw.contains('\0') { return Err(Rule::NulByte); }
+    let path = Path::new(raw);
+    for component in path.components() {
+        match component {
+            // \\.\PhysicalDrive0、\\?\GLOBALROOT\… 之类绕过文件系统的命名空间；\\?\C:\ 与 \\?\UNC\ 长路径形式照常放行
+            Component::Prefix(prefix) if matches!(prefix.kind(), Prefix::DeviceNS(_) | Prefix::Verbatim(_)) => return Err(Rule::DeviceNamespace),
+            Component::Normal(name) if cfg!(windows) && is_reserved_name(&name.to_string_lossy()) => return Err(Rule::ReservedDeviceName),
+            _ => {}
+        }
+    }
+    if !path.is_absolute() { return Err(Rule::RelativePath); }
+    Ok(())
+}
+
+// 解析 ".." 与符号链接；目标尚不存在时（导出）解析其父目录
+fn resolve(path: &Path) -> Option<PathBuf> {
+    fs::canonicalize(path).ok().or_else(|| {
+        let name = path.file_name()?;
+        fs::canonicalize(path.parent()?).ok().map(|dir| dir.join(name))
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PathPolicy {
+    pub restrict_modifications: bool,
+    pub library_roots: Vec<String>,
+    #[serde(skip)]
+    canonical_roots: Vec<PathBuf>,
+}
+
+impl PathPolicy {
+    // 根目录按真实路径比较，避免大小写、"..", 或 \\?\ 前缀造成的误判；暂不可达的根目录只告警
+    pub fn prepare(mut self) -> Self {
+        self.canonical_roots = self.library_roots.iter().filter_map(|r| match fs::canonicalize(r) {
+            Ok(root) => Some(root),
+            Err(e) => { eprintln!("[PATH] Library root unavailable {:?}: {}", r, e); None }
+        }).collect();
+        self
+    }
+
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("path_policy.json")).ok()
+            .and_then(|json| serde_json::from_str::<Self>(&json).ok())
+            .map(Self::prepare)
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(dir.join("path_policy.json"), json).map_err(|e| e.to_string())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for root in &self.library_roots {
+            lexical_check(root).map_err(|rule| violation(rule, root))?;
+            if !Path::new(root).is_dir() { return Err(format!("ROOT_NOT_A_DIRECTORY: {}", root)); }
+        }
+        if self.restrict_modifications && self.library_roots.is_empty() { return Err(violation(Rule::NoLibraryRoots, "")); }
+        Ok(())
+    }
+
+    pub fn check(&self, raw: &str, access: Access) -> Result<PathBuf, String> {
+        lexical_check(raw).map_err(|rule| violation(rule, raw))?;
+        if access == Access::Read { return Ok(PathBuf::from(raw)); }
+        let resolved = resolve(Path::new(raw)).ok_or_else(|| violation(Rule::Unresolvable, raw))?;
+        if access == Access::Modify && self.restrict_modifications {
+            if self.canonical_roots.is_empty() { return Err(violation(Rule::NoLibraryRoots, raw)); }
+            if !self.canonical_roots.iter().any(|root| resolved.starts_with(root)) { return Err(violation(Rule::OutsideLibraryRoots, raw)); }
+        }
+        Ok(resolved)
+    }
+}
+
+static ACTIVE_POLICY: RwLock<Option<PathPolicy>> = RwLock::new(None);
+
+pub fn set_active(policy: PathPolicy) { *ACTIVE_POLICY.write().unwrap_or_else(|e| e.into_inner()) = Some(policy); }
+
+pub fn active() -> PathPolicy { ACTIVE_POLICY.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default() }
+
+fn with_active<R>(f: impl FnOnce(&PathPolicy) -> R) -> R {
+    let guard = ACTIVE_POLICY.read().unwrap_or_else(|e| e.into_inner());
+    match guard.as_ref() {
+        Some(policy) => f(policy),
+        None => f(&PathPolicy::default()),
+    }
+}
+
+pub fn check(raw: &str, access: Access) -> Result<PathBuf, String> { with_active(|p| p.check(raw, access)) }
+
+pub fn check_all(paths: &[String], access: Access) -> Result<(), String> {
+    with_active(|policy| paths.iter().try_for_each(|p| policy.check(p, access).map(|_| ())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("agm_path_policy_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("library")).unwrap();
+        fs::write(dir.join("library").join("a.mp3"), b"").unwrap();
+        fs::write(dir.join("outside.mp3"), b"").unwrap();
+        fs::canonicalize(dir).unwrap()
+    }
+
+    fn code(result: Result<PathBuf, String>) -> String {
+        let err = result.expect_err("expected a policy violation");
+        err.split(": ").nth(1).unwrap_or_default().to_string()
+    }
+
+    fn restricted(root: &Path, restrict: bool) -> PathPolicy {
+        PathPolicy { restrict_modifications: restrict, library_roots: vec![root.to_string_lossy().to_string()], ..Default::default() }.prepare()
+    }
+
+    #[test]
+    fn reserved_device_names_ignore_extension_and_trailing_spaces() {
+        for name in ["nul", "NUL.mp3", "nul.tar.gz", "COM1 .txt", "com9", "LPT3.flac", "con ", "CONOUT$"] {
+            assert!(is_reserved_name(name), "{name} should be reserved");
+        }
+        for name in ["null.mp3", "COM0", "COM10", "LPT", "console.log", "a nul.mp3", "AUXILIARY"] {
+            assert!(!is_reserved_name(name), "{name} is an ordinary name");
+        }
+    }
+
+    #[test]
+    fn lexical_rejects_empty_nul_and_relative() {
+        assert_eq!(lexical_check(""), Err(Rule::EmptyPath));
+        assert_eq!(lexical_check("   "), Err(Rule::EmptyPath));
+        assert_eq!(lexical_check("/music/a\0.mp3"), Err(Rule::NulByte));
+        assert_eq!(lexical_check("music/a.mp3"), Err(Rule::RelativePath));
+        assert_eq!(lexical_check("../a.mp3"), Err(Rule::RelativePath));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn lexical_handles_windows_prefixes() {
+        assert_eq!(lexical_check(r"C:\Music\a.mp3"), Ok(()));
+        assert_eq!(lexical_check(r"\\nas\share\Music\a.mp3"), Ok(()));
+        assert_eq!(lexical_check(r"\\?\C:\Music\a.mp3"), Ok(()));
+        assert_eq!(lexical_check(r"\\?\UNC\nas\share\a.mp3"), Ok(()));
+        assert_eq!(lexical_check(r"\\.\PhysicalDrive0"), Err(Rule::DeviceNamespace));
+        assert_eq!(lexical_check(r"\\?\GLOBALROOT\Device\HarddiskVolume1\a.mp3"), Err(Rule::DeviceNamespace));
+        assert_eq!(lexical_check(r"C:\Music\nul.mp3"), Err(Rule::ReservedDeviceName));
+        assert_eq!(lexical_check(r"C:\Music\COM1 .txt"), Err(Rule::ReservedDeviceName));
+        assert_eq!(lexical_check(r"C:\Music\a.mp3"), Ok(()));
+        assert_eq!(lexical_check(r"Music\a.mp3"), Err(Rule::RelativePath));
+    }
+
+    #[test]
+    fn traversal_out_of_the_library_is_caught_for_modify() {
+        let dir = scratch("traversal");
+        let policy = restricted(&dir.join("library"), true);
+        let escaped = dir.join("library").join("..").join("outside.mp3").to_string_lossy().to_string();
+        assert_eq!(code(policy.check(&escaped, Access::Modify)), "OUTSIDE_LIBRARY_ROOTS");
+        // 读取只做词法检查，原样放行
+        assert_eq!(policy.check(&escaped, Access::Read), Ok(PathBuf::from(&escaped)));
+        let inside = dir.join("library").join(".").join("a.mp3").to_string_lossy().to_string();
+        assert_eq!(policy.check(&inside, Access::Modify), Ok(dir.join("library").join("a.mp3")));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_out_of_the_library_is_caught_for_modify() {
+        let dir = scratch("symlink");
+        std::os::unix::fs::symlink(dir.join("outside.mp3"), dir.join("library").join("link.mp3")).unwrap();
+        let policy = restricted(&dir.join("library"), true);
+        let link = dir.join("library").join("link.mp3").to_string_lossy().to_string();
+        assert_eq!(code(policy.check(&link, Access::Modify)), "OUTSIDE_LIBRARY_ROOTS");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn root_restriction_toggle() {
+        let dir = scratch("toggle");
+        let outside = dir.join("outside.mp3").to_string_lossy().to_string();
+        assert_eq!(restricted(&dir.join("library"), false).check(&outside, Access::Modify), Ok(dir.join("outside.mp3")));
+        assert_eq!(code(restricted(&dir.join("library"), true).check(&outside, Access::Modify)), "OUTSIDE_LIBRARY_ROOTS");
+        // 导出目标不受根目录限制，尚不存在的文件按父目录解析
+        let export = dir.join("new.flac").to_string_lossy().to_string();
+        assert_eq!(restricted(&dir.join("library"), true).check(&export, Access::Export), Ok(dir.join("new.flac")));
+
+        let rootless = PathPolicy { restrict_modifications: true, ..Default::default() };
+        assert!(rootless.validate().unwrap_err().contains("NO_LIBRARY_ROOTS"));
+        assert_eq!(code(rootless.check(&outside, Access::Modify)), "NO_LIBRARY_ROOTS");
+        let missing = dir.join("missing").join("x.mp3").to_string_lossy().to_string();
+        assert_eq!(code(PathPolicy::default().check(&missing, Access::Modify)), "UNRESOLVABLE");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
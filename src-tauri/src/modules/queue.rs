@@ -0,0 +1,411 @@
+use std::fs;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+
+// ==========================================
+// 🎶 后端播放队列：脱离 WebView 调度，保证切歌无缝
+// ==========================================
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    One,
+    All,
+}
+
+// 队列自然播完后的行为
+// 尚缺：radio 要靠推荐选曲（排除隔离区与近期播放），sleep 还要释放系统休眠许可；两者补齐前设置时一律拒绝
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EndAction {
+    #[default]
+    Stop,
+    RepeatAll,
+    Radio,
+    Sleep,
+}
+
+// 队列推进的决策结果
+#[derive(Debug, PartialEq)]
+pub enum Advance {
+    Restart,       // 单曲循环：走引擎内存 seek，不重新 load
+    Play(String),
+    Finished(EndAction),
+    End,
+}
+
+// 队列播完后的收尾步骤，按顺序执行：先让引擎落定，再通知前端，前端收到事件时看到的已是最终状态
+#[derive(Debug, PartialEq)]
+pub enum FinishStep {
+    Pause,
+    Notify(EndAction),
+}
+
+impl EndAction {
+    pub fn is_supported(self) -> bool { matches!(self, EndAction::Stop | EndAction::RepeatAll) }
+
+    pub fn finish_steps(self) -> Vec<FinishStep> {
+        match self {
+            EndAction::Sleep => vec![FinishStep::Pause, FinishStep::Notify(self)],
+            EndAction::Stop | EndAction::RepeatAll | EndAction::Radio => vec![FinishStep::Notify(self)],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct QueuePrefs {
+    pub end_action: EndAction,
+}
+
+impl QueuePrefs {
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("queue_prefs.json")).ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .filter(|prefs: &QueuePrefs| prefs.end_action.is_supported())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(dir.join("queue_prefs.json"), json).map_err(|e| e.to_string())
+    }
+}
+
+// 崩溃恢复快照里的队列段
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueueSection {
+    pub paths: Vec<String>,
+    pub index: Option<usize>,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct PlayQueue {
+    pub paths: Vec<String>,
+    order: Vec<usize>,      // 播放顺序（随机模式下为持久化的洗牌结果）
+    cursor: Option<usize>,  // 在 order 中的位置
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+    pub end_action: EndAction,
+}
+
+impl PlayQueue {
+    pub fn set(&mut self, paths: Vec<String>) {
+        self.paths = paths;
+        self.rebuild_order(None);
+    }
+
+    pub fn add(&mut self, path: String) {
+        let current = self.current_index();
+        self.paths.push(path);
+        self.rebuild_order(current);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<(), String> {
+        if index >= self.paths.len() { return Err("INDEX_OUT_OF_RANGE".to_string()); }
+        let current = self.current_index();
+        self.paths.remove(index);
+        let current = match current {
+            Some(cur) if cur == index => if self.paths.is_empty() { None } else { Some(cur.saturating_sub(1)) },
+            Some(cur) if cur > index => Some(cur - 1),
+            other => other,
+        };
+        self.rebuild_order(current);
+        Ok(())
+    }
+
+    pub fn set_mode(&mut self, shuffle: bool, repeat: RepeatMode) {
+        self.repeat = repeat;
+        if self.shuffle != shuffle {
+            self.shuffle = shuffle;
+            self.rebuild_order(self.current_index());
+        }
+    }
+
+    pub fn section(&self) -> QueueSection {
+        QueueSection { paths: self.paths.clone(), index: self.current_index(), shuffle: self.shuffle, repeat: self.repeat }
+    }
+
+    // 已不存在的文件从队列中剔除，当前曲目的下标随之平移
+    pub fn restore(&mut self, section: QueueSection, exists: impl Fn(&str) -> bool) -> usize {
+        let current = section.index.and_then(|i| section.paths.get(i).cloned());
+        let total = section.paths.len();
+        self.paths = section.paths.into_iter().filter(|p| exists(p)).collect();
+        self.shuffle = section.shuffle;
+        self.repeat = section.repeat;
+        let index = current.and_then(|c| self.paths.iter().position(|p| *p == c));
+        self.rebuild_order(index);
+        total - self.paths.len()
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        self.cursor.and_then(|c| self.order.get(c).copied())
+    }
+
+    pub fn current_path(&self) -> Option<String> {
+        self.current_index().and_then(|i| self.paths.get(i).cloned())
+    }
+
+    // auto = 曲目自然播完触发；手动切歌时单曲循环不生效
+    pub fn advance(&mut self, auto: bool) -> Advance {
+        if self.order.is_empty() { return Advance::End; }
+        if auto && self.repeat == RepeatMode::One && self.cursor.is_some() { return Advance::Restart; }
+
+        let next = match self.cursor { Some(c) => c + 1, None => 0 };
+        let next = if next < self.order.len() {
+            next
+        } else if self.repeat == RepeatMode::All || (auto && self.end_action == EndAction::RepeatAll) {
+            0
+        } else if auto {
+            return Advance::Finished(self.end_action);
+        } else {
+            return Advance::End;
+        };
+        self.cursor = Some(next);
+        self.current_path().map(Advance::Play).unwrap_or(Advance::End)
+    }
+
+    pub fn previous(&mut self) -> Option<String> {
+        let prev = match self.cursor?.checked_sub(1) {
+            Some(p) => p,
+            None if self.repeat == RepeatMode::All => self.order.len().checked_sub(1)?,
+            None => return None,
+        };
+        self.cursor = Some(prev);
+        self.current_path()
+    }
+
+    // 内容或随机开关变化时才重排；当前曲目置于新顺序的当前位置，上一首/下一首沿同一序列行走
+    fn rebuild_order(&mut self, current: Option<usize>) {
+        let current = current.filter(|&i| i < self.paths.len());
+        self.order = (0..self.paths.len()).collect();
+
+        if self.shuffle {
+            let mut rng = XorShift::seeded();
+            for i in (1..self.order.len()).rev() {
+                let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+                self.order.swap(i, j);
+            }
+            if let Some(cur) = current {
+                if let Some(pos) = self.order.iter().position(|&i| i == cur) { self.order.swap(0, pos); }
+            }
+            self.cursor = current.map(|_| 0);
+        } else {
+            self.cursor = current;
+        }
+    }
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn seeded() -> Self {
+        let seed = chrono::Local::now().timestamp_nanos_opt().map_or(0x9E37_79B9_7F4A_7C15, |n| n as u64);
+        Self(seed | 1)
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(n: usize, end_action: EndAction) -> PlayQueue {
+        let mut q = PlayQueue { end_action, ..Default::default() };
+        q.set((0..n).map(|i| format!("/music/{}.flac", i)).collect());
+        q
+    }
+
+    fn play(i: usize) -> Advance { Advance::Play(format!("/music/{}.flac", i)) }
+
+    #[test]
+    fn auto_advance_at_the_end_follows_the_end_action() {
+        for action in [EndAction::Stop, EndAction::Sleep] {
+            let mut q = queue(3, action);
+            assert_eq!([q.advance(true), q.advance(true), q.advance(true)], [play(0), play(1), play(2)]);
+            assert_eq!(q.advance(true), Advance::Finished(action));
+            // 播完后停在最后一首，再次推进仍是同一结果
+            assert_eq!(q.current_index(), Some(2));
+            assert_eq!(q.advance(true), Advance::Finished(action));
+        }
+        let mut q = queue(2, EndAction::RepeatAll);
+        assert_eq!([q.advance(true), q.advance(true), q.advance(true), q.advance(true)], [play(0), play(1), play(0), play(1)]);
+    }
+
+    #[test]
+    fn manual_next_at_the_end_ignores_the_end_action() {
+        for action in [EndAction::Stop, EndAction::RepeatAll, EndAction::Sleep] {
+            let mut q = queue(2, action);
+            q.advance(false);
+            q.advance(false);
+            assert_eq!(q.advance(false), Advance::End, "{:?}", action);
+            assert_eq!(q.current_index(), Some(1));
+        }
+    }
+
+    #[test]
+    fn repeat_modes_take_precedence_over_the_end_action() {
+        let mut q = queue(2, EndAction::Stop);
+        q.set_mode(false, RepeatMode::All);
+        q.advance(true);
+        q.advance(true);
+        assert_eq!(q.advance(true), play(0));
+
+        let mut q = queue(2, EndAction::Sleep);
+        q.set_mode(false, RepeatMode::One);
+        // 尚未开始播放时没有可重播的曲目
+        assert_eq!(q.advance(true), play(0));
+        assert_eq!(q.advance(true), Advance::Restart);
+        assert_eq!(q.advance(false), play(1));
+        assert_eq!(q.advance(true), Advance::Restart);
+    }
+
+    #[test]
+    fn empty_queue_never_finishes() {
+        for action in [EndAction::Stop, EndAction::RepeatAll, EndAction::Sleep] {
+            assert_eq!(queue(0, action).advance(true), Advance::End);
+        }
+    }
+
+    #[test]
+    fn finish_steps_settle_playback_before_notifying() {
+        assert_eq!(EndAction::Sleep.finish_steps(), vec![FinishStep::Pause, FinishStep::Notify(EndAction::Sleep)]);
+        assert_eq!(EndAction::Stop.finish_steps(), vec![FinishStep::Notify(EndAction::Stop)]);
+        // 通知总是最后一步
+        for action in [EndAction::Stop, EndAction::RepeatAll, EndAction::Sleep] {
+            assert_eq!(action.finish_steps().last(), Some(&FinishStep::Notify(action)));
+        }
+    }
+
+    #[test]
+    fn end_action_persists_in_snake_case() {
+        let dir = std::env::temp_dir().join(format!("agm_queue_prefs_{}", std::process::id()));
+        QueuePrefs { end_action: EndAction::RepeatAll }.save(&dir).unwrap();
+        assert!(std::fs::read_to_string(dir.join("queue_prefs.json")).unwrap().contains("\"repeat_all\""));
+        assert_eq!(QueuePrefs::load(&dir).end_action, EndAction::RepeatAll);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(QueuePrefs::load(&dir).end_action, EndAction::Stop);
+    }
+
+    #[test]
+    fn unimplemented_end_actions_are_not_supported_and_not_restored() {
+        assert!(EndAction::Stop.is_supported() && EndAction::RepeatAll.is_supported());
+        let dir = std::env::temp_dir().join(format!("agm_queue_prefs_unsupported_{}", std::process::id()));
+        for action in [EndAction::Radio, EndAction::Sleep] {
+            assert!(!action.is_supported());
+            // 旧版本存下的值读回时退回默认
+            QueuePrefs { end_action: action }.save(&dir).unwrap();
+            assert_eq!(QueuePrefs::load(&dir).end_action, EndAction::Stop);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn restore_drops_missing_files_and_keeps_the_current_track() {
+        let mut q = queue(5, EndAction::Stop);
+        q.set_mode(false, RepeatMode::All);
+        q.advance(false);
+        q.advance(false);
+        // 与快照一样经过一次 JSON 往返
+        let section: QueueSection = serde_json::from_str(&serde_json::to_string(&q.section()).unwrap()).unwrap();
+
+        let mut restored = PlayQueue::default();
+        let gone = ["/music/0.flac", "/music/3.flac"];
+        assert_eq!(restored.restore(section.clone(), |p| !gone.contains(&p)), 2);
+        assert_eq!(restored.paths, ["/music/1.flac", "/music/2.flac", "/music/4.flac"]);
+        assert_eq!(restored.current_path().as_deref(), Some("/music/1.flac"));
+        assert_eq!(restored.repeat, RepeatMode::All);
+        assert_eq!(restored.advance(false), play(2));
+
+        // 当前曲目本身丢失：队列保留其余文件，从头开始
+        let mut restored = PlayQueue::default();
+        assert_eq!(restored.restore(section.clone(), |p| p != "/music/1.flac"), 1);
+        assert_eq!(restored.current_index(), None);
+        assert_eq!(restored.advance(false), play(0));
+
+        let mut restored = PlayQueue::default();
+        assert_eq!(restored.restore(section, |_| false), 5);
+        assert_eq!(restored.advance(false), Advance::End);
+    }
+
+    fn index_of(advance: Advance) -> usize {
+        match advance { Advance::Play(path) => path[7..path.len() - 5].parse().unwrap(), other => panic!("{:?}", other) }
+    }
+
+    #[test]
+    fn shuffled_order_is_a_persistent_permutation() {
+        let mut q = queue(20, EndAction::Stop);
+        q.set_mode(true, RepeatMode::Off);
+        let walked: Vec<usize> = (0..20).map(|_| index_of(q.advance(false))).collect();
+        let mut sorted = walked.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+        // 上一首沿同一序列原路返回
+        let back: Vec<usize> = (0..19).map(|_| index_of(Advance::Play(q.previous().unwrap()))).collect();
+        assert_eq!(back, walked[..19].iter().rev().copied().collect::<Vec<_>>());
+        assert_eq!(q.previous(), None);
+        // 再往下走仍是同一顺序，不会每次随机
+        let again: Vec<usize> = (1..20).map(|_| index_of(q.advance(false))).collect();
+        assert_eq!(again, walked[1..]);
+    }
+
+    #[test]
+    fn repeat_change_keeps_the_shuffle_and_toggling_keeps_the_current_track() {
+        let mut q = queue(30, EndAction::Stop);
+        q.set_mode(true, RepeatMode::Off);
+        for _ in 0..5 { q.advance(false); }
+        let (order, current) = (q.order.clone(), q.current_index());
+        q.set_mode(true, RepeatMode::All);
+        q.set_mode(true, RepeatMode::One);
+        assert_eq!((q.order.clone(), q.current_index()), (order, current));
+
+        // 关闭随机：回到自然顺序，当前曲目不变，下一首是它的后继
+        q.set_mode(false, RepeatMode::Off);
+        let cur = current.unwrap();
+        assert_eq!(q.current_index(), Some(cur));
+        if cur + 1 < 30 { assert_eq!(q.advance(false), play(cur + 1)); }
+
+        // 重新打开随机：当前曲目排在新序列的最前面
+        q.set_mode(true, RepeatMode::Off);
+        let cur = q.current_index();
+        assert_eq!((q.cursor, q.order.first().copied()), (Some(0), cur));
+        assert_eq!(q.previous(), None);
+    }
+
+    #[test]
+    fn content_change_reshuffles_around_the_current_track() {
+        let mut q = queue(10, EndAction::Stop);
+        q.set_mode(true, RepeatMode::Off);
+        for _ in 0..4 { q.advance(false); }
+        let current = q.current_path();
+        q.add("/music/10.flac".to_string());
+        assert_eq!(q.current_path(), current);
+        assert_eq!((q.cursor, q.order.len()), (Some(0), 11));
+        let first = q.order[0];
+        q.remove(if first == 0 { 1 } else { 0 }).unwrap();
+        assert_eq!(q.current_path(), current);
+        // 新序列走完恰好覆盖剩余的每一首
+        let mut seen: Vec<String> = (1..10).filter_map(|_| match q.advance(false) { Advance::Play(p) => Some(p), _ => None }).collect();
+        seen.extend(current);
+        seen.sort();
+        let mut paths = q.paths.clone();
+        paths.sort();
+        assert_eq!(seen, paths);
+    }
+
+    #[test]
+    fn repeat_all_wraps_previous_to_the_end_of_the_shuffled_order() {
+        let mut q = queue(8, EndAction::Stop);
+        q.set_mode(true, RepeatMode::All);
+        q.advance(false);
+        let last = q.order[7];
+        assert_eq!(q.previous(), Some(format!("/music/{}.flac", last)));
+    }
+}
@@ -0,0 +1,44 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+use crate::audio::room::RoomPreset;
+
+// ==========================================
+// 🛋️ 房间预设持久化：每个预设一个 JSON 文件
+// ==========================================
+pub fn presets_dir(app: &tauri::AppHandle) -> PathBuf {
+    app.path().app_local_data_dir().unwrap_or_else(|_| PathBuf::from("./data")).join("room_presets")
+}
+
+fn preset_path(dir: &Path, name: &str) -> PathBuf {
+    let safe: String = name.chars().map(|c| if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' { c } else { '_' }).collect();
+    dir.join(format!("{}.json", safe.trim()))
+}
+
+pub fn save(dir: &Path, preset: &RoomPreset) -> Result<(), String> {
+    if preset.name.trim().is_empty() { return Err("PRESET_NAME_EMPTY".to_string()); }
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(preset).map_err(|e| e.to_string())?;
+    fs::write(preset_path(dir, &preset.name), json).map_err(|e| e.to_string())
+}
+
+pub fn load(dir: &Path, name: &str) -> Result<RoomPreset, String> {
+    let json = fs::read_to_string(preset_path(dir, name)).map_err(|_| "PRESET_NOT_FOUND".to_string())?;
+    serde_json::from_str(&json).map_err(|e| format!("PRESET_CORRUPT: {}", e))
+}
+
+pub fn list(dir: &Path) -> Vec<RoomPreset> {
+    let Ok(entries) = fs::read_dir(dir) else { return vec![] };
+    let mut presets: Vec<RoomPreset> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().map(|x| x == "json").unwrap_or(false))
+        .filter_map(|p| {
+            let parsed = fs::read_to_string(&p).map_err(|e| e.to_string())
+                .and_then(|json| serde_json::from_str::<RoomPreset>(&json).map_err(|e| e.to_string()));
+            parsed.map_err(|e| eprintln!("[ROOM] Skipping invalid preset {:?}: {}", p, e)).ok()
+        })
+        .collect();
+    presets.sort_by_key(|p| p.name.to_lowercase());
+    presets
+}
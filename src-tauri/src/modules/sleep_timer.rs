@@ -0,0 +1,79 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use super::api_schema::events;
+use crate::audio::AudioCommand;
+use crate::util::jobs::{JobHandle, JobKind};
+use super::state::AppState;
+
+// ==========================================
+// 🌙 睡眠定时：到点前 30 秒逐步压低音量后暂停，或在当前曲目播完时停下
+// ==========================================
+// 压低的是管理层的额外衰减，不改用户音量；暂停后衰减保留到下一次播放时才复位，避免暂停淡出时音量回弹。
+// 重新设定会顶替同 id 的旧任务；换曲不影响定时。
+pub const JOB_ID: &str = "sleep_timer";
+const FADE_WINDOW: Duration = Duration::from_secs(30);
+const TICK: Duration = Duration::from_millis(250);
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepMode { Timer, AfterTrack }
+
+// 「播完当前曲目」模式不计时，句柄留在这里等 track-ended 取走
+static AFTER_TRACK: Mutex<Option<JobHandle>> = Mutex::new(None);
+
+fn after_track() -> std::sync::MutexGuard<'static, Option<JobHandle>> { AFTER_TRACK.lock().unwrap_or_else(|e| e.into_inner()) }
+
+// minutes = 0 表示播完当前曲目后停下
+pub fn arm(app: &AppHandle, minutes: u32) {
+    let state = app.state::<AppState>();
+    let label = if minutes == 0 { "after current track".to_string() } else { format!("{} min", minutes) };
+    let job = state.jobs.register(JOB_ID, JobKind::SleepTimer, label);
+    if minutes == 0 {
+        *after_track() = Some(job);
+        return;
+    }
+    after_track().take();
+    tauri::async_runtime::spawn(run(app.clone(), job, Duration::from_secs(minutes as u64 * 60)));
+}
+
+pub fn cancel(app: &AppHandle) -> Result<(), String> {
+    let cancelled = app.state::<AppState>().jobs.cancel(JOB_ID).map_err(|_| "SLEEP_TIMER_NOT_ARMED".to_string());
+    after_track().take();
+    cancelled
+}
+
+// 曲目自然播完时调用：已设「播完当前曲目」则取走并返回 true
+pub fn take_after_track() -> bool {
+    after_track().take().is_some_and(|job| !job.is_cancelled())
+}
+
+pub fn fire(app: &AppHandle, mode: SleepMode) {
+    let _ = app.state::<AppState>().audio_tx.send(AudioCommand::Pause);
+    let _ = events::SLEEP_TIMER_FIRED.emit(app, &mode);
+}
+
+async fn run(app: AppHandle, job: JobHandle, total: Duration) {
+    let started = Instant::now();
+    let audio_tx = app.state::<AppState>().audio_tx.clone();
+    let mut fading = false;
+    loop {
+        tokio::time::sleep(TICK).await;
+        if job.is_cancelled() {
+            // 淡出途中被取消或顶替：立即恢复音量
+            if fading { let _ = audio_tx.send(AudioCommand::SetSleepGain(1.0)); }
+            return;
+        }
+        let elapsed = started.elapsed();
+        job.set_progress(elapsed.as_secs_f32() / total.as_secs_f32());
+        let remaining = total.saturating_sub(elapsed);
+        if remaining.is_zero() { break; }
+        if remaining < FADE_WINDOW {
+            fading = true;
+            let _ = audio_tx.send(AudioCommand::SetSleepGain(remaining.as_secs_f32() / FADE_WINDOW.as_secs_f32()));
+        }
+    }
+    let _ = audio_tx.send(AudioCommand::SetSleepGain(0.0));
+    fire(&app, SleepMode::Timer);
+}
@@ -1,6 +1,16 @@
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::sync::mpsc::Sender;
 use crate::audio::AudioCommand;
+use crate::util::jobs::JobRegistry;
+use super::queue::PlayQueue;
 
 pub struct AppState {
     pub audio_tx: Sender<AudioCommand>,
-}
\ No newline at end of file
+    pub queue: Mutex<PlayQueue>,
+    pub jobs: Arc<JobRegistry>,
+}
+
+impl AppState {
+    // 队列是纯数据，持锁线程 panic 后取回内部状态照常使用
+    pub fn queue(&self) -> MutexGuard<'_, PlayQueue> { self.queue.lock().unwrap_or_else(|e| e.into_inner()) }
+}
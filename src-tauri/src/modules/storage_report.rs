@@ -0,0 +1,375 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::hash::Hasher;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use lofty::{read_from_path, AudioFile};
+use serde::{Serialize, Deserialize};
+use tauri::Manager;
+use crate::util::jobs::JobHandle;
+
+// ==========================================
+// 💽 曲库存储统计：按顶层文件夹 / 格式汇总体积，并找出完全重复的文件
+// ==========================================
+// 每个文件的时长与内容指纹按 (大小, mtime) 缓存落盘，未变动的文件只做一次 stat。
+pub const DEFAULT_LARGEST_N: usize = 20;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CachedEntry {
+    size: u64,
+    mtime: i64,
+    duration_secs: f64,
+    #[serde(default)]
+    fingerprint: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct StorageCache {
+    entries: HashMap<String, CachedEntry>,
+}
+
+impl StorageCache {
+    fn path(app: &tauri::AppHandle) -> PathBuf {
+        app.path().app_local_data_dir().unwrap_or_else(|_| PathBuf::from("./data")).join("storage_cache.json")
+    }
+    pub fn load(app: &tauri::AppHandle) -> Self {
+        fs::read_to_string(Self::path(app)).ok().and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default()
+    }
+    pub fn save(&self, app: &tauri::AppHandle) {
+        let path = Self::path(app);
+        if let Some(dir) = path.parent() { let _ = fs::create_dir_all(dir); }
+        if let Ok(json) = serde_json::to_string(self) { let _ = fs::write(path, json); }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct StorageGroup {
+    pub key: String,
+    pub bytes: u64,
+    pub tracks: usize,
+    pub avg_bitrate_kbps: f64,
+    #[serde(skip)]
+    duration_secs: f64,
+}
+
+impl StorageGroup {
+    fn add(&mut self, bytes: u64, duration_secs: f64) {
+        self.bytes += bytes;
+        self.tracks += 1;
+        self.duration_secs += duration_secs;
+        self.avg_bitrate_kbps = if self.duration_secs > 0.0 { self.bytes as f64 * 8.0 / self.duration_secs / 1000.0 } else { 0.0 };
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DuplicateSet {
+    pub size: u64,
+    pub paths: Vec<String>,
+    pub wasted_bytes: u64, // 保留一份之外的体积
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FileEntry {
+    pub path: String,
+    pub bytes: u64,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct StorageReport {
+    pub total: StorageGroup,
+    pub by_folder: Vec<StorageGroup>,
+    pub by_format: Vec<StorageGroup>,
+    pub duplicates: Vec<DuplicateSet>,
+    pub duplicate_wasted_bytes: u64,
+    pub largest: Vec<FileEntry>,
+    pub missing: Vec<String>,
+    pub stat_only: usize, // 命中缓存、仅 stat 的文件数
+    pub generated_at: i64,
+}
+
+// 最近一次生成的报告，供导出使用
+static LAST_REPORT: Mutex<Option<StorageReport>> = Mutex::new(None);
+
+pub fn last_report() -> Option<StorageReport> { LAST_REPORT.lock().unwrap_or_else(|e| e.into_inner()).clone() }
+
+struct Scanned {
+    path: String,
+    size: u64,
+    duration_secs: f64,
+}
+
+fn mtime_of(meta: &fs::Metadata) -> i64 {
+    meta.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+fn content_fingerprint(path: &Path) -> Option<String> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    let mut buf = vec![0u8; 256 * 1024];
+    loop {
+        let n = file.read(&mut buf).ok()?;
+        if n == 0 { break; }
+        hasher.write(&buf[..n]);
+    }
+    Some(format!("{:016x}", hasher.finish()))
+}
+
+// 给定曲库根目录时取根下第一级目录；否则以全部路径的公共祖先为根
+fn folder_key(path: &Path, roots: &[PathBuf]) -> String {
+    let (root, rel) = match roots.iter().find_map(|r| path.strip_prefix(r).ok().map(|rel| (r, rel))) {
+        Some(found) => found,
+        None => return path.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default(),
+    };
+    let mut components = rel.components();
+    match (components.next(), components.next()) {
+        (Some(first), Some(_)) => root.join(first).to_string_lossy().to_string(),
+        _ => root.to_string_lossy().to_string(),
+    }
+}
+
+fn common_root(paths: &[String]) -> Option<PathBuf> {
+    let mut iter = paths.iter().filter_map(|p| Path::new(p).parent().map(Path::to_path_buf));
+    let mut root = iter.next()?;
+    for parent in iter {
+        while !parent.starts_with(&root) {
+            if !root.pop() { return None; }
+        }
+    }
+    Some(root)
+}
+
+pub fn build_report(app: &tauri::AppHandle, paths: &[String], roots: Option<Vec<String>>, largest_n: usize, job: &JobHandle, on_progress: impl Fn(usize, usize)) -> Option<StorageReport> {
+    let mut cache = StorageCache::load(app);
+    let report = summarize(paths, roots, largest_n, &mut cache, job, on_progress)?;
+    cache.save(app);
+    *LAST_REPORT.lock().unwrap_or_else(|e| e.into_inner()) = Some(report.clone());
+    Some(report)
+}
+
+// 取消时返回 None，缓存的改动不落盘
+fn summarize(paths: &[String], roots: Option<Vec<String>>, largest_n: usize, cache: &mut StorageCache, job: &JobHandle, on_progress: impl Fn(usize, usize)) -> Option<StorageReport> {
+    let roots: Vec<PathBuf> = match roots {
+        Some(r) if !r.is_empty() => r.into_iter().map(PathBuf::from).collect(),
+        _ => common_root(paths).into_iter().collect(),
+    };
+    let mut report = StorageReport { total: StorageGroup { key: "total".into(), ..Default::default() }, ..Default::default() };
+    let mut scanned: Vec<Scanned> = Vec::with_capacity(paths.len());
+
+    for (i, path) in paths.iter().enumerate() {
+        if job.is_cancelled() { return None; }
+        let Ok(meta) = fs::metadata(path) else { report.missing.push(path.clone()); continue };
+        let (size, mtime) = (meta.len(), mtime_of(&meta));
+        let duration_secs = match cache.entries.get(path) {
+            Some(entry) if entry.size == size && entry.mtime == mtime => { report.stat_only += 1; entry.duration_secs }
+            _ => {
+                let duration_secs = read_from_path(path).map(|f| f.properties().duration().as_secs_f64()).unwrap_or(0.0);
+                cache.entries.insert(path.clone(), CachedEntry { size, mtime, duration_secs, fingerprint: None });
+                duration_secs
+            }
+        };
+        scanned.push(Scanned { path: path.clone(), size, duration_secs });
+        job.set_progress((i + 1) as f32 / paths.len().max(1) as f32 * 0.8);
+        on_progress(i + 1, paths.len());
+    }
+
+    let mut folders: BTreeMap<String, StorageGroup> = BTreeMap::new();
+    let mut formats: BTreeMap<String, StorageGroup> = BTreeMap::new();
+    let mut by_size: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (idx, file) in scanned.iter().enumerate() {
+        let path = Path::new(&file.path);
+        report.total.add(file.size, file.duration_secs);
+        let folder = folder_key(path, &roots);
+        folders.entry(folder.clone()).or_insert_with(|| StorageGroup { key: folder, ..Default::default() }).add(file.size, file.duration_secs);
+        let format = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_else(|| "unknown".into());
+        formats.entry(format.clone()).or_insert_with(|| StorageGroup { key: format, ..Default::default() }).add(file.size, file.duration_secs);
+        by_size.entry(file.size).or_default().push(idx);
+    }
+
+    // 只有体积相同的文件才需要计算内容指纹
+    let candidates: Vec<usize> = by_size.values().filter(|v| v.len() > 1).flatten().copied().collect();
+    let mut by_fingerprint: HashMap<(u64, String), Vec<String>> = HashMap::new();
+    for (n, idx) in candidates.iter().enumerate() {
+        if job.is_cancelled() { return None; }
+        let file = &scanned[*idx];
+        let cached = cache.entries.get(&file.path).and_then(|e| e.fingerprint.clone());
+        let fingerprint = match cached {
+            Some(fp) => Some(fp),
+            None => {
+                let fp = content_fingerprint(Path::new(&file.path));
+                if let Some(entry) = cache.entries.get_mut(&file.path) { entry.fingerprint = fp.clone(); }
+                fp
+            }
+        };
+        if let Some(fp) = fingerprint { by_fingerprint.entry((file.size, fp)).or_default().push(file.path.clone()); }
+        job.set_progress(0.8 + (n + 1) as f32 / candidates.len() as f32 * 0.2);
+    }
+    report.duplicates = by_fingerprint.into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((size, _), mut paths)| { paths.sort(); DuplicateSet { size, wasted_bytes: size * (paths.len() as u64 - 1), paths } })
+        .collect();
+    report.duplicates.sort_by_key(|d| Reverse(d.wasted_bytes));
+    report.duplicate_wasted_bytes = report.duplicates.iter().map(|d| d.wasted_bytes).sum();
+
+    scanned.sort_by_key(|f| Reverse(f.size));
+    report.largest = scanned.iter().take(largest_n).map(|f| FileEntry { path: f.path.clone(), bytes: f.size }).collect();
+    report.by_folder = folders.into_values().collect();
+    report.by_folder.sort_by_key(|g| Reverse(g.bytes));
+    report.by_format = formats.into_values().collect();
+    report.by_format.sort_by_key(|g| Reverse(g.bytes));
+    report.generated_at = chrono::Local::now().timestamp();
+
+    // 已从曲库移除的文件不再保留缓存
+    cache.entries.retain(|path, _| paths.contains(path));
+    Some(report)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) { format!("\"{}\"", value.replace('"', "\"\"")) } else { value.to_string() }
+}
+
+fn to_csv(report: &StorageReport) -> String {
+    let mut out = String::from("section,key,bytes,tracks,avg_bitrate_kbps\n");
+    let groups = std::iter::once(("total", &report.total))
+        .chain(report.by_folder.iter().map(|g| ("folder", g)))
+        .chain(report.by_format.iter().map(|g| ("format", g)));
+    for (section, g) in groups {
+        out.push_str(&format!("{},{},{},{},{:.1}\n", section, csv_field(&g.key), g.bytes, g.tracks, g.avg_bitrate_kbps));
+    }
+    for (i, set) in report.duplicates.iter().enumerate() {
+        for path in &set.paths { out.push_str(&format!("duplicate_{},{},{},,\n", i + 1, csv_field(path), set.size)); }
+    }
+    for file in &report.largest { out.push_str(&format!("largest,{},{},,\n", csv_field(&file.path), file.bytes)); }
+    out
+}
+
+// 扩展名为 .csv 时导出 CSV，否则导出 JSON
+pub fn export(report: &StorageReport, path: &Path) -> Result<(), String> {
+    let is_csv = path.extension().map(|e| e.eq_ignore_ascii_case("csv")).unwrap_or(false);
+    let content = if is_csv { to_csv(report) } else { serde_json::to_string_pretty(report).map_err(|e| e.to_string())? };
+    fs::write(path, content).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::jobs::{JobKind, JobRegistry};
+
+    // 内容由种子决定：同种子同长度即完全相同的文件
+    fn write(path: &Path, len: usize, seed: u8) -> String {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, (0..len).map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed)).collect::<Vec<u8>>()).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    struct Fixture { root: PathBuf, paths: Vec<String> }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) { let _ = fs::remove_dir_all(&self.root); }
+    }
+
+    // root/A/x.flac ≡ root/B/z.flac；root/B/w.FLAC 同体积不同内容；root/A/sub/y.mp3 ≡ root/C/y1.mp3 ≡ root/C/y2.mp3
+    fn fixture(name: &str) -> Fixture {
+        let root = std::env::temp_dir().join(format!("agm_storage_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let paths = vec![
+            write(&root.join("A").join("x.flac"), 1200, 1),
+            write(&root.join("A").join("sub").join("y.mp3"), 500, 2),
+            write(&root.join("B").join("z.flac"), 1200, 1),
+            write(&root.join("B").join("w.FLAC"), 1200, 9),
+            write(&root.join("C").join("y1.mp3"), 500, 2),
+            write(&root.join("C").join("y2.mp3"), 500, 2),
+            write(&root.join("loose.wav"), 300, 3),
+            root.join("gone.flac").to_string_lossy().to_string(),
+        ];
+        Fixture { root, paths }
+    }
+
+    fn key(root: &Path, parts: &[&str]) -> String {
+        parts.iter().fold(root.to_path_buf(), |p, c| p.join(c)).to_string_lossy().to_string()
+    }
+
+    fn groups(list: &[StorageGroup]) -> Vec<(String, u64, usize)> {
+        list.iter().map(|g| (g.key.clone(), g.bytes, g.tracks)).collect()
+    }
+
+    #[test]
+    fn aggregates_by_folder_and_format() {
+        let fx = fixture("groups");
+        let jobs = JobRegistry::new();
+        let job = jobs.register("report", JobKind::StorageReport, "");
+        let mut cache = StorageCache::default();
+        let report = summarize(&fx.paths, None, 2, &mut cache, &job, |_, _| {}).unwrap();
+
+        assert_eq!((report.total.bytes, report.total.tracks), (5400, 7));
+        assert_eq!(report.missing, vec![fx.paths[7].clone()]);
+        // 未给根目录时以公共祖先为根，按其下第一级目录归组；根下的散文件归根本身
+        assert_eq!(groups(&report.by_folder), vec![
+            (key(&fx.root, &["B"]), 2400, 2),
+            (key(&fx.root, &["A"]), 1700, 2),
+            (key(&fx.root, &["C"]), 1000, 2),
+            (key(&fx.root, &[]), 300, 1),
+        ]);
+        // 扩展名不区分大小写
+        assert_eq!(groups(&report.by_format), vec![("flac".to_string(), 3600, 3), ("mp3".to_string(), 1500, 3), ("wav".to_string(), 300, 1)]);
+        assert_eq!(report.largest.iter().map(|f| f.bytes).collect::<Vec<_>>(), vec![1200, 1200]);
+
+        // 给定根目录：根外的文件按所在目录归组，根下子目录的文件归到第一级
+        let report = summarize(&fx.paths, Some(vec![key(&fx.root, &["A"])]), 2, &mut cache, &job, |_, _| {}).unwrap();
+        let folders: Vec<String> = report.by_folder.iter().map(|g| g.key.clone()).collect();
+        assert!(folders.contains(&key(&fx.root, &["A", "sub"])) && folders.contains(&key(&fx.root, &["A"])));
+        assert!(folders.contains(&key(&fx.root, &["C"])) && folders.contains(&key(&fx.root, &[])));
+    }
+
+    #[test]
+    fn duplicate_space_counts_every_copy_but_one() {
+        let fx = fixture("dupes");
+        let jobs = JobRegistry::new();
+        let job = jobs.register("report", JobKind::StorageReport, "");
+        let mut cache = StorageCache::default();
+        let report = summarize(&fx.paths, None, 20, &mut cache, &job, |_, _| {}).unwrap();
+
+        let sets: Vec<(u64, Vec<String>, u64)> = report.duplicates.iter().map(|d| (d.size, d.paths.clone(), d.wasted_bytes)).collect();
+        // 同体积但内容不同的 w.FLAC 不算重复
+        assert_eq!(sets, vec![
+            (1200, vec![fx.paths[0].clone(), fx.paths[2].clone()], 1200),
+            (500, vec![fx.paths[1].clone(), fx.paths[4].clone(), fx.paths[5].clone()], 1000),
+        ]);
+        assert_eq!(report.duplicate_wasted_bytes, 2200);
+    }
+
+    #[test]
+    fn unchanged_files_are_only_stated_on_the_second_run() {
+        let fx = fixture("cache");
+        let jobs = JobRegistry::new();
+        let job = jobs.register("report", JobKind::StorageReport, "");
+        let mut cache = StorageCache::default();
+        let first = summarize(&fx.paths, None, 20, &mut cache, &job, |_, _| {}).unwrap();
+        assert_eq!(first.stat_only, 0);
+        assert_eq!(cache.entries.len(), 7);
+        assert_eq!(cache.entries.values().filter(|e| e.fingerprint.is_some()).count(), 6);
+
+        // 缓存里的时长参与码率计算：B 组 2400 字节 / 8 秒 = 2.4 kbps
+        cache.entries.get_mut(&fx.paths[3]).unwrap().duration_secs = 8.0;
+        let second = summarize(&fx.paths, None, 20, &mut cache, &job, |_, _| {}).unwrap();
+        assert_eq!(second.stat_only, 7);
+        assert_eq!(second.duplicate_wasted_bytes, first.duplicate_wasted_bytes);
+        let b = second.by_folder.iter().find(|g| g.key == key(&fx.root, &["B"])).unwrap();
+        assert!((b.avg_bitrate_kbps - 2400.0 * 8.0 / 8.0 / 1000.0).abs() < 1e-9);
+
+        // 从曲库移除的文件随之清出缓存
+        summarize(&fx.paths[..3], None, 20, &mut cache, &job, |_, _| {}).unwrap();
+        assert_eq!(cache.entries.len(), 3);
+    }
+
+    #[test]
+    fn cancelled_job_yields_no_report() {
+        let fx = fixture("cancel");
+        let jobs = JobRegistry::new();
+        let job = jobs.register("report", JobKind::StorageReport, "");
+        jobs.cancel("report").unwrap();
+        assert!(summarize(&fx.paths, None, 20, &mut StorageCache::default(), &job, |_, _| {}).is_none());
+    }
+}
@@ -1,19 +1,86 @@
 use std::path::{Path, PathBuf};
 use std::fs;
-use std::io::Read;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use base64::{Engine as _, engine::general_purpose};
 use encoding_rs::{GBK, UTF_8};
-use lofty::{read_from_path, Accessor, TaggedFileExt, AudioFile};
+use lofty::{read_from_path, Accessor, AudioFile, ItemKey, TaggedFileExt};
 use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use super::collation::{self, SortKeys};
+use super::metadata_policy::{self, MetadataPolicy, MetadataSource};
+use crate::audio::ffprobe;
+use crate::audio::replaygain::ReplayGainTags;
 
 #[derive(Serialize, Clone, Debug)]
 pub struct TrackMetadata {
+    pub id: String,
     pub path: String,
     pub title: String,
     pub artist: String,
     pub album: String,
+    pub album_artist: Option<String>,
+    // 以下缺失时为 null / 空数组，前端据此区分“未知”与真实值
+    pub track: Option<u32>,
+    pub disc: Option<u32>,
+    pub year: Option<u32>,
+    pub genres: Vec<String>,
     pub cover: String,
     pub duration: f64,
+    pub sort_title: String,
+    pub sort_artist: String,
+    pub sort_album: String,
+    pub tags_missing: bool, // 音频属性读得出但没有任何标签，标题等取自文件名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cover_issue: Option<CoverIssue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replaygain: Option<ReplayGainTags>,
+    // 仅 verbose 模式返回：每个字段由哪个来源提供
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<BTreeMap<&'static str, MetadataSource>>,
+}
+
+// ==========================================
+// 🖼️ 封面防护：超大或非图片的“封面”不再拖垮导入线程与 IPC
+// ==========================================
+pub const DEFAULT_COVER_CAP: usize = 15 * 1024 * 1024;
+static COVER_CAP: AtomicUsize = AtomicUsize::new(DEFAULT_COVER_CAP);
+
+pub fn set_cover_cap(bytes: usize) { COVER_CAP.store(bytes.max(1), Ordering::Relaxed); }
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverIssue { Oversized, Invalid }
+
+impl CoverIssue {
+    pub fn marker(self) -> &'static str {
+        match self { CoverIssue::Oversized => "OVERSIZED", CoverIssue::Invalid => "INVALID" }
+    }
+}
+
+// 只看文件头魔数，廉价判断是否真的是图片
+fn sniff_image_mime(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) { return Some("image/jpeg"); }
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") { return Some("image/png"); }
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") { return Some("image/gif"); }
+    if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" { return Some("image/webp"); }
+    if bytes.starts_with(b"BM") { return Some("image/bmp"); }
+    None
+}
+
+// 导入时封面只校验不编码，批量事件里带这个标记，前端随后按需经 get_cover_full 取回
+pub const DEFERRED_COVER: &str = "DEFERRED_COVER";
+
+fn check_cover(len: usize, header: &[u8], cap: Option<usize>) -> Result<&'static str, CoverIssue> {
+    if cap.map(|c| len > c).unwrap_or(false) { return Err(CoverIssue::Oversized); }
+    sniff_image_mime(header).ok_or(CoverIssue::Invalid)
+}
+
+fn encode_cover(bytes: &[u8], cap: Option<usize>, inline: bool) -> Result<String, CoverIssue> {
+    let mime = check_cover(bytes.len(), bytes, cap)?;
+    if !inline { return Ok(DEFERRED_COVER.to_string()); }
+    Ok(format!("data:{};base64,{}", mime, general_purpose::STANDARD.encode(bytes)))
 }
 
 pub fn repair_mojibake(input: &str) -> String {
@@ -24,11 +91,10 @@ pub fn repair_mojibake(input: &str) -> String {
     input.to_string()
 }
 
-fn find_cover_image(file_path: &Path, tag: &lofty::Tag) -> String {
+// cap 为 None 时不设上限（用户显式请求原图）；inline 为假时旁路图片只读文件头
+fn find_cover_image(file_path: &Path, tag: &lofty::Tag, cap: Option<usize>, inline: bool) -> Result<String, CoverIssue> {
     if let Some(picture) = tag.pictures().first() {
-        let base64_str = general_purpose::STANDARD.encode(picture.data());
-        let mime = picture.mime_type().as_str(); 
-        return format!("data:{};base64,{}", mime, base64_str);
+        return encode_cover(picture.data(), cap, inline);
     }
     if let Some(parent) = file_path.parent() {
         let stem = file_path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
@@ -37,36 +103,151 @@ fn find_cover_image(file_path: &Path, tag: &lofty::Tag) -> String {
         ];
         for name in &exact_matches {
             let img_path = parent.join(name);
-            if img_path.exists() {
-                if let Ok(bytes) = fs::read(img_path) {
-                    let base64_str = general_purpose::STANDARD.encode(&bytes);
-                    return format!("data:image/jpeg;base64,{}", base64_str);
+            // 先 stat 再读，超限的旁路图片根本不进内存
+            if let Ok(meta) = fs::metadata(&img_path) {
+                if cap.map(|c| meta.len() as usize > c).unwrap_or(false) { return Err(CoverIssue::Oversized); }
+                if !inline {
+                    let mut header = Vec::with_capacity(12);
+                    if fs::File::open(&img_path).and_then(|f| f.take(12).read_to_end(&mut header)).is_ok() {
+                        return check_cover(meta.len() as usize, &header, cap).map(|_| DEFERRED_COVER.to_string());
+                    }
+                } else if let Ok(bytes) = fs::read(img_path) {
+                    return encode_cover(&bytes, cap, true);
                 }
             }
         }
     }
-    "DEFAULT_COVER".to_string()
+    Ok("DEFAULT_COVER".to_string())
+}
+
+// 按需获取完整封面；allow_oversized 为真时绕过体积上限。lofty 读不了的文件与导入时一样以 ffprobe 取内嵌封面
+pub fn get_cover_full(path: &Path, allow_oversized: bool) -> Result<String, String> {
+    let cap = if allow_oversized { None } else { Some(COVER_CAP.load(Ordering::Relaxed)) };
+    let tagged_file = match read_from_path(path) {
+        Ok(file) => file,
+        Err(e) => {
+            let cover = ffprobe::probe(path).and_then(|probe| probe.cover.clone()).ok_or_else(|| e.to_string())?;
+            return encode_cover(&cover, cap, true).map_err(|issue| issue.marker().to_string());
+        }
+    };
+    let empty_tag = lofty::Tag::new(lofty::TagType::Id3v2);
+    let tag = tagged_file.primary_tag().or_else(|| tagged_file.first_tag()).unwrap_or(&empty_tag);
+    find_cover_image(path, tag, cap, true).map_err(|issue| issue.marker().to_string())
+}
+
+// 稳定曲目 id：文件大小 + 文件正中 64KB 的 SHA-256 (取前 16 字节)，移动或改名后不变。
+// 标签都在首尾，取中段音频数据，改标签只要没改变文件大小 (填充区够用) id 也不变。读不了文件时退回规范化路径的哈希
+const ID_SAMPLE_BYTES: u64 = 64 * 1024;
+
+pub fn track_id(path: &Path) -> String {
+    let mut hasher = Sha256::new();
+    match fs::File::open(path).and_then(|f| Ok((f.metadata()?.len(), f))) {
+        Ok((len, mut file)) => {
+            hasher.update(len.to_le_bytes());
+            if file.seek(SeekFrom::Start(len.saturating_sub(ID_SAMPLE_BYTES) / 2)).is_ok() {
+                let _ = std::io::copy(&mut file.take(ID_SAMPLE_BYTES), &mut hasher);
+            }
+        }
+        Err(_) => hasher.update(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()).to_string_lossy().as_bytes()),
+    }
+    hasher.finalize()[..16].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// 播放、单曲查询等场景：读不了也给出以文件名兜底的条目
+pub fn extract_metadata(path: &PathBuf, policy: &MetadataPolicy, verbose: bool) -> TrackMetadata { build_metadata(path, policy, verbose, true).0 }
+
+// 导入用：文件打不开，或 lofty 与 ffprobe 都解析不了时返回 "UNREADABLE: …" / "CORRUPT: …"，不把坏文件当成曲目入库。
+// 封面不内联，有可用封面时 cover 为 DEFERRED_COVER
+pub fn extract_for_import(path: &PathBuf, policy: &MetadataPolicy) -> Result<TrackMetadata, String> {
+    if let Err(e) = fs::File::open(path) { return Err(format!("UNREADABLE: {}", e)); }
+    match build_metadata(path, policy, false, false) {
+        (meta, None) => Ok(meta),
+        (_, Some(e)) => Err(format!("CORRUPT: {}", e)),
+    }
 }
 
-pub fn extract_metadata(path: &PathBuf) -> TrackMetadata {
-    let filename = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+// 第二项为 lofty 读取失败且没有 ffprobe 结果兜底时的错误；inline_cover 为假时封面只给标记
+fn build_metadata(path: &PathBuf, policy: &MetadataPolicy, verbose: bool, inline_cover: bool) -> (TrackMetadata, Option<String>) {
     let mut meta = TrackMetadata {
+        id: track_id(path),
         path: path.to_string_lossy().to_string(),
-        title: filename.clone(), artist: "Unknown Artist".to_string(), album: "Unknown Album".to_string(), cover: "DEFAULT_COVER".to_string(), duration: 0.0,
+        title: String::new(), artist: String::new(), album: String::new(), cover: "DEFAULT_COVER".to_string(), duration: 0.0,
+        album_artist: None, track: None, disc: None, year: None, genres: Vec::new(),
+        sort_title: String::new(), sort_artist: String::new(), sort_album: String::new(),
+        tags_missing: false, cover_issue: None, replaygain: None, provenance: None,
+    };
+    let prefs = collation::active();
+    let (tagged_file, read_error) = match read_from_path(path) {
+        Ok(file) => (Some(file), None),
+        Err(e) => (None, Some(e.to_string())),
     };
-    if let Ok(tagged_file) = read_from_path(path) {
-        let tag = tagged_file.primary_tag().or_else(|| tagged_file.first_tag());
-        let properties = tagged_file.properties();
-        if let Some(t) = tag {
-            if let Some(title) = t.title() { let trimmed = title.trim(); if !trimmed.is_empty() { meta.title = repair_mojibake(trimmed); } }
-            if let Some(artist) = t.artist() { let trimmed = artist.trim(); if !trimmed.is_empty() { meta.artist = repair_mojibake(trimmed); } }
-            if let Some(album) = t.album() { let trimmed = album.trim(); if !trimmed.is_empty() { meta.album = repair_mojibake(trimmed); } }
-            let empty_tag = lofty::Tag::new(lofty::TagType::Id3v2);
-            meta.cover = find_cover_image(path, tag.unwrap_or(&empty_tag));
+    let tag = tagged_file.as_ref().and_then(|f| f.primary_tag().or_else(|| f.first_tag()));
+    let resolved = metadata_policy::resolve(path, tag, policy);
+    meta.title = resolved.title;
+    meta.artist = resolved.artist;
+    meta.album = resolved.album;
+    if verbose { meta.provenance = Some(resolved.provenance); }
+    if let Some(t) = tag {
+        apply_tag_details(&mut meta, t);
+        match find_cover_image(path, t, Some(COVER_CAP.load(Ordering::Relaxed)), inline_cover) {
+            Ok(cover) => meta.cover = cover,
+            Err(issue) => { meta.cover = issue.marker().to_string(); meta.cover_issue = Some(issue); }
         }
-        meta.duration = properties.duration().as_secs_f64();
     }
-    meta
+    if let Some(tagged_file) = &tagged_file {
+        meta.duration = tagged_file.properties().duration().as_secs_f64();
+        meta.replaygain = ReplayGainTags::from_file(tagged_file);
+    }
+    let probed = if tagged_file.is_none() || meta.duration <= 0.0 { apply_probe(&mut meta, path, tag.is_none(), inline_cover) } else { None };
+    meta.tags_missing = tag.is_none() && probed != Some(true);
+    let keys = SortKeys::compute(&meta.title, &meta.artist, &meta.album, tag, &prefs);
+    meta.sort_title = keys.sort_title;
+    meta.sort_artist = keys.sort_artist;
+    meta.sort_album = keys.sort_album;
+    (meta, read_error.filter(|_| probed.is_none()))
+}
+
+// 曲序、碟号、年份、流派与专辑艺术家；编号为 0 视同缺失
+fn apply_tag_details(meta: &mut TrackMetadata, tag: &lofty::Tag) {
+    let text = |v: &str| Some(v.trim()).filter(|v| !v.is_empty()).map(repair_mojibake);
+    meta.album_artist = tag.get_string(&ItemKey::AlbumArtist).and_then(text);
+    meta.track = tag.track().filter(|n| *n > 0);
+    meta.disc = tag.disk().filter(|n| *n > 0);
+    meta.year = tag.year().filter(|n| *n > 0);
+    // 多值流派可能以 ; 或 \0 分隔 (ID3v2.4)，拆开去重
+    for genre in tag.genre().iter().flat_map(|g| g.split([';', '\0'])).filter_map(text) {
+        if !meta.genres.contains(&genre) { meta.genres.push(genre); }
+    }
+}
+
+// lofty 读不了或读不出时长时以 ffprobe 补齐：时长总是取用；标签与封面只在 lofty 没拿到标签时填入。
+// ffprobe 不可用或也读不了时返回 None，否则返回是否填入了标签
+fn apply_probe(meta: &mut TrackMetadata, path: &Path, untagged: bool, inline_cover: bool) -> Option<bool> {
+    let probe = ffprobe::probe(path)?;
+    if meta.duration <= 0.0 { meta.duration = probe.duration; }
+    if !untagged { return Some(false); }
+    let tagged = probe.title.is_some() || probe.artist.is_some() || probe.album.is_some();
+    for (field, slot, value) in [("title", &mut meta.title, &probe.title), ("artist", &mut meta.artist, &probe.artist), ("album", &mut meta.album, &probe.album)] {
+        let Some(value) = value else { continue };
+        *slot = value.clone();
+        if let Some(provenance) = meta.provenance.as_mut() { provenance.insert(field, MetadataSource::Tags); }
+    }
+    if let Some(bytes) = &probe.cover {
+        match encode_cover(bytes, Some(COVER_CAP.load(Ordering::Relaxed)), inline_cover) {
+            Ok(cover) => meta.cover = cover,
+            Err(issue) => { meta.cover = issue.marker().to_string(); meta.cover_issue = Some(issue); }
+        }
+    }
+    Some(tagged)
+}
+
+// 仅重算排序键（切换排序语言时使用），不读取封面
+pub fn extract_sort_keys(path: &Path) -> SortKeys {
+    let prefs = collation::active();
+    let tagged_file = read_from_path(path).ok();
+    let tag = tagged_file.as_ref().and_then(|f| f.primary_tag().or_else(|| f.first_tag()));
+    let resolved = metadata_policy::resolve(path, tag, &metadata_policy::active());
+    SortKeys::compute(&resolved.title, &resolved.artist, &resolved.album, tag, &prefs)
 }
 
 pub fn parse_lyrics_file(path: String) -> Result<String, String> {
@@ -86,4 +267,72 @@ pub fn parse_lyrics_file(path: String) -> Result<String, String> {
         return Ok(decoded_gbk.into_owned());
     }
     Ok("".to_string())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    const JPEG: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F', 0, 1];
+
+    #[test]
+    fn sniff_recognises_headers_not_extensions() {
+        assert_eq!(sniff_image_mime(JPEG), Some("image/jpeg"));
+        assert_eq!(sniff_image_mime(PNG), Some("image/png"));
+        assert_eq!(sniff_image_mime(b"GIF89a\x01\0\x01\0"), Some("image/gif"));
+        assert_eq!(sniff_image_mime(b"RIFF\x24\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff_image_mime(b"BM\x36\0\0\0"), Some("image/bmp"));
+        // RIFF 但不是 WEBP (例如被当成封面塞进去的 WAV)、截断的头、文本与空数据都不算图片
+        assert_eq!(sniff_image_mime(b"RIFF\x24\0\0\0WAVEfmt "), None);
+        assert_eq!(sniff_image_mime(b"RIFF\x24\0\0\0WEB"), None);
+        assert_eq!(sniff_image_mime(&[0xFF, 0xD8]), None);
+        assert_eq!(sniff_image_mime(b"<html>not a cover</html>"), None);
+        assert_eq!(sniff_image_mime(b""), None);
+    }
+
+    #[test]
+    fn encode_cover_enforces_the_cap_before_sniffing() {
+        let mut big = PNG.to_vec();
+        big.resize(1024, 0);
+        assert_eq!(encode_cover(&big, Some(1023), true), Err(CoverIssue::Oversized));
+        // 恰好等于上限仍放行；None 表示用户显式要原图，不设上限
+        assert!(encode_cover(&big, Some(1024), true).is_ok());
+        assert!(encode_cover(&big, None, true).is_ok());
+        // 超限的垃圾数据报 Oversized 而不是 Invalid：不为它做头部检查
+        assert_eq!(encode_cover(&[0u8; 64], Some(8), true), Err(CoverIssue::Oversized));
+        assert_eq!(encode_cover(&[0u8; 64], Some(64), true), Err(CoverIssue::Invalid));
+    }
+
+    #[test]
+    fn encode_cover_inlines_or_defers() {
+        let inline = encode_cover(JPEG, Some(DEFAULT_COVER_CAP), true).unwrap();
+        let b64 = inline.strip_prefix("data:image/jpeg;base64,").unwrap();
+        assert_eq!(general_purpose::STANDARD.decode(b64).unwrap(), JPEG);
+        assert_eq!(encode_cover(JPEG, Some(DEFAULT_COVER_CAP), false).unwrap(), DEFERRED_COVER);
+        // 延迟模式同样校验，坏封面不会以 DEFERRED 标记混过去
+        assert_eq!(encode_cover(b"nope", None, false), Err(CoverIssue::Invalid));
+        assert_eq!(encode_cover(PNG, Some(4), false), Err(CoverIssue::Oversized));
+    }
+
+    #[test]
+    fn sidecar_cover_is_capped_by_size_and_checked_by_header() {
+        let dir = std::env::temp_dir().join(format!("agm_cover_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let track = dir.join("song.flac");
+        let tag = lofty::Tag::new(lofty::TagType::Id3v2);
+        assert_eq!(find_cover_image(&track, &tag, Some(1024), true), Ok("DEFAULT_COVER".to_string()));
+
+        let mut png = PNG.to_vec();
+        png.resize(2048, 7);
+        fs::write(dir.join("song.png"), &png).unwrap();
+        assert_eq!(find_cover_image(&track, &tag, Some(1024), false), Err(CoverIssue::Oversized));
+        assert_eq!(find_cover_image(&track, &tag, Some(4096), false), Ok(DEFERRED_COVER.to_string()));
+        assert!(find_cover_image(&track, &tag, None, true).unwrap().starts_with("data:image/png;base64,"));
+
+        // .jpg 优先于 .png；扩展名是 jpg 但内容不是图片时如实报 Invalid
+        fs::write(dir.join("song.jpg"), b"this is a text file").unwrap();
+        assert_eq!(find_cover_image(&track, &tag, Some(4096), false), Err(CoverIssue::Invalid));
+        assert_eq!(find_cover_image(&track, &tag, Some(4096), true), Err(CoverIssue::Invalid));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
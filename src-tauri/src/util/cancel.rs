@@ -0,0 +1,129 @@
+// src/util/cancel.rs
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+// =================================================================
+// ✋ 统一取消令牌：导入、下载、后台解码等长任务共用
+// =================================================================
+// 父令牌取消时级联取消全部子令牌；子令牌取消不影响父令牌。
+// on_cancel 注册的回调在取消时于调用 cancel() 的线程上执行一次。
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+struct Inner {
+    cancelled: AtomicBool,
+    children: Mutex<Vec<Weak<Inner>>>,
+    callbacks: Mutex<Vec<Callback>>,
+}
+
+impl Inner {
+    fn cancel(&self) {
+        if self.cancelled.swap(true, Ordering::SeqCst) { return; }
+        let callbacks = std::mem::take(&mut *self.callbacks.lock().unwrap_or_else(|e| e.into_inner()));
+        for cb in callbacks { cb(); }
+        let children = std::mem::take(&mut *self.children.lock().unwrap_or_else(|e| e.into_inner()));
+        for child in children.iter().filter_map(Weak::upgrade) { child.cancel(); }
+    }
+}
+
+#[derive(Clone)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self { Self::new() }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self { inner: Arc::new(Inner { cancelled: AtomicBool::new(false), children: Mutex::new(Vec::new()), callbacks: Mutex::new(Vec::new()) }) }
+    }
+
+    // 父令牌已取消时，新建的子令牌同样处于取消状态
+    pub fn child_token(&self) -> Self {
+        let child = Self::new();
+        {
+            let mut children = self.inner.children.lock().unwrap_or_else(|e| e.into_inner());
+            if !self.is_cancelled() {
+                children.retain(|w| w.strong_count() > 0);
+                children.push(Arc::downgrade(&child.inner));
+                return child;
+            }
+        }
+        child.cancel();
+        child
+    }
+
+    pub fn cancel(&self) { self.inner.cancel(); }
+
+    pub fn is_cancelled(&self) -> bool { self.inner.cancelled.load(Ordering::SeqCst) }
+
+    // 已取消则立即执行回调
+    pub fn on_cancel<F: FnOnce() + Send + 'static>(&self, f: F) {
+        {
+            let mut callbacks = self.inner.callbacks.lock().unwrap_or_else(|e| e.into_inner());
+            if !self.is_cancelled() { callbacks.push(Box::new(f)); return; }
+        }
+        f();
+    }
+
+    pub fn same_as(&self, other: &CancellationToken) -> bool { Arc::ptr_eq(&self.inner, &other.inner) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn parent_cancel_reaches_grandchildren() {
+        let root = CancellationToken::new();
+        let child = root.child_token();
+        let grandchild = child.child_token();
+        let sibling = root.child_token();
+        root.cancel();
+        assert!(child.is_cancelled() && grandchild.is_cancelled() && sibling.is_cancelled());
+    }
+
+    #[test]
+    fn child_cancel_stays_local() {
+        let root = CancellationToken::new();
+        let child = root.child_token();
+        let sibling = root.child_token();
+        child.cancel();
+        assert!(!root.is_cancelled() && !sibling.is_cancelled());
+    }
+
+    #[test]
+    fn child_of_cancelled_parent_starts_cancelled() {
+        let root = CancellationToken::new();
+        root.cancel();
+        assert!(root.child_token().is_cancelled());
+    }
+
+    #[test]
+    fn dropped_children_are_pruned() {
+        let root = CancellationToken::new();
+        for _ in 0..100 { drop(root.child_token()); }
+        let _kept = root.child_token();
+        assert_eq!(root.inner.children.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn callbacks_run_once_including_late_registrations() {
+        let root = CancellationToken::new();
+        let child = root.child_token();
+        let fired = Arc::new(AtomicUsize::new(0));
+        let counter = fired.clone();
+        child.on_cancel(move || { counter.fetch_add(1, Ordering::SeqCst); });
+        root.cancel();
+        root.cancel();
+        child.cancel();
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+        let counter = fired.clone();
+        child.on_cancel(move || { counter.fetch_add(1, Ordering::SeqCst); });
+        assert_eq!(fired.load(Ordering::SeqCst), 2);
+    }
+}
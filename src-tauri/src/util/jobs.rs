@@ -0,0 +1,142 @@
+// src/util/jobs.rs
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use super::cancel::CancellationToken;
+
+// =================================================================
+// 📋 后台任务登记表：列出正在运行的长任务并可按 id 取消
+// =================================================================
+// 任务以 JobHandle 的生命周期为准，句柄析构即从表中移除；
+// 所有任务令牌都是根令牌的子令牌，退出时一次 cancel_all 即可全部叫停。
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind { Import, FfmpegDownload, Decode, StorageReport, Integrity, LoudnessPreview, ReplayGainScan, SleepTimer, SilenceBounds }
+
+#[derive(Serialize, Clone, Debug)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: JobKind,
+    pub label: String,
+    pub progress: f32, // 0.0–1.0，未知总量时保持 0
+    pub started_at: i64,
+}
+
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<String, (JobInfo, CancellationToken)>>,
+    root: CancellationToken,
+}
+
+impl JobRegistry {
+    pub fn new() -> Arc<Self> { Arc::new(Self::default()) }
+
+    // 同 id 的旧任务会被取消并顶替
+    pub fn register(self: &Arc<Self>, id: impl Into<String>, kind: JobKind, label: impl Into<String>) -> JobHandle {
+        let id = id.into();
+        let token = self.root.child_token();
+        let info = JobInfo { id: id.clone(), kind, label: label.into(), progress: 0.0, started_at: chrono::Local::now().timestamp_millis() };
+        let replaced = self.lock().insert(id.clone(), (info, token.clone()));
+        if let Some((_, old)) = replaced { old.cancel(); }
+        JobHandle { id, token, registry: self.clone() }
+    }
+
+    pub fn cancel(&self, id: &str) -> Result<(), String> {
+        let token = self.lock().get(id).map(|(_, t)| t.clone()).ok_or_else(|| "JOB_NOT_FOUND".to_string())?;
+        token.cancel();
+        Ok(())
+    }
+
+    pub fn cancel_all(&self) { self.root.cancel(); }
+
+    pub fn list(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self.lock().values().map(|(info, _)| info.clone()).collect();
+        jobs.sort_by_key(|j| j.started_at);
+        jobs
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, (JobInfo, CancellationToken)>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub struct JobHandle {
+    id: String,
+    token: CancellationToken,
+    registry: Arc<JobRegistry>,
+}
+
+impl JobHandle {
+    pub fn id(&self) -> &str { &self.id }
+    pub fn token(&self) -> &CancellationToken { &self.token }
+    pub fn is_cancelled(&self) -> bool { self.token.is_cancelled() }
+
+    pub fn set_progress(&self, progress: f32) {
+        if let Some((info, token)) = self.registry.lock().get_mut(&self.id) {
+            if token.same_as(&self.token) { info.progress = progress.clamp(0.0, 1.0); }
+        }
+    }
+}
+
+impl Drop for JobHandle {
+    fn drop(&mut self) {
+        let mut jobs = self.registry.lock();
+        // 已被同 id 新任务顶替时不能误删对方的登记
+        if jobs.get(&self.id).map(|(_, t)| t.same_as(&self.token)).unwrap_or(false) { jobs.remove(&self.id); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lifecycle_across_two_kinds() {
+        let registry = JobRegistry::new();
+        let import = registry.register("import_1", JobKind::Import, "3 files");
+        let scan = registry.register("scan_1", JobKind::ReplayGainScan, "album");
+        let kinds: Vec<JobKind> = registry.list().iter().map(|j| j.kind).collect();
+        assert_eq!(kinds.len(), 2);
+        assert!(kinds.contains(&JobKind::Import) && kinds.contains(&JobKind::ReplayGainScan));
+
+        import.set_progress(1.5);
+        assert_eq!(registry.list().iter().find(|j| j.id == "import_1").unwrap().progress, 1.0);
+
+        registry.cancel("import_1").unwrap();
+        assert!(import.is_cancelled() && !scan.is_cancelled());
+        assert_eq!(registry.cancel("missing"), Err("JOB_NOT_FOUND".to_string()));
+
+        drop(import);
+        assert_eq!(registry.list().iter().map(|j| j.id.as_str()).collect::<Vec<_>>(), vec!["scan_1"]);
+        drop(scan);
+        assert!(registry.list().is_empty());
+    }
+
+    #[test]
+    fn cancel_all_propagates_to_every_job() {
+        let registry = JobRegistry::new();
+        let import = registry.register("import_1", JobKind::Import, "");
+        let download = registry.register("ffmpeg", JobKind::FfmpegDownload, "");
+        let nested = download.token().child_token();
+        registry.cancel_all();
+        assert!(import.is_cancelled() && download.is_cancelled() && nested.is_cancelled());
+    }
+
+    #[test]
+    fn same_id_replaces_without_losing_the_new_entry() {
+        let registry = JobRegistry::new();
+        let old = registry.register("report", JobKind::StorageReport, "old");
+        let new = registry.register("report", JobKind::StorageReport, "new");
+        assert!(old.is_cancelled() && !new.is_cancelled());
+        // 旧句柄的进度与析构都不能动到新任务的登记
+        old.set_progress(0.7);
+        drop(old);
+        let jobs = registry.list();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!((jobs[0].label.as_str(), jobs[0].progress), ("new", 0.0));
+        drop(new);
+        assert!(registry.list().is_empty());
+    }
+}
@@ -149,8 +149,8 @@ export const usePlayerStore = defineStore('player', () => {
 
   const syncEngine = async () => {
       try {
-          const realEngine = await invoke<string>('get_current_engine');
-          engine.activeEngine.value = realEngine;
+          const realEngine = await invoke<{ id: string }>('get_current_engine');
+          engine.activeEngine.value = realEngine.id;
       } catch (e) { console.error(e); }
   };
 
@@ -269,6 +269,18 @@ export const usePlayerStore = defineStore('player', () => {
               engine.isDownloadingFFmpeg.value = false;
               engine.isEngineSwitching.value = false; 
               notifyUI.value?.('Download error', 'error');
+          } else if (status === 'checksum-failed') {
+              engine.isDownloadingFFmpeg.value = false;
+              engine.isEngineSwitching.value = false;
+              notifyUI.value?.('Download corrupted, please retry', 'error');
+          } else if (status === 'busy') {
+              engine.isDownloadingFFmpeg.value = false;
+              engine.isEngineSwitching.value = false;
+              notifyUI.value?.('Stop playback before updating FFmpeg', 'error');
+          } else if (status === 'cancelled') {
+              engine.isDownloadingFFmpeg.value = false;
+              engine.isEngineSwitching.value = false;
+              notifyUI.value?.('Download cancelled', 'info');
           }
       });
 
@@ -750,6 +762,23 @@ export const usePlayerStore = defineStore('player', () => {
 
   let listenersBound = false;
 
+  // 导入批次里的封面只是 DEFERRED_COVER 标记，这里限量并发逐个取回，不占用批量事件
+  const coverQueue: string[] = [];
+  let coverWorkers = 0;
+  const hydrateCovers = () => {
+    while (coverWorkers < 4 && coverQueue.length > 0) {
+      const path = coverQueue.shift()!;
+      coverWorkers++;
+      invoke<string>('get_cover_full', { path, allowOversized: false })
+        .then((cover) => {
+          const track = playlist.queue.value.find(t => t.path === path);
+          if (track && cover.startsWith('data:')) track.cover = cover;
+        })
+        .catch(() => {})
+        .finally(() => { coverWorkers--; hydrateCovers(); });
+    }
+  };
+
   const setupEventListeners = async () => {
     if (listenersBound) return;
     listenersBound = true;
@@ -758,23 +787,36 @@ export const usePlayerStore = defineStore('player', () => {
         importTotal.value = e.payload; importCount.value = 0; importProgress.value = 0;
     });
     
-    await listen<Track>('import-track', (e) => {
-        const t = e.payload;
-        if (!playlist.queue.value.some(track => track.path === t.path)) {
-            playlist.queue.value.push({ 
-                ...t, 
-                id: Date.now().toString() + Math.random().toString(36).substring(2, 8), 
-                cover: t.cover === 'DEFAULT_COVER' ? DEFAULT_COVER : t.cover, 
-                isAvailable: true 
-            });
+    await listen<{ tracks: Track[] }>('import-tracks-batch', (e) => {
+        for (const t of e.payload.tracks) {
+            const deferred = t.cover === 'DEFERRED_COVER';
+            const cover = deferred || ['DEFAULT_COVER', 'OVERSIZED', 'INVALID'].includes(t.cover) ? DEFAULT_COVER : t.cover;
+            const existing = playlist.queue.value.find(track => track.path === t.path);
+            // 强制重新导入时刷新已有条目的元数据，保留原 id 以免打断引用
+            if (existing) Object.assign(existing, { ...t, id: existing.id, cover });
+            else playlist.queue.value.push({ ...t, id: t.id || Date.now().toString() + Math.random().toString(36).substring(2, 8), cover, isAvailable: true });
+            if (deferred) coverQueue.push(t.path);
         }
+        hydrateCovers();
+        importCount.value += e.payload.tracks.length;
+        if (importTotal.value > 0) importProgress.value = (importCount.value / importTotal.value) * 100;
+    });
+
+    // 已导入过或读不了的文件后端直接跳过，只推进进度
+    await listen('import-skipped', () => {
+        importCount.value++;
+        if (importTotal.value > 0) importProgress.value = (importCount.value / importTotal.value) * 100;
+    });
+    await listen('import-error', () => {
         importCount.value++;
         if (importTotal.value > 0) importProgress.value = (importCount.value / importTotal.value) * 100;
     });
     
-    await listen('import-finish', () => { 
+    await listen<{ cancelled?: boolean, errors?: number }>('import-finish', (e) => { 
         isImporting.value = false; 
-        setTimeout(() => notifyUI.value?.('Library updated'), 400); 
+        const errors = e.payload?.errors ?? 0;
+        const message = e.payload?.cancelled ? 'Import cancelled' : errors > 0 ? `Library updated, ${errors} file${errors > 1 ? 's' : ''} could not be read` : 'Library updated';
+        setTimeout(() => notifyUI.value?.(message), 400); 
     });
     
     await listen('import-cancel', () => { isImporting.value = false; });
@@ -789,9 +831,30 @@ export const usePlayerStore = defineStore('player', () => {
         }
     });
 
+    // 解码器给不出时长的曲目，整曲解完后后端按实际样本数补上精确时长
+    await listen<{ path: string, duration: number }>('duration-updated', (e) => {
+        const track = playlist.currentTrack.value;
+        if (!track || track.path !== e.payload.path) return;
+        track.duration = e.payload.duration;
+        if (e.payload.duration > 0) progress.value = (currentTime.value / e.payload.duration) * 100;
+    });
+
     await listen('force-pause', () => { 
         isPlaying.value = false; isPaused.value = true; stopProgressLoop();
     });
+
+    // 功放关机 / HDMI 断开：后端已暂停并回退到端点最后在线时的位置
+    await listen<{ position: number }>('output-inactive-paused', (e) => {
+        isPlaying.value = false; isPaused.value = true; stopProgressLoop();
+        currentTime.value = e.payload.position;
+        const duration = playlist.currentTrack.value?.duration ?? 0;
+        if (duration > 0) progress.value = (e.payload.position / duration) * 100;
+    });
+
+    await listen<{ resumed: boolean }>('output-reactivated', (e) => {
+        if (!e.payload.resumed) return;
+        isPlaying.value = true; isPaused.value = false; startProgressLoop();
+    });
   };
 
   const importTracks = async () => { 